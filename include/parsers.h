@@ -0,0 +1,258 @@
+#ifndef PARSERS_H
+#define PARSERS_H
+
+/* Generated by cbindgen from src/ffi. Do not edit. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+typedef enum {
+  PARSERS_JSON_TYPE_NULL,
+  PARSERS_JSON_TYPE_BOOLEAN,
+  PARSERS_JSON_TYPE_NUMBER,
+  PARSERS_JSON_TYPE_STRING,
+  PARSERS_JSON_TYPE_ARRAY,
+  PARSERS_JSON_TYPE_OBJECT,
+} ParsersJsonType;
+
+typedef struct ParsersError ParsersError;
+
+/**
+ * A JSON value. Handles point at a `JsonValue`; those for nested values
+ * borrow from the root.
+ */
+typedef struct ParsersJson ParsersJson;
+
+typedef struct ParsersUri ParsersUri;
+
+/**
+ * The error message, NUL-terminated.
+ *
+ * # Safety
+ *
+ * `error` must be a live handle from a parse function.
+ */
+const char *parsers_error_message(const ParsersError *error);
+
+/**
+ * Byte offset of the error in the input.
+ *
+ * # Safety
+ *
+ * `error` must be a live handle from a parse function.
+ */
+size_t parsers_error_offset(const ParsersError *error);
+
+/**
+ * # Safety
+ *
+ * `error` must be null or a handle from a parse function that has not been
+ * freed.
+ */
+void parsers_error_free(ParsersError *error);
+
+/**
+ * Parses a NUL-terminated URI. Trailing input is an error.
+ *
+ * # Safety
+ *
+ * `input` must be null or a NUL-terminated string, and `error` null or
+ * valid for writes.
+ */
+ParsersUri *parsers_uri_parse(const char *input, ParsersError **error);
+
+/**
+ * # Safety
+ *
+ * `uri` must be a live handle from `parsers_uri_parse`.
+ */
+const char *parsers_uri_scheme(const ParsersUri *uri);
+
+/**
+ * The user from the authority, or null.
+ *
+ * # Safety
+ *
+ * `uri` must be a live handle from `parsers_uri_parse`.
+ */
+const char *parsers_uri_user(const ParsersUri *uri);
+
+/**
+ * The password from the authority, or null.
+ *
+ * # Safety
+ *
+ * `uri` must be a live handle from `parsers_uri_parse`.
+ */
+const char *parsers_uri_password(const ParsersUri *uri);
+
+/**
+ * The host, or null if the URI has no authority.
+ *
+ * # Safety
+ *
+ * `uri` must be a live handle from `parsers_uri_parse`.
+ */
+const char *parsers_uri_host(const ParsersUri *uri);
+
+/**
+ * The explicit port, or -1.
+ *
+ * # Safety
+ *
+ * `uri` must be a live handle from `parsers_uri_parse`.
+ */
+int32_t parsers_uri_port(const ParsersUri *uri);
+
+/**
+ * # Safety
+ *
+ * `uri` must be a live handle from `parsers_uri_parse`.
+ */
+size_t parsers_uri_path_len(const ParsersUri *uri);
+
+/**
+ * The path segment at `index`, or null if out of range.
+ *
+ * # Safety
+ *
+ * `uri` must be a live handle from `parsers_uri_parse`.
+ */
+const char *parsers_uri_path_segment(const ParsersUri *uri, size_t index);
+
+/**
+ * # Safety
+ *
+ * `uri` must be a live handle from `parsers_uri_parse`.
+ */
+size_t parsers_uri_query_len(const ParsersUri *uri);
+
+/**
+ * The key of the query parameter at `index`, or null if out of range.
+ *
+ * # Safety
+ *
+ * `uri` must be a live handle from `parsers_uri_parse`.
+ */
+const char *parsers_uri_query_key(const ParsersUri *uri, size_t index);
+
+/**
+ * The value of the query parameter at `index`, or null if out of range or
+ * the key has no `=`, as in `?flag`.
+ *
+ * # Safety
+ *
+ * `uri` must be a live handle from `parsers_uri_parse`.
+ */
+const char *parsers_uri_query_value(const ParsersUri *uri, size_t index);
+
+/**
+ * The fragment, or null.
+ *
+ * # Safety
+ *
+ * `uri` must be a live handle from `parsers_uri_parse`.
+ */
+const char *parsers_uri_fragment(const ParsersUri *uri);
+
+/**
+ * # Safety
+ *
+ * `uri` must be null or a handle from `parsers_uri_parse` that has not
+ * been freed.
+ */
+void parsers_uri_free(ParsersUri *uri);
+
+/**
+ * Parses a NUL-terminated JSON document.
+ *
+ * # Safety
+ *
+ * `input` must be null or a NUL-terminated string, and `error` null or
+ * valid for writes.
+ */
+ParsersJson *parsers_json_parse(const char *input, ParsersError **error);
+
+/**
+ * # Safety
+ *
+ * `value` must be a live JSON handle.
+ */
+ParsersJsonType parsers_json_type(const ParsersJson *value);
+
+/**
+ * The boolean value, or false for other types.
+ *
+ * # Safety
+ *
+ * `value` must be a live JSON handle.
+ */
+bool parsers_json_boolean(const ParsersJson *value);
+
+/**
+ * The numeric value, or NaN for other types.
+ *
+ * # Safety
+ *
+ * `value` must be a live JSON handle.
+ */
+double parsers_json_number(const ParsersJson *value);
+
+/**
+ * The string value, or null for other types.
+ *
+ * # Safety
+ *
+ * `value` must be a live JSON handle and `len` null or valid for writes.
+ */
+const char *parsers_json_string(const ParsersJson *value, size_t *len);
+
+/**
+ * The number of elements or members, or 0 for scalars.
+ *
+ * # Safety
+ *
+ * `value` must be a live JSON handle.
+ */
+size_t parsers_json_len(const ParsersJson *value);
+
+/**
+ * The array element or object member value at `index`, or null.
+ *
+ * # Safety
+ *
+ * `value` must be a live JSON handle.
+ */
+const ParsersJson *parsers_json_at(const ParsersJson *value, size_t index);
+
+/**
+ * The key of the object member at `index`, or null.
+ *
+ * # Safety
+ *
+ * `value` must be a live JSON handle and `len` null or valid for writes.
+ */
+const char *parsers_json_key(const ParsersJson *value, size_t index, size_t *len);
+
+/**
+ * The value of the first member named `key`, or null.
+ *
+ * # Safety
+ *
+ * `value` must be a live JSON handle and `key` a NUL-terminated string.
+ */
+const ParsersJson *parsers_json_get(const ParsersJson *value, const char *key);
+
+/**
+ * Frees a document. Only handles from `parsers_json_parse` may be freed,
+ * not the nested values borrowed from them.
+ *
+ * # Safety
+ *
+ * `value` must be null or a handle from `parsers_json_parse` that has not
+ * been freed.
+ */
+void parsers_json_free(ParsersJson *value);
+
+#endif  /* PARSERS_H */
@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, take_till, take_while1},
+    character::complete::{char, digit1, multispace0, not_line_ending},
+    combinator::{map, opt, value},
+    error::{context, VerboseError},
+    multi::{many0, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    Err as NomErr, IResult,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EntryType {
+    Article,
+    Book,
+    Booklet,
+    InBook,
+    InCollection,
+    InProceedings,
+    Manual,
+    MastersThesis,
+    Misc,
+    PhdThesis,
+    Proceedings,
+    TechReport,
+    Unpublished,
+    Other(String),
+}
+
+impl From<&str> for EntryType {
+    fn from(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "article" => EntryType::Article,
+            "book" => EntryType::Book,
+            "booklet" => EntryType::Booklet,
+            "inbook" => EntryType::InBook,
+            "incollection" => EntryType::InCollection,
+            "inproceedings" | "conference" => EntryType::InProceedings,
+            "manual" => EntryType::Manual,
+            "mastersthesis" => EntryType::MastersThesis,
+            "misc" => EntryType::Misc,
+            "phdthesis" => EntryType::PhdThesis,
+            "proceedings" => EntryType::Proceedings,
+            "techreport" => EntryType::TechReport,
+            "unpublished" => EntryType::Unpublished,
+            other => EntryType::Other(other.to_owned()),
+        }
+    }
+}
+
+/// A single bibliography entry with all macros expanded. Field names are
+/// lowercased, as BibTeX treats them case-insensitively.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Entry {
+    pub entry_type: EntryType,
+    pub key: String,
+    pub fields: Vec<(String, String)>,
+}
+
+impl Entry {
+    pub fn get(&self, field: &str) -> Option<&str> {
+        let field = field.to_lowercase();
+        self.fields
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Bibliography {
+    pub preambles: Vec<String>,
+    pub entries: Vec<Entry>,
+}
+
+impl Bibliography {
+    pub fn get(&self, key: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.key == key)
+    }
+}
+
+/// One operand of a `#`-concatenated field value.
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Piece<'a> {
+    Literal(&'a str),
+    Macro(&'a str),
+}
+
+type Field<'a> = (&'a str, Vec<Piece<'a>>);
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Item<'a> {
+    Comment,
+    String(&'a str, Vec<Piece<'a>>),
+    Preamble(Vec<Piece<'a>>),
+    Entry(&'a str, &'a str, Vec<Field<'a>>),
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+/// Months are predefined by the standard styles.
+const MONTHS: [(&str, &str); 12] = [
+    ("jan", "January"),
+    ("feb", "February"),
+    ("mar", "March"),
+    ("apr", "April"),
+    ("may", "May"),
+    ("jun", "June"),
+    ("jul", "July"),
+    ("aug", "August"),
+    ("sep", "September"),
+    ("oct", "October"),
+    ("nov", "November"),
+    ("dec", "December"),
+];
+
+fn is_identifier_char(c: char) -> bool {
+    !c.is_whitespace() && !"\"#%'(),={}@".contains(c)
+}
+
+fn identifier(input: &str) -> Res<&str, &str> {
+    context("identifier", take_while1(is_identifier_char))(input)
+}
+
+fn ws(input: &str) -> Res<&str, ()> {
+    value((), multispace0)(input)
+}
+
+/// Matches the inside of a `{...}` group, keeping nested braces.
+fn balanced(input: &str) -> Res<&str, &str> {
+    let mut depth = 0usize;
+    for (i, c) in input.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Ok((&input[i..], &input[..i])),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    Err(NomErr::Error(VerboseError { errors: vec![] }))
+}
+
+fn braced(input: &str) -> Res<&str, &str> {
+    context("braced value", delimited(char('{'), balanced, char('}')))(input)
+}
+
+/// Matches the inside of a `"..."` value, which ends at the first `"` that
+/// is not inside braces.
+fn quoted_inner(input: &str) -> Res<&str, &str> {
+    let mut depth = 0usize;
+    for (i, c) in input.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            '"' if depth == 0 => return Ok((&input[i..], &input[..i])),
+            _ => {}
+        }
+    }
+    Err(NomErr::Error(VerboseError { errors: vec![] }))
+}
+
+fn quoted(input: &str) -> Res<&str, &str> {
+    context(
+        "quoted value",
+        delimited(char('"'), quoted_inner, char('"')),
+    )(input)
+}
+
+fn piece(input: &str) -> Res<&str, Piece<'_>> {
+    alt((
+        map(braced, Piece::Literal),
+        map(quoted, Piece::Literal),
+        map(digit1, Piece::Literal),
+        map(identifier, Piece::Macro),
+    ))(input)
+}
+
+fn field_value(input: &str) -> Res<&str, Vec<Piece<'_>>> {
+    context("value", separated_list1(tuple((ws, char('#'), ws)), piece))(input)
+}
+
+fn field(input: &str) -> Res<&str, Field<'_>> {
+    context(
+        "field",
+        tuple((
+            identifier,
+            preceded(tuple((ws, char('='), ws)), field_value),
+        )),
+    )(input)
+}
+
+/// Entries may be delimited by either braces or parentheses.
+fn body<'a, O>(
+    mut inner: impl FnMut(&'a str) -> Res<&'a str, O>,
+) -> impl FnMut(&'a str) -> Res<&'a str, O> {
+    move |input| {
+        let (input, open) = preceded(ws, alt((char('{'), char('('))))(input)?;
+        let close = if open == '{' { '}' } else { ')' };
+        let (input, _) = ws(input)?;
+        let (input, result) = inner(input)?;
+        let (input, _) = preceded(ws, char(close))(input)?;
+        Ok((input, result))
+    }
+}
+
+fn entry_fields(input: &str) -> Res<&str, (&str, Vec<Field<'_>>)> {
+    tuple((
+        take_till(|c: char| c == ',' || c.is_whitespace() || c == '}' || c == ')'),
+        terminated(
+            many0(preceded(tuple((ws, char(','), ws)), field)),
+            opt(preceded(ws, char(','))),
+        ),
+    ))(input)
+}
+
+fn item(input: &str) -> Res<&str, Item<'_>> {
+    let (rest, kind) = context("entry type", preceded(char('@'), preceded(ws, identifier)))(input)?;
+    match kind.to_lowercase().as_str() {
+        "comment" => context(
+            "comment",
+            alt((
+                value(Item::Comment, braced),
+                value(Item::Comment, not_line_ending),
+            )),
+        )(rest.trim_start()),
+        "string" => context(
+            "string",
+            map(body(field), |(name, value)| Item::String(name, value)),
+        )(rest),
+        "preamble" => context("preamble", map(body(field_value), Item::Preamble))(rest),
+        _ => context(
+            "entry",
+            map(body(entry_fields), move |(key, fields)| {
+                Item::Entry(kind, key, fields)
+            }),
+        )(rest),
+    }
+}
+
+/// Text outside of `@` entries is ignored by BibTeX, as are `%` line
+/// comments.
+fn junk(input: &str) -> Res<&str, ()> {
+    value(
+        (),
+        many0(alt((
+            value((), pair(char('%'), not_line_ending)),
+            value((), is_not("@%")),
+        ))),
+    )(input)
+}
+
+fn resolve(pieces: &[Piece], macros: &HashMap<String, String>) -> Result<String, String> {
+    let mut resolved = String::new();
+    for piece in pieces {
+        match piece {
+            Piece::Literal(literal) => resolved.push_str(literal),
+            Piece::Macro(name) => match macros.get(&name.to_lowercase()) {
+                Some(expansion) => resolved.push_str(expansion),
+                None => return Err(name.to_string()),
+            },
+        }
+    }
+    Ok(resolved)
+}
+
+/// Parses a whole `.bib` file. `@string` macros are expanded in the order
+/// they are defined, so a macro must be defined before it is used.
+pub fn bibliography(input: &str) -> Res<&str, Bibliography> {
+    let mut macros: HashMap<String, String> = MONTHS
+        .iter()
+        .map(|(short, long)| (short.to_string(), long.to_string()))
+        .collect();
+    let mut bibliography = Bibliography::default();
+
+    let (mut input, _) = junk(input)?;
+    while !input.is_empty() {
+        let start = input;
+        let (rest, item) = item(input)?;
+        let undefined = |_| {
+            NomErr::Failure(VerboseError {
+                errors: vec![(
+                    start,
+                    nom::error::VerboseErrorKind::Context("undefined macro"),
+                )],
+            })
+        };
+        match item {
+            Item::Comment => {}
+            Item::String(name, value) => {
+                let value = resolve(&value, &macros).map_err(undefined)?;
+                macros.insert(name.to_lowercase(), value);
+            }
+            Item::Preamble(value) => {
+                bibliography
+                    .preambles
+                    .push(resolve(&value, &macros).map_err(undefined)?);
+            }
+            Item::Entry(kind, key, fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, value)| Ok((name.to_lowercase(), resolve(value, &macros)?)))
+                    .collect::<Result<Vec<_>, String>>()
+                    .map_err(undefined)?;
+                bibliography.entries.push(Entry {
+                    entry_type: kind.into(),
+                    key: key.to_owned(),
+                    fields,
+                });
+            }
+        }
+        let (rest, _) = junk(rest)?;
+        input = rest;
+    }
+
+    Ok((input, bibliography))
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::error::VerboseErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn test_braced() {
+        assert_eq!(braced("{The {RNA} World}"), Ok(("", "The {RNA} World")));
+        assert_eq!(braced("{}, x"), Ok((", x", "")));
+        assert!(braced("{unbalanced").is_err());
+    }
+
+    #[test]
+    fn test_quoted() {
+        assert_eq!(quoted(r#""a {"} b""#), Ok(("", r#"a {"} b"#)));
+    }
+
+    #[test]
+    fn test_field_value() {
+        assert_eq!(
+            field_value(r#"jan # " 1" # 2023,"#),
+            Ok((
+                ",",
+                vec![
+                    Piece::Macro("jan"),
+                    Piece::Literal(" 1"),
+                    Piece::Literal("2023")
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_entry() {
+        let input = r#"@Article{knuth1984,
+            Author = {Donald E. Knuth},
+            title  = "Literate {P}rogramming",
+            year   = 1984,
+        }"#;
+        let (rest, bib) = bibliography(input).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            bib.entries,
+            vec![Entry {
+                entry_type: EntryType::Article,
+                key: "knuth1984".to_owned(),
+                fields: vec![
+                    ("author".to_owned(), "Donald E. Knuth".to_owned()),
+                    ("title".to_owned(), "Literate {P}rogramming".to_owned()),
+                    ("year".to_owned(), "1984".to_owned()),
+                ],
+            }]
+        );
+        assert_eq!(bib.entries[0].get("Title"), Some("Literate {P}rogramming"));
+    }
+
+    #[test]
+    fn test_macros_and_comments() {
+        let input = r#"
+            % a line comment
+            Free text is ignored.
+            @comment{ anything {goes} here }
+            @string{acm = "ACM"}
+            @STRING(cacm = acm # " Communications")
+            @preamble{"\newcommand{\noop}[1]{}"}
+            @misc(note1, publisher = cacm, month = feb)
+        "#;
+        let (_, bib) = bibliography(input).unwrap();
+        assert_eq!(bib.preambles, vec![r"\newcommand{\noop}[1]{}".to_owned()]);
+        let entry = bib.get("note1").unwrap();
+        assert_eq!(entry.entry_type, EntryType::Misc);
+        assert_eq!(entry.get("publisher"), Some("ACM Communications"));
+        assert_eq!(entry.get("month"), Some("February"));
+    }
+
+    #[test]
+    fn test_other_entry_type() {
+        let (_, bib) = bibliography("@online{web, url = {https://x.org}}").unwrap();
+        assert_eq!(
+            bib.entries[0].entry_type,
+            EntryType::Other("online".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_undefined_macro() {
+        let input = "@misc{a, title = nope}";
+        assert_eq!(
+            bibliography(input),
+            Err(NomErr::Failure(VerboseError {
+                errors: vec![(input, VerboseErrorKind::Context("undefined macro"))]
+            }))
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! An interactive shell for trying out the parsers. Pick a format with
+//! `:mode NAME` and every other line is parsed with it, printing the result
+//! or the error with the offending input underlined.
+
+use std::fmt::Debug;
+use std::io::{self, BufRead, Write};
+
+use parsers::error::parse_all;
+use parsers::http::{nested_query_params, UriBuf};
+use parsers::json::JsonValue;
+use parsers::money::Money;
+use parsers::pep508::Requirement;
+use parsers::phone::PhoneNumber;
+use parsers::quantity::Quantity;
+use parsers::Error;
+
+type Mode = (&'static str, fn(&str) -> Result<String, Error>);
+
+fn show<T: Debug>(result: Result<T, Error>) -> Result<String, Error> {
+    result.map(|value| format!("{:#?}", value))
+}
+
+const MODES: &[Mode] = &[
+    ("json", |input| show(input.parse::<JsonValue>())),
+    ("uri", |input| {
+        input
+            .parse::<UriBuf>()
+            .map(|buf| format!("{:#?}", buf.uri()))
+    }),
+    ("query", |input| {
+        show(parse_all("query", input, nested_query_params))
+    }),
+    ("irc", |input| {
+        show(parse_all("irc", input, parsers::irc::message))
+    }),
+    ("regex", |input| {
+        show(parse_all("regex", input, parsers::regex::pattern))
+    }),
+    ("money", |input| show(input.parse::<Money>())),
+    ("quantity", |input| show(input.parse::<Quantity>())),
+    ("phone", |input| show(input.parse::<PhoneNumber>())),
+    ("pep508", |input| show(input.parse::<Requirement>())),
+];
+
+const HELP: &str = ":mode NAME  switch format
+:modes      list formats
+:help       show this help
+:quit       exit";
+
+struct Repl {
+    mode: &'static Mode,
+}
+
+impl Repl {
+    fn new() -> Self {
+        Repl { mode: &MODES[0] }
+    }
+
+    fn prompt(&self) -> String {
+        format!("{}> ", self.mode.0)
+    }
+
+    /// Handles one line of input, returning what to print, or `None` to
+    /// exit.
+    fn handle(&mut self, line: &str) -> Option<String> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let mut words = line.split_whitespace();
+        let output = match words.next() {
+            None => String::new(),
+            Some(":quit" | ":q") => return None,
+            Some(":help") => HELP.to_string(),
+            Some(":modes") => MODES
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(" "),
+            Some(":mode") => match words.next() {
+                Some(name) => match MODES.iter().find(|(mode, _)| *mode == name) {
+                    Some(mode) => {
+                        self.mode = mode;
+                        format!("mode: {}", name)
+                    }
+                    None => format!("unknown mode `{}`; try :modes", name),
+                },
+                None => format!("mode: {}", self.mode.0),
+            },
+            Some(command) if command.starts_with(':') => {
+                format!("unknown command `{}`; try :help", command)
+            }
+            Some(_) => match (self.mode.1)(line) {
+                Ok(output) => output,
+                Err(error) => error.render(line).trim_end().to_string(),
+            },
+        };
+        Some(output)
+    }
+}
+
+fn main() -> io::Result<()> {
+    let mut repl = Repl::new();
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut line = String::new();
+    loop {
+        write!(stdout, "{}", repl.prompt())?;
+        stdout.flush()?;
+        line.clear();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            writeln!(stdout)?;
+            return Ok(());
+        }
+        match repl.handle(&line) {
+            Some(output) if output.is_empty() => {}
+            Some(output) => writeln!(stdout, "{}", output)?,
+            None => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repl() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.prompt(), "json> ");
+        assert_eq!(repl.handle("null\n").as_deref(), Some("Null"));
+        assert_eq!(repl.handle(":mode uri").as_deref(), Some("mode: uri"));
+        assert_eq!(repl.prompt(), "uri> ");
+        assert_eq!(
+            repl.handle("http:x").as_deref(),
+            Some(
+                "error: expected literal, found `x`\n \
+                 --> uri:1:6\n  \
+                 |\n\
+                 1 | http:x\n  \
+                 |      ^\n  \
+                 = while parsing uri > scheme"
+            )
+        );
+        assert!(repl
+            .handle(":mode yaml")
+            .unwrap()
+            .starts_with("unknown mode"));
+        assert_eq!(repl.handle(""), Some(String::new()));
+        assert_eq!(repl.handle(":quit"), None);
+    }
+}
@@ -0,0 +1,297 @@
+//! An HTTP server exposing the parsers, for callers not written in Rust.
+//! Built with the `server` feature.
+//!
+//! ```text
+//! parsers-server [ADDRESS]
+//!
+//! GET  /formats
+//! POST /parse/FORMAT
+//! POST /convert?from=FORMAT&to=json|toml
+//! ```
+//!
+//! FORMAT is any format in the registry, `uri`, or `auto` to detect it.
+//! Responses are JSON. Input that doesn't parse gets a `422` whose body
+//! describes the error, including where in the input it is. The server
+//! listens on 127.0.0.1:8080 unless given an address, and handles one
+//! request per connection.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::ExitCode;
+
+use parsers::http::{nested_query_params, UriBuf};
+use parsers::json::JsonValue;
+use parsers::pretty::{Indent, PrettyPrint};
+use parsers::registry::Registry;
+use parsers::{Error, FromValue, ToValue, Value};
+
+const MAX_HEAD_LEN: usize = 64 << 10;
+const MAX_BODY_LEN: usize = 16 << 20;
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json(status: u16, value: &JsonValue) -> Self {
+        Response {
+            status,
+            content_type: "application/json",
+            body: value.to_pretty_string(Indent::compact()),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        let body = JsonValue::Object(vec![(
+            "error".to_string(),
+            JsonValue::Object(vec![(
+                "message".to_string(),
+                JsonValue::String(message.to_string()),
+            )]),
+        )]);
+        Response::json(status, &body)
+    }
+
+    /// A `422` describing a parse error in `input`.
+    fn diagnostic(error: &Error, input: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(input);
+        let string = |s: &str| JsonValue::String(s.to_string());
+        let number = |n: usize| JsonValue::Number(n as f64);
+        let fields = vec![
+            ("format", string(error.format)),
+            ("message", string(&error.to_string())),
+            ("start", number(error.span.start)),
+            ("end", number(error.span.end)),
+            ("line", number(error.line)),
+            ("column", number(error.column)),
+            (
+                "expected",
+                error.expected.as_deref().map_or(JsonValue::Null, string),
+            ),
+            ("found", string(&error.found)),
+            (
+                "context",
+                JsonValue::Array(error.context.iter().map(|c| string(c)).collect()),
+            ),
+            ("rendered", string(&error.render(&text))),
+        ];
+        let error = fields
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+        let body = JsonValue::Object(vec![("error".to_string(), JsonValue::Object(error))]);
+        Response::json(422, &body)
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            422 => "Unprocessable Entity",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// Parses `input` as `format`, giving the format's name with the value.
+fn parse(registry: &Registry, format: &str, input: &[u8]) -> Result<(String, Value), Error> {
+    match format {
+        "uri" => {
+            let text = String::from_utf8_lossy(input);
+            let buf = text.trim().parse::<UriBuf>()?;
+            Ok(("uri".to_string(), buf.uri().to_value()))
+        }
+        "auto" => registry
+            .parse_any(input)
+            .map(|(format, value)| (format.name().to_string(), value)),
+        name => Ok((name.to_lowercase(), registry.parse(name, input)?)),
+    }
+}
+
+fn to_json(value: Value) -> JsonValue {
+    JsonValue::from_value(value).expect("every value converts to JSON")
+}
+
+fn handle(registry: &Registry, method: &str, target: &str, body: &[u8]) -> Response {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    match (method, path) {
+        ("GET", "/formats") => {
+            let mut names: Vec<_> = registry.formats().map(|f| f.name().to_string()).collect();
+            names.push("uri".to_string());
+            Response::json(200, &to_json(names.to_value()))
+        }
+        ("POST", path) if path.starts_with("/parse/") => {
+            match parse(registry, &path["/parse/".len()..], body) {
+                Ok((_, value)) => Response::json(200, &to_json(value)),
+                Err(error) => Response::diagnostic(&error, body),
+            }
+        }
+        ("POST", "/convert") => {
+            let params = match nested_query_params(query) {
+                Ok(("", JsonValue::Object(params))) => params,
+                _ => return Response::error(400, "expected a query such as `from=json&to=toml`"),
+            };
+            let param = |name: &str| match params.iter().find(|(key, _)| key == name) {
+                Some((_, JsonValue::String(value))) => value.as_str(),
+                _ => "auto",
+            };
+            let (from, to) = (param("from"), param("to"));
+            let value = match parse(registry, from, body) {
+                Ok((_, value)) => value,
+                Err(error) => return Response::diagnostic(&error, body),
+            };
+            match to {
+                "json" | "auto" => Response::json(200, &to_json(value)),
+                "toml" => Response {
+                    status: 200,
+                    content_type: "application/toml",
+                    body: value.to_pretty_string(Indent::default()),
+                },
+                other => Response::error(400, &format!("can't convert to `{}`", other)),
+            }
+        }
+        (_, "/formats" | "/convert") => Response::error(405, "method not allowed"),
+        (_, path) if path.starts_with("/parse/") => Response::error(405, "method not allowed"),
+        _ => Response::error(404, "not found"),
+    }
+}
+
+/// Reads one request: the request line, headers up to a blank line, and a
+/// body of `Content-Length` bytes.
+fn read_request(stream: &mut BufReader<TcpStream>) -> Result<(String, String, Vec<u8>), Response> {
+    let bad = |message: &str| Response::error(400, message);
+    let mut head = String::new();
+    let mut content_length = 0;
+    loop {
+        let start = head.len();
+        let read = stream
+            .by_ref()
+            .take((MAX_HEAD_LEN - start) as u64)
+            .read_line(&mut head)
+            .map_err(|_| bad("expected a UTF-8 request head"))?;
+        let line = head[start..].trim_end_matches(['\r', '\n']);
+        if read == 0 || !head.ends_with('\n') {
+            return Err(bad("request head is incomplete or too long"));
+        }
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| bad("invalid Content-Length"))?;
+            }
+        }
+    }
+    if content_length > MAX_BODY_LEN {
+        return Err(Response::error(413, "request body is too large"));
+    }
+    let mut parts = head.lines().next().unwrap_or("").split(' ');
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(bad(
+            "expected a request line such as `POST /parse/json HTTP/1.1`",
+        ));
+    };
+    let mut body = vec![0; content_length];
+    stream
+        .read_exact(&mut body)
+        .map_err(|_| bad("request body is shorter than its Content-Length"))?;
+    Ok((method.to_string(), target.to_string(), body))
+}
+
+fn serve(registry: &Registry, stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let response = match read_request(&mut reader) {
+        Ok((method, target, body)) => handle(registry, &method, &target, &body),
+        Err(response) => response,
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+fn main() -> ExitCode {
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let listener = match TcpListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("error: can't listen on {}: {}", address, error);
+            return ExitCode::FAILURE;
+        }
+    };
+    eprintln!("listening on http://{}", address);
+    let registry = Registry::default();
+    let registry = &registry;
+    std::thread::scope(|scope| {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    scope.spawn(move || {
+                        if let Err(error) = serve(registry, stream) {
+                            eprintln!("error: {}", error);
+                        }
+                    });
+                }
+                Err(error) => eprintln!("error: {}", error),
+            }
+        }
+    });
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle() {
+        let registry = Registry::default();
+        let response = handle(&registry, "POST", "/parse/json", br#"{"a": [1, 2]}"#);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, r#"{"a":[1,2]}"#);
+
+        let response = handle(
+            &registry,
+            "POST",
+            "/parse/uri",
+            b"https://example.com:8080/a?b=c",
+        );
+        assert_eq!(response.status, 200);
+        assert!(response.body.contains(r#""port":8080"#));
+
+        let response = handle(&registry, "POST", "/parse/json", b"[1]\n x");
+        assert_eq!(response.status, 422);
+        assert!(response.body.contains(r#""line":2,"column":2"#));
+        assert!(response.body.contains(r#""found":"`x`""#));
+
+        let response = handle(
+            &registry,
+            "POST",
+            "/convert?from=json&to=toml",
+            br#"{"a": 1}"#,
+        );
+        assert_eq!((response.status, response.body.trim()), (200, "a = 1"));
+        let response = handle(&registry, "POST", "/convert?from=json&to=xml", b"1");
+        assert_eq!(response.status, 400);
+        assert_eq!(handle(&registry, "GET", "/parse/json", b"").status, 405);
+        assert_eq!(handle(&registry, "GET", "/nope", b"").status, 404);
+    }
+}
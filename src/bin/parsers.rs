@@ -0,0 +1,219 @@
+//! Command-line front end for the library.
+//!
+//! ```text
+//! parsers json fmt [--compact] [FILE]
+//! parsers json validate [FILE]
+//! parsers uri inspect [URI...]
+//! parsers query to-json [QUERY]
+//! parsers parse [--format NAME] [FILE]
+//! ```
+//!
+//! Input comes from FILE or the arguments, or from stdin when they are
+//! omitted or `-`. Results go to stdout as JSON; errors go to stderr with the
+//! offending input underlined.
+
+use std::io::{self, Read};
+use std::process::ExitCode;
+
+use parsers::encoding::{self, Policy};
+use parsers::http::{nested_query_params, UriBuf};
+use parsers::json::JsonValue;
+use parsers::pretty::{Indent, PrettyPrint};
+use parsers::registry::Registry;
+use parsers::Error;
+use parsers::FromValue;
+
+const USAGE: &str = "usage:
+    parsers json fmt [--compact] [FILE]
+    parsers json validate [FILE]
+    parsers uri inspect [URI...]
+    parsers query to-json [QUERY]
+    parsers parse [--format NAME] [FILE]";
+
+enum Failure {
+    Usage(String),
+    Io(io::Error),
+    Parse(Box<Error>, String),
+}
+
+impl From<io::Error> for Failure {
+    fn from(error: io::Error) -> Self {
+        Failure::Io(error)
+    }
+}
+
+fn read_bytes(path: Option<&str>) -> io::Result<Vec<u8>> {
+    match path {
+        None | Some("-") => {
+            let mut bytes = vec![];
+            io::stdin().read_to_end(&mut bytes)?;
+            Ok(bytes)
+        }
+        Some(path) => std::fs::read(path),
+    }
+}
+
+/// Reads the input and transcodes it to UTF-8, so UTF-16 and Latin-1 files
+/// work too.
+fn read_input(path: Option<&str>) -> io::Result<String> {
+    let bytes = read_bytes(path)?;
+    match encoding::decode(&bytes, Policy::Strict) {
+        Ok(decoded) => Ok(decoded.text.into_owned()),
+        Err(error) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            error.to_string(),
+        )),
+    }
+}
+
+fn print_json(value: &JsonValue, indent: Indent) {
+    println!("{}", value.to_pretty_string(indent));
+}
+
+fn json(args: &[String]) -> Result<(), Failure> {
+    let (command, rest) = args
+        .split_first()
+        .ok_or_else(|| Failure::Usage("missing json command".to_string()))?;
+    let compact = rest.iter().any(|arg| arg == "--compact");
+    let paths: Vec<&str> = rest
+        .iter()
+        .map(String::as_str)
+        .filter(|arg| *arg != "--compact")
+        .collect();
+    if paths.len() > 1 {
+        return Err(Failure::Usage("expected at most one file".to_string()));
+    }
+    let input = read_input(paths.first().copied())?;
+    let value = input
+        .parse::<JsonValue>()
+        .map_err(|error| Failure::Parse(Box::new(error), input.clone()))?;
+    match command.as_str() {
+        "fmt" => print_json(
+            &value,
+            if compact {
+                Indent::compact()
+            } else {
+                Indent::default()
+            },
+        ),
+        "validate" => println!("ok"),
+        other => return Err(Failure::Usage(format!("unknown json command `{}`", other))),
+    }
+    Ok(())
+}
+
+fn uri(args: &[String]) -> Result<(), Failure> {
+    match args.first().map(String::as_str) {
+        Some("inspect") => {}
+        Some(other) => return Err(Failure::Usage(format!("unknown uri command `{}`", other))),
+        None => return Err(Failure::Usage("missing uri command".to_string())),
+    }
+    let inputs: Vec<String> = match &args[1..] {
+        [] => read_input(None)?.lines().map(String::from).collect(),
+        uris => uris.to_vec(),
+    };
+    for input in inputs.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        let buf = input
+            .parse::<UriBuf>()
+            .map_err(|error| Failure::Parse(Box::new(error), input.to_string()))?;
+        print_json(&buf.uri().to_json(), Indent::default());
+    }
+    Ok(())
+}
+
+fn query(args: &[String]) -> Result<(), Failure> {
+    match args.first().map(String::as_str) {
+        Some("to-json") => {}
+        Some(other) => return Err(Failure::Usage(format!("unknown query command `{}`", other))),
+        None => return Err(Failure::Usage("missing query command".to_string())),
+    }
+    let input = match args.get(1) {
+        Some(query) if query != "-" => query.clone(),
+        _ => read_input(None)?,
+    };
+    let input = input.trim();
+    match nested_query_params(input) {
+        Ok(("", value)) => {
+            print_json(&value, Indent::default());
+            Ok(())
+        }
+        Ok((rest, _)) => Err(Failure::Parse(
+            Box::new(Error::trailing("query", input, rest)),
+            input.to_string(),
+        )),
+        Err(error) => Err(Failure::Parse(
+            Box::new(Error::from_verbose("query", input, error)),
+            input.to_string(),
+        )),
+    }
+}
+
+/// Parses any format in the registry, detecting it unless `--format` is
+/// given, and prints the result as JSON.
+fn parse(args: &[String]) -> Result<(), Failure> {
+    let (format, paths) = match args {
+        [flag, name, rest @ ..] if flag == "--format" => (Some(name.as_str()), rest),
+        rest => (None, rest),
+    };
+    if paths.len() > 1 {
+        return Err(Failure::Usage("expected at most one file".to_string()));
+    }
+    let input = read_bytes(paths.first().map(String::as_str))?;
+    let registry = Registry::default();
+    let value = match format {
+        Some(name) => registry.parse(name, &input),
+        None => registry.parse_any(&input).map(|(_, value)| value),
+    }
+    .map_err(|error| {
+        let text = String::from_utf8_lossy(&input).into_owned();
+        Failure::Parse(Box::new(error), text)
+    })?;
+    let json = JsonValue::from_value(value).expect("every value converts to JSON");
+    print_json(&json, Indent::default());
+    Ok(())
+}
+
+fn run(args: &[String]) -> Result<(), Failure> {
+    match args.split_first() {
+        Some((format, rest)) => match format.as_str() {
+            "json" => json(rest),
+            "uri" => uri(rest),
+            "query" => query(rest),
+            "parse" => parse(rest),
+            "-h" | "--help" | "help" => {
+                println!("{}", USAGE);
+                Ok(())
+            }
+            other => Err(Failure::Usage(format!("unknown format `{}`", other))),
+        },
+        None => Err(Failure::Usage("missing format".to_string())),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Usage(message)) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            ExitCode::from(2)
+        }
+        Err(Failure::Io(error)) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+        #[cfg(feature = "diagnostics")]
+        Err(Failure::Parse(error, input)) => {
+            eprint!(
+                "{}",
+                error.diagnostic(&input, io::IsTerminal::is_terminal(&io::stderr()))
+            );
+            ExitCode::FAILURE
+        }
+        #[cfg(not(feature = "diagnostics"))]
+        Err(Failure::Parse(error, input)) => {
+            eprint!("{}", error.render(&input));
+            ExitCode::FAILURE
+        }
+    }
+}
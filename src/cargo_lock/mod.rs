@@ -0,0 +1,763 @@
+//! Cargo writes lockfiles in a small, regular subset of TOML: table and
+//! array-of-table headers, and keys holding strings, integers or arrays of
+//! strings. That subset is parsed here directly, into the shared `Value`
+//! model.
+use std::fmt;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{escaped_transform, is_not, tag, take_while1},
+    character::complete::{char, digit1, line_ending, multispace1, not_line_ending, space0},
+    combinator::{eof, map, map_res, opt, recognize, value},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::{many0, separated_list0},
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+    Err as NomErr, IResult,
+};
+
+use crate::cst::{GreenNode, SyntaxKind, TokenParser};
+use crate::error::{Error, Recovered};
+use crate::limits::nested;
+use crate::value::{ToValue, Value};
+
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre: Option<String>,
+    pub build: Option<String>,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "-{}", pre)?;
+        }
+        if let Some(build) = &self.build {
+            write!(f, "+{}", build)?;
+        }
+        Ok(())
+    }
+}
+
+/// A dependency as listed in a package's `dependencies` array. The version
+/// and source are only written when they are needed to disambiguate.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Dependency {
+    pub name: String,
+    pub version: Option<Version>,
+    pub source: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Package {
+    pub name: String,
+    pub version: Version,
+    pub source: Option<String>,
+    pub checksum: Option<String>,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Lockfile {
+    pub version: Option<u32>,
+    pub packages: Vec<Package>,
+}
+
+impl Lockfile {
+    pub fn package(&self, name: &str) -> Option<&Package> {
+        self.packages.iter().find(|package| package.name == name)
+    }
+
+    /// Resolves every package's dependencies to indices into `packages`.
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        let edges = self
+            .packages
+            .iter()
+            .map(|package| {
+                package
+                    .dependencies
+                    .iter()
+                    .filter_map(|dependency| {
+                        self.packages.iter().position(|candidate| {
+                            candidate.name == dependency.name
+                                && dependency
+                                    .version
+                                    .as_ref()
+                                    .is_none_or(|v| *v == candidate.version)
+                                && dependency
+                                    .source
+                                    .as_ref()
+                                    .is_none_or(|s| Some(s) == candidate.source.as_ref())
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        DependencyGraph { edges }
+    }
+}
+
+/// The lockfile in the shape of its TOML, with dependencies written back
+/// as `"name version (source)"` strings.
+impl ToValue for Lockfile {
+    fn to_value(&self) -> Value {
+        let mut table = vec![];
+        if let Some(version) = self.version {
+            table.push(("version".to_string(), Value::Integer(version.into())));
+        }
+        let packages = self.packages.iter().map(|package| {
+            let mut fields = vec![
+                ("name".to_string(), package.name.to_value()),
+                (
+                    "version".to_string(),
+                    package.version.to_string().to_value(),
+                ),
+            ];
+            if let Some(source) = &package.source {
+                fields.push(("source".to_string(), source.to_value()));
+            }
+            if let Some(checksum) = &package.checksum {
+                fields.push(("checksum".to_string(), checksum.to_value()));
+            }
+            if !package.dependencies.is_empty() {
+                let dependencies = package.dependencies.iter().map(|dependency| {
+                    let mut s = dependency.name.clone();
+                    if let Some(version) = &dependency.version {
+                        s += &format!(" {}", version);
+                    }
+                    if let Some(source) = &dependency.source {
+                        s += &format!(" ({})", source);
+                    }
+                    Value::String(s)
+                });
+                fields.push((
+                    "dependencies".to_string(),
+                    Value::Array(dependencies.collect()),
+                ));
+            }
+            Value::Table(fields)
+        });
+        table.push(("package".to_string(), Value::Array(packages.collect())));
+        Value::Table(table)
+    }
+}
+
+/// Edges between packages, by index into [`Lockfile::packages`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DependencyGraph {
+    pub edges: Vec<Vec<usize>>,
+}
+
+impl DependencyGraph {
+    pub fn dependencies(&self, package: usize) -> &[usize] {
+        &self.edges[package]
+    }
+
+    pub fn dependents(&self, package: usize) -> Vec<usize> {
+        (0..self.edges.len())
+            .filter(|i| self.edges[*i].contains(&package))
+            .collect()
+    }
+
+    /// Every package reachable from `package`, in the order first found.
+    pub fn transitive_dependencies(&self, package: usize) -> Vec<usize> {
+        let mut seen = vec![false; self.edges.len()];
+        let mut order = vec![];
+        let mut stack = vec![package];
+        seen[package] = true;
+        while let Some(current) = stack.pop() {
+            if current != package {
+                order.push(current);
+            }
+            for &next in self.edges[current].iter().rev() {
+                if !seen[next] {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        order
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Line<'a> {
+    Table(&'a str),
+    ArrayTable(&'a str),
+    KeyValue(&'a str, Value),
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+fn number(input: &str) -> Res<&str, u64> {
+    map_res(digit1, str::parse)(input)
+}
+
+fn identifiers(input: &str) -> Res<&str, &str> {
+    take_while1(|c: char| c.is_ascii_alphanumeric() || c == '-' || c == '.')(input)
+}
+
+/// Parses a semantic version such as `1.0.0-beta.1+build.5`.
+pub fn version(input: &str) -> Res<&str, Version> {
+    context(
+        "version",
+        tuple((
+            number,
+            preceded(char('.'), number),
+            preceded(char('.'), number),
+            opt(preceded(char('-'), identifiers)),
+            opt(preceded(char('+'), identifiers)),
+        )),
+    )(input)
+    .map(|(next_input, (major, minor, patch, pre, build))| {
+        (
+            next_input,
+            Version {
+                major,
+                minor,
+                patch,
+                pre: pre.map(str::to_owned),
+                build: build.map(str::to_owned),
+            },
+        )
+    })
+}
+
+fn string(input: &str) -> Res<&str, String> {
+    context(
+        "string",
+        delimited(
+            char('"'),
+            map(
+                opt(escaped_transform(
+                    is_not("\\\""),
+                    '\\',
+                    alt((value("\\", char('\\')), value("\"", char('"')))),
+                )),
+                Option::unwrap_or_default,
+            ),
+            char('"'),
+        ),
+    )(input)
+}
+
+/// Whitespace, newlines and comments between array elements.
+fn array_space(input: &str) -> Res<&str, ()> {
+    value(
+        (),
+        many0(alt((
+            value((), multispace1),
+            value((), pair(char('#'), not_line_ending)),
+        ))),
+    )(input)
+}
+
+fn toml_value(input: &str) -> Res<&str, Value> {
+    context(
+        "value",
+        alt((
+            map(string, Value::String),
+            map(
+                map_res(recognize(pair(opt(char('-')), digit1)), str::parse),
+                Value::Integer,
+            ),
+            map(
+                nested(
+                    char('['),
+                    delimited(
+                        pair(char('['), array_space),
+                        terminated(
+                            separated_list0(
+                                tuple((array_space, char(','), array_space)),
+                                toml_value,
+                            ),
+                            pair(array_space, opt(pair(char(','), array_space))),
+                        ),
+                        char(']'),
+                    ),
+                ),
+                Value::Array,
+            ),
+        )),
+    )(input)
+}
+
+fn line(input: &str) -> Res<&str, Line<'_>> {
+    let key = alt((
+        take_while1(|c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        delimited(char('"'), is_not("\"\n"), char('"')),
+    ));
+    alt((
+        map(
+            delimited(tag("[["), is_not("]\n"), tag("]]")),
+            Line::ArrayTable,
+        ),
+        map(delimited(char('['), is_not("]\n"), char(']')), Line::Table),
+        map(
+            separated_pair(key, tuple((space0, char('='), space0)), toml_value),
+            |(key, value)| Line::KeyValue(key, value),
+        ),
+    ))(input)
+}
+
+/// Blank lines and comments between statements.
+fn blank(input: &str) -> Res<&str, ()> {
+    value(
+        (),
+        many0(alt((
+            value((), pair(space0, line_ending)),
+            value((), tuple((space0, char('#'), not_line_ending))),
+            value((), multispace1),
+        ))),
+    )(input)
+}
+
+fn dependency(spec: &str) -> Option<Dependency> {
+    let mut parts = spec.splitn(3, ' ');
+    let name = parts.next()?.to_owned();
+    let version = match parts.next() {
+        Some(text) => Some(all_version(text)?),
+        None => None,
+    };
+    let source = match parts.next() {
+        Some(text) => Some(text.strip_prefix('(')?.strip_suffix(')')?.to_owned()),
+        None => None,
+    };
+    Some(Dependency {
+        name,
+        version,
+        source,
+    })
+}
+
+fn all_version(text: &str) -> Option<Version> {
+    terminated(version, eof)(text).ok().map(|(_, v)| v)
+}
+
+fn package<'a>(
+    start: &'a str,
+    entries: Vec<(&str, Value)>,
+) -> Result<Package, NomErr<VerboseError<&'a str>>> {
+    let mut name = None;
+    let mut package_version = None;
+    let mut source = None;
+    let mut checksum = None;
+    let mut dependencies = vec![];
+    for (key, value) in entries {
+        match (key, value) {
+            ("name", Value::String(s)) => name = Some(s),
+            ("version", Value::String(s)) => {
+                package_version =
+                    Some(all_version(&s).ok_or_else(|| failure(start, "invalid version"))?)
+            }
+            ("source", Value::String(s)) => source = Some(s),
+            ("checksum", Value::String(s)) => checksum = Some(s),
+            ("dependencies", Value::Array(items)) => {
+                for item in items {
+                    let Value::String(spec) = item else {
+                        return Err(failure(start, "invalid dependency"));
+                    };
+                    dependencies.push(
+                        dependency(&spec).ok_or_else(|| failure(start, "invalid dependency"))?,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(Package {
+        name: name.ok_or_else(|| failure(start, "package without name"))?,
+        version: package_version.ok_or_else(|| failure(start, "package without version"))?,
+        source,
+        checksum,
+        dependencies,
+    })
+}
+
+/// Parses a lockfile. When `errors` is given, a malformed line or package
+/// is recorded there and skipped instead of failing the parse.
+fn lockfile_with<'a>(
+    input: &'a str,
+    mut errors: Option<&mut Vec<NomErr<VerboseError<&'a str>>>>,
+) -> Res<&'a str, Lockfile> {
+    let mut recover = |error| match errors.as_deref_mut() {
+        Some(errors) => {
+            errors.push(error);
+            Ok(())
+        }
+        None => Err(error),
+    };
+    let mut lockfile = Lockfile::default();
+    // The start of the current `[[package]]` table and its entries.
+    let mut current: Option<(&str, Vec<(&str, Value)>)> = None;
+    let mut in_root = true;
+
+    let (mut input, _) = blank(input)?;
+    while !input.is_empty() {
+        let start = input;
+        let parsed = context("line", line)(input).and_then(|(rest, line)| {
+            let (rest, _) = preceded(space0, alt((line_ending, eof)))(rest)?;
+            Ok((rest, line))
+        });
+        let (rest, line) = match parsed {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                recover(error)?;
+                let (rest, _) = pair(not_line_ending, opt(line_ending))(input)?;
+                input = blank(rest)?.0;
+                continue;
+            }
+        };
+        match line {
+            Line::ArrayTable(name) | Line::Table(name) => {
+                if let Some((start, entries)) = current.take() {
+                    match package(start, entries) {
+                        Ok(package) => lockfile.packages.push(package),
+                        Err(error) => recover(error)?,
+                    }
+                }
+                in_root = false;
+                if matches!(line, Line::ArrayTable(_)) && name.trim() == "package" {
+                    current = Some((start, vec![]));
+                }
+            }
+            Line::KeyValue(key, value) => match (&mut current, in_root, key, value) {
+                (Some((_, entries)), _, key, value) => entries.push((key, value)),
+                (None, true, "version", Value::Integer(n)) => match u32::try_from(n) {
+                    Ok(version) => lockfile.version = Some(version),
+                    Err(_) => recover(failure(start, "invalid version"))?,
+                },
+                _ => {}
+            },
+        }
+        let (rest, _) = blank(rest)?;
+        input = rest;
+    }
+    if let Some((start, entries)) = current.take() {
+        match package(start, entries) {
+            Ok(package) => lockfile.packages.push(package),
+            Err(error) => recover(error)?,
+        }
+    }
+    Ok((input, lockfile))
+}
+
+/// Parses a `Cargo.lock` file. Tables other than `[[package]]`, such as
+/// `[metadata]`, are skipped.
+pub fn lockfile(input: &str) -> Res<&str, Lockfile> {
+    lockfile_with(input, None)
+}
+
+/// Like `lockfile`, but skips malformed lines and packages, returning the
+/// rest of the lockfile along with an error for each one.
+pub fn lockfile_recover(input: &str) -> Recovered<Lockfile> {
+    let mut errors = vec![];
+    let value = match lockfile_with(input, Some(&mut errors)) {
+        Ok((_, lockfile)) => lockfile,
+        Err(error) => {
+            errors.push(error);
+            Lockfile::default()
+        }
+    };
+    Recovered {
+        value,
+        errors: errors
+            .into_iter()
+            .map(|error| Error::from_verbose("cargo_lock", input, error))
+            .collect(),
+    }
+}
+
+fn lex(input: &str) -> Vec<(SyntaxKind, &str)> {
+    let mut tokens = vec![];
+    let mut rest = input;
+    while let Some(c) = rest.chars().next() {
+        let line_len = || rest.find(['\r', '\n']).unwrap_or(rest.len());
+        let (kind, len) = match c {
+            ' ' | '\t' => (
+                SyntaxKind::Whitespace,
+                rest.len() - rest.trim_start_matches([' ', '\t']).len(),
+            ),
+            '\n' => (SyntaxKind::Newline, 1),
+            '\r' if rest.starts_with("\r\n") => (SyntaxKind::Newline, 2),
+            '#' => (SyntaxKind::Comment, line_len()),
+            '[' => (SyntaxKind::LBracket, 1),
+            ']' => (SyntaxKind::RBracket, 1),
+            ',' => (SyntaxKind::Comma, 1),
+            '=' => (SyntaxKind::Equals, 1),
+            '"' => match string(rest) {
+                Ok((after, _)) => (SyntaxKind::String, rest.len() - after.len()),
+                Err(_) => (SyntaxKind::Error, line_len()),
+            },
+            c if c.is_ascii_alphanumeric() || "-_.".contains(c) => {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || "-_.".contains(c)))
+                    .unwrap_or(rest.len());
+                let word = &rest[..len];
+                match word
+                    .strip_prefix('-')
+                    .unwrap_or(word)
+                    .bytes()
+                    .all(|b| b.is_ascii_digit())
+                {
+                    true if word != "-" => (SyntaxKind::Number, len),
+                    _ => (SyntaxKind::BareKey, len),
+                }
+            }
+            c => (SyntaxKind::Error, c.len_utf8()),
+        };
+        tokens.push((kind, &rest[..len]));
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+/// Parses a lockfile into a lossless syntax tree. Each `[table]` or
+/// `[[table]]` header starts a `Table` or `ArrayTable` node holding the
+/// header and the `KeyValue` lines under it. Like `json::parse_json_cst`,
+/// this never fails; broken lines end up as `KeyValue` nodes containing
+/// whatever tokens they had.
+pub fn lockfile_cst(input: &str) -> GreenNode {
+    let mut p = TokenParser::new(lex(input));
+    p.builder.start_node(SyntaxKind::Root);
+    let mut in_table = false;
+    loop {
+        p.trivia();
+        match p.peek() {
+            None => break,
+            Some(SyntaxKind::LBracket) => {
+                if in_table {
+                    p.builder.finish_node();
+                }
+                in_table = true;
+                p.builder
+                    .start_node(match p.peek_nth(1) == Some(SyntaxKind::LBracket) {
+                        true => SyntaxKind::ArrayTable,
+                        false => SyntaxKind::Table,
+                    });
+                while p.peek().is_some_and(|kind| kind != SyntaxKind::Newline) {
+                    p.bump();
+                }
+            }
+            Some(_) => {
+                p.builder.start_node(SyntaxKind::KeyValue);
+                let mut depth = 0usize;
+                while let Some(kind) = p.peek() {
+                    match kind {
+                        SyntaxKind::Newline if depth == 0 => break,
+                        SyntaxKind::LBracket => depth += 1,
+                        SyntaxKind::RBracket => depth = depth.saturating_sub(1),
+                        _ => {}
+                    }
+                    p.bump();
+                }
+                p.builder.finish_node();
+            }
+        }
+    }
+    if in_table {
+        p.builder.finish_node();
+    }
+    p.builder.finish_node();
+    p.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCKFILE: &str = r#"# This file is automatically @generated by Cargo.
+# It is not intended for manual editing.
+version = 3
+
+[[package]]
+name = "memchr"
+version = "2.5.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "2dffe52ecf27772e601905b7522cb4ef790d2cc203488bbd0e2fe85fcb74566d"
+
+[[package]]
+name = "minimal-lexical"
+version = "0.2.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "nom"
+version = "7.1.3"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = [
+ "memchr",
+ "minimal-lexical",
+]
+
+[[package]]
+name = "parsers"
+version = "0.1.0-alpha.1"
+dependencies = [
+ "nom 7.1.3 (registry+https://github.com/rust-lang/crates.io-index)",
+]
+
+[metadata]
+"checksum x" = "y"
+"#;
+
+    #[test]
+    fn test_version() {
+        assert_eq!(
+            version("1.0.0-beta.1+build.5"),
+            Ok((
+                "",
+                Version {
+                    major: 1,
+                    minor: 0,
+                    patch: 0,
+                    pre: Some("beta.1".to_owned()),
+                    build: Some("build.5".to_owned()),
+                }
+            ))
+        );
+        assert_eq!(
+            version("0.2.1").map(|(_, v)| v.to_string()),
+            Ok("0.2.1".to_owned())
+        );
+        assert!(version("1.2").is_err());
+    }
+
+    #[test]
+    fn test_toml_value() {
+        assert_eq!(
+            toml_value("[\n \"a\", # comment\n \"b\",\n]"),
+            Ok((
+                "",
+                Value::Array(vec![
+                    Value::String("a".to_owned()),
+                    Value::String("b".to_owned())
+                ])
+            ))
+        );
+        assert_eq!(toml_value("[]"), Ok(("", Value::Array(vec![]))));
+        assert_eq!(
+            toml_value(r#""a\"b""#),
+            Ok(("", Value::String("a\"b".to_owned())))
+        );
+    }
+
+    #[test]
+    fn test_lockfile() {
+        let (rest, lockfile) = lockfile(LOCKFILE).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(lockfile.version, Some(3));
+        assert_eq!(lockfile.packages.len(), 4);
+
+        let nom = lockfile.package("nom").unwrap();
+        assert_eq!(nom.version.to_string(), "7.1.3");
+        assert_eq!(nom.checksum, None);
+        assert_eq!(nom.dependencies.len(), 2);
+
+        let parsers = lockfile.package("parsers").unwrap();
+        assert_eq!(parsers.source, None);
+        assert_eq!(parsers.version.pre, Some("alpha.1".to_owned()));
+        assert_eq!(
+            parsers.dependencies,
+            vec![Dependency {
+                name: "nom".to_owned(),
+                version: version("7.1.3").ok().map(|(_, v)| v),
+                source: Some("registry+https://github.com/rust-lang/crates.io-index".to_owned()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_to_value() {
+        let (_, lockfile) = lockfile(LOCKFILE).unwrap();
+        let value = lockfile.to_value();
+        assert_eq!(value.get("version"), Some(&Value::Integer(3)));
+        let Some(Value::Array(packages)) = value.get("package") else {
+            panic!("no packages");
+        };
+        assert_eq!(
+            packages[3].get("dependencies"),
+            Some(&Value::Array(vec![Value::String(
+                "nom 7.1.3 (registry+https://github.com/rust-lang/crates.io-index)".to_owned()
+            )]))
+        );
+        let json: crate::JsonValue = crate::value::convert(&lockfile).unwrap();
+        assert!(matches!(json, crate::JsonValue::Object(_)));
+    }
+
+    #[test]
+    fn test_dependency_graph() {
+        let (_, lockfile) = lockfile(LOCKFILE).unwrap();
+        let graph = lockfile.dependency_graph();
+        assert_eq!(graph.dependencies(2), &[0, 1]);
+        assert_eq!(graph.dependencies(3), &[2]);
+        assert_eq!(graph.dependents(0), vec![2]);
+        assert_eq!(graph.transitive_dependencies(3), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn test_lockfile_recover() {
+        let input = "version = 3\n\n[[package]]\nname = \"a\"\n\n[[package]]\nname = \"b\"\nversion = \"1.0.0\"\nsource = oops\n";
+        let result = lockfile_recover(input);
+        assert_eq!(result.value.version, Some(3));
+        assert_eq!(result.value.packages.len(), 1);
+        assert_eq!(result.value.packages[0].name, "b");
+        let contexts: Vec<_> = result.errors.iter().map(|e| e.context.clone()).collect();
+        assert_eq!(
+            contexts,
+            vec![vec!["package without version"], vec!["line", "value"]]
+        );
+        assert!(lockfile_recover(LOCKFILE).into_result().is_ok());
+    }
+
+    #[test]
+    fn test_lockfile_cst() {
+        use crate::cst::{GreenToken, SyntaxNode};
+
+        let root = SyntaxNode::new_root(lockfile_cst(LOCKFILE));
+        assert_eq!(root.to_string(), LOCKFILE);
+        let kinds: Vec<_> = root.children().iter().map(|node| node.kind()).collect();
+        assert_eq!(kinds[0], SyntaxKind::KeyValue);
+        assert_eq!(kinds[1..5], [SyntaxKind::ArrayTable; 4]);
+        assert_eq!(kinds[5], SyntaxKind::Table);
+
+        let nom = &root.children()[3];
+        let dependencies = nom.children().pop().unwrap();
+        assert_eq!(dependencies.tokens()[0].text(), "dependencies");
+        assert!(dependencies
+            .to_string()
+            .ends_with("\"minimal-lexical\",\n]"));
+
+        let version = nom.children()[1].tokens().pop().unwrap();
+        assert_eq!(version.text(), "\"7.1.3\"");
+        let edited = version.replace_with(GreenToken::new(SyntaxKind::String, "\"7.2.0\""));
+        assert_eq!(
+            edited.to_string(),
+            LOCKFILE.replace("version = \"7.1.3\"", "version = \"7.2.0\"")
+        );
+        let (_, reparsed) = lockfile(&edited.to_string()).unwrap();
+        assert_eq!(
+            reparsed.package("nom").unwrap().version.to_string(),
+            "7.2.0"
+        );
+
+        for broken in ["[[package", "a = [\"b\"", "x = \"open\n= 1\r\n"] {
+            assert_eq!(lockfile_cst(broken).to_string(), broken);
+        }
+    }
+
+    #[test]
+    fn test_invalid_package() {
+        assert!(lockfile("[[package]]\nname = \"a\"\n").is_err());
+        assert!(lockfile("[[package]]\nname = \"a\"\nversion = \"x\"\n").is_err());
+    }
+}
@@ -0,0 +1,636 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{escaped_transform, is_not, tag, take_while1},
+    character::complete::{char, digit1, multispace1, not_line_ending, one_of, space1},
+    combinator::{map, map_res, opt, value},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::{many0, many1, separated_list1},
+    sequence::{delimited, preceded, separated_pair, terminated, tuple},
+    Err as NomErr, IResult,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Color {
+    White,
+    Black,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PieceKind {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+impl TryFrom<char> for PieceKind {
+    type Error = char;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value.to_ascii_uppercase() {
+            'P' => Ok(PieceKind::Pawn),
+            'N' => Ok(PieceKind::Knight),
+            'B' => Ok(PieceKind::Bishop),
+            'R' => Ok(PieceKind::Rook),
+            'Q' => Ok(PieceKind::Queen),
+            'K' => Ok(PieceKind::King),
+            _ => Err(value),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Piece {
+    pub color: Color,
+    pub kind: PieceKind,
+}
+
+/// A board square. Files and ranks are zero-based, so `a1` is `(0, 0)` and
+/// `h8` is `(7, 7)`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Square {
+    pub file: u8,
+    pub rank: u8,
+}
+
+impl Square {
+    pub fn new(file: u8, rank: u8) -> Self {
+        Square { file, rank }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct CastlingRights {
+    pub white_kingside: bool,
+    pub white_queenside: bool,
+    pub black_kingside: bool,
+    pub black_queenside: bool,
+}
+
+/// Squares indexed by `[rank][file]`, rank 0 being White's back rank.
+pub type Board = [[Option<Piece>; 8]; 8];
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Position {
+    pub board: Board,
+    pub active_color: Color,
+    pub castling: CastlingRights,
+    pub en_passant: Option<Square>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+}
+
+impl Position {
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        self.board[square.rank as usize][square.file as usize]
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum San {
+    Castle {
+        long: bool,
+    },
+    Move {
+        piece: PieceKind,
+        from_file: Option<u8>,
+        from_rank: Option<u8>,
+        capture: bool,
+        to: Square,
+        promotion: Option<PieceKind>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Check {
+    None,
+    Check,
+    Mate,
+}
+
+/// A move in the movetext along with everything annotating it.
+/// Suffix annotations such as `!?` are normalized to their NAG numbers.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MoveNode {
+    pub san: San,
+    pub check: Check,
+    pub nags: Vec<u8>,
+    pub comments: Vec<String>,
+    pub variations: Vec<Vec<MoveNode>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unknown,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub comment: Option<String>,
+    pub moves: Vec<MoveNode>,
+    pub result: GameResult,
+}
+
+impl Game {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+fn square(input: &str) -> Res<&str, Square> {
+    context("square", tuple((one_of("abcdefgh"), one_of("12345678"))))(input).map(
+        |(next_input, (file, rank))| {
+            (
+                next_input,
+                Square::new(file as u8 - b'a', rank as u8 - b'1'),
+            )
+        },
+    )
+}
+
+fn rank(input: &str) -> Res<&str, [Option<Piece>; 8]> {
+    let (next_input, text) = context("rank", take_while1(|c: char| c != '/' && c != ' '))(input)?;
+    let mut squares = [None; 8];
+    let mut file = 0usize;
+    for c in text.chars() {
+        if let Some(empty) = c.to_digit(10).filter(|n| (1..=8).contains(n)) {
+            file += empty as usize;
+        } else {
+            let kind = PieceKind::try_from(c).map_err(|_| failure(input, "invalid piece"))?;
+            let color = if c.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            if file < 8 {
+                squares[file] = Some(Piece { color, kind });
+            }
+            file += 1;
+        }
+        if file > 8 {
+            return Err(failure(input, "rank too long"));
+        }
+    }
+    if file != 8 {
+        return Err(failure(input, "rank too short"));
+    }
+    Ok((next_input, squares))
+}
+
+fn board(input: &str) -> Res<&str, Board> {
+    let (next_input, ranks) = context("board", separated_list1(char('/'), rank))(input)?;
+    if ranks.len() != 8 {
+        return Err(failure(input, "board must have 8 ranks"));
+    }
+    let mut board = [[None; 8]; 8];
+    for (i, rank) in ranks.into_iter().enumerate() {
+        board[7 - i] = rank;
+    }
+    Ok((next_input, board))
+}
+
+fn active_color(input: &str) -> Res<&str, Color> {
+    context(
+        "active color",
+        alt((
+            value(Color::White, char('w')),
+            value(Color::Black, char('b')),
+        )),
+    )(input)
+}
+
+fn castling(input: &str) -> Res<&str, CastlingRights> {
+    context(
+        "castling",
+        alt((
+            value(CastlingRights::default(), char('-')),
+            map(take_while1(|c| "KQkq".contains(c)), |rights: &str| {
+                CastlingRights {
+                    white_kingside: rights.contains('K'),
+                    white_queenside: rights.contains('Q'),
+                    black_kingside: rights.contains('k'),
+                    black_queenside: rights.contains('q'),
+                }
+            }),
+        )),
+    )(input)
+}
+
+fn en_passant(input: &str) -> Res<&str, Option<Square>> {
+    context(
+        "en passant",
+        alt((value(None, char('-')), map(square, Some))),
+    )(input)
+}
+
+fn number(input: &str) -> Res<&str, u32> {
+    map_res(digit1, str::parse)(input)
+}
+
+/// Parses a FEN record. The halfmove clock and fullmove number are often
+/// omitted in the wild and default to `0` and `1`.
+pub fn fen(input: &str) -> Res<&str, Position> {
+    context(
+        "fen",
+        tuple((
+            board,
+            preceded(space1, active_color),
+            preceded(space1, castling),
+            preceded(space1, en_passant),
+            opt(preceded(space1, separated_pair(number, space1, number))),
+        )),
+    )(input)
+    .map(|(next_input, result)| {
+        let (board, active_color, castling, en_passant, clocks) = result;
+        let (halfmove_clock, fullmove_number) = clocks.unwrap_or((0, 1));
+        (
+            next_input,
+            Position {
+                board,
+                active_color,
+                castling,
+                en_passant,
+                halfmove_clock,
+                fullmove_number,
+            },
+        )
+    })
+}
+
+fn tag_pair(input: &str) -> Res<&str, (String, String)> {
+    context(
+        "tag pair",
+        delimited(
+            char('['),
+            separated_pair(
+                take_while1(|c: char| c.is_alphanumeric() || c == '_'),
+                space1,
+                delimited(
+                    char('"'),
+                    map(
+                        opt(escaped_transform(
+                            is_not("\\\""),
+                            '\\',
+                            alt((value("\\", char('\\')), value("\"", char('"')))),
+                        )),
+                        Option::unwrap_or_default,
+                    ),
+                    char('"'),
+                ),
+            ),
+            char(']'),
+        ),
+    )(input)
+    .map(|(next_input, (name, value))| (next_input, (name.to_owned(), value)))
+}
+
+fn comment(input: &str) -> Res<&str, String> {
+    context(
+        "comment",
+        alt((
+            delimited(char('{'), is_not("}"), char('}')),
+            value("", tag("{}")),
+            preceded(char(';'), not_line_ending),
+        )),
+    )(input)
+    .map(|(next_input, comment)| (next_input, comment.trim().to_owned()))
+}
+
+/// Whitespace and `%` escape lines, which are ignored.
+fn skip(input: &str) -> Res<&str, ()> {
+    value(
+        (),
+        many0(alt((
+            value((), multispace1),
+            value((), preceded(char('%'), not_line_ending)),
+        ))),
+    )(input)
+}
+
+fn result(input: &str) -> Res<&str, GameResult> {
+    context(
+        "result",
+        alt((
+            value(GameResult::WhiteWins, tag("1-0")),
+            value(GameResult::BlackWins, tag("0-1")),
+            value(GameResult::Draw, tag("1/2-1/2")),
+            value(GameResult::Unknown, char('*')),
+        )),
+    )(input)
+}
+
+fn move_number(input: &str) -> Res<&str, &str> {
+    terminated(digit1, many1(char('.')))(input)
+}
+
+fn san(input: &str) -> Res<&str, San> {
+    let castle = alt((
+        value(
+            San::Castle { long: true },
+            alt((tag("O-O-O"), tag("0-0-0"))),
+        ),
+        value(San::Castle { long: false }, alt((tag("O-O"), tag("0-0")))),
+    ));
+    let normal = tuple((
+        opt(one_of("KQRBN")),
+        opt(one_of("abcdefgh")),
+        opt(one_of("12345678")),
+        opt(char('x')),
+        square,
+        opt(preceded(opt(char('=')), one_of("QRBN"))),
+    ));
+    let normal = map(normal, |(piece, file, rank, capture, to, promotion)| {
+        San::Move {
+            piece: piece.map_or(PieceKind::Pawn, |p| p.try_into().unwrap()),
+            from_file: file.map(|f| f as u8 - b'a'),
+            from_rank: rank.map(|r| r as u8 - b'1'),
+            capture: capture.is_some(),
+            to,
+            promotion: promotion.map(|p| p.try_into().unwrap()),
+        }
+    });
+    // A bare destination such as `e4` is first consumed as a disambiguating
+    // file and rank, so fall back to the destination-only form.
+    let target_only = tuple((
+        opt(one_of("KQRBN")),
+        opt(char('x')),
+        square,
+        opt(preceded(opt(char('=')), one_of("QRBN"))),
+    ));
+    let target_only = map(target_only, |(piece, capture, to, promotion)| San::Move {
+        piece: piece.map_or(PieceKind::Pawn, |p| p.try_into().unwrap()),
+        from_file: None,
+        from_rank: None,
+        capture: capture.is_some(),
+        to,
+        promotion: promotion.map(|p| p.try_into().unwrap()),
+    });
+    context("san", alt((castle, normal, target_only)))(input)
+}
+
+fn suffix_annotation(input: &str) -> Res<&str, u8> {
+    alt((
+        value(3, tag("!!")),
+        value(4, tag("??")),
+        value(5, tag("!?")),
+        value(6, tag("?!")),
+        value(1, tag("!")),
+        value(2, tag("?")),
+    ))(input)
+}
+
+fn nag(input: &str) -> Res<&str, u8> {
+    context("nag", preceded(char('$'), map_res(digit1, str::parse)))(input)
+}
+
+fn move_node(input: &str) -> Res<&str, MoveNode> {
+    let (input, _) = opt(terminated(move_number, skip))(input)?;
+    let (input, san) = san(input)?;
+    let (input, check) = map(opt(one_of("+#")), |c| match c {
+        Some('+') => Check::Check,
+        Some(_) => Check::Mate,
+        None => Check::None,
+    })(input)?;
+    let (mut input, mut nags) = map(opt(suffix_annotation), |nag| {
+        nag.into_iter().collect::<Vec<_>>()
+    })(input)?;
+
+    let mut node = MoveNode {
+        san,
+        check,
+        nags: vec![],
+        comments: vec![],
+        variations: vec![],
+    };
+    loop {
+        let (rest, _) = skip(input)?;
+        if let Ok((rest, n)) = nag(rest) {
+            nags.push(n);
+            input = rest;
+        } else if let Ok((rest, comment)) = comment(rest) {
+            node.comments.push(comment);
+            input = rest;
+        } else if let Ok((rest, variation)) = variation(rest) {
+            node.variations.push(variation);
+            input = rest;
+        } else {
+            break;
+        }
+    }
+    node.nags = nags;
+    Ok((input, node))
+}
+
+/// Parses a sequence of moves. A comment before the first move is
+/// attached to that move.
+fn line(input: &str) -> Res<&str, Vec<MoveNode>> {
+    let (input, leading) = many0(delimited(skip, comment, skip))(input)?;
+    let (input, mut moves) = many0(preceded(skip, move_node))(input)?;
+    if let Some(first) = moves.first_mut() {
+        first.comments.splice(0..0, leading);
+    }
+    Ok((input, moves))
+}
+
+fn variation(input: &str) -> Res<&str, Vec<MoveNode>> {
+    context(
+        "variation",
+        delimited(char('('), line, preceded(skip, char(')'))),
+    )(input)
+}
+
+/// Parses one game: the tag pair section followed by the movetext and a
+/// game termination marker.
+pub fn game(input: &str) -> Res<&str, Game> {
+    let (input, tags) = many0(preceded(skip, tag_pair))(input)?;
+    let (input, comment) = opt(preceded(skip, comment))(input)?;
+    let (input, moves) = line(input)?;
+    let (input, result) = preceded(skip, result)(input)?;
+    let (input, _) = skip(input)?;
+    Ok((
+        input,
+        Game {
+            tags,
+            comment,
+            moves,
+            result,
+        },
+    ))
+}
+
+/// Parses every game in a PGN file.
+pub fn pgn(input: &str) -> Res<&str, Vec<Game>> {
+    context("pgn", many0(game))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn test_fen_start_position() {
+        let (rest, position) = fen(START).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(position.active_color, Color::White);
+        assert_eq!(
+            position.castling,
+            CastlingRights {
+                white_kingside: true,
+                white_queenside: true,
+                black_kingside: true,
+                black_queenside: true,
+            }
+        );
+        assert_eq!(
+            position.piece_at(Square::new(4, 0)),
+            Some(Piece {
+                color: Color::White,
+                kind: PieceKind::King
+            })
+        );
+        assert_eq!(
+            position.piece_at(Square::new(3, 7)),
+            Some(Piece {
+                color: Color::Black,
+                kind: PieceKind::Queen
+            })
+        );
+        assert_eq!(position.piece_at(Square::new(4, 3)), None);
+        assert_eq!((position.halfmove_clock, position.fullmove_number), (0, 1));
+    }
+
+    #[test]
+    fn test_fen_en_passant_and_clocks() {
+        let (_, position) =
+            fen("rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w Kq c6 0 2").unwrap();
+        assert_eq!(position.en_passant, Some(Square::new(2, 5)));
+        assert!(position.castling.white_kingside && !position.castling.white_queenside);
+        assert_eq!(position.fullmove_number, 2);
+
+        let (_, position) = fen("8/8/8/8/8/8/8/K6k b - -").unwrap();
+        assert_eq!(position.active_color, Color::Black);
+        assert_eq!(position.castling, CastlingRights::default());
+    }
+
+    #[test]
+    fn test_fen_invalid() {
+        assert!(fen("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_err());
+        assert!(fen("rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_err());
+        assert!(fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1").is_err());
+    }
+
+    #[test]
+    fn test_san() {
+        assert_eq!(
+            san("e4"),
+            Ok((
+                "",
+                San::Move {
+                    piece: PieceKind::Pawn,
+                    from_file: None,
+                    from_rank: None,
+                    capture: false,
+                    to: Square::new(4, 3),
+                    promotion: None
+                }
+            ))
+        );
+        assert_eq!(
+            san("Nbxd7"),
+            Ok((
+                "",
+                San::Move {
+                    piece: PieceKind::Knight,
+                    from_file: Some(1),
+                    from_rank: None,
+                    capture: true,
+                    to: Square::new(3, 6),
+                    promotion: None
+                }
+            ))
+        );
+        assert_eq!(
+            san("exf8=Q"),
+            Ok((
+                "",
+                San::Move {
+                    piece: PieceKind::Pawn,
+                    from_file: Some(4),
+                    from_rank: None,
+                    capture: true,
+                    to: Square::new(5, 7),
+                    promotion: Some(PieceKind::Queen)
+                }
+            ))
+        );
+        assert_eq!(san("O-O-O"), Ok(("", San::Castle { long: true })));
+        assert_eq!(san("O-O+"), Ok(("+", San::Castle { long: false })));
+    }
+
+    #[test]
+    fn test_tag_pair() {
+        assert_eq!(
+            tag_pair(r#"[Event "F/S \"Return\" Match"]"#),
+            Ok(("", ("Event".to_owned(), r#"F/S "Return" Match"#.to_owned())))
+        );
+        assert_eq!(
+            tag_pair(r#"[Round ""]"#),
+            Ok(("", ("Round".to_owned(), "".to_owned())))
+        );
+    }
+
+    #[test]
+    fn test_game() {
+        let input = r#"[Event "Casual"]
+[White "Anderssen"]
+[Result "1-0"]
+
+{Opening comment} 1. e4 e5 2. Nf3 $1 {Develops} Nc6 (2... d6 3. d4) 3. Bc4!?
+; rest of line
+Bc5 4. Qxf7# 1-0
+"#;
+        let (rest, game) = game(input).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(game.tag("White"), Some("Anderssen"));
+        assert_eq!(game.comment, Some("Opening comment".to_owned()));
+        assert_eq!(game.result, GameResult::WhiteWins);
+        assert_eq!(game.moves.len(), 7);
+        assert_eq!(game.moves[2].nags, vec![1]);
+        assert_eq!(game.moves[2].comments, vec!["Develops".to_owned()]);
+        assert_eq!(game.moves[3].variations.len(), 1);
+        assert_eq!(game.moves[3].variations[0].len(), 2);
+        assert_eq!(game.moves[4].nags, vec![5]);
+        assert_eq!(game.moves[4].comments, vec!["rest of line".to_owned()]);
+        assert_eq!(game.moves[6].check, Check::Mate);
+    }
+
+    #[test]
+    fn test_pgn_multiple_games() {
+        let input = "[Event \"A\"]\n\n1. d4 d5 1/2-1/2\n\n[Event \"B\"]\n\n1. c4 *\n";
+        let (rest, games) = pgn(input).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].result, GameResult::Draw);
+        assert_eq!(games[1].result, GameResult::Unknown);
+        assert_eq!(games[1].tag("Event"), Some("B"));
+    }
+}
@@ -0,0 +1,313 @@
+//! `tokio_util` codecs that frame HTTP/1.1 messages on a connection, built
+//! on the streaming byte parsers in `http::bytes`. A server decodes
+//! requests and encodes responses with `HttpRequestCodec`; a client does
+//! the opposite with `HttpResponseCodec`. Messages are framed by
+//! `Content-Length` alone: one with a `Transfer-Encoding`, such as
+//! `chunked`, is a decoding error rather than a body whose chunks are
+//! left to be read as the next message.
+#![cfg(feature = "codec")]
+
+use std::{fmt, io};
+
+use bytes::{Buf, BufMut, BytesMut};
+use nom::{error::VerboseError, Err as NomErr, IResult};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::http::{bytes as http_bytes, Request, Response, Version};
+use crate::Error;
+
+/// A decoded request, owning its parts since the buffer it came from is
+/// reused.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub target: String,
+    pub version: Version,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HttpResponse {
+    pub version: Version,
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+fn find_header<'h>(headers: &'h [(String, String)], name: &str) -> Option<&'h str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+fn owned_headers(headers: &crate::http::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+impl From<Request<'_, [u8]>> for HttpRequest {
+    fn from(request: Request<'_, [u8]>) -> Self {
+        HttpRequest {
+            method: request.method.to_string(),
+            target: request.target.to_string(),
+            version: request.version,
+            headers: owned_headers(&request.headers),
+            body: request.body.to_vec(),
+        }
+    }
+}
+
+impl From<Response<'_, [u8]>> for HttpResponse {
+    fn from(response: Response<'_, [u8]>) -> Self {
+        HttpResponse {
+            version: response.version,
+            status: response.status,
+            reason: response.reason.to_string(),
+            headers: owned_headers(&response.headers),
+            body: response.body.to_vec(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CodecError {
+    Io(io::Error),
+    Parse(Box<Error>),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Io(error) => write!(f, "{}", error),
+            CodecError::Parse(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CodecError::Io(error) => Some(error),
+            CodecError::Parse(error) => Some(error.as_ref()),
+        }
+    }
+}
+
+impl From<io::Error> for CodecError {
+    fn from(error: io::Error) -> Self {
+        CodecError::Io(error)
+    }
+}
+
+impl From<Error> for CodecError {
+    fn from(error: Error) -> Self {
+        CodecError::Parse(Box::new(error))
+    }
+}
+
+/// Maps a parser's result over `buffer` to the item, owned, and the number
+/// of bytes it took up.
+fn nom_step<'a, T, O>(
+    buffer: &'a [u8],
+    result: IResult<&'a [u8], T, VerboseError<&'a [u8]>>,
+    owned: impl FnOnce(T) -> O,
+) -> Result<Option<(usize, O)>, CodecError> {
+    match result {
+        Ok((rest, item)) => Ok(Some((buffer.len() - rest.len(), owned(item)))),
+        Err(NomErr::Incomplete(_)) => Ok(None),
+        Err(error) => Err(Error::from_verbose("http", buffer, error).into()),
+    }
+}
+
+/// Takes a decoded item's bytes off the front of the buffer.
+fn advance<O>(src: &mut BytesMut, step: Option<(usize, O)>) -> Option<O> {
+    let (consumed, item) = step?;
+    src.advance(consumed);
+    Some(item)
+}
+
+/// Writes the headers, adding a `Content-Length` for the body if they don't
+/// give one and `needs_length`.
+fn encode_head(
+    dst: &mut BytesMut,
+    start_line: String,
+    headers: &[(String, String)],
+    body: &[u8],
+    needs_length: bool,
+) {
+    dst.put_slice(start_line.as_bytes());
+    dst.put_slice(b"\r\n");
+    for (name, value) in headers {
+        dst.put_slice(format!("{}: {}\r\n", name, value).as_bytes());
+    }
+    if needs_length && find_header(headers, "content-length").is_none() {
+        dst.put_slice(format!("Content-Length: {}\r\n", body.len()).as_bytes());
+    }
+    dst.put_slice(b"\r\n");
+    dst.put_slice(body);
+}
+
+/// Server side: decodes requests and encodes responses.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HttpRequestCodec;
+
+impl Decoder for HttpRequestCodec {
+    type Item = HttpRequest;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<HttpRequest>, CodecError> {
+        let step = nom_step(src, http_bytes::streaming::request(src), HttpRequest::from)?;
+        Ok(advance(src, step))
+    }
+}
+
+impl Encoder<HttpResponse> for HttpRequestCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, response: HttpResponse, dst: &mut BytesMut) -> Result<(), CodecError> {
+        let start_line = format!(
+            "{} {} {}",
+            response.version, response.status, response.reason
+        );
+        let has_body = !matches!(response.status, 100..=199 | 204 | 304);
+        encode_head(dst, start_line, &response.headers, &response.body, has_body);
+        Ok(())
+    }
+}
+
+/// Client side: decodes responses and encodes requests. A response body
+/// that runs until the connection closes is returned at end of input.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HttpResponseCodec;
+
+impl Decoder for HttpResponseCodec {
+    type Item = HttpResponse;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<HttpResponse>, CodecError> {
+        let step = nom_step(
+            src,
+            http_bytes::streaming::response(src),
+            HttpResponse::from,
+        )?;
+        Ok(advance(src, step))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<HttpResponse>, CodecError> {
+        if let Some(response) = self.decode(src)? {
+            return Ok(Some(response));
+        }
+        if src.is_empty() {
+            return Ok(None);
+        }
+        let step = nom_step(src, http_bytes::response(src), HttpResponse::from)?;
+        Ok(advance(src, step))
+    }
+}
+
+impl Encoder<HttpRequest> for HttpResponseCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, request: HttpRequest, dst: &mut BytesMut) -> Result<(), CodecError> {
+        let start_line = format!("{} {} {}", request.method, request.target, request.version);
+        let has_body = !request.body.is_empty();
+        encode_head(dst, start_line, &request.headers, &request.body, has_body);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_codec() {
+        let mut codec = HttpRequestCodec;
+        let mut buffer = BytesMut::from(&b"POST /a HTTP/1.1\r\nContent-Length: 3\r\n\r\nab"[..]);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        buffer.extend_from_slice(b"cGET /b HTTP/1.1\r\n\r\n");
+        let request = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(
+            (request.method.as_str(), &request.body[..]),
+            ("POST", &b"abc"[..])
+        );
+        assert_eq!(request.header("content-length"), Some("3"));
+        let request = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(request.target, "/b");
+        assert!(buffer.is_empty());
+
+        buffer.extend_from_slice(b"BAD\r\n");
+        assert!(matches!(
+            codec.decode(&mut buffer),
+            Err(CodecError::Parse(_))
+        ));
+
+        buffer.clear();
+        buffer.extend_from_slice(
+            b"POST /a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+              1c\r\nGET /admin HTTP/1.1\r\n\r\n\r\n0\r\n\r\n\
+              GET /b HTTP/1.1\r\n\r\n",
+        );
+        let Err(CodecError::Parse(error)) = codec.decode(&mut buffer) else {
+            panic!("expected a chunked request to be refused");
+        };
+        assert_eq!(error.context, ["a message without Transfer-Encoding"]);
+
+        let mut out = BytesMut::new();
+        let response = HttpResponse {
+            version: Version { major: 1, minor: 1 },
+            status: 200,
+            reason: "OK".to_string(),
+            headers: vec![("Content-Type".to_string(), "text/plain".to_string())],
+            body: b"hi".to_vec(),
+        };
+        codec.encode(response.clone(), &mut out).unwrap();
+        assert_eq!(
+            &out[..],
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\r\nhi"
+        );
+
+        let mut client = HttpResponseCodec;
+        let decoded = client.decode(&mut out).unwrap().unwrap();
+        assert_eq!(decoded.body, response.body);
+    }
+
+    #[test]
+    fn test_response_codec() {
+        let mut codec = HttpResponseCodec;
+        let mut buffer = BytesMut::from(&b"HTTP/1.0 200 OK\r\n\r\nuntil close"[..]);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        let response = codec.decode_eof(&mut buffer).unwrap().unwrap();
+        assert_eq!(response.body, b"until close");
+        assert_eq!(codec.decode_eof(&mut buffer).unwrap(), None);
+
+        let mut out = BytesMut::new();
+        let request = HttpRequest {
+            method: "GET".to_string(),
+            target: "/".to_string(),
+            version: Version { major: 1, minor: 1 },
+            headers: vec![("Host".to_string(), "example.org".to_string())],
+            body: vec![],
+        };
+        codec.encode(request.clone(), &mut out).unwrap();
+        assert_eq!(&out[..], b"GET / HTTP/1.1\r\nHost: example.org\r\n\r\n");
+        assert_eq!(HttpRequestCodec.decode(&mut out).unwrap(), Some(request));
+    }
+}
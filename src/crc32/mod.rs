@@ -0,0 +1,48 @@
+/// The CRC-32 lookup table for the reflected polynomial `0xEDB88320`, as
+/// used by PNG, gzip and ZIP.
+const TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+/// Continues a CRC over more bytes. Start with `0` and pass the previous
+/// result back in to checksum data that arrives in pieces.
+pub fn update(crc: u32, bytes: &[u8]) -> u32 {
+    let mut c = !crc;
+    for byte in bytes {
+        c = TABLE[((c ^ u32::from(*byte)) & 0xff) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    update(0, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(update(crc32(b"1234"), b"56789"), 0xcbf4_3926);
+    }
+}
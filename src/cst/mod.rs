@@ -0,0 +1,463 @@
+//! Lossless concrete syntax trees. A tree keeps every byte of its input,
+//! whitespace and comments included, so it prints back exactly and an edit
+//! only changes the text it touches.
+//!
+//! Trees come in two layers. Green nodes are immutable, know only their
+//! kind, length and children, and are shared between versions of a tree.
+//! Red nodes (`SyntaxNode`) are built on demand over a green tree and add
+//! parent links and absolute offsets.
+
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum SyntaxKind {
+    // Tokens.
+    Whitespace,
+    Newline,
+    Comment,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Comma,
+    Colon,
+    Equals,
+    String,
+    Number,
+    /// `true`, `false` or `null`.
+    Keyword,
+    BareKey,
+    /// Text the lexer couldn't make sense of.
+    Error,
+
+    // Nodes.
+    Root,
+    Object,
+    Member,
+    Array,
+    Table,
+    ArrayTable,
+    KeyValue,
+}
+
+impl SyntaxKind {
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::Whitespace | SyntaxKind::Newline | SyntaxKind::Comment
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: Box<str>,
+}
+
+impl GreenToken {
+    pub fn new(kind: SyntaxKind, text: &str) -> Self {
+        GreenToken {
+            kind,
+            text: text.into(),
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            GreenElement::Node(node) => node.kind,
+            GreenElement::Token(token) => token.kind,
+        }
+    }
+
+    pub fn text_len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+}
+
+impl From<GreenNode> for GreenElement {
+    fn from(node: GreenNode) -> Self {
+        GreenElement::Node(Rc::new(node))
+    }
+}
+
+impl From<GreenToken> for GreenElement {
+    fn from(token: GreenToken) -> Self {
+        GreenElement::Token(Rc::new(token))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    len: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        GreenNode {
+            kind,
+            len: children.iter().map(GreenElement::text_len).sum(),
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text_len(&self) -> usize {
+        self.len
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    /// A copy of this node with child `index` replaced. Other children are
+    /// shared, not copied.
+    pub fn with_child(&self, index: usize, child: GreenElement) -> GreenNode {
+        let mut children = self.children.clone();
+        children[index] = child;
+        GreenNode::new(self.kind, children)
+    }
+}
+
+impl fmt::Display for GreenNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => write!(f, "{}", node)?,
+                GreenElement::Token(token) => f.write_str(&token.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds a green tree from a flat sequence of tokens, in the order they
+/// appear in the input.
+#[derive(Debug, Default)]
+pub struct GreenNodeBuilder {
+    /// Open nodes and where their children start in `children`.
+    parents: Vec<(SyntaxKind, usize)>,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNodeBuilder {
+    pub fn new() -> Self {
+        GreenNodeBuilder::default()
+    }
+
+    pub fn start_node(&mut self, kind: SyntaxKind) {
+        self.parents.push((kind, self.children.len()));
+    }
+
+    pub fn token(&mut self, kind: SyntaxKind, text: &str) {
+        self.children.push(GreenToken::new(kind, text).into());
+    }
+
+    /// # Panics
+    ///
+    /// If no node is open.
+    pub fn finish_node(&mut self) {
+        let (kind, start) = self.parents.pop().expect("no node to finish");
+        let children = self.children.split_off(start);
+        self.children.push(GreenNode::new(kind, children).into());
+    }
+
+    /// # Panics
+    ///
+    /// If a node is still open, or there isn't exactly one root node.
+    pub fn finish(mut self) -> GreenNode {
+        assert!(self.parents.is_empty(), "unfinished node");
+        match (self.children.pop(), self.children.is_empty()) {
+            (Some(GreenElement::Node(node)), true) => Rc::unwrap_or_clone(node),
+            _ => panic!("expected a single root node"),
+        }
+    }
+}
+
+struct NodeData {
+    green: Rc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    /// Position among the parent's children.
+    index: usize,
+    offset: usize,
+}
+
+/// A node in a tree, with its position and a link to its parent.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+#[derive(Debug, Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    parent: SyntaxNode,
+    index: usize,
+    offset: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: GreenNode) -> Self {
+        SyntaxNode(Rc::new(NodeData {
+            green: Rc::new(green),
+            parent: None,
+            index: 0,
+            offset: 0,
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &GreenNode {
+        &self.0.green
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.len
+    }
+
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    pub fn children_with_tokens(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        let mut elements = vec![];
+        for (index, child) in self.0.green.children.iter().enumerate() {
+            elements.push(match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                    green: green.clone(),
+                    parent: Some(self.clone()),
+                    index,
+                    offset,
+                }))),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    green: green.clone(),
+                    parent: self.clone(),
+                    index,
+                    offset,
+                }),
+            });
+            offset += child.text_len();
+        }
+        elements
+    }
+
+    pub fn children(&self) -> Vec<SyntaxNode> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    /// The node's own tokens, not those of its child nodes.
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Token(token) => Some(token),
+                SyntaxElement::Node(_) => None,
+            })
+            .collect()
+    }
+
+    /// This node and everything below it, in document order.
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut nodes = vec![self.clone()];
+        for child in self.children() {
+            nodes.extend(child.descendants());
+        }
+        nodes
+    }
+
+    /// Replaces this node, returning the root of the new tree. The old tree
+    /// is left as it was.
+    pub fn replace_with(&self, green: GreenNode) -> SyntaxNode {
+        self.replace_in_parent(green.into())
+    }
+
+    fn replace_in_parent(&self, element: GreenElement) -> SyntaxNode {
+        match (&self.0.parent, element) {
+            (None, GreenElement::Node(green)) => SyntaxNode(Rc::new(NodeData {
+                green,
+                parent: None,
+                index: 0,
+                offset: 0,
+            })),
+            (None, GreenElement::Token(_)) => unreachable!("a token can't be a root"),
+            (Some(parent), element) => {
+                parent.replace_with(parent.0.green.with_child(self.0.index, element))
+            }
+        }
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.green)
+    }
+}
+
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}@{:?}", self.kind(), self.text_range())
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text.len()
+    }
+
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+
+    /// Replaces this token, returning the root of the new tree.
+    pub fn replace_with(&self, green: GreenToken) -> SyntaxNode {
+        self.parent
+            .replace_with(self.parent.0.green.with_child(self.index, green.into()))
+    }
+}
+
+/// Replaces the text in `range` with `replacement`, for re-parsing a tree
+/// after an edit without starting from scratch.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    /// # Panics
+    ///
+    /// If `range` is out of bounds or not on character boundaries.
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        text.replace_range(self.range.clone(), &self.replacement);
+        text
+    }
+}
+
+/// Steps through lexed tokens while building a tree, for the parsers that
+/// produce one.
+pub(crate) struct TokenParser<'a> {
+    tokens: Vec<(SyntaxKind, &'a str)>,
+    position: usize,
+    pub(crate) builder: GreenNodeBuilder,
+}
+
+impl<'a> TokenParser<'a> {
+    pub(crate) fn new(tokens: Vec<(SyntaxKind, &'a str)>) -> Self {
+        TokenParser {
+            tokens,
+            position: 0,
+            builder: GreenNodeBuilder::new(),
+        }
+    }
+
+    pub(crate) fn peek(&self) -> Option<SyntaxKind> {
+        self.tokens.get(self.position).map(|(kind, _)| *kind)
+    }
+
+    pub(crate) fn peek_nth(&self, n: usize) -> Option<SyntaxKind> {
+        self.tokens.get(self.position + n).map(|(kind, _)| *kind)
+    }
+
+    /// Adds the next token to the current node.
+    pub(crate) fn bump(&mut self) {
+        let (kind, text) = self.tokens[self.position];
+        self.builder.token(kind, text);
+        self.position += 1;
+    }
+
+    /// Adds any whitespace and comments to the current node.
+    pub(crate) fn trivia(&mut self) {
+        while self.peek().is_some_and(SyntaxKind::is_trivia) {
+            self.bump();
+        }
+    }
+
+    pub(crate) fn finish(self) -> GreenNode {
+        self.builder.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let mut builder = GreenNodeBuilder::new();
+        builder.start_node(SyntaxKind::Array);
+        builder.token(SyntaxKind::LBracket, "[");
+        builder.token(SyntaxKind::Number, "1");
+        builder.token(SyntaxKind::Whitespace, " ");
+        builder.token(SyntaxKind::RBracket, "]");
+        builder.finish_node();
+        let green = builder.finish();
+        assert_eq!(green.text_len(), 4);
+        assert_eq!(green.to_string(), "[1 ]");
+
+        let root = SyntaxNode::new_root(green);
+        let number = &root.tokens()[1];
+        assert_eq!((number.text(), number.text_range()), ("1", 1..2));
+        let edited = number.replace_with(GreenToken::new(SyntaxKind::Number, "42"));
+        assert_eq!(edited.to_string(), "[42 ]");
+        assert_eq!(root.to_string(), "[1 ]");
+        assert!(Rc::ptr_eq(
+            match &edited.green().children()[3] {
+                GreenElement::Token(token) => token,
+                _ => unreachable!(),
+            },
+            match &root.green().children()[3] {
+                GreenElement::Token(token) => token,
+                _ => unreachable!(),
+            }
+        ));
+    }
+}
@@ -0,0 +1,48 @@
+//! Calendar arithmetic for the parsers that read dates, such as GPX
+//! timestamps and HTTP dates. Dates are in the proleptic Gregorian
+//! calendar.
+
+/// Days from 1970-01-01 to the given date, negative before it.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // Howard Hinnant's algorithm for days since 1970-01-01.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+pub fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+/// The number of days in `month`, from 1 to 12, of `year`, or `None` for a
+/// month out of range.
+pub fn days_in_month(year: i64, month: i64) -> Option<i64> {
+    match month {
+        2 if is_leap_year(year) => Some(29),
+        2 => Some(28),
+        4 | 6 | 9 | 11 => Some(30),
+        1..=12 => Some(31),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_days_from_civil() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(days_in_month(2000, 2), Some(29));
+        assert_eq!(days_in_month(1900, 2), Some(28));
+        assert_eq!(
+            (days_in_month(2023, 4), days_in_month(2023, 13)),
+            (Some(30), None)
+        );
+    }
+}
@@ -0,0 +1,545 @@
+//! Guessing the format of an input. Binary formats are recognised by their
+//! magic bytes; text formats by their first significant characters and,
+//! where those are ambiguous, by the shape of the first few lines.
+
+use std::borrow::Cow;
+
+use nom::{error::VerboseError, IResult};
+
+use crate::encoding::{self, Decoded, Policy};
+use crate::error::traced;
+use crate::value::{ToValue, Value};
+use crate::{bibtex, cargo_lock, chess, gpx, gzip, json, obj, png, subtitles, zip, Error};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Format {
+    Png,
+    Gzip,
+    Zip,
+    Json,
+    Gpx,
+    WebVtt,
+    Srt,
+    Bibtex,
+    CargoLock,
+    Pgn,
+    Obj,
+}
+
+impl Format {
+    /// The name used for the format in errors, such as `"json"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Gzip => "gzip",
+            Format::Zip => "zip",
+            Format::Json => "json",
+            Format::Gpx => "gpx",
+            Format::WebVtt => "webvtt",
+            Format::Srt => "srt",
+            Format::Bibtex => "bibtex",
+            Format::CargoLock => "cargo_lock",
+            Format::Pgn => "pgn",
+            Format::Obj => "obj",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct FormatGuess {
+    pub format: Format,
+    /// Between 0 and 1. Magic bytes give 1; heuristics give less.
+    pub confidence: f32,
+}
+
+/// The result of `parse_any`, holding whichever parser's output won.
+#[derive(Debug, PartialEq)]
+pub enum Document<'a> {
+    Png(Vec<png::Chunk<'a>>),
+    Gzip(gzip::Member<'a>),
+    Zip(zip::Archive<'a>),
+    Json(json::JsonValue),
+    Gpx(gpx::Gpx),
+    WebVtt(subtitles::WebVtt<'a>),
+    Srt(Vec<subtitles::Cue<'a>>),
+    Bibtex(bibtex::Bibliography),
+    CargoLock(cargo_lock::Lockfile),
+    Pgn(Vec<chess::Game>),
+    Obj(obj::Mesh),
+}
+
+impl Document<'_> {
+    pub fn format(&self) -> Format {
+        match self {
+            Document::Png(_) => Format::Png,
+            Document::Gzip(_) => Format::Gzip,
+            Document::Zip(_) => Format::Zip,
+            Document::Json(_) => Format::Json,
+            Document::Gpx(_) => Format::Gpx,
+            Document::WebVtt(_) => Format::WebVtt,
+            Document::Srt(_) => Format::Srt,
+            Document::Bibtex(_) => Format::Bibtex,
+            Document::CargoLock(_) => Format::CargoLock,
+            Document::Pgn(_) => Format::Pgn,
+            Document::Obj(_) => Format::Obj,
+        }
+    }
+}
+
+fn table(entries: Vec<(&str, Value)>) -> Value {
+    Value::Table(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+    )
+}
+
+fn cue(cue: &subtitles::Cue<'_>) -> Value {
+    table(vec![
+        ("id", cue.id.map(str::to_string).to_value()),
+        ("start", Value::Float(cue.start.as_secs_f64())),
+        ("end", Value::Float(cue.end.as_secs_f64())),
+        ("text", cue.text.to_value()),
+    ])
+}
+
+fn waypoint(point: &gpx::Waypoint) -> Value {
+    table(vec![
+        ("lat", Value::Float(point.lat)),
+        ("lon", Value::Float(point.lon)),
+        ("elevation", point.elevation.to_value()),
+        (
+            "time",
+            point
+                .time
+                .map_or(Value::Null, |time| Value::Integer(time.unix_millis)),
+        ),
+        ("name", point.name.to_value()),
+    ])
+}
+
+fn points(points: &[[f64; 3]]) -> Value {
+    Value::Array(
+        points
+            .iter()
+            .map(|point| point.to_vec().to_value())
+            .collect(),
+    )
+}
+
+/// The document's contents in the shared data model. Binary formats give
+/// their metadata rather than payloads, and chess games give their tags
+/// and result with a count of the moves rather than the move tree.
+impl ToValue for Document<'_> {
+    fn to_value(&self) -> Value {
+        match self {
+            Document::Png(chunks) => table(vec![(
+                "chunks",
+                Value::Array(
+                    chunks
+                        .iter()
+                        .map(|chunk| {
+                            table(vec![
+                                (
+                                    "type",
+                                    String::from_utf8_lossy(&chunk.chunk_type).to_value(),
+                                ),
+                                ("length", Value::Integer(chunk.data.len() as i64)),
+                                ("crc", Value::Integer(chunk.crc.into())),
+                            ])
+                        })
+                        .collect(),
+                ),
+            )]),
+            Document::Gzip(member) => table(vec![
+                ("name", member.header.name.to_value()),
+                ("comment", member.header.comment.to_value()),
+                ("mtime", Value::Integer(member.header.mtime.into())),
+                ("size", Value::Integer(member.trailer.size.into())),
+                ("crc32", Value::Integer(member.trailer.crc32.into())),
+            ]),
+            Document::Zip(archive) => table(vec![
+                (
+                    "entries",
+                    Value::Array(
+                        archive
+                            .entries
+                            .iter()
+                            .map(|entry| {
+                                table(vec![
+                                    ("name", entry.name().to_value()),
+                                    ("size", Value::Integer(entry.uncompressed_size as i64)),
+                                    (
+                                        "compressed_size",
+                                        Value::Integer(entry.compressed_size as i64),
+                                    ),
+                                    ("directory", Value::Boolean(entry.is_dir())),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+                (
+                    "comment",
+                    String::from_utf8_lossy(archive.comment).to_value(),
+                ),
+            ]),
+            Document::Json(value) => value.to_value(),
+            Document::Gpx(gpx) => table(vec![
+                ("creator", gpx.creator.to_value()),
+                (
+                    "waypoints",
+                    Value::Array(gpx.waypoints.iter().map(waypoint).collect()),
+                ),
+                (
+                    "routes",
+                    Value::Array(
+                        gpx.routes
+                            .iter()
+                            .map(|route| {
+                                table(vec![
+                                    ("name", route.name.to_value()),
+                                    (
+                                        "points",
+                                        Value::Array(route.points.iter().map(waypoint).collect()),
+                                    ),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+                (
+                    "tracks",
+                    Value::Array(
+                        gpx.tracks
+                            .iter()
+                            .map(|track| {
+                                let segments = track.segments.iter().map(|segment| {
+                                    Value::Array(segment.iter().map(waypoint).collect())
+                                });
+                                table(vec![
+                                    ("name", track.name.to_value()),
+                                    ("segments", Value::Array(segments.collect())),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+            ]),
+            Document::WebVtt(vtt) => table(vec![
+                ("title", vtt.title.map(str::to_string).to_value()),
+                ("cues", Value::Array(vtt.cues.iter().map(cue).collect())),
+            ]),
+            Document::Srt(cues) => Value::Array(cues.iter().map(cue).collect()),
+            Document::Bibtex(bibliography) => table(vec![
+                ("preambles", bibliography.preambles.to_value()),
+                (
+                    "entries",
+                    Value::Array(
+                        bibliography
+                            .entries
+                            .iter()
+                            .map(|entry| {
+                                let entry_type = match &entry.entry_type {
+                                    bibtex::EntryType::Other(name) => name.clone(),
+                                    known => format!("{:?}", known).to_lowercase(),
+                                };
+                                let fields = entry
+                                    .fields
+                                    .iter()
+                                    .map(|(name, value)| (name.clone(), value.to_value()));
+                                table(vec![
+                                    ("type", Value::String(entry_type)),
+                                    ("key", entry.key.to_value()),
+                                    ("fields", Value::Table(fields.collect())),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+            ]),
+            Document::CargoLock(lockfile) => lockfile.to_value(),
+            Document::Pgn(games) => Value::Array(
+                games
+                    .iter()
+                    .map(|game| {
+                        let tags = game
+                            .tags
+                            .iter()
+                            .map(|(name, value)| (name.clone(), value.to_value()));
+                        let result = match game.result {
+                            chess::GameResult::WhiteWins => "1-0",
+                            chess::GameResult::BlackWins => "0-1",
+                            chess::GameResult::Draw => "1/2-1/2",
+                            chess::GameResult::Unknown => "*",
+                        };
+                        table(vec![
+                            ("tags", Value::Table(tags.collect())),
+                            ("comment", game.comment.to_value()),
+                            ("moves", Value::Integer(game.moves.len() as i64)),
+                            ("result", result.to_value()),
+                        ])
+                    })
+                    .collect(),
+            ),
+            Document::Obj(mesh) => table(vec![
+                ("positions", points(&mesh.positions)),
+                ("texture_coords", points(&mesh.texture_coords)),
+                ("normals", points(&mesh.normals)),
+                (
+                    "faces",
+                    Value::Array(
+                        mesh.faces
+                            .iter()
+                            .map(|face| {
+                                let corners = face
+                                    .vertices
+                                    .iter()
+                                    .map(|vertex| Value::Integer(vertex.position as i64));
+                                Value::Array(corners.collect())
+                            })
+                            .collect(),
+                    ),
+                ),
+                ("objects", mesh.objects.to_value()),
+                ("groups", mesh.groups.to_value()),
+                ("materials", mesh.materials.to_value()),
+            ]),
+        }
+    }
+}
+
+fn magic(input: &[u8], guesses: &mut Vec<FormatGuess>) {
+    let mut guess = |format, confidence| guesses.push(FormatGuess { format, confidence });
+    if input.starts_with(&png::SIGNATURE) {
+        guess(Format::Png, 1.0);
+    } else if input.starts_with(&[0x1f, 0x8b, 0x08]) {
+        guess(Format::Gzip, 1.0);
+    } else if input.starts_with(b"PK\x03\x04") || input.starts_with(b"PK\x05\x06") {
+        guess(Format::Zip, 1.0);
+    }
+}
+
+/// Whether `line` looks like an SRT timing line, `00:00:01,000 --> ...`.
+fn is_timing(line: &str) -> bool {
+    line.contains("-->") && line.trim_start().starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn text(input: &str, guesses: &mut Vec<FormatGuess>) {
+    let mut guess = |format, confidence| guesses.push(FormatGuess { format, confidence });
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let trimmed = input.trim();
+    let mut lines = trimmed
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+
+    if trimmed.starts_with("WEBVTT") {
+        guess(Format::WebVtt, 1.0);
+    }
+    if trimmed.starts_with("# This file is automatically @generated by Cargo") {
+        guess(Format::CargoLock, 1.0);
+    } else if trimmed.contains("[[package]]") && trimmed.contains("name = ") {
+        guess(Format::CargoLock, 0.8);
+    }
+    match trimmed.chars().next() {
+        Some('{') if trimmed.ends_with('}') => guess(Format::Json, 0.9),
+        Some('[') if trimmed.ends_with(']') && !trimmed.starts_with("[[") => {
+            guess(Format::Json, 0.8)
+        }
+        Some('{' | '[') => guess(Format::Json, 0.4),
+        Some('"' | '-' | '0'..='9') if trimmed.parse::<json::JsonValue>().is_ok() => {
+            guess(Format::Json, 0.6)
+        }
+        _ => {}
+    }
+    if trimmed.starts_with('<') && trimmed.contains("<gpx") {
+        guess(Format::Gpx, 0.9);
+    }
+    if let (Some(first), Some(second)) = (lines.next(), lines.next()) {
+        if first.bytes().all(|b| b.is_ascii_digit()) && is_timing(second) {
+            guess(Format::Srt, 0.9);
+        }
+    }
+    if let Some(rest) = trimmed.strip_prefix('@') {
+        let name = rest.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+        if name.len() < rest.len() && name.trim_start().starts_with(['{', '(']) {
+            guess(Format::Bibtex, 0.85);
+        }
+    }
+    if trimmed.starts_with("[Event ") {
+        guess(Format::Pgn, 0.9);
+    } else if trimmed.starts_with("1.") {
+        guess(Format::Pgn, 0.4);
+    }
+
+    let statements: Vec<&str> = trimmed
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .take(50)
+        .collect();
+    let keywords = ["v", "vt", "vn", "f", "o", "g", "s", "usemtl", "mtllib"];
+    let known = statements
+        .iter()
+        .filter(|line| {
+            let keyword = line.split_whitespace().next().unwrap_or("");
+            keywords.contains(&keyword)
+        })
+        .count();
+    if statements.iter().any(|line| line.starts_with("v ")) && known * 2 > statements.len() {
+        guess(Format::Obj, 0.7 * known as f32 / statements.len() as f32);
+    }
+}
+
+/// Ranks the formats `input` might be in, most likely first. Formats that
+/// look impossible are left out, so the result may be empty.
+pub fn detect(input: &[u8]) -> Vec<FormatGuess> {
+    let mut guesses = vec![];
+    magic(input, &mut guesses);
+    if guesses.is_empty() {
+        if let Ok(decoded) = encoding::decode(input, Policy::Replace) {
+            text(&decoded.text, &mut guesses);
+        }
+    }
+    guesses.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    guesses
+}
+
+fn parse_bytes<'a, O>(
+    format: Format,
+    input: &'a [u8],
+    parser: impl FnOnce(&'a [u8]) -> IResult<&'a [u8], O, VerboseError<&'a [u8]>>,
+) -> Result<O, Error> {
+    traced(format.name(), input.len(), || match parser(input) {
+        Ok((&[], value)) => Ok(value),
+        Ok((rest, _)) => Err(Error::trailing(format.name(), input, rest)),
+        Err(error) => Err(Error::from_verbose(format.name(), input, error)),
+    })
+}
+
+/// Parses `input` as `format`.
+pub fn parse_as(format: Format, input: &[u8]) -> Result<Document<'_>, Error> {
+    use crate::error::parse_all;
+
+    // Documents borrow from `input`, so text in other encodings has to be
+    // transcoded by the caller first, with `encoding::decode`.
+    let text = || match encoding::decode(input, Policy::Strict)? {
+        Decoded {
+            text: Cow::Borrowed(text),
+            ..
+        } => Ok(text),
+        decoded => {
+            let mut error = Error::expected(format.name(), input, input, "UTF-8 text");
+            error.found = format!("{} text", decoded.encoding);
+            Err(error)
+        }
+    };
+    let name = format.name();
+    crate::limits::check_len(name, input)?;
+    Ok(match format {
+        Format::Png => Document::Png(parse_bytes(format, input, png::png)?),
+        Format::Gzip => Document::Gzip(parse_bytes(format, input, gzip::member)?),
+        Format::Zip => Document::Zip(parse_bytes(format, input, zip::archive)?),
+        Format::Json => Document::Json(text()?.parse()?),
+        Format::Gpx => Document::Gpx(parse_all(name, text()?, gpx::gpx)?),
+        Format::WebVtt => Document::WebVtt(parse_all(name, text()?, subtitles::webvtt)?),
+        Format::Srt => Document::Srt(parse_all(name, text()?, subtitles::srt)?),
+        Format::Bibtex => Document::Bibtex(parse_all(name, text()?, bibtex::bibliography)?),
+        Format::CargoLock => Document::CargoLock(parse_all(name, text()?, cargo_lock::lockfile)?),
+        Format::Pgn => Document::Pgn(parse_all(name, text()?, chess::pgn)?),
+        Format::Obj => Document::Obj(parse_all(name, text()?, obj::obj)?),
+    })
+}
+
+/// Detects the format of `input` and parses it with the most likely
+/// parser that accepts it. If none do, the error is the one from the
+/// most likely format.
+pub fn parse_any(input: &[u8]) -> Result<Document<'_>, Error> {
+    let mut first_error = None;
+    for guess in detect(input) {
+        match parse_as(guess.format, input) {
+            Ok(document) => return Ok(document),
+            Err(error) => {
+                first_error.get_or_insert(error);
+            }
+        }
+    }
+    Err(first_error.unwrap_or_else(|| Error {
+        format: "unknown",
+        span: 0..input.len().min(1),
+        line: 1,
+        column: 1,
+        expected: Some("a known format".to_string()),
+        found: input.first().map_or("end of input".to_string(), |byte| {
+            format!("byte 0x{:02x}", byte)
+        }),
+        context: vec![],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn best(input: &str) -> Option<Format> {
+        detect(input.as_bytes()).first().map(|guess| guess.format)
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(best(" {\"a\": [1, 2]} "), Some(Format::Json));
+        assert_eq!(best("[1, 2]"), Some(Format::Json));
+        assert_eq!(
+            best("WEBVTT\n\n00:01.000 --> 00:02.000\nHi\n"),
+            Some(Format::WebVtt)
+        );
+        assert_eq!(
+            best("1\n00:00:01,000 --> 00:00:02,000\nHi\n"),
+            Some(Format::Srt)
+        );
+        assert_eq!(best("@article{key, title = {T}}"), Some(Format::Bibtex));
+        assert_eq!(best("[Event \"Casual\"]\n\n1. e4 e5 *"), Some(Format::Pgn));
+        assert_eq!(
+            best("[[package]]\nname = \"a\"\nversion = \"1.0.0\"\n"),
+            Some(Format::CargoLock)
+        );
+        assert_eq!(
+            best("<?xml version=\"1.0\"?>\n<gpx version=\"1.1\"></gpx>"),
+            Some(Format::Gpx)
+        );
+        assert_eq!(
+            best("# cube\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n"),
+            Some(Format::Obj)
+        );
+        assert_eq!(best("just some words"), None);
+        assert_eq!(detect(&png::SIGNATURE)[0].format, Format::Png);
+        assert_eq!(detect(&[0x1f, 0x8b, 8, 0])[0].format, Format::Gzip);
+        assert!(detect(&[0xff, 0xfe, 0x00]).is_empty());
+        let utf16: Vec<u8> = "[1, 2]".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(detect(&utf16)[0].format, Format::Json);
+    }
+
+    #[test]
+    fn test_parse_any() {
+        let document = parse_any(b"[true, null]").unwrap();
+        assert_eq!(
+            document,
+            Document::Json(json::JsonValue::Array(vec![
+                json::JsonValue::Boolean(true),
+                json::JsonValue::Null
+            ]))
+        );
+        let document = parse_any(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        assert_eq!(document.format(), Format::Obj);
+
+        let error = parse_any(b"[1, x]").unwrap_err();
+        assert_eq!(error.format, "json");
+        assert!(parse_any(b"\xef\xbb\xbf[1]").is_ok());
+        let error = parse_any(b"\xff\xfe[\x001\x00]\x00").unwrap_err();
+        assert_eq!(error.found, "UTF-16LE text");
+        let error = parse_any(b"just some words").unwrap_err();
+        assert_eq!(error.format, "unknown");
+    }
+}
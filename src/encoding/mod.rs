@@ -0,0 +1,237 @@
+//! Getting text out of bytes of unknown encoding. The text parsers all take
+//! `&str`, so files saved by legacy tools in UTF-16 or Latin-1 go through
+//! here first instead of failing UTF-8 validation.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::Error;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO 8859-1, where every byte is the code point of the same value.
+    Latin1,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Latin1 => "ISO-8859-1",
+        })
+    }
+}
+
+/// What to do with bytes that aren't valid in the encoding.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Policy {
+    Strict,
+    /// Substitute U+FFFD.
+    Replace,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Decoded<'a> {
+    /// Borrowed from the input when it was already UTF-8.
+    pub text: Cow<'a, str>,
+    pub encoding: Encoding,
+    /// Whether the input started with a byte order mark. It is not part of
+    /// `text`.
+    pub bom: bool,
+    /// How many U+FFFD characters were substituted.
+    pub replacements: usize,
+}
+
+const BOMS: [(&[u8], Encoding); 3] = [
+    (&[0xef, 0xbb, 0xbf], Encoding::Utf8),
+    (&[0xff, 0xfe], Encoding::Utf16Le),
+    (&[0xfe, 0xff], Encoding::Utf16Be),
+];
+
+/// Guesses the encoding of `bytes`, returning it and the length of its
+/// byte order mark, if any. Without a BOM, text that is mostly ASCII with
+/// a zero byte in every other position is taken as UTF-16, valid UTF-8 as
+/// UTF-8, and anything else as Latin-1.
+pub fn detect(bytes: &[u8]) -> (Encoding, usize) {
+    if let Some((bom, encoding)) = BOMS.iter().find(|(bom, _)| bytes.starts_with(bom)) {
+        return (*encoding, bom.len());
+    }
+    let sample = &bytes[..bytes.len().min(1024) & !1];
+    let zeros = |parity: usize| {
+        sample
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count()
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+    let pairs = sample.len() / 2;
+    if pairs > 0 && even == 0 && odd * 2 > pairs {
+        (Encoding::Utf16Le, 0)
+    } else if pairs > 0 && odd == 0 && even * 2 > pairs {
+        (Encoding::Utf16Be, 0)
+    } else if std::str::from_utf8(bytes).is_ok() {
+        (Encoding::Utf8, 0)
+    } else {
+        (Encoding::Latin1, 0)
+    }
+}
+
+/// Detects the encoding of `bytes` and decodes them.
+pub fn decode(bytes: &[u8], policy: Policy) -> Result<Decoded<'_>, Error> {
+    let (encoding, bom) = detect(bytes);
+    decode_with(bytes, bom, encoding, policy)
+}
+
+/// Decodes `bytes` as `encoding`, skipping a byte order mark if there is
+/// one for that encoding.
+pub fn decode_as(bytes: &[u8], encoding: Encoding, policy: Policy) -> Result<Decoded<'_>, Error> {
+    let bom = BOMS
+        .iter()
+        .find(|(bom, e)| *e == encoding && bytes.starts_with(bom))
+        .map_or(0, |(bom, _)| bom.len());
+    decode_with(bytes, bom, encoding, policy)
+}
+
+fn invalid(bytes: &[u8], at: usize, encoding: Encoding) -> Error {
+    Error::expected(
+        "encoding",
+        bytes,
+        &bytes[at..],
+        format!("valid {}", encoding),
+    )
+}
+
+fn decode_with(
+    bytes: &[u8],
+    bom: usize,
+    encoding: Encoding,
+    policy: Policy,
+) -> Result<Decoded<'_>, Error> {
+    let body = &bytes[bom..];
+    let mut replacements = 0;
+    let text = match encoding {
+        Encoding::Utf8 => match std::str::from_utf8(body) {
+            Ok(text) => Cow::Borrowed(text),
+            Err(error) if policy == Policy::Strict => {
+                return Err(invalid(bytes, bom + error.valid_up_to(), encoding))
+            }
+            Err(_) => {
+                let mut text = String::with_capacity(body.len());
+                for chunk in body.utf8_chunks() {
+                    text.push_str(chunk.valid());
+                    if !chunk.invalid().is_empty() {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        replacements += 1;
+                    }
+                }
+                Cow::Owned(text)
+            }
+        },
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let unit = |pair: &[u8]| match encoding {
+                Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                _ => u16::from_be_bytes([pair[0], pair[1]]),
+            };
+            let mut text = String::with_capacity(body.len() / 2);
+            let mut position = bom;
+            for c in char::decode_utf16(body.chunks_exact(2).map(unit)) {
+                match c {
+                    Ok(c) => {
+                        text.push(c);
+                        position += c.len_utf16() * 2;
+                    }
+                    Err(_) if policy == Policy::Strict => {
+                        return Err(invalid(bytes, position, encoding))
+                    }
+                    Err(_) => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        replacements += 1;
+                        position += 2;
+                    }
+                }
+            }
+            if body.len() % 2 == 1 {
+                if policy == Policy::Strict {
+                    return Err(invalid(bytes, bytes.len() - 1, encoding));
+                }
+                text.push(char::REPLACEMENT_CHARACTER);
+                replacements += 1;
+            }
+            Cow::Owned(text)
+        }
+        Encoding::Latin1 => Cow::Owned(body.iter().map(|&b| char::from(b)).collect()),
+    };
+    Ok(Decoded {
+        text,
+        encoding,
+        bom: bom > 0,
+        replacements,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, encode: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        text.encode_utf16().flat_map(encode).collect()
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(b"\xef\xbb\xbf{}"), (Encoding::Utf8, 3));
+        assert_eq!(detect(b"\xff\xfe{\0}\0"), (Encoding::Utf16Le, 2));
+        assert_eq!(detect("héllo".as_bytes()), (Encoding::Utf8, 0));
+        assert_eq!(detect(b"h\xe9llo"), (Encoding::Latin1, 0));
+        assert_eq!(
+            detect(&utf16("[1, 2]", u16::to_le_bytes)),
+            (Encoding::Utf16Le, 0)
+        );
+        assert_eq!(
+            detect(&utf16("[1, 2]", u16::to_be_bytes)),
+            (Encoding::Utf16Be, 0)
+        );
+        assert_eq!(detect(b""), (Encoding::Utf8, 0));
+    }
+
+    #[test]
+    fn test_decode() {
+        let decoded = decode(b"\xef\xbb\xbf{}", Policy::Strict).unwrap();
+        assert_eq!(decoded.text, Cow::Borrowed("{}"));
+        assert!(decoded.bom);
+
+        let decoded = decode(b"caf\xe9", Policy::Strict).unwrap();
+        assert_eq!(
+            (decoded.text.as_ref(), decoded.encoding),
+            ("café", Encoding::Latin1)
+        );
+
+        let mut bytes = vec![0xfe, 0xff];
+        bytes.extend(utf16("a😀", u16::to_be_bytes));
+        assert_eq!(decode(&bytes, Policy::Strict).unwrap().text, "a😀");
+
+        let bytes = [b'a', 0, 0x00, 0xd8, b'b', 0];
+        let error = decode_as(&bytes, Encoding::Utf16Le, Policy::Strict).unwrap_err();
+        assert_eq!(error.span, 2..3);
+        assert_eq!(error.expected.as_deref(), Some("valid UTF-16LE"));
+        let decoded = decode_as(&bytes, Encoding::Utf16Le, Policy::Replace).unwrap();
+        assert_eq!(
+            (decoded.text.as_ref(), decoded.replacements),
+            ("a\u{fffd}b", 1)
+        );
+
+        let decoded = decode_as(b"a\xffb\xfe", Encoding::Utf8, Policy::Replace).unwrap();
+        assert_eq!(
+            (decoded.text.as_ref(), decoded.replacements),
+            ("a\u{fffd}b\u{fffd}", 2)
+        );
+        assert!(decode_as(b"a\xffb", Encoding::Utf8, Policy::Strict).is_err());
+    }
+}
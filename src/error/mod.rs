@@ -0,0 +1,572 @@
+//! A crate-wide error type. Parsers keep returning nom errors so they
+//! compose, and entry points convert them into an `Error` that records
+//! where the input went wrong and can render it for a terminal.
+
+use std::fmt;
+use std::ops::Range;
+
+use nom::{
+    error::{ErrorKind, VerboseError, VerboseErrorKind},
+    Err as NomErr, IResult, Offset,
+};
+
+use crate::span::Span;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Error {
+    /// The format being parsed, such as `"json"` or `"http"`.
+    pub format: &'static str,
+    /// Byte range of the offending input.
+    pub span: Range<usize>,
+    /// Where `span` starts, 1-based. Columns count characters.
+    pub line: usize,
+    pub column: usize,
+    /// What the parser wanted. `None` when the innermost context names a
+    /// semantic problem instead, such as a checksum mismatch.
+    pub expected: Option<String>,
+    pub found: String,
+    /// Contexts from `nom::error::context`, outermost first.
+    pub context: Vec<&'static str>,
+}
+
+/// What a parser's errors are called elsewhere; the same type as `Error`.
+pub type ParseError = Error;
+
+/// The line and column just after `before`, counting UTF-8 continuation
+/// bytes as part of the character they continue.
+pub(crate) fn line_column(before: &[u8]) -> (usize, usize) {
+    let line_start = before
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    let line = before.iter().filter(|&&b| b == b'\n').count() + 1;
+    let column = before[line_start..]
+        .iter()
+        .filter(|&&b| b & 0xc0 != 0x80)
+        .count();
+    (line, column + 1)
+}
+
+/// Input types an `Error` can be built from.
+pub trait Fragment: Copy {
+    /// Byte offset of `self` within `input`, which it must be a suffix of.
+    fn offset_in(self, input: Self) -> usize;
+    fn byte_len(self) -> usize;
+    /// The line and column at byte `offset`.
+    fn line_column(self, offset: usize) -> (usize, usize);
+    /// Length in bytes of the token at the start of `self`.
+    fn token_len(self) -> usize;
+    fn describe(self) -> String;
+}
+
+impl Fragment for &str {
+    fn offset_in(self, input: Self) -> usize {
+        input.offset(self)
+    }
+
+    fn byte_len(self) -> usize {
+        self.len()
+    }
+
+    fn line_column(self, offset: usize) -> (usize, usize) {
+        line_column(&self.as_bytes()[..offset])
+    }
+
+    fn token_len(self) -> usize {
+        match self.chars().next() {
+            Some(c) if c.is_alphanumeric() => self
+                .find(|c: char| !c.is_alphanumeric())
+                .unwrap_or(self.len()),
+            Some(c) => c.len_utf8(),
+            None => 0,
+        }
+    }
+
+    fn describe(self) -> String {
+        match self.chars().next() {
+            Some(c) if c.is_alphanumeric() => format!("`{}`", &self[..self.token_len()]),
+            Some(c) => format!("{:?}", c),
+            None => "end of input".to_string(),
+        }
+    }
+}
+
+impl Fragment for &[u8] {
+    fn offset_in(self, input: Self) -> usize {
+        input.offset(self)
+    }
+
+    fn byte_len(self) -> usize {
+        self.len()
+    }
+
+    fn line_column(self, offset: usize) -> (usize, usize) {
+        line_column(&self[..offset])
+    }
+
+    fn token_len(self) -> usize {
+        self.len().min(1)
+    }
+
+    fn describe(self) -> String {
+        match self.first() {
+            Some(byte) => format!("byte 0x{:02x}", byte),
+            None => "end of input".to_string(),
+        }
+    }
+}
+
+fn describe_kind(kind: ErrorKind) -> String {
+    match kind {
+        ErrorKind::Tag | ErrorKind::TagBits => "literal".to_string(),
+        ErrorKind::Char => "character".to_string(),
+        ErrorKind::Digit => "digit".to_string(),
+        ErrorKind::HexDigit => "hex digit".to_string(),
+        ErrorKind::Alpha => "letter".to_string(),
+        ErrorKind::AlphaNumeric => "letter or digit".to_string(),
+        ErrorKind::Space | ErrorKind::MultiSpace => "whitespace".to_string(),
+        ErrorKind::CrLf => "line ending".to_string(),
+        ErrorKind::Eof => "end of input".to_string(),
+        ErrorKind::Float => "number".to_string(),
+        ErrorKind::TooLarge => "less deeply nested input".to_string(),
+        other => other.description().to_lowercase(),
+    }
+}
+
+impl Error {
+    fn at<I: Fragment>(format: &'static str, input: I, fragment: I) -> Self {
+        let start = fragment.offset_in(input);
+        let (line, column) = input.line_column(start);
+        Error {
+            format,
+            span: start..start + fragment.token_len(),
+            line,
+            column,
+            expected: None,
+            found: fragment.describe(),
+            context: vec![],
+        }
+    }
+
+    /// An error for input left over after a successful parse.
+    pub fn trailing<I: Fragment>(format: &'static str, input: I, rest: I) -> Self {
+        let mut error = Error::at(format, input, rest);
+        error.expected = Some("end of input".to_string());
+        error
+    }
+
+    /// An error at `at`, a suffix of `input`, for parsers that report
+    /// problems themselves instead of through nom.
+    pub fn expected<I: Fragment>(
+        format: &'static str,
+        input: I,
+        at: I,
+        expected: impl Into<String>,
+    ) -> Self {
+        let mut error = Error::at(format, input, at);
+        error.expected = Some(expected.into());
+        error
+    }
+
+    fn incomplete<I: Fragment>(format: &'static str, input: I) -> Self {
+        let end = input.byte_len();
+        let (line, column) = input.line_column(end);
+        Error {
+            format,
+            span: end..end,
+            line,
+            column,
+            expected: Some("more input".to_string()),
+            found: "end of input".to_string(),
+            context: vec![],
+        }
+    }
+
+    /// Converts an error from a parser using `VerboseError`. `input` is the
+    /// complete input the parser was given.
+    pub fn from_verbose<I: Fragment>(
+        format: &'static str,
+        input: I,
+        error: NomErr<VerboseError<I>>,
+    ) -> Self {
+        let error = match error {
+            NomErr::Incomplete(_) => return Error::incomplete(format, input),
+            NomErr::Error(error) | NomErr::Failure(error) => error,
+        };
+        let Some(&(fragment, _)) = error.errors.first() else {
+            return Error::at(format, input, input);
+        };
+        let mut result = Error::at(format, input, fragment);
+        result.expected = match error.errors[0].1 {
+            VerboseErrorKind::Char(c) => Some(format!("{:?}", c)),
+            VerboseErrorKind::Nom(kind) => Some(describe_kind(kind)),
+            VerboseErrorKind::Context(_) => None,
+        };
+        result.context = error
+            .errors
+            .iter()
+            .rev()
+            .filter_map(|(_, kind)| match kind {
+                VerboseErrorKind::Context(context) => Some(*context),
+                _ => None,
+            })
+            .collect();
+        result
+    }
+
+    /// Converts an error from a parser using nom's default error type.
+    pub fn from_nom<I: Fragment>(
+        format: &'static str,
+        input: I,
+        error: NomErr<nom::error::Error<I>>,
+    ) -> Self {
+        match error {
+            NomErr::Incomplete(_) => Error::incomplete(format, input),
+            NomErr::Error(error) | NomErr::Failure(error) => {
+                let mut result = Error::at(format, input, error.input);
+                result.expected = Some(describe_kind(error.code));
+                result
+            }
+        }
+    }
+
+    /// Moves the error from a piece of a larger input to that input, given
+    /// where the piece starts in it.
+    pub(crate) fn relocate(&mut self, offset: usize, line: usize, column: usize) {
+        self.span = self.span.start + offset..self.span.end + offset;
+        if self.line == 1 {
+            self.column += column - 1;
+        }
+        self.line += line - 1;
+    }
+
+    fn message(&self) -> String {
+        match (&self.expected, self.context.last()) {
+            (Some(expected), _) => format!("expected {}, found {}", expected, self.found),
+            (None, Some(reason)) => format!("{}, found {}", reason, self.found),
+            (None, None) => format!("unexpected {}", self.found),
+        }
+    }
+
+    /// Where the error is in `input`.
+    pub fn location<'a>(&self, input: &'a str) -> Span<'a> {
+        Span::at(input, self.span.start, self.span.len())
+    }
+
+    /// Renders the error with the offending line of `input` and the span
+    /// underlined, in the style of compiler diagnostics.
+    pub fn render(&self, input: &str) -> String {
+        let location = self.location(input);
+        let (line, column) = (location.line, location.column);
+        let line_start = input[..location.offset].rfind('\n').map_or(0, |i| i + 1);
+        let text = input[line_start..].lines().next().unwrap_or("");
+        let width = location
+            .fragment
+            .chars()
+            .take_while(|&c| c != '\n')
+            .count()
+            .max(1);
+        let gutter = line.to_string().len();
+        let mut out = format!("error: {}\n", self.message());
+        out += &format!(
+            "{:gutter$}--> {}:{}:{}\n",
+            "",
+            self.format,
+            line,
+            column,
+            gutter = gutter
+        );
+        out += &format!("{:gutter$} |\n", "", gutter = gutter);
+        out += &format!("{} | {}\n", line, text);
+        out += &format!(
+            "{:gutter$} | {:pad$}{}\n",
+            "",
+            "",
+            "^".repeat(width),
+            gutter = gutter,
+            pad = column - 1
+        );
+        if !self.context.is_empty() {
+            out += &format!(
+                "{:gutter$} = while parsing {}\n",
+                "",
+                self.context.join(" > "),
+                gutter = gutter
+            );
+        }
+        out
+    }
+
+    /// Renders the error as an annotated snippet of `input` with `ariadne`:
+    /// the span labelled with what was expected and the parse context as a
+    /// note. `color` adds ANSI colors, for terminals.
+    #[cfg(feature = "diagnostics")]
+    pub fn diagnostic(&self, input: &str, color: bool) -> String {
+        use ariadne::{Config, IndexType, Label, Report, ReportKind, Source};
+
+        let span = self.span.start.min(input.len())..self.span.end.min(input.len());
+        let label = match &self.expected {
+            Some(expected) => format!("expected {}", expected),
+            None => format!("found {}", self.found),
+        };
+        let config = Config::default()
+            .with_color(color)
+            .with_index_type(IndexType::Byte);
+        let mut report = Report::build(ReportKind::Error, (self.format, span.clone()))
+            .with_config(config)
+            .with_message(self.message())
+            .with_label(Label::new((self.format, span)).with_message(label));
+        if !self.context.is_empty() {
+            report = report.with_note(format!("while parsing {}", self.context.join(" > ")));
+        }
+        let mut out = vec![];
+        report
+            .finish()
+            .write((self.format, Source::from(input)), &mut out)
+            .expect("writing to a Vec can't fail");
+        String::from_utf8_lossy(&out).into_owned()
+    }
+}
+
+/// The result of a recovering parse: a best-effort value and every error
+/// that was skipped over to produce it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Recovered<T> {
+    pub value: T,
+    pub errors: Vec<Error>,
+}
+
+impl<T> Recovered<T> {
+    /// The value, if it was parsed without any errors.
+    pub fn into_result(self) -> Result<T, Vec<Error>> {
+        match self.errors.is_empty() {
+            true => Ok(self.value),
+            false => Err(self.errors),
+        }
+    }
+}
+
+/// Runs `parser` over the whole of `input`, converting failures and
+/// leftover input into an `Error`.
+pub fn parse_all<'a, O>(
+    format: &'static str,
+    input: &'a str,
+    parser: impl FnOnce(&'a str) -> IResult<&'a str, O, VerboseError<&'a str>>,
+) -> Result<O, Error> {
+    traced(format, input.len(), || {
+        crate::limits::check_len(format, input)?;
+        match parser(input) {
+            Ok(("", value)) => Ok(value),
+            Ok((rest, _)) => Err(Error::trailing(format, input, rest)),
+            Err(error) => Err(Error::from_verbose(format, input, error)),
+        }
+    })
+}
+
+/// Runs an entry point's `parse` inside a `parse` span, then records how
+/// long it took and, if it failed, where. Used by every public function
+/// that turns input into an `Error`, so a subscriber sees each parse once.
+#[cfg(feature = "tracing")]
+pub(crate) fn traced<T>(
+    format: &'static str,
+    len: usize,
+    parse: impl FnOnce() -> Result<T, Error>,
+) -> Result<T, Error> {
+    let span = tracing::debug_span!("parse", format, bytes = len);
+    let _entered = span.enter();
+    let start = std::time::Instant::now();
+    let result = parse();
+    let elapsed = start.elapsed();
+    match &result {
+        Ok(_) => tracing::debug!(?elapsed, "parsed"),
+        Err(error) => tracing::debug!(
+            ?elapsed,
+            offset = error.span.start,
+            expected = error.expected.as_deref(),
+            found = %error.found,
+            "parse failed"
+        ),
+    }
+    result
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn traced<T>(
+    _format: &'static str,
+    _len: usize,
+    parse: impl FnOnce() -> Result<T, Error>,
+) -> Result<T, Error> {
+    parse()
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} error at byte {}: {}",
+            self.format,
+            self.span.start,
+            self.message()
+        )
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_verbose() {
+        let input = "https:example.com";
+        let error = crate::http::uri(input).unwrap_err();
+        let error = Error::from_verbose("http", input, error);
+        assert_eq!(error.span, 6..13);
+        assert_eq!(error.expected.as_deref(), Some("literal"));
+        assert_eq!(error.found, "`example`");
+        assert_eq!(error.context, vec!["uri", "scheme"]);
+        assert_eq!(
+            error.to_string(),
+            "http error at byte 6: expected literal, found `example`"
+        );
+    }
+
+    #[test]
+    fn test_from_nom() {
+        let input = "[\n  1,\n  x\n]";
+        let error = crate::json::parse_array(input).unwrap_err();
+        let error = Error::from_nom("json", input, error);
+        assert_eq!(error.span, 9..10);
+        assert_eq!((error.line, error.column), (3, 3));
+        assert_eq!(
+            error.render(input),
+            "error: expected character, found `x`\n \
+             --> json:3:3\n  \
+             |\n\
+             3 |   x\n  \
+             |   ^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_context() {
+        let input = "x = tru";
+        let error = Error {
+            format: "toml",
+            span: 4..7,
+            line: 1,
+            column: 5,
+            expected: Some("boolean".to_string()),
+            found: "`tru`".to_string(),
+            context: vec!["table", "value"],
+        };
+        assert_eq!(
+            error.render(input),
+            "error: expected boolean, found `tru`\n \
+             --> toml:1:5\n  \
+             |\n\
+             1 | x = tru\n  \
+             |     ^^^\n  \
+             = while parsing table > value\n"
+        );
+    }
+
+    #[test]
+    fn test_bytes() {
+        let input: &[u8] = &[0x1f, 0x8b, 9, 0, 0, 0, 0, 0, 0, 3];
+        let error = crate::gzip::header(input).unwrap_err();
+        let error = Error::from_verbose("gzip", input, error);
+        assert_eq!(error.span, 0..1);
+        assert_eq!(error.found, "byte 0x1f");
+        assert_eq!((error.line, error.column), (1, 1));
+        assert_eq!(
+            Error::from_verbose::<&[u8]>("gzip", input, NomErr::Incomplete(nom::Needed::Unknown))
+                .span,
+            10..10
+        );
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[test]
+    fn test_diagnostic() {
+        let input = "https:example.com";
+        let error = crate::http::uri_complete(input).unwrap_err();
+        let rendered = error.diagnostic(input, false);
+        assert!(rendered.starts_with("Error: "), "{}", rendered);
+        assert!(rendered.contains(input), "{}", rendered);
+        assert!(rendered.contains("while parsing uri > scheme"), "{}", rendered);
+
+        let input = "[1]\n x";
+        let error = input.parse::<crate::JsonValue>().unwrap_err();
+        let rendered = error.diagnostic(input, false);
+        assert!(rendered.contains("json:2:2"), "{}", rendered);
+        assert!(!error.diagnostic(input, true).is_empty());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_traced() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        #[derive(Default)]
+        struct Fields(Vec<String>);
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                if field.name() != "elapsed" {
+                    self.0.push(format!("{}={:?}", field.name(), value));
+                }
+            }
+        }
+
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut fields = Fields::default();
+                span.record(&mut fields);
+                self.0.lock().unwrap().push(fields.0.join(" "));
+                Id::from_u64(1)
+            }
+
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields::default();
+                event.record(&mut fields);
+                self.0.lock().unwrap().push(fields.0.join(" "));
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            assert!("[1, 2]".parse::<crate::json::JsonValue>().is_ok());
+            assert!("[1] x".parse::<crate::json::JsonValue>().is_err());
+        });
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                "format=\"json\" bytes=6",
+                "message=parsed",
+                "format=\"json\" bytes=5",
+                "message=parse failed offset=4 expected=\"end of input\" found=`x`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,528 @@
+//! C bindings, enabled with the `ffi` feature. See `include/parsers.h`.
+//!
+//! Parse functions return an owned handle or null, in which case `*error`
+//! is set to an owned error if `error` is not null. Every owned handle must
+//! be released with its `_free` function. Pointers returned by accessors
+//! borrow from the handle they came from and stay valid until it is freed.
+//! Strings from JSON values are not NUL-terminated; their length is written
+//! to `*len`.
+
+#![cfg(feature = "ffi")]
+
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+use crate::error::Error;
+use crate::http::UriBuf;
+use crate::json::JsonValue;
+
+pub struct ParsersError {
+    message: CString,
+    offset: usize,
+}
+
+pub struct ParsersUri {
+    scheme: CString,
+    user: Option<CString>,
+    password: Option<CString>,
+    host: Option<CString>,
+    port: i32,
+    path: Vec<CString>,
+    query: Vec<(CString, Option<CString>)>,
+    fragment: Option<CString>,
+}
+
+/// A JSON value. Handles point at a `JsonValue`; those for nested values
+/// borrow from the root.
+pub struct ParsersJson {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ParsersJsonType {
+    Null,
+    Boolean,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+fn c_string(s: &str) -> CString {
+    // The input came from a C string, so nothing parsed from it has a NUL.
+    CString::new(s).expect("no interior NUL")
+}
+
+fn opt_ptr(s: &Option<CString>) -> *const c_char {
+    s.as_ref().map_or(ptr::null(), |s| s.as_ptr())
+}
+
+unsafe fn report(error: *mut *mut ParsersError, message: &str, offset: usize) {
+    if !error.is_null() {
+        *error = Box::into_raw(Box::new(ParsersError {
+            message: c_string(&message.replace('\0', "")),
+            offset,
+        }));
+    }
+}
+
+unsafe fn parse_input<'a, T>(
+    input: *const c_char,
+    error: *mut *mut ParsersError,
+    parse: impl FnOnce(&'a str) -> Result<T, Error>,
+) -> Option<T> {
+    if input.is_null() {
+        report(error, "input is null", 0);
+        return None;
+    }
+    let Ok(input) = CStr::from_ptr(input).to_str() else {
+        report(error, "input is not valid UTF-8", 0);
+        return None;
+    };
+    parse(input)
+        .map_err(|e| report(error, &e.to_string(), e.span.start))
+        .ok()
+}
+
+/// The error message, NUL-terminated.
+///
+/// # Safety
+///
+/// `error` must be a live handle from a parse function.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_error_message(error: *const ParsersError) -> *const c_char {
+    (*error).message.as_ptr()
+}
+
+/// Byte offset of the error in the input.
+///
+/// # Safety
+///
+/// `error` must be a live handle from a parse function.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_error_offset(error: *const ParsersError) -> usize {
+    (*error).offset
+}
+
+/// # Safety
+///
+/// `error` must be null or a handle from a parse function that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_error_free(error: *mut ParsersError) {
+    if !error.is_null() {
+        drop(Box::from_raw(error));
+    }
+}
+
+/// Parses a NUL-terminated URI. Trailing input is an error.
+///
+/// # Safety
+///
+/// `input` must be null or a NUL-terminated string, and `error` null or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_uri_parse(
+    input: *const c_char,
+    error: *mut *mut ParsersError,
+) -> *mut ParsersUri {
+    let Some(buf) = parse_input(input, error, |s| s.parse::<UriBuf>()) else {
+        return ptr::null_mut();
+    };
+    let uri = buf.uri();
+    Box::into_raw(Box::new(ParsersUri {
+        scheme: c_string(uri.scheme.as_str()),
+        user: uri.user().map(c_string),
+        password: uri.password().map(c_string),
+        host: uri.host().map(|host| c_string(&host.to_string())),
+        port: uri.port.map_or(-1, i32::from),
+        path: uri
+            .path_segments()
+            .iter()
+            .map(|s| c_string(s.as_str()))
+            .collect(),
+        query: uri
+            .query
+            .iter()
+            .flatten()
+            .map(|(key, value)| (c_string(key), value.map(c_string)))
+            .collect(),
+        fragment: uri.fragment.map(c_string),
+    }))
+}
+
+/// # Safety
+///
+/// `uri` must be a live handle from `parsers_uri_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_uri_scheme(uri: *const ParsersUri) -> *const c_char {
+    (*uri).scheme.as_ptr()
+}
+
+/// The user from the authority, or null.
+///
+/// # Safety
+///
+/// `uri` must be a live handle from `parsers_uri_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_uri_user(uri: *const ParsersUri) -> *const c_char {
+    opt_ptr(&(*uri).user)
+}
+
+/// The password from the authority, or null.
+///
+/// # Safety
+///
+/// `uri` must be a live handle from `parsers_uri_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_uri_password(uri: *const ParsersUri) -> *const c_char {
+    opt_ptr(&(*uri).password)
+}
+
+/// The host, or null if the URI has no authority.
+///
+/// # Safety
+///
+/// `uri` must be a live handle from `parsers_uri_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_uri_host(uri: *const ParsersUri) -> *const c_char {
+    opt_ptr(&(*uri).host)
+}
+
+/// The explicit port, or -1.
+///
+/// # Safety
+///
+/// `uri` must be a live handle from `parsers_uri_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_uri_port(uri: *const ParsersUri) -> i32 {
+    (*uri).port
+}
+
+/// # Safety
+///
+/// `uri` must be a live handle from `parsers_uri_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_uri_path_len(uri: *const ParsersUri) -> usize {
+    (*uri).path.len()
+}
+
+/// The path segment at `index`, or null if out of range.
+///
+/// # Safety
+///
+/// `uri` must be a live handle from `parsers_uri_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_uri_path_segment(
+    uri: *const ParsersUri,
+    index: usize,
+) -> *const c_char {
+    let uri = &*uri;
+    uri.path.get(index).map_or(ptr::null(), |s| s.as_ptr())
+}
+
+/// # Safety
+///
+/// `uri` must be a live handle from `parsers_uri_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_uri_query_len(uri: *const ParsersUri) -> usize {
+    (*uri).query.len()
+}
+
+/// The key of the query parameter at `index`, or null if out of range.
+///
+/// # Safety
+///
+/// `uri` must be a live handle from `parsers_uri_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_uri_query_key(
+    uri: *const ParsersUri,
+    index: usize,
+) -> *const c_char {
+    let uri = &*uri;
+    uri.query
+        .get(index)
+        .map_or(ptr::null(), |(k, _)| k.as_ptr())
+}
+
+/// The value of the query parameter at `index`, or null if out of range or
+/// the key has no `=`, as in `?flag`.
+///
+/// # Safety
+///
+/// `uri` must be a live handle from `parsers_uri_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_uri_query_value(
+    uri: *const ParsersUri,
+    index: usize,
+) -> *const c_char {
+    let uri = &*uri;
+    uri.query
+        .get(index)
+        .map_or(ptr::null(), |(_, v)| opt_ptr(v))
+}
+
+/// The fragment, or null.
+///
+/// # Safety
+///
+/// `uri` must be a live handle from `parsers_uri_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_uri_fragment(uri: *const ParsersUri) -> *const c_char {
+    opt_ptr(&(*uri).fragment)
+}
+
+/// # Safety
+///
+/// `uri` must be null or a handle from `parsers_uri_parse` that has not
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_uri_free(uri: *mut ParsersUri) {
+    if !uri.is_null() {
+        drop(Box::from_raw(uri));
+    }
+}
+
+/// Parses a NUL-terminated JSON document.
+///
+/// # Safety
+///
+/// `input` must be null or a NUL-terminated string, and `error` null or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_json_parse(
+    input: *const c_char,
+    error: *mut *mut ParsersError,
+) -> *mut ParsersJson {
+    match parse_input(input, error, |s| s.parse::<JsonValue>()) {
+        Some(value) => Box::into_raw(Box::new(value)) as *mut ParsersJson,
+        None => ptr::null_mut(),
+    }
+}
+
+fn child(value: Option<&JsonValue>) -> *const ParsersJson {
+    value.map_or(ptr::null(), |value| {
+        value as *const JsonValue as *const ParsersJson
+    })
+}
+
+unsafe fn json<'a>(value: *const ParsersJson) -> &'a JsonValue {
+    &*(value as *const JsonValue)
+}
+
+unsafe fn bytes(s: &str, len: *mut usize) -> *const c_char {
+    if !len.is_null() {
+        *len = s.len();
+    }
+    s.as_ptr() as *const c_char
+}
+
+/// # Safety
+///
+/// `value` must be a live JSON handle.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_json_type(value: *const ParsersJson) -> ParsersJsonType {
+    match json(value) {
+        JsonValue::Null => ParsersJsonType::Null,
+        JsonValue::Boolean(_) => ParsersJsonType::Boolean,
+        JsonValue::Number(_) | JsonValue::Decimal(_) => ParsersJsonType::Number,
+        JsonValue::String(_) => ParsersJsonType::String,
+        JsonValue::Array(_) => ParsersJsonType::Array,
+        JsonValue::Object(_) => ParsersJsonType::Object,
+    }
+}
+
+/// The boolean value, or false for other types.
+///
+/// # Safety
+///
+/// `value` must be a live JSON handle.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_json_boolean(value: *const ParsersJson) -> bool {
+    matches!(*json(value), JsonValue::Boolean(true))
+}
+
+/// The numeric value, or NaN for other types.
+///
+/// # Safety
+///
+/// `value` must be a live JSON handle.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_json_number(value: *const ParsersJson) -> f64 {
+    match *json(value) {
+        JsonValue::Number(number) => number,
+        JsonValue::Decimal(ref number) => number.as_f64(),
+        _ => f64::NAN,
+    }
+}
+
+/// The string value, or null for other types.
+///
+/// # Safety
+///
+/// `value` must be a live JSON handle and `len` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_json_string(
+    value: *const ParsersJson,
+    len: *mut usize,
+) -> *const c_char {
+    match json(value) {
+        JsonValue::String(string) => bytes(string, len),
+        _ => ptr::null(),
+    }
+}
+
+/// The number of elements or members, or 0 for scalars.
+///
+/// # Safety
+///
+/// `value` must be a live JSON handle.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_json_len(value: *const ParsersJson) -> usize {
+    match json(value) {
+        JsonValue::Array(items) => items.len(),
+        JsonValue::Object(fields) => fields.len(),
+        _ => 0,
+    }
+}
+
+/// The array element or object member value at `index`, or null.
+///
+/// # Safety
+///
+/// `value` must be a live JSON handle.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_json_at(
+    value: *const ParsersJson,
+    index: usize,
+) -> *const ParsersJson {
+    match json(value) {
+        JsonValue::Array(items) => child(items.get(index)),
+        JsonValue::Object(fields) => child(fields.get(index).map(|(_, v)| v)),
+        _ => ptr::null(),
+    }
+}
+
+/// The key of the object member at `index`, or null.
+///
+/// # Safety
+///
+/// `value` must be a live JSON handle and `len` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_json_key(
+    value: *const ParsersJson,
+    index: usize,
+    len: *mut usize,
+) -> *const c_char {
+    match json(value) {
+        JsonValue::Object(fields) => fields
+            .get(index)
+            .map_or(ptr::null(), |(key, _)| bytes(key, len)),
+        _ => ptr::null(),
+    }
+}
+
+/// The value of the first member named `key`, or null.
+///
+/// # Safety
+///
+/// `value` must be a live JSON handle and `key` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_json_get(
+    value: *const ParsersJson,
+    key: *const c_char,
+) -> *const ParsersJson {
+    let key = CStr::from_ptr(key).to_bytes();
+    match json(value) {
+        JsonValue::Object(fields) => child(
+            fields
+                .iter()
+                .find(|(name, _)| name.as_bytes() == key)
+                .map(|(_, v)| v),
+        ),
+        _ => ptr::null(),
+    }
+}
+
+/// Frees a document. Only handles from `parsers_json_parse` may be freed,
+/// not the nested values borrowed from them.
+///
+/// # Safety
+///
+/// `value` must be null or a handle from `parsers_json_parse` that has not
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn parsers_json_free(value: *mut ParsersJson) {
+    if !value.is_null() {
+        drop(Box::from_raw(value as *mut JsonValue));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn str_at(ptr: *const c_char) -> &'static str {
+        unsafe { CStr::from_ptr(ptr).to_str().unwrap() }
+    }
+
+    #[test]
+    fn test_uri() {
+        unsafe {
+            let uri = parsers_uri_parse(
+                c"https://me@example.org:8080/a/b?x=1&flag".as_ptr(),
+                ptr::null_mut(),
+            );
+            assert!(!uri.is_null());
+            assert_eq!(str_at(parsers_uri_scheme(uri)), "https");
+            assert_eq!(str_at(parsers_uri_user(uri)), "me");
+            assert!(parsers_uri_password(uri).is_null());
+            assert_eq!(str_at(parsers_uri_host(uri)), "example.org");
+            assert_eq!(parsers_uri_port(uri), 8080);
+            assert_eq!(parsers_uri_path_len(uri), 2);
+            assert_eq!(str_at(parsers_uri_path_segment(uri, 1)), "b");
+            assert!(parsers_uri_path_segment(uri, 2).is_null());
+            assert_eq!(str_at(parsers_uri_query_value(uri, 0)), "1");
+            assert_eq!(str_at(parsers_uri_query_key(uri, 1)), "flag");
+            assert!(parsers_uri_query_value(uri, 1).is_null());
+            assert!(parsers_uri_fragment(uri).is_null());
+            parsers_uri_free(uri);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            let mut error = ptr::null_mut();
+            let uri = parsers_uri_parse(c"https://example.org x".as_ptr(), &mut error);
+            assert!(uri.is_null());
+            assert_eq!(parsers_error_offset(error), 19);
+            assert!(str_at(parsers_error_message(error)).contains("expected end of input"));
+            parsers_error_free(error);
+            assert!(parsers_json_parse(ptr::null(), ptr::null_mut()).is_null());
+        }
+    }
+
+    #[test]
+    fn test_json() {
+        unsafe {
+            let root = parsers_json_parse(
+                c"{\"a\": [1, \"x\"], \"b\": true}".as_ptr(),
+                ptr::null_mut(),
+            );
+            assert_eq!(parsers_json_type(root), ParsersJsonType::Object);
+            assert_eq!(parsers_json_len(root), 2);
+            let mut len = 0;
+            let key = parsers_json_key(root, 1, &mut len);
+            assert_eq!(std::slice::from_raw_parts(key as *const u8, len), b"b");
+            let a = parsers_json_get(root, c"a".as_ptr());
+            assert_eq!(parsers_json_number(parsers_json_at(a, 0)), 1.0);
+            let x = parsers_json_string(parsers_json_at(a, 1), &mut len);
+            assert_eq!(std::slice::from_raw_parts(x as *const u8, len), b"x");
+            assert!(parsers_json_boolean(parsers_json_at(root, 1)));
+            assert!(parsers_json_get(root, c"c".as_ptr()).is_null());
+            parsers_json_free(root);
+        }
+    }
+}
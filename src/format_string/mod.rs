@@ -0,0 +1,584 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, tag, take_while1},
+    character::complete::{anychar, char, digit1, one_of},
+    combinator::{map, map_res, opt, peek, value, verify},
+    error::{context, VerboseError},
+    multi::many0,
+    sequence::{pair, preceded, terminated, tuple},
+    IResult,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Piece<'a, S> {
+    Literal(&'a str),
+    Spec(S),
+}
+
+/// A width or precision: a fixed number, or taken from an argument.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Count<'a> {
+    Fixed(usize),
+    /// `*` in C, `.*` in Rust.
+    Next,
+    Positional(usize),
+    Named(&'a str),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct PrintfFlags {
+    pub left_align: bool,
+    pub plus: bool,
+    pub space: bool,
+    pub alternate: bool,
+    pub zero_pad: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Length {
+    Char,
+    Short,
+    Long,
+    LongLong,
+    LongDouble,
+    Size,
+    IntMax,
+    PtrDiff,
+}
+
+/// A C `printf` conversion such as `%-08.2f`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PrintfSpec<'a> {
+    pub position: Option<usize>,
+    pub flags: PrintfFlags,
+    pub width: Option<Count<'a>>,
+    pub precision: Option<Count<'a>>,
+    pub length: Option<Length>,
+    pub conversion: char,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Argument<'a> {
+    Next,
+    Positional(usize),
+    Named(&'a str),
+}
+
+/// A Rust `format!` placeholder such as `{name:>8.2}`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RustSpec<'a> {
+    pub argument: Argument<'a>,
+    pub fill: Option<char>,
+    pub align: Option<Align>,
+    pub sign: Option<char>,
+    pub alternate: bool,
+    pub zero_pad: bool,
+    pub width: Option<Count<'a>>,
+    pub precision: Option<Count<'a>>,
+    /// The formatting trait: `""` for `Display`, `"?"` for `Debug`, `"x"`
+    /// for `LowerHex` and so on.
+    pub kind: &'a str,
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn number(input: &str) -> Res<&str, usize> {
+    map_res(digit1, str::parse)(input)
+}
+
+fn printf_flags(input: &str) -> Res<&str, PrintfFlags> {
+    map(many0(one_of("-+ #0")), |flags| {
+        flags
+            .into_iter()
+            .fold(PrintfFlags::default(), |mut acc, flag| {
+                match flag {
+                    '-' => acc.left_align = true,
+                    '+' => acc.plus = true,
+                    ' ' => acc.space = true,
+                    '#' => acc.alternate = true,
+                    _ => acc.zero_pad = true,
+                }
+                acc
+            })
+    })(input)
+}
+
+fn printf_count(input: &str) -> Res<&'_ str, Count<'_>> {
+    alt((
+        map(terminated(preceded(char('*'), number), char('$')), |n| {
+            Count::Positional(n)
+        }),
+        value(Count::Next, char('*')),
+        map(number, Count::Fixed),
+    ))(input)
+}
+
+fn length(input: &str) -> Res<&str, Length> {
+    alt((
+        value(Length::Char, tag("hh")),
+        value(Length::LongLong, tag("ll")),
+        value(Length::Short, char('h')),
+        value(Length::Long, char('l')),
+        value(Length::LongDouble, char('L')),
+        value(Length::LongLong, char('q')),
+        value(Length::Size, char('z')),
+        value(Length::IntMax, char('j')),
+        value(Length::PtrDiff, char('t')),
+    ))(input)
+}
+
+fn printf_spec(input: &str) -> Res<&str, PrintfSpec<'_>> {
+    context(
+        "conversion",
+        preceded(
+            char('%'),
+            tuple((
+                opt(terminated(number, char('$'))),
+                printf_flags,
+                opt(printf_count),
+                // A bare `.` means a precision of zero.
+                opt(preceded(
+                    char('.'),
+                    map(opt(printf_count), |count| count.unwrap_or(Count::Fixed(0))),
+                )),
+                opt(length),
+                one_of("diouxXfFeEgGaAcspn"),
+            )),
+        ),
+    )(input)
+    .map(|(next_input, result)| {
+        let (position, flags, width, precision, length, conversion) = result;
+        (
+            next_input,
+            PrintfSpec {
+                position,
+                flags,
+                width,
+                precision,
+                length,
+                conversion,
+            },
+        )
+    })
+}
+
+/// Parses a C-style format string into literal text and conversions. `%%`
+/// is returned as a literal `%`.
+pub fn printf(input: &str) -> Res<&str, Vec<Piece<'_, PrintfSpec<'_>>>> {
+    context(
+        "printf",
+        many0(alt((
+            map(is_not("%"), Piece::Literal),
+            map(tag("%%"), |_| Piece::Literal("%")),
+            map(printf_spec, Piece::Spec),
+        ))),
+    )(input)
+}
+
+fn identifier(input: &str) -> Res<&str, &str> {
+    verify(
+        take_while1(|c: char| c.is_alphanumeric() || c == '_'),
+        |id: &str| !id.starts_with(|c: char| c.is_ascii_digit()),
+    )(input)
+}
+
+fn rust_count(input: &str) -> Res<&str, Count<'_>> {
+    alt((
+        map(terminated(number, char('$')), Count::Positional),
+        map(terminated(identifier, char('$')), Count::Named),
+        map(number, Count::Fixed),
+    ))(input)
+}
+
+fn align(input: &str) -> Res<&str, Align> {
+    alt((
+        value(Align::Left, char('<')),
+        value(Align::Center, char('^')),
+        value(Align::Right, char('>')),
+    ))(input)
+}
+
+fn fill_align(input: &str) -> Res<&str, (Option<char>, Option<Align>)> {
+    alt((
+        map(pair(anychar, align), |(fill, align)| {
+            (Some(fill), Some(align))
+        }),
+        map(align, |align| (None, Some(align))),
+        value((None, None), tag("")),
+    ))(input)
+}
+
+fn format_spec(input: &str) -> Res<&str, RustSpec<'_>> {
+    tuple((
+        fill_align,
+        opt(one_of("+-")),
+        map(opt(char('#')), |c| c.is_some()),
+        // `0` is only the zero flag when something follows it as width,
+        // otherwise it is a width of zero.
+        map(
+            opt(terminated(char('0'), peek(one_of("123456789$")))),
+            |c| c.is_some(),
+        ),
+        opt(rust_count),
+        opt(preceded(
+            char('.'),
+            alt((value(Count::Next, char('*')), rust_count)),
+        )),
+        alt((
+            tag("x?"),
+            tag("X?"),
+            tag("?"),
+            take_while1(char::is_alphabetic),
+            tag(""),
+        )),
+    ))(input)
+    .map(|(next_input, result)| {
+        let ((fill, align), sign, alternate, zero_pad, width, precision, kind) = result;
+        (
+            next_input,
+            RustSpec {
+                argument: Argument::Next,
+                fill,
+                align,
+                sign,
+                alternate,
+                zero_pad,
+                width,
+                precision,
+                kind,
+            },
+        )
+    })
+}
+
+fn rust_spec(input: &str) -> Res<&str, RustSpec<'_>> {
+    let argument = alt((
+        map(number, Argument::Positional),
+        map(identifier, Argument::Named),
+        value(Argument::Next, tag("")),
+    ));
+    context(
+        "placeholder",
+        tuple((
+            preceded(char('{'), argument),
+            terminated(opt(preceded(char(':'), format_spec)), char('}')),
+        )),
+    )(input)
+    .map(|(next_input, (argument, spec))| {
+        let spec = spec.unwrap_or(RustSpec {
+            argument: Argument::Next,
+            fill: None,
+            align: None,
+            sign: None,
+            alternate: false,
+            zero_pad: false,
+            width: None,
+            precision: None,
+            kind: "",
+        });
+        (next_input, RustSpec { argument, ..spec })
+    })
+}
+
+/// Parses a Rust `format!`-style string. `{{` and `}}` are returned as
+/// literal braces.
+pub fn rust_format(input: &str) -> Res<&str, Vec<Piece<'_, RustSpec<'_>>>> {
+    context(
+        "rust format",
+        many0(alt((
+            map(is_not("{}"), Piece::Literal),
+            map(tag("{{"), |_| Piece::Literal("{")),
+            map(tag("}}"), |_| Piece::Literal("}")),
+            map(rust_spec, Piece::Spec),
+        ))),
+    )(input)
+}
+
+/// The type of an argument passed to a C format function.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ArgType {
+    Int,
+    UInt,
+    Float,
+    Char,
+    Str,
+    Pointer,
+}
+
+impl ArgType {
+    fn for_conversion(conversion: char) -> Option<ArgType> {
+        match conversion {
+            'd' | 'i' => Some(ArgType::Int),
+            'o' | 'u' | 'x' | 'X' => Some(ArgType::UInt),
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'a' | 'A' => Some(ArgType::Float),
+            'c' => Some(ArgType::Char),
+            's' => Some(ArgType::Str),
+            'p' | 'n' => Some(ArgType::Pointer),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CheckError {
+    MissingArgument(usize),
+    UnusedArgument(usize),
+    UnknownName(String),
+    TypeMismatch {
+        index: usize,
+        expected: ArgType,
+        found: ArgType,
+    },
+}
+
+/// Checks that the conversions in a parsed `printf` string line up with the
+/// given argument types. Signed and unsigned integers are interchangeable,
+/// as most compilers only warn about them.
+pub fn check_printf(pieces: &[Piece<PrintfSpec>], args: &[ArgType]) -> Result<(), CheckError> {
+    let mut used = vec![false; args.len()];
+    let mut next = 0;
+    let mut take = |position: Option<usize>, expected: ArgType| {
+        let index = match position {
+            Some(position) => position.saturating_sub(1),
+            None => {
+                next += 1;
+                next - 1
+            }
+        };
+        let found = *args.get(index).ok_or(CheckError::MissingArgument(index))?;
+        let integers = [ArgType::Int, ArgType::UInt];
+        if found != expected && !(integers.contains(&found) && integers.contains(&expected)) {
+            return Err(CheckError::TypeMismatch {
+                index,
+                expected,
+                found,
+            });
+        }
+        used[index] = true;
+        Ok(())
+    };
+
+    for piece in pieces {
+        let Piece::Spec(spec) = piece else { continue };
+        for count in [&spec.width, &spec.precision].into_iter().flatten() {
+            match count {
+                Count::Next => take(None, ArgType::Int)?,
+                Count::Positional(n) => take(Some(*n), ArgType::Int)?,
+                _ => {}
+            }
+        }
+        if let Some(expected) = ArgType::for_conversion(spec.conversion) {
+            take(spec.position, expected)?;
+        }
+    }
+
+    match used.iter().position(|used| !used) {
+        Some(index) => Err(CheckError::UnusedArgument(index)),
+        None => Ok(()),
+    }
+}
+
+/// Checks a parsed Rust format string against the number of positional
+/// arguments and the names of the named arguments, with the same rules as
+/// `format!`: every argument must be used and every reference must exist.
+pub fn check_rust(
+    pieces: &[Piece<RustSpec>],
+    positional: usize,
+    named: &[&str],
+) -> Result<(), CheckError> {
+    let mut used = vec![false; positional + named.len()];
+    let mut next = 0;
+    let mut lookup = |argument: &Argument| {
+        let index = match argument {
+            Argument::Next => {
+                next += 1;
+                next - 1
+            }
+            Argument::Positional(n) => *n,
+            Argument::Named(name) => match named.iter().position(|n| n == name) {
+                Some(index) => positional + index,
+                None => return Err(CheckError::UnknownName(name.to_string())),
+            },
+        };
+        if !matches!(argument, Argument::Named(_)) && index >= positional {
+            return Err(CheckError::MissingArgument(index));
+        }
+        used[index] = true;
+        Ok(())
+    };
+
+    for piece in pieces {
+        let Piece::Spec(spec) = piece else { continue };
+        // `.*` takes the precision first, then the value.
+        if spec.precision == Some(Count::Next) {
+            lookup(&Argument::Next)?;
+        }
+        lookup(&spec.argument)?;
+        for count in [&spec.width, &spec.precision].into_iter().flatten() {
+            match count {
+                Count::Positional(n) => lookup(&Argument::Positional(*n))?,
+                Count::Named(name) => lookup(&Argument::Named(name))?,
+                _ => {}
+            }
+        }
+    }
+
+    match used.iter().position(|used| !used) {
+        Some(index) => Err(CheckError::UnusedArgument(index)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_printf_spec() {
+        assert_eq!(
+            printf_spec("%-08.2f"),
+            Ok((
+                "",
+                PrintfSpec {
+                    position: None,
+                    flags: PrintfFlags {
+                        left_align: true,
+                        zero_pad: true,
+                        ..Default::default()
+                    },
+                    width: Some(Count::Fixed(8)),
+                    precision: Some(Count::Fixed(2)),
+                    length: None,
+                    conversion: 'f',
+                }
+            ))
+        );
+        assert_eq!(
+            printf_spec("%2$*1$.*lld"),
+            Ok((
+                "",
+                PrintfSpec {
+                    position: Some(2),
+                    flags: PrintfFlags::default(),
+                    width: Some(Count::Positional(1)),
+                    precision: Some(Count::Next),
+                    length: Some(Length::LongLong),
+                    conversion: 'd',
+                }
+            ))
+        );
+        assert!(printf_spec("%y").is_err());
+    }
+
+    #[test]
+    fn test_printf() {
+        let (rest, pieces) = printf("100%% of %s: %d").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(pieces.len(), 6);
+        assert_eq!(pieces[0], Piece::Literal("100"));
+        assert_eq!(pieces[1], Piece::Literal("%"));
+        assert!(matches!(
+            pieces[3],
+            Piece::Spec(PrintfSpec {
+                conversion: 's',
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_rust_spec() {
+        assert_eq!(
+            rust_spec("{name:>8.2}"),
+            Ok((
+                "",
+                RustSpec {
+                    argument: Argument::Named("name"),
+                    fill: None,
+                    align: Some(Align::Right),
+                    sign: None,
+                    alternate: false,
+                    zero_pad: false,
+                    width: Some(Count::Fixed(8)),
+                    precision: Some(Count::Fixed(2)),
+                    kind: "",
+                }
+            ))
+        );
+        let (_, spec) = rust_spec("{0:*^+#010.prec$x?}").unwrap();
+        assert_eq!(spec.argument, Argument::Positional(0));
+        assert_eq!((spec.fill, spec.align), (Some('*'), Some(Align::Center)));
+        assert_eq!(spec.sign, Some('+'));
+        assert!(spec.alternate && spec.zero_pad);
+        assert_eq!(spec.width, Some(Count::Fixed(10)));
+        assert_eq!(spec.precision, Some(Count::Named("prec")));
+        assert_eq!(spec.kind, "x?");
+
+        let (_, spec) = rust_spec("{:0}").unwrap();
+        assert!(!spec.zero_pad);
+        assert_eq!(spec.width, Some(Count::Fixed(0)));
+    }
+
+    #[test]
+    fn test_rust_format() {
+        let (rest, pieces) = rust_format("{{x}} = {x:?}").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(pieces[0], Piece::Literal("{"));
+        assert_eq!(pieces[1], Piece::Literal("x"));
+        assert_eq!(pieces[2], Piece::Literal("}"));
+        assert!(rust_format("{").map(|(rest, _)| rest) == Ok("{"));
+    }
+
+    #[test]
+    fn test_check_printf() {
+        let (_, pieces) = printf("%s is %*d%%").unwrap();
+        assert_eq!(
+            check_printf(&pieces, &[ArgType::Str, ArgType::Int, ArgType::UInt]),
+            Ok(())
+        );
+        assert_eq!(
+            check_printf(&pieces, &[ArgType::Str, ArgType::Int]),
+            Err(CheckError::MissingArgument(2))
+        );
+        assert_eq!(
+            check_printf(&pieces, &[ArgType::Str, ArgType::Int, ArgType::Float]),
+            Err(CheckError::TypeMismatch {
+                index: 2,
+                expected: ArgType::Int,
+                found: ArgType::Float
+            })
+        );
+        assert_eq!(
+            check_printf(
+                &pieces,
+                &[ArgType::Str, ArgType::Int, ArgType::Int, ArgType::Str]
+            ),
+            Err(CheckError::UnusedArgument(3))
+        );
+    }
+
+    #[test]
+    fn test_check_rust() {
+        let (_, pieces) = rust_format("{} {0} {name:>width$} {:.*}").unwrap();
+        assert_eq!(check_rust(&pieces, 3, &["name", "width"]), Ok(()));
+        assert_eq!(
+            check_rust(&pieces, 3, &["name"]),
+            Err(CheckError::UnknownName("width".to_owned()))
+        );
+        assert_eq!(
+            check_rust(&pieces, 2, &["name", "width"]),
+            Err(CheckError::MissingArgument(2))
+        );
+        assert_eq!(
+            check_rust(&pieces, 4, &["name", "width"]),
+            Err(CheckError::UnusedArgument(3))
+        );
+    }
+}
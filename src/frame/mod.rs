@@ -0,0 +1,169 @@
+use nom::{
+    bytes::complete::take,
+    character::complete::{char, digit1},
+    combinator::{map, map_res, verify},
+    error::{context, VerboseError},
+    number::complete::{be_u16, be_u32, le_u16, le_u32},
+    sequence::terminated,
+    IResult,
+};
+
+/// Netstring lengths are capped at nine digits so the length always fits
+/// in a `usize`, even on 32-bit targets.
+const MAX_LENGTH_DIGITS: usize = 9;
+
+/// How the length of a length-prefixed frame is encoded.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Prefix {
+    U16Be,
+    U16Le,
+    U32Be,
+    U32Le,
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn parse_length(digits: &[u8]) -> Result<usize, std::num::ParseIntError> {
+    // `digit1` only matches ASCII digits, so this is valid UTF-8.
+    String::from_utf8_lossy(digits).parse()
+}
+
+fn is_canonical(digits: &[u8]) -> bool {
+    digits.len() <= MAX_LENGTH_DIGITS && (digits == b"0" || digits[0] != b'0')
+}
+
+/// Parses a netstring such as `5:hello,` and returns its payload. Lengths
+/// with leading zeros are rejected.
+pub fn netstring(input: &[u8]) -> Res<&[u8], &[u8]> {
+    let (input, length) = context(
+        "netstring",
+        terminated(
+            map_res(verify(digit1, is_canonical), parse_length),
+            char(':'),
+        ),
+    )(input)?;
+    context("netstring", terminated(take(length), char(',')))(input)
+}
+
+/// Returns a parser for frames whose payload is preceded by its length.
+pub fn length_prefixed(prefix: Prefix) -> impl FnMut(&[u8]) -> Res<&[u8], &[u8]> {
+    move |input| {
+        let (input, length) = match prefix {
+            Prefix::U16Be => map(be_u16, usize::from)(input)?,
+            Prefix::U16Le => map(le_u16, usize::from)(input)?,
+            Prefix::U32Be => map(be_u32, |n| n as usize)(input)?,
+            Prefix::U32Le => map(le_u32, |n| n as usize)(input)?,
+        };
+        context("frame", take(length))(input)
+    }
+}
+
+/// Variants of the frame parsers that return `Err::Incomplete` until a
+/// whole frame has been buffered.
+pub mod streaming {
+    use nom::{
+        bytes::streaming::take,
+        character::streaming::{char, digit1},
+        combinator::{map, map_res, verify},
+        error::context,
+        number::streaming::{be_u16, be_u32, le_u16, le_u32},
+        sequence::terminated,
+    };
+
+    use super::{is_canonical, parse_length, Prefix, Res};
+
+    pub fn netstring(input: &[u8]) -> Res<&[u8], &[u8]> {
+        let (input, length) = context(
+            "netstring",
+            terminated(
+                map_res(verify(digit1, is_canonical), parse_length),
+                char(':'),
+            ),
+        )(input)?;
+        context("netstring", terminated(take(length), char(',')))(input)
+    }
+
+    pub fn length_prefixed(prefix: Prefix) -> impl FnMut(&[u8]) -> Res<&[u8], &[u8]> {
+        move |input| {
+            let (input, length) = match prefix {
+                Prefix::U16Be => map(be_u16, usize::from)(input)?,
+                Prefix::U16Le => map(le_u16, usize::from)(input)?,
+                Prefix::U32Be => map(be_u32, |n| n as usize)(input)?,
+                Prefix::U32Le => map(le_u32, |n| n as usize)(input)?,
+            };
+            context("frame", take(length))(input)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::{Err as NomErr, Needed};
+
+    use super::*;
+
+    #[test]
+    fn test_netstring() {
+        assert_eq!(
+            netstring(b"5:hello,rest"),
+            Ok((&b"rest"[..], &b"hello"[..]))
+        );
+        assert_eq!(netstring(b"0:,"), Ok((&b""[..], &b""[..])));
+        assert_eq!(netstring(b"3:a,b,"), Ok((&b""[..], &b"a,b"[..])));
+        assert!(netstring(b"05:hello,").is_err());
+        assert!(netstring(b"5:hello").is_err());
+        assert!(netstring(b"5:hello!").is_err());
+        assert!(netstring(b"1234567890:x,").is_err());
+    }
+
+    #[test]
+    fn test_length_prefixed() {
+        let input = [0x00, 0x03, b'a', b'b', b'c', b'!'];
+        assert_eq!(
+            length_prefixed(Prefix::U16Be)(&input),
+            Ok((&b"!"[..], &b"abc"[..]))
+        );
+        let input = [0x02, 0x00, 0x00, 0x00, b'h', b'i'];
+        assert_eq!(
+            length_prefixed(Prefix::U32Le)(&input),
+            Ok((&b""[..], &b"hi"[..]))
+        );
+        assert!(length_prefixed(Prefix::U16Le)(&[0x05, 0x00, b'a']).is_err());
+    }
+
+    #[test]
+    fn test_streaming_netstring() {
+        assert_eq!(
+            streaming::netstring(b"5:hel"),
+            Err(NomErr::Incomplete(Needed::new(2)))
+        );
+        assert_eq!(
+            streaming::netstring(b"5:hello"),
+            Err(NomErr::Incomplete(Needed::new(1)))
+        );
+        assert!(matches!(
+            streaming::netstring(b"12"),
+            Err(NomErr::Incomplete(_))
+        ));
+        assert_eq!(
+            streaming::netstring(b"5:hello,"),
+            Ok((&b""[..], &b"hello"[..]))
+        );
+    }
+
+    #[test]
+    fn test_streaming_length_prefixed() {
+        assert_eq!(
+            streaming::length_prefixed(Prefix::U32Be)(&[0x00, 0x00]),
+            Err(NomErr::Incomplete(Needed::new(2)))
+        );
+        assert_eq!(
+            streaming::length_prefixed(Prefix::U16Be)(&[0x00, 0x04, b'a']),
+            Err(NomErr::Incomplete(Needed::new(3)))
+        );
+        assert_eq!(
+            streaming::length_prefixed(Prefix::U16Be)(&[0x00, 0x01, b'a']),
+            Ok((&b""[..], &b"a"[..]))
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Entry points for fuzzers. Each takes arbitrary bytes, runs them through
+//! a group of parsers and throws the results away; the only way one can
+//! fail is by panicking, hanging or overflowing the stack. With a
+//! `cargo fuzz` target they are one line each:
+//!
+//! ```ignore
+//! fuzz_target!(|data: &[u8]| parsers::fuzz::json(data));
+//! ```
+//!
+//! Enabled by the `hardened` feature, which also turns on the limits in
+//! `limits`, so deeply nested input is an error rather than a crash.
+#![cfg(feature = "hardened")]
+
+use crate::registry::Registry;
+use crate::{cst, json};
+
+fn text(data: &[u8]) -> Option<&str> {
+    std::str::from_utf8(data).ok()
+}
+
+pub fn json(data: &[u8]) {
+    let Some(text) = text(data) else { return };
+    let _ = text.parse::<json::JsonValue>();
+    let _ = json::parse_json_recover(text);
+    let _ = json::parse_json_spanned(text);
+    let root = cst::SyntaxNode::new_root(json::parse_json_cst(text));
+    assert_eq!(root.to_string(), text);
+}
+
+pub fn uri(data: &[u8]) {
+    let Some(text) = text(data) else { return };
+    let _ = text.parse::<crate::UriBuf>();
+    let _ = crate::http::nested_query_params(text);
+}
+
+/// Format detection, and every format it can detect.
+pub fn detect(data: &[u8]) {
+    let _ = crate::detect(data);
+    let _ = crate::parse_any(data);
+    let registry = Registry::default();
+    for format in registry.formats() {
+        let _ = format.parse(data);
+    }
+}
+
+/// The binary protocol parsers.
+pub fn protocols(data: &[u8]) {
+    let _ = crate::packet::packet(data);
+    let _ = crate::websocket::frame(data);
+    let _ = crate::frame::netstring(data);
+    let _ = crate::hpack::huffman_decode(data);
+    let _ = crate::hpack::Decoder::new(4096).decode(data);
+}
+
+/// The small text formats that don't belong anywhere else.
+pub fn text_formats(data: &[u8]) {
+    let Some(text) = text(data) else { return };
+    let _ = crate::regex::pattern(text);
+    let _ = crate::irc::message(text);
+    let _ = crate::template::template(text);
+    let _ = crate::format_string::printf(text);
+    let _ = crate::format_string::rust_format(text);
+    let _ = crate::htpasswd::htpasswd(text);
+    let _ = crate::htpasswd::htdigest(text);
+    let _ = crate::procfs::meminfo(text);
+    let _ = crate::procfs::stat(text);
+    let _ = crate::chess::fen(text);
+    let _ = crate::cargo_lock::lockfile_recover(text);
+    let _ = crate::cargo_lock::lockfile_cst(text);
+    let _ = text.parse::<crate::money::Money>();
+    let _ = text.parse::<crate::quantity::Quantity>();
+    let _ = text.parse::<crate::pep508::Requirement>();
+    let _ = text.parse::<crate::phone::PhoneNumber>();
+    let _ = text.parse::<crate::grammar::Grammar>();
+}
+
+pub fn all(data: &[u8]) {
+    json(data);
+    uri(data);
+    detect(data);
+    protocols(data);
+    text_formats(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fixed xorshift generator, so failures reproduce.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    const SEEDS: &[&[u8]] = &[
+        br#"{"a": [1, 2.5, true, null], "b": {"c": "d"}}"#,
+        b"https://user:pw@example.com:8080/a/b?x=1&y[z]=2#top",
+        b"WEBVTT\n\n00:00:01.000 --> 00:00:02.000\n<b>Hi</b>\n",
+        b"1\n00:00:01,000 --> 00:00:02,000\nHi\n",
+        b"@book{key, title = {A {B} C}, year = 2000}\n",
+        b"[[package]]\nname = \"a\"\nversion = \"1.0.0\"\ndependencies = [\"b\"]\n",
+        b"[Event \"x\"]\n\n1. e4 e5 2. Nf3 {ok} Nc6 1-0\n",
+        b"v 1 2 3\nvt 0 1\nf 1/1 2/1 3/1\n",
+        b"<gpx><trk><trkseg><trkpt lat=\"1\" lon=\"2\"><ele>3</ele></trkpt></trkseg></trk></gpx>",
+        b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x01\x00\x00\x00\x01\x08\x02\x00\x00\x00",
+        b"\x1f\x8b\x08\x08\x00\x00\x00\x00\x00\x03a\x00",
+        b"PK\x05\x06\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00",
+        b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58",
+        b"rule = \"a\" rule / !\"b\" [c-e]* &x\nx = .",
+        b"(a|b)*[^c-d]{2,3}\\d+?",
+        b"{{#items}}{{> row}}{{/items}}",
+        b"%-5.2f %s {0:>8} {name}",
+    ];
+
+    fn mutate(rng: &mut Rng, input: &mut Vec<u8>) {
+        for _ in 0..=rng.below(4) {
+            let at = rng.below(input.len() + 1);
+            match rng.below(4) {
+                0 if at < input.len() => input[at] = rng.next() as u8,
+                1 if at < input.len() => {
+                    input.remove(at);
+                }
+                2 => {
+                    let byte = b"{}[]\"\\:,.-0eE \n<>@/%#&=!*()"[rng.below(27)];
+                    input.insert(at, byte);
+                }
+                _ => {
+                    let from = rng.below(input.len() + 1);
+                    let to = (from + rng.below(16)).min(input.len());
+                    let chunk = input[from..to].to_vec();
+                    input.splice(at..at, chunk);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_panics() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for seed in SEEDS {
+            let mut input = seed.to_vec();
+            for _ in 0..300 {
+                mutate(&mut rng, &mut input);
+                all(&input);
+                if input.len() > 256 {
+                    input = seed.to_vec();
+                }
+            }
+        }
+        for _ in 0..1000 {
+            let input: Vec<u8> = (0..rng.below(64)).map(|_| rng.next() as u8).collect();
+            all(&input);
+        }
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        for open in [b'[', b'{'] {
+            let mut input = vec![open; 10_000];
+            if open == b'{' {
+                input = b"{\"a\":".repeat(10_000);
+            }
+            json(&input);
+            detect(&input);
+            uri(&b"http://a?b"
+                .iter()
+                .chain(&[b'['; 10_000])
+                .copied()
+                .collect::<Vec<_>>());
+        }
+        text_formats(&[b'('; 10_000]);
+        text_formats(&b"{{#a}}".repeat(10_000));
+    }
+}
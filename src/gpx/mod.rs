@@ -0,0 +1,530 @@
+//! GPX tracks, routes and waypoints.
+//!
+//! The crate has no general XML parser yet, so this module carries a small
+//! element-tree reader covering what GPX files use: a prolog, comments,
+//! attributes, character data, CDATA sections and the predefined and
+//! numeric entities.
+
+use std::time::Duration;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_until, take_while, take_while1, take_while_m_n},
+    character::complete::{char, digit1, multispace0, multispace1, one_of},
+    combinator::{map, map_res, opt, value},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::many0,
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+    Err as NomErr, IResult,
+};
+
+use crate::date;
+use crate::limits::nested;
+
+/// A point in time, in milliseconds since the Unix epoch.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct Timestamp {
+    pub unix_millis: i64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Waypoint {
+    pub lat: f64,
+    pub lon: f64,
+    /// Elevation in metres.
+    pub elevation: Option<f64>,
+    pub time: Option<Timestamp>,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Route {
+    pub name: Option<String>,
+    pub points: Vec<Waypoint>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Track {
+    pub name: Option<String>,
+    pub segments: Vec<Vec<Waypoint>>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Gpx {
+    pub creator: Option<String>,
+    pub waypoints: Vec<Waypoint>,
+    pub routes: Vec<Route>,
+    pub tracks: Vec<Track>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Summary {
+    /// Great-circle distance in metres.
+    pub distance: f64,
+    /// Time between the first and last timestamped points.
+    pub duration: Option<Duration>,
+    pub elevation_gain: f64,
+    pub elevation_loss: f64,
+}
+
+/// An XML element, keeping the input it started at for error reporting.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Element<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, String)>,
+    children: Vec<Element<'a>>,
+    text: String,
+    source: &'a str,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Content<'a> {
+    Element(Element<'a>),
+    Text(String),
+    Comment,
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest.find(';').map(|end| &rest[1..end]);
+        let c = match entity {
+            Some("lt") => Some('<'),
+            Some("gt") => Some('>'),
+            Some("amp") => Some('&'),
+            Some("quot") => Some('"'),
+            Some("apos") => Some('\''),
+            Some(e) if e.starts_with("#x") => u32::from_str_radix(&e[2..], 16)
+                .ok()
+                .and_then(char::from_u32),
+            Some(e) if e.starts_with('#') => e[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match (c, entity) {
+            (Some(c), Some(entity)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            // Unknown entities are kept as written.
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn name(input: &str) -> Res<&str, &str> {
+    take_while1(|c: char| c.is_alphanumeric() || ":_-.".contains(c))(input)
+}
+
+fn attribute(input: &str) -> Res<&str, (&str, String)> {
+    let quoted = alt((
+        delimited(char('"'), take_while(|c| c != '"'), char('"')),
+        delimited(char('\''), take_while(|c| c != '\''), char('\'')),
+    ));
+    separated_pair(
+        name,
+        tuple((multispace0, char('='), multispace0)),
+        map(quoted, decode_entities),
+    )(input)
+}
+
+fn comment(input: &str) -> Res<&str, &str> {
+    delimited(tag("<!--"), take_until("-->"), tag("-->"))(input)
+}
+
+fn content(input: &str) -> Res<&str, Content<'_>> {
+    alt((
+        map(nested(pair(char('<'), name), element), Content::Element),
+        value(Content::Comment, comment),
+        map(
+            delimited(tag("<![CDATA["), take_until("]]>"), tag("]]>")),
+            |text: &str| Content::Text(text.to_owned()),
+        ),
+        map(take_while1(|c| c != '<'), |text| {
+            Content::Text(decode_entities(text))
+        }),
+    ))(input)
+}
+
+fn element(input: &str) -> Res<&str, Element<'_>> {
+    let (next_input, (name, attributes, _, self_closing)) = context(
+        "element",
+        tuple((
+            preceded(char('<'), name),
+            many0(preceded(multispace1, attribute)),
+            multispace0,
+            alt((value(true, tag("/>")), value(false, char('>')))),
+        )),
+    )(input)?;
+    let mut element = Element {
+        name,
+        attributes,
+        children: vec![],
+        text: String::new(),
+        source: input,
+    };
+    if self_closing {
+        return Ok((next_input, element));
+    }
+    let (next_input, contents) = many0(content)(next_input)?;
+    for content in contents {
+        match content {
+            Content::Element(child) => element.children.push(child),
+            Content::Text(text) => element.text.push_str(&text),
+            Content::Comment => {}
+        }
+    }
+    let (next_input, closing) = context(
+        "closing tag",
+        delimited(tag("</"), self::name, pair(multispace0, char('>'))),
+    )(next_input)?;
+    if closing != name {
+        return Err(failure(next_input, "mismatched closing tag"));
+    }
+    Ok((next_input, element))
+}
+
+/// Skips the XML declaration, comments, a doctype and whitespace.
+fn misc(input: &str) -> Res<&str, ()> {
+    value(
+        (),
+        many0(alt((
+            multispace1,
+            comment,
+            delimited(tag("<?"), take_until("?>"), tag("?>")),
+            delimited(tag("<!DOCTYPE"), take_until(">"), tag(">")),
+        ))),
+    )(input)
+}
+
+fn document(input: &str) -> Res<&str, Element<'_>> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    delimited(misc, element, misc)(input)
+}
+
+fn number(n: usize) -> impl FnMut(&str) -> Res<&str, i64> {
+    move |input| {
+        map_res(
+            take_while_m_n(n, n, |c: char| c.is_ascii_digit()),
+            str::parse,
+        )(input)
+    }
+}
+
+/// Parses an ISO 8601 date and time such as `2024-05-01T07:30:15.250Z` or
+/// `2024-05-01T09:30:15+02:00`. A missing offset is read as UTC.
+pub fn timestamp(input: &str) -> Res<&str, Timestamp> {
+    let (next_input, (year, month, day, hour, minute, second, fraction, offset)) = context(
+        "timestamp",
+        tuple((
+            terminated(number(4), char('-')),
+            terminated(number(2), char('-')),
+            terminated(number(2), one_of("Tt ")),
+            terminated(number(2), char(':')),
+            terminated(number(2), char(':')),
+            number(2),
+            opt(preceded(one_of(".,"), digit1)),
+            opt(alt((
+                value(0, one_of("Zz")),
+                map(
+                    tuple((one_of("+-"), number(2), opt(char(':')), number(2))),
+                    |(sign, h, _, m)| {
+                        if sign == '-' {
+                            -(h * 60 + m)
+                        } else {
+                            h * 60 + m
+                        }
+                    },
+                ),
+            ))),
+        )),
+    )(input)?;
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return Err(failure(input, "invalid timestamp"));
+    }
+    let millis = fraction.map_or(0, |digits: &str| {
+        format!("{:0<3}", &digits[..digits.len().min(3)])
+            .parse()
+            .unwrap_or(0)
+    });
+    let seconds =
+        date::days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second
+            - offset.unwrap_or(0) * 60;
+    Ok((
+        next_input,
+        Timestamp {
+            unix_millis: seconds * 1000 + millis,
+        },
+    ))
+}
+
+impl<'a> Element<'a> {
+    fn child(&self, name: &str) -> Option<&Element<'a>> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children<'s>(&'s self, name: &'s str) -> impl Iterator<Item = &'s Element<'a>> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn child_text(&self, name: &str) -> Option<String> {
+        self.child(name).map(|child| child.text.trim().to_owned())
+    }
+}
+
+fn waypoint<'a>(element: &Element<'a>) -> Result<Waypoint, NomErr<VerboseError<&'a str>>> {
+    let coordinate = |name| {
+        element
+            .attribute(name)
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .ok_or_else(|| failure(element.source, "invalid coordinates"))
+    };
+    let (lat, lon) = (coordinate("lat")?, coordinate("lon")?);
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return Err(failure(element.source, "invalid coordinates"));
+    }
+    let elevation = match element.child("ele") {
+        Some(ele) => Some(
+            ele.text
+                .trim()
+                .parse()
+                .map_err(|_| failure(ele.source, "invalid elevation"))?,
+        ),
+        None => None,
+    };
+    let time = match element.child("time") {
+        Some(time) => Some(
+            timestamp(time.text.trim())
+                .map(|(_, timestamp)| timestamp)
+                .map_err(|_| failure(time.source, "invalid time"))?,
+        ),
+        None => None,
+    };
+    Ok(Waypoint {
+        lat,
+        lon,
+        elevation,
+        time,
+        name: element.child_text("name"),
+    })
+}
+
+fn waypoints<'a>(
+    element: &Element<'a>,
+    name: &str,
+) -> Result<Vec<Waypoint>, NomErr<VerboseError<&'a str>>> {
+    element.children(name).map(waypoint).collect()
+}
+
+/// Parses a GPX document. Elements this module doesn't model, such as
+/// metadata and extensions, are ignored.
+pub fn gpx(input: &str) -> Res<&str, Gpx> {
+    let (next_input, root) = context("gpx", document)(input)?;
+    if root.name != "gpx" {
+        return Err(failure(root.source, "root element is not gpx"));
+    }
+    let routes = root
+        .children("rte")
+        .map(|route| {
+            Ok(Route {
+                name: route.child_text("name"),
+                points: waypoints(route, "rtept")?,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    let tracks = root
+        .children("trk")
+        .map(|track| {
+            Ok(Track {
+                name: track.child_text("name"),
+                segments: track
+                    .children("trkseg")
+                    .map(|segment| waypoints(segment, "trkpt"))
+                    .collect::<Result<_, _>>()?,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((
+        next_input,
+        Gpx {
+            creator: root.attribute("creator").map(str::to_owned),
+            waypoints: waypoints(&root, "wpt")?,
+            routes,
+            tracks,
+        },
+    ))
+}
+
+/// The haversine distance between two points in metres.
+pub fn distance(a: &Waypoint, b: &Waypoint) -> f64 {
+    const EARTH_RADIUS: f64 = 6_371_000.0;
+    let (lat1, lat2) = (a.lat.to_radians(), b.lat.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (b.lon - a.lon).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
+
+/// Summarises a sequence of points, such as a route or track segment.
+pub fn summary(points: &[Waypoint]) -> Summary {
+    let mut summary = Summary::default();
+    for pair in points.windows(2) {
+        summary.distance += distance(&pair[0], &pair[1]);
+        if let (Some(from), Some(to)) = (pair[0].elevation, pair[1].elevation) {
+            if to > from {
+                summary.elevation_gain += to - from;
+            } else {
+                summary.elevation_loss += from - to;
+            }
+        }
+    }
+    let mut times = points.iter().filter_map(|point| point.time);
+    if let (Some(first), Some(last)) = (times.next(), times.next_back()) {
+        summary.duration = u64::try_from(last.unix_millis - first.unix_millis)
+            .ok()
+            .map(Duration::from_millis);
+    }
+    summary
+}
+
+impl Route {
+    pub fn summary(&self) -> Summary {
+        summary(&self.points)
+    }
+}
+
+impl Track {
+    /// Sums the segments. Gaps between segments add neither distance nor
+    /// duration.
+    pub fn summary(&self) -> Summary {
+        self.segments
+            .iter()
+            .map(|segment| summary(segment))
+            .fold(Summary::default(), |total, s| Summary {
+                distance: total.distance + s.distance,
+                duration: match (total.duration, s.duration) {
+                    (Some(a), Some(b)) => Some(a + b),
+                    (a, b) => a.or(b),
+                },
+                elevation_gain: total.elevation_gain + s.elevation_gain,
+                elevation_loss: total.elevation_loss + s.elevation_loss,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- exported -->
+<gpx version="1.1" creator="Tracker &amp; Co" xmlns="http://www.topografix.com/GPX/1/1">
+  <metadata><name>Morning</name></metadata>
+  <wpt lat="51.5" lon="-0.1"><name><![CDATA[Start <here>]]></name></wpt>
+  <rte>
+    <name>Route</name>
+    <rtept lat="0" lon="0"/>
+    <rtept lat="0" lon="1"/>
+  </rte>
+  <trk>
+    <name>Run</name>
+    <trkseg>
+      <trkpt lat="0" lon="0"><ele>10</ele><time>2024-05-01T07:30:00Z</time></trkpt>
+      <trkpt lat="0.001" lon="0"><ele>15.5</ele><time>2024-05-01T07:30:30Z</time></trkpt>
+      <trkpt lat="0.002" lon="0"><ele>12</ele><time>2024-05-01T09:31:00+02:00</time></trkpt>
+    </trkseg>
+    <extensions><x:hr xmlns:x="urn:x">150</x:hr></extensions>
+  </trk>
+</gpx>
+"#;
+
+    #[test]
+    fn test_gpx() {
+        let (rest, gpx) = gpx(GPX).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(gpx.creator.as_deref(), Some("Tracker & Co"));
+        assert_eq!(gpx.waypoints.len(), 1);
+        assert_eq!(gpx.waypoints[0].name.as_deref(), Some("Start <here>"));
+        assert_eq!(gpx.routes[0].name.as_deref(), Some("Route"));
+        assert_eq!(gpx.routes[0].points.len(), 2);
+        let track = &gpx.tracks[0];
+        assert_eq!(track.name.as_deref(), Some("Run"));
+        assert_eq!(track.segments[0].len(), 3);
+        assert_eq!(track.segments[0][1].elevation, Some(15.5));
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(
+            timestamp("1970-01-01T00:00:00Z"),
+            Ok(("", Timestamp { unix_millis: 0 }))
+        );
+        assert_eq!(
+            timestamp("2024-05-01T07:30:15.25Z"),
+            Ok((
+                "",
+                Timestamp {
+                    unix_millis: 1_714_548_615_250
+                }
+            ))
+        );
+        assert_eq!(
+            timestamp("2024-05-01T09:30:15+02:00"),
+            timestamp("2024-05-01T07:30:15Z")
+        );
+        assert!(timestamp("2024-13-01T00:00:00Z").is_err());
+    }
+
+    #[test]
+    fn test_summary() {
+        let (_, gpx) = gpx(GPX).unwrap();
+        let route = gpx.routes[0].summary();
+        assert!((route.distance - 111_194.9).abs() < 1.0);
+        assert_eq!(route.duration, None);
+        let track = gpx.tracks[0].summary();
+        assert!((track.distance - 222.4).abs() < 0.1);
+        assert_eq!(track.duration, Some(Duration::from_secs(60)));
+        assert_eq!(track.elevation_gain, 5.5);
+        assert_eq!(track.elevation_loss, 3.5);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(gpx("<gpx><trk></gpx>").is_err());
+        let input = r#"<gpx><wpt lat="north" lon="0"/></gpx>"#;
+        assert_eq!(gpx(input), Err(failure(&input[5..], "invalid coordinates")));
+        let input = "<kml></kml>";
+        assert_eq!(gpx(input), Err(failure(input, "root element is not gpx")));
+        assert_eq!(
+            decode_entities("a &lt;&#65;&#x42;&bogus; b"),
+            "a <AB&bogus; b"
+        );
+    }
+}
@@ -0,0 +1,731 @@
+//! Parsers built at runtime from a PEG grammar, for prototyping a format
+//! before writing it with nom. A grammar is a list of rules:
+//!
+//! ```text
+//! # Comments run to the end of the line.
+//! list    = "[" _ws (number (_ws "," _ws number)*)? _ws "]" ;
+//! number  = "-"? [0-9]+ ;
+//! _ws     = [ \t\n]* ;
+//! ```
+//!
+//! Rules are tried in order with `/` (or `|`), and `*`, `+`, `?`, `&` and
+//! `!` work as in PEG. Terminals are `"literals"`, `[character classes]`,
+//! `[^negated classes]` and `.` for any character. Parsing produces a tree
+//! of `Node`s, one per rule matched; rules whose names start with `_` are
+//! silent, so their children are attached to the enclosing rule instead.
+//!
+//! Matching backtracks without memoization, so grammars that retry long
+//! alternatives can take exponential time. Left recursion is rejected when
+//! the grammar is built.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::str::FromStr;
+
+use nom::{
+    branch::alt,
+    bytes::complete::take_while,
+    character::complete::{char, multispace1, none_of, not_line_ending, one_of, satisfy},
+    combinator::{cut, map, not, opt, recognize, value},
+    error::{context, VerboseError},
+    multi::{fold_many0, many0, many1, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+use crate::error::parse_all;
+use crate::limits::nested;
+use crate::visit::{Segment, Tree};
+use crate::Error;
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+/// How deeply rules may nest while matching, so that deeply nested input
+/// fails with an error instead of overflowing the stack.
+const MAX_DEPTH: usize = 256;
+
+/// A parsing expression. `R` names rules: the text of the reference while
+/// the grammar is parsed, then the rule's index.
+#[derive(Debug, PartialEq, Clone)]
+enum Expr<R> {
+    Literal(String),
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+    Any,
+    Ref(R),
+    Sequence(Vec<Expr<R>>),
+    Choice(Vec<Expr<R>>),
+    Repeat {
+        expr: Box<Expr<R>>,
+        min: usize,
+        max: Option<usize>,
+    },
+    And(Box<Expr<R>>),
+    Not(Box<Expr<R>>),
+}
+
+impl<R> Expr<R> {
+    fn resolve<S>(self, f: &mut impl FnMut(R) -> Result<S, Error>) -> Result<Expr<S>, Error> {
+        let all = |exprs: Vec<Expr<R>>, f: &mut _| {
+            exprs
+                .into_iter()
+                .map(|expr| expr.resolve(f))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(match self {
+            Expr::Literal(s) => Expr::Literal(s),
+            Expr::Class { ranges, negated } => Expr::Class { ranges, negated },
+            Expr::Any => Expr::Any,
+            Expr::Ref(name) => Expr::Ref(f(name)?),
+            Expr::Sequence(items) => Expr::Sequence(all(items, f)?),
+            Expr::Choice(choices) => Expr::Choice(all(choices, f)?),
+            Expr::Repeat { expr, min, max } => Expr::Repeat {
+                expr: Box::new(expr.resolve(f)?),
+                min,
+                max,
+            },
+            Expr::And(expr) => Expr::And(Box::new(expr.resolve(f)?)),
+            Expr::Not(expr) => Expr::Not(Box::new(expr.resolve(f)?)),
+        })
+    }
+}
+
+/// Whitespace and comments.
+fn skip(input: &str) -> Res<&str, ()> {
+    value(
+        (),
+        many0(alt((
+            multispace1,
+            recognize(pair(char('#'), not_line_ending)),
+        ))),
+    )(input)
+}
+
+fn identifier(input: &str) -> Res<&str, &str> {
+    recognize(pair(
+        satisfy(|c| c.is_ascii_alphabetic() || c == '_'),
+        take_while(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+    ))(input)
+}
+
+fn escape(input: &str) -> Res<&str, char> {
+    preceded(
+        char('\\'),
+        context(
+            "escape",
+            cut(alt((
+                value('\n', char('n')),
+                value('\t', char('t')),
+                value('\r', char('r')),
+                one_of("\\\"'[]^-"),
+            ))),
+        ),
+    )(input)
+}
+
+fn literal(input: &str) -> Res<&str, String> {
+    let quoted = |quote: char, plain: &'static str| {
+        delimited(
+            char(quote),
+            fold_many0(alt((escape, none_of(plain))), String::new, |mut s, c| {
+                s.push(c);
+                s
+            }),
+            cut(char(quote)),
+        )
+    };
+    context(
+        "literal",
+        alt((quoted('"', "\\\"\n"), quoted('\'', "\\'\n"))),
+    )(input)
+}
+
+fn class(input: &str) -> Res<&str, Expr<&str>> {
+    let class_char = |input| alt((escape, none_of("\\]\n")))(input);
+    let range = map(
+        pair(class_char, opt(preceded(char('-'), class_char))),
+        |(lo, hi)| (lo, hi.unwrap_or(lo)),
+    );
+    context(
+        "character class",
+        map(
+            delimited(
+                char('['),
+                pair(opt(char('^')), many1(range)),
+                cut(char(']')),
+            ),
+            |(negated, ranges)| Expr::Class {
+                ranges,
+                negated: negated.is_some(),
+            },
+        ),
+    )(input)
+}
+
+fn primary(input: &str) -> Res<&str, Expr<&str>> {
+    preceded(
+        skip,
+        alt((
+            map(literal, Expr::Literal),
+            class,
+            value(Expr::Any, char('.')),
+            nested(
+                char('('),
+                delimited(char('('), expression, cut(preceded(skip, char(')')))),
+            ),
+            // A name followed by `=` starts the next rule.
+            map(
+                terminated(identifier, not(preceded(skip, char('=')))),
+                Expr::Ref,
+            ),
+        )),
+    )(input)
+}
+
+fn suffixed(input: &str) -> Res<&str, Expr<&str>> {
+    let (input, expr) = primary(input)?;
+    let (input, suffix) = opt(preceded(skip, one_of("*+?")))(input)?;
+    let (min, max) = match suffix {
+        Some('*') => (0, None),
+        Some('+') => (1, None),
+        Some('?') => (0, Some(1)),
+        _ => return Ok((input, expr)),
+    };
+    let expr = Expr::Repeat {
+        expr: Box::new(expr),
+        min,
+        max,
+    };
+    Ok((input, expr))
+}
+
+fn prefixed(input: &str) -> Res<&str, Expr<&str>> {
+    alt((
+        map(preceded(pair(skip, char('&')), cut(suffixed)), |expr| {
+            Expr::And(Box::new(expr))
+        }),
+        map(preceded(pair(skip, char('!')), cut(suffixed)), |expr| {
+            Expr::Not(Box::new(expr))
+        }),
+        suffixed,
+    ))(input)
+}
+
+fn sequence(input: &str) -> Res<&str, Expr<&str>> {
+    map(many1(prefixed), |mut items| match items.len() {
+        1 => items.remove(0),
+        _ => Expr::Sequence(items),
+    })(input)
+}
+
+fn expression(input: &str) -> Res<&str, Expr<&str>> {
+    map(
+        separated_list1(preceded(skip, one_of("/|")), sequence),
+        |mut choices| match choices.len() {
+            1 => choices.remove(0),
+            _ => Expr::Choice(choices),
+        },
+    )(input)
+}
+
+fn rule(input: &str) -> Res<&str, (&str, Expr<&str>)> {
+    context(
+        "rule",
+        map(
+            tuple((
+                preceded(skip, identifier),
+                preceded(skip, cut(char('='))),
+                cut(expression),
+                preceded(skip, cut(char(';'))),
+            )),
+            |(name, _, expr, _)| (name, expr),
+        ),
+    )(input)
+}
+
+fn rules(input: &str) -> Res<&str, Vec<(&str, Expr<&str>)>> {
+    terminated(many1(rule), skip)(input)
+}
+
+/// An error about a part of the grammar that parsed but doesn't make sense.
+fn invalid(input: &str, at: &str, reason: &'static str) -> Error {
+    let mut error = Error::expected("grammar", input, at, "");
+    error.expected = None;
+    error.context = vec![reason];
+    error
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    name: String,
+    expr: Expr<usize>,
+}
+
+/// A grammar, checked and ready to parse with.
+#[derive(Debug, Clone)]
+pub struct Grammar {
+    rules: Vec<Rule>,
+}
+
+/// Whether `expr` can succeed without consuming input.
+fn nullable(expr: &Expr<usize>, rules: &[bool]) -> bool {
+    match expr {
+        Expr::Literal(s) => s.is_empty(),
+        Expr::Class { .. } | Expr::Any => false,
+        Expr::Ref(i) => rules[*i],
+        Expr::Sequence(items) => items.iter().all(|item| nullable(item, rules)),
+        Expr::Choice(choices) => choices.iter().any(|choice| nullable(choice, rules)),
+        Expr::Repeat { expr, min, .. } => *min == 0 || nullable(expr, rules),
+        Expr::And(_) | Expr::Not(_) => true,
+    }
+}
+
+/// The rules `expr` may call before consuming any input.
+fn leftmost(expr: &Expr<usize>, nullable_rules: &[bool], out: &mut Vec<usize>) {
+    match expr {
+        Expr::Ref(i) => out.push(*i),
+        Expr::Sequence(items) => {
+            for item in items {
+                leftmost(item, nullable_rules, out);
+                if !nullable(item, nullable_rules) {
+                    break;
+                }
+            }
+        }
+        Expr::Choice(choices) => {
+            for choice in choices {
+                leftmost(choice, nullable_rules, out);
+            }
+        }
+        Expr::Repeat { expr, .. } | Expr::And(expr) | Expr::Not(expr) => {
+            leftmost(expr, nullable_rules, out)
+        }
+        Expr::Literal(_) | Expr::Class { .. } | Expr::Any => {}
+    }
+}
+
+impl Grammar {
+    /// Finds a rule that can call itself without consuming input, which
+    /// would recurse forever.
+    fn left_recursive(&self) -> Option<usize> {
+        let mut nullable_rules = vec![false; self.rules.len()];
+        loop {
+            let next: Vec<bool> = self
+                .rules
+                .iter()
+                .map(|rule| nullable(&rule.expr, &nullable_rules))
+                .collect();
+            if next == nullable_rules {
+                break;
+            }
+            nullable_rules = next;
+        }
+        let calls: Vec<Vec<usize>> = self
+            .rules
+            .iter()
+            .map(|rule| {
+                let mut calls = vec![];
+                leftmost(&rule.expr, &nullable_rules, &mut calls);
+                calls
+            })
+            .collect();
+        (0..self.rules.len()).find(|&start| {
+            let mut seen = vec![false; self.rules.len()];
+            let mut stack = calls[start].clone();
+            while let Some(i) = stack.pop() {
+                if i == start {
+                    return true;
+                }
+                if !std::mem::replace(&mut seen[i], true) {
+                    stack.extend(&calls[i]);
+                }
+            }
+            false
+        })
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|rule| rule.name.as_str())
+    }
+
+    /// Parses the whole of `input` with the grammar's first rule.
+    pub fn parse(&self, input: &str) -> Result<Node, Error> {
+        self.parse_at(0, input)
+    }
+
+    /// Parses the whole of `input` with the rule called `rule`.
+    pub fn parse_rule(&self, rule: &str, input: &str) -> Result<Node, Error> {
+        match self.rules.iter().position(|r| r.name == rule) {
+            Some(i) => self.parse_at(i, input),
+            None => Err(Error {
+                format: "grammar",
+                span: 0..0,
+                line: 1,
+                column: 1,
+                expected: Some("a rule of the grammar".to_string()),
+                found: format!("`{}`", rule),
+                context: vec![],
+            }),
+        }
+    }
+
+    fn parse_at(&self, start: usize, input: &str) -> Result<Node, Error> {
+        let mut matcher = Matcher {
+            rules: &self.rules,
+            input,
+            farthest: 0,
+            expected: vec![],
+            lookahead: 0,
+            depth: 0,
+            too_deep: None,
+        };
+        let rule = &self.rules[start];
+        let mut children = vec![];
+        match matcher.eval(&rule.expr, 0, &mut children) {
+            Some(end) if end == input.len() => Ok(Node {
+                rule: rule.name.clone(),
+                span: 0..end,
+                children,
+            }),
+            Some(end) => {
+                matcher.fail(end, || "end of input".to_string());
+                Err(matcher.error())
+            }
+            None => Err(matcher.error()),
+        }
+    }
+}
+
+impl FromStr for Grammar {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed = parse_all("grammar", s, rules)?;
+        let mut index = HashMap::new();
+        for (i, (name, _)) in parsed.iter().enumerate() {
+            if index.insert(*name, i).is_some() {
+                return Err(invalid(s, name, "rule defined twice"));
+            }
+        }
+        let mut rules = vec![];
+        for (name, expr) in parsed {
+            let expr = expr.resolve(&mut |reference: &str| {
+                index
+                    .get(reference)
+                    .copied()
+                    .ok_or_else(|| invalid(s, reference, "undefined rule"))
+            })?;
+            rules.push(Rule {
+                name: name.to_string(),
+                expr,
+            });
+        }
+        let grammar = Grammar { rules };
+        if let Some(i) = grammar.left_recursive() {
+            let name = index
+                .iter()
+                .find(|(_, &j)| j == i)
+                .map(|(name, _)| *name)
+                .expect("every rule is indexed");
+            return Err(invalid(s, name, "left-recursive rule"));
+        }
+        Ok(grammar)
+    }
+}
+
+/// A rule matched by a `Grammar`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Node {
+    pub rule: String,
+    /// Byte range of the match in the input.
+    pub span: Range<usize>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    /// The text this node matched, given the input it was parsed from.
+    pub fn text<'a>(&self, input: &'a str) -> &'a str {
+        &input[self.span.clone()]
+    }
+}
+
+impl Tree for Node {
+    fn children(&self) -> Vec<(Segment, &Self)> {
+        self.children
+            .iter()
+            .enumerate()
+            .map(|(i, child)| (Segment::Index(i), child))
+            .collect()
+    }
+
+    fn children_mut(&mut self) -> Vec<(Segment, &mut Self)> {
+        self.children
+            .iter_mut()
+            .enumerate()
+            .map(|(i, child)| (Segment::Index(i), child))
+            .collect()
+    }
+}
+
+fn describe_class(ranges: &[(char, char)], negated: bool) -> String {
+    let mut out = String::from(if negated { "[^" } else { "[" });
+    for &(lo, hi) in ranges {
+        out.extend(lo.escape_debug());
+        if hi != lo {
+            out.push('-');
+            out.extend(hi.escape_debug());
+        }
+    }
+    out.push(']');
+    out
+}
+
+struct Matcher<'g, 'i> {
+    rules: &'g [Rule],
+    input: &'i str,
+    /// The furthest position a terminal failed at, and what was expected
+    /// there. Errors are reported there, as it is usually where the input
+    /// went wrong.
+    farthest: usize,
+    expected: Vec<String>,
+    /// Above zero inside `&` and `!`, whose failures aren't errors.
+    lookahead: usize,
+    depth: usize,
+    /// Where matching gave up for nesting too deeply.
+    too_deep: Option<usize>,
+}
+
+impl Matcher<'_, '_> {
+    fn fail(&mut self, pos: usize, expected: impl FnOnce() -> String) {
+        if self.lookahead > 0 || pos < self.farthest {
+            return;
+        }
+        if pos > self.farthest {
+            self.farthest = pos;
+            self.expected.clear();
+        }
+        let expected = expected();
+        if !self.expected.contains(&expected) {
+            self.expected.push(expected);
+        }
+    }
+
+    fn error(&self) -> Error {
+        if let Some(at) = self.too_deep {
+            return invalid(self.input, &self.input[at..], "nesting too deep");
+        }
+        let at = &self.input[self.farthest..];
+        let mut error = Error::expected("grammar", self.input, at, "");
+        error.expected = match self.expected.as_slice() {
+            [] => None,
+            [one] => Some(one.clone()),
+            [init @ .., last] => Some(format!("one of {} or {}", init.join(", "), last)),
+        };
+        error
+    }
+
+    /// Matches `expr` at `pos`, returning where the match ended. Nodes for
+    /// the rules matched are appended to `out`, which is left unchanged if
+    /// the match fails.
+    fn eval(&mut self, expr: &Expr<usize>, pos: usize, out: &mut Vec<Node>) -> Option<usize> {
+        if self.too_deep.is_some() {
+            return None;
+        }
+        let rest = &self.input[pos..];
+        match expr {
+            Expr::Literal(s) => match rest.starts_with(s.as_str()) {
+                true => Some(pos + s.len()),
+                false => {
+                    self.fail(pos, || format!("{:?}", s));
+                    None
+                }
+            },
+            Expr::Any => match rest.chars().next() {
+                Some(c) => Some(pos + c.len_utf8()),
+                None => {
+                    self.fail(pos, || "any character".to_string());
+                    None
+                }
+            },
+            Expr::Class { ranges, negated } => match rest.chars().next() {
+                Some(c) if ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated => {
+                    Some(pos + c.len_utf8())
+                }
+                _ => {
+                    self.fail(pos, || describe_class(ranges, *negated));
+                    None
+                }
+            },
+            Expr::Ref(i) => self.rule(*i, pos, out),
+            Expr::Sequence(items) => {
+                let mark = out.len();
+                let mut end = pos;
+                for item in items {
+                    match self.eval(item, end, out) {
+                        Some(next) => end = next,
+                        None => {
+                            out.truncate(mark);
+                            return None;
+                        }
+                    }
+                }
+                Some(end)
+            }
+            Expr::Choice(choices) => {
+                for choice in choices {
+                    if let Some(end) = self.eval(choice, pos, out) {
+                        return Some(end);
+                    }
+                }
+                None
+            }
+            Expr::Repeat { expr, min, max } => {
+                let mark = out.len();
+                let (mut end, mut count) = (pos, 0);
+                while max.is_none_or(|max| count < max) {
+                    match self.eval(expr, end, out) {
+                        // A match that consumes nothing would repeat forever.
+                        Some(next) if next == end => {
+                            count += 1;
+                            break;
+                        }
+                        Some(next) => {
+                            end = next;
+                            count += 1;
+                        }
+                        None => break,
+                    }
+                }
+                if count < *min {
+                    out.truncate(mark);
+                    return None;
+                }
+                Some(end)
+            }
+            Expr::And(expr) => self.lookahead(expr, pos).then_some(pos),
+            Expr::Not(expr) => (!self.lookahead(expr, pos)).then_some(pos),
+        }
+    }
+
+    fn lookahead(&mut self, expr: &Expr<usize>, pos: usize) -> bool {
+        self.lookahead += 1;
+        let matched = self.eval(expr, pos, &mut vec![]).is_some();
+        self.lookahead -= 1;
+        matched
+    }
+
+    fn rule(&mut self, i: usize, pos: usize, out: &mut Vec<Node>) -> Option<usize> {
+        if self.depth >= MAX_DEPTH {
+            self.too_deep.get_or_insert(pos);
+            return None;
+        }
+        let rule = &self.rules[i];
+        let mut children = vec![];
+        self.depth += 1;
+        let end = self.eval(&rule.expr, pos, &mut children);
+        self.depth -= 1;
+        let end = end?;
+        match rule.name.starts_with('_') {
+            true => out.extend(children),
+            false => out.push(Node {
+                rule: rule.name.clone(),
+                span: pos..end,
+                children,
+            }),
+        }
+        Some(end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARITHMETIC: &str = r#"
+        # Arithmetic over integers.
+        expr   = term (_ws ("+" / "-") _ws term)* ;
+        term   = factor (_ws ("*" / "/") _ws factor)* ;
+        factor = number / "(" _ws expr _ws ")" ;
+        number = [0-9]+ ;
+        _ws    = [ \t]* ;
+    "#;
+
+    fn show(node: &Node) -> String {
+        match node.children.as_slice() {
+            [] => node.rule.clone(),
+            children => {
+                let children: Vec<String> = children.iter().map(show).collect();
+                format!("{}({})", node.rule, children.join(" "))
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let grammar: Grammar = ARITHMETIC.parse().unwrap();
+        assert_eq!(
+            grammar.rules().collect::<Vec<_>>(),
+            vec!["expr", "term", "factor", "number", "_ws"]
+        );
+        let input = "1 + 2*(30)";
+        let tree = grammar.parse(input).unwrap();
+        assert_eq!(
+            show(&tree),
+            "expr(term(factor(number)) term(factor(number) factor(expr(term(factor(number))))))"
+        );
+        let inner = &tree.children[1].children[1];
+        assert_eq!((inner.text(input), inner.span.clone()), ("(30)", 6..10));
+        assert_eq!(show(&grammar.parse_rule("number", "42").unwrap()), "number");
+    }
+
+    #[test]
+    fn test_operators() {
+        let grammar: Grammar = r#"
+            words   = word (" " word)* ;
+            word    = !"end" [a-z]+ / 'end' &eof ;
+            eof     = !. ;
+        "#
+        .parse()
+        .unwrap();
+        assert!(grammar.parse("foo bar end").is_ok());
+        let error = grammar.parse("end foo").unwrap_err();
+        assert_eq!((error.span, error.expected), (0..3, None));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let grammar: Grammar = ARITHMETIC.parse().unwrap();
+        let error = grammar.parse("1 + ").unwrap_err();
+        assert_eq!(error.span.start, 4);
+        assert_eq!(
+            error.to_string(),
+            "grammar error at byte 4: expected one of [ \\t], [0-9] or \"(\", found end of input"
+        );
+        let error = grammar.parse("(1))").unwrap_err();
+        assert_eq!(error.span, 3..4);
+
+        let nested = format!("{}1{}", "(".repeat(300), ")".repeat(300));
+        let error = grammar.parse(&nested).unwrap_err();
+        assert_eq!(error.context, vec!["nesting too deep"]);
+    }
+
+    #[test]
+    fn test_grammar_errors() {
+        let error = "a = b ;".parse::<Grammar>().unwrap_err();
+        assert_eq!((error.span, error.context), (4..5, vec!["undefined rule"]));
+        let error = "a = \"x\" ; a = \"y\" ;".parse::<Grammar>().unwrap_err();
+        assert_eq!(error.span, 10..11);
+        let error = "a = b \"x\" / \"y\" ;\nb = \"\"? a ;"
+            .parse::<Grammar>()
+            .unwrap_err();
+        assert_eq!(
+            (error.span, error.context),
+            (0..1, vec!["left-recursive rule"])
+        );
+        let error = "a = \"x\"\nb = \"y\" ;".parse::<Grammar>().unwrap_err();
+        assert_eq!(error.span, 8..9);
+        assert!("a = [x-".parse::<Grammar>().is_err());
+    }
+}
@@ -0,0 +1,251 @@
+use nom::{
+    bytes::complete::{tag, take, take_until},
+    combinator::{cond, map},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::many0,
+    number::complete::{le_u16, le_u32, u8},
+    sequence::{pair, terminated, tuple},
+    Err as NomErr, IResult,
+};
+
+use crate::crc32::crc32;
+
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+const DEFLATE: u8 = 8;
+
+const FTEXT: u8 = 0x01;
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+const RESERVED: u8 = 0xe0;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Os {
+    Fat,
+    Unix,
+    Macintosh,
+    Ntfs,
+    Unknown,
+    Other(u8),
+}
+
+impl From<u8> for Os {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Os::Fat,
+            3 => Os::Unix,
+            7 => Os::Macintosh,
+            11 => Os::Ntfs,
+            255 => Os::Unknown,
+            other => Os::Other(other),
+        }
+    }
+}
+
+/// A member header. The name and comment are Latin-1 and are decoded to
+/// `String`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Header<'a> {
+    pub text: bool,
+    /// Seconds since the Unix epoch, or 0 if unknown.
+    pub mtime: u32,
+    pub extra_flags: u8,
+    pub os: Os,
+    pub extra: Option<&'a [u8]>,
+    pub name: Option<String>,
+    pub comment: Option<String>,
+    /// The header CRC, already checked against the header bytes.
+    pub header_crc: Option<u16>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Trailer {
+    pub crc32: u32,
+    /// The uncompressed size modulo 2^32.
+    pub size: u32,
+}
+
+impl Trailer {
+    /// Checks the trailer against the decompressed data.
+    pub fn verify(&self, data: &[u8]) -> bool {
+        crc32(data) == self.crc32 && data.len() as u32 == self.size
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Member<'a> {
+    pub header: Header<'a>,
+    /// The deflate stream, still compressed.
+    pub payload: &'a [u8],
+    pub trailer: Trailer,
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+/// A subfield of `FEXTRA`: a two-byte identifier and its data.
+type ExtraField<'a> = ([u8; 2], &'a [u8]);
+
+fn failure<'a>(input: &'a [u8], reason: &'static str) -> NomErr<VerboseError<&'a [u8]>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| char::from(b)).collect()
+}
+
+fn length_prefixed(input: &[u8]) -> Res<&[u8], &[u8]> {
+    let (input, length) = le_u16(input)?;
+    take(length)(input)
+}
+
+fn zero_terminated(input: &[u8]) -> Res<&[u8], String> {
+    map(terminated(take_until(&b"\0"[..]), tag(&b"\0"[..])), latin1)(input)
+}
+
+/// Parses a member header, verifying the header CRC when present.
+pub fn header(input: &[u8]) -> Res<&[u8], Header<'_>> {
+    let start = input;
+    let (input, (_, method, flags, mtime, extra_flags, os)) = context(
+        "gzip header",
+        tuple((tag(&MAGIC[..]), u8, u8, le_u32, u8, u8)),
+    )(input)?;
+    if method != DEFLATE {
+        return Err(failure(start, "unsupported compression method"));
+    }
+    if flags & RESERVED != 0 {
+        return Err(failure(start, "reserved flags set"));
+    }
+    let (input, (extra, name, comment)) = context(
+        "gzip header",
+        tuple((
+            cond(flags & FEXTRA != 0, length_prefixed),
+            cond(flags & FNAME != 0, zero_terminated),
+            cond(flags & FCOMMENT != 0, zero_terminated),
+        )),
+    )(input)?;
+    let header_length = start.len() - input.len();
+    let (input, header_crc) = context("header crc", cond(flags & FHCRC != 0, le_u16))(input)?;
+    if let Some(expected) = header_crc {
+        // The header CRC is the low 16 bits of the CRC-32 of the header.
+        if crc32(&start[..header_length]) as u16 != expected {
+            return Err(failure(start, "header crc mismatch"));
+        }
+    }
+    Ok((
+        input,
+        Header {
+            text: flags & FTEXT != 0,
+            mtime,
+            extra_flags,
+            os: os.into(),
+            extra,
+            name,
+            comment,
+            header_crc,
+        },
+    ))
+}
+
+/// Splits an `FEXTRA` field into its subfields.
+pub fn extra_fields(input: &[u8]) -> Res<&[u8], Vec<ExtraField<'_>>> {
+    many0(map(
+        pair(take(2usize), length_prefixed),
+        |(id, data): (&[u8], &[u8])| ([id[0], id[1]], data),
+    ))(input)
+}
+
+pub fn trailer(input: &[u8]) -> Res<&[u8], Trailer> {
+    context("gzip trailer", pair(le_u32, le_u32))(input)
+        .map(|(next_input, (crc32, size))| (next_input, Trailer { crc32, size }))
+}
+
+/// Parses a single-member gzip file. Without inflating, the end of the
+/// deflate stream can't be found, so the payload is everything between the
+/// header and the eight-byte trailer at the end of the input.
+pub fn member(input: &[u8]) -> Res<&[u8], Member<'_>> {
+    let (body, header) = header(input)?;
+    let payload_length = body
+        .len()
+        .checked_sub(8)
+        .ok_or_else(|| failure(body, "missing trailer"))?;
+    let (input, (payload, trailer)) = pair(take(payload_length), trailer)(body)?;
+    Ok((
+        input,
+        Member {
+            header,
+            payload,
+            trailer,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `printf hello | gzip -n`, with the name and a header CRC added.
+    fn sample() -> Vec<u8> {
+        let mut bytes = vec![0x1f, 0x8b, 8, FNAME | FCOMMENT | FEXTRA | FHCRC];
+        bytes.extend(1_700_000_000u32.to_le_bytes());
+        bytes.extend([0, 3]);
+        bytes.extend([6, 0, b'A', b'P', 2, 0, 1, 2]);
+        bytes.extend(b"hello.txt\0caf\xe9\0");
+        let header_crc = crc32(&bytes) as u16;
+        bytes.extend(header_crc.to_le_bytes());
+        bytes.extend([0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00]);
+        bytes.extend(crc32(b"hello").to_le_bytes());
+        bytes.extend(5u32.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_member() {
+        let bytes = sample();
+        let (rest, member) = member(&bytes).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(member.header.mtime, 1_700_000_000);
+        assert_eq!(member.header.os, Os::Unix);
+        assert_eq!(member.header.name.as_deref(), Some("hello.txt"));
+        assert_eq!(member.header.comment.as_deref(), Some("café"));
+        assert!(member.header.header_crc.is_some());
+        assert_eq!(member.payload, &[0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00]);
+        assert!(member.trailer.verify(b"hello"));
+        assert!(!member.trailer.verify(b"hellO"));
+        assert_eq!(
+            extra_fields(member.header.extra.unwrap()),
+            Ok((&b""[..], vec![(*b"AP", &[1, 2][..])]))
+        );
+    }
+
+    #[test]
+    fn test_minimal_header() {
+        let bytes = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255, 0xaa];
+        let (rest, header) = header(&bytes).unwrap();
+        assert_eq!(rest, &[0xaa]);
+        assert_eq!(header.os, Os::Unknown);
+        assert_eq!(header.name, None);
+        assert_eq!(header.extra, None);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut bytes = sample();
+        bytes[20] ^= 1;
+        assert_eq!(member(&bytes), Err(failure(&bytes, "header crc mismatch")));
+        let bytes = [0x1f, 0x8b, 7, 0, 0, 0, 0, 0, 0, 3];
+        assert_eq!(
+            header(&bytes),
+            Err(failure(&bytes, "unsupported compression method"))
+        );
+        let bytes = [0x1f, 0x8b, 8, 0x20, 0, 0, 0, 0, 0, 3];
+        assert_eq!(header(&bytes), Err(failure(&bytes, "reserved flags set")));
+        let bytes = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3, 1, 2];
+        assert_eq!(
+            member(&bytes),
+            Err(failure(&bytes[10..], "missing trailer"))
+        );
+    }
+}
@@ -0,0 +1,494 @@
+use std::collections::VecDeque;
+
+use nom::{
+    bytes::complete::take,
+    error::{context, VerboseError, VerboseErrorKind},
+    number::complete::u8,
+    Err as NomErr, IResult,
+};
+
+/// The static table from RFC 7541 Appendix A. Index 1 is the first entry.
+pub const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// Huffman code lengths from RFC 7541 Appendix B, indexed by symbol, with
+/// EOS as symbol 256. The code is canonical, so the lengths are enough to
+/// rebuild it.
+const CODE_LENGTHS: [u8; 257] = [
+    13, 23, 28, 28, 28, 28, 28, 28, 28, 24, 30, 28, 28, 30, 28, 28, 28, 28, 28, 28, 28, 28, 30, 28,
+    28, 28, 28, 28, 28, 28, 28, 28, 6, 10, 10, 12, 13, 6, 8, 11, 10, 10, 8, 11, 8, 6, 6, 6, 5, 5,
+    5, 6, 6, 6, 6, 6, 6, 6, 7, 8, 15, 6, 12, 10, 13, 6, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 8, 7, 8, 13, 19, 13, 14, 6, 15, 5, 6, 5, 6, 5, 6, 6, 6, 5, 7, 7, 6, 6,
+    6, 5, 6, 7, 6, 5, 5, 6, 7, 7, 7, 7, 7, 15, 11, 14, 13, 28, 20, 22, 20, 20, 22, 22, 22, 23, 22,
+    23, 23, 23, 23, 23, 24, 23, 24, 24, 22, 23, 24, 23, 23, 23, 23, 21, 22, 23, 22, 23, 23, 24, 22,
+    21, 20, 22, 22, 23, 23, 21, 23, 22, 22, 24, 21, 22, 23, 23, 21, 21, 22, 21, 23, 22, 23, 23, 20,
+    22, 22, 22, 23, 22, 22, 23, 26, 26, 20, 19, 22, 23, 22, 25, 26, 26, 26, 27, 27, 26, 24, 25, 19,
+    21, 26, 27, 27, 26, 27, 24, 21, 21, 26, 26, 28, 27, 27, 27, 20, 24, 20, 21, 22, 21, 21, 23, 22,
+    22, 25, 25, 24, 24, 26, 23, 26, 27, 26, 26, 27, 27, 27, 27, 27, 28, 27, 27, 27, 27, 27, 26, 30,
+];
+
+const MAX_CODE_LENGTH: usize = 30;
+const EOS: u16 = 256;
+
+/// How many codes there are of each length.
+const CODE_COUNTS: [u16; MAX_CODE_LENGTH + 1] = code_counts();
+/// Symbols in canonical code order: by code length, then by value.
+const SYMBOLS: [u16; 257] = canonical_symbols();
+
+const fn code_counts() -> [u16; MAX_CODE_LENGTH + 1] {
+    let mut counts = [0; MAX_CODE_LENGTH + 1];
+    let mut symbol = 0;
+    while symbol < CODE_LENGTHS.len() {
+        counts[CODE_LENGTHS[symbol] as usize] += 1;
+        symbol += 1;
+    }
+    counts
+}
+
+const fn canonical_symbols() -> [u16; 257] {
+    let mut symbols = [0; 257];
+    let mut next = 0;
+    let mut length = 1;
+    while length <= MAX_CODE_LENGTH {
+        let mut symbol = 0;
+        while symbol < CODE_LENGTHS.len() {
+            if CODE_LENGTHS[symbol] as usize == length {
+                symbols[next] = symbol as u16;
+                next += 1;
+            }
+            symbol += 1;
+        }
+        length += 1;
+    }
+    symbols
+}
+
+/// A decoded header field.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+    /// Sent as a never-indexed literal, so intermediaries must not add it
+    /// to their own tables.
+    pub sensitive: bool,
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a [u8], reason: &'static str) -> NomErr<VerboseError<&'a [u8]>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+/// Decodes Huffman-coded bytes. Returns `None` if the data contains EOS or
+/// ends in padding that is longer than seven bits or not all ones.
+pub fn huffman_decode(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(bytes.len() * 8 / 5);
+    // Canonical decoding: `first` is the first code of the current length
+    // and `index` the position of its symbol in `SYMBOLS`.
+    let (mut code, mut first, mut index, mut length) = (0u32, 0u32, 0usize, 0usize);
+    for byte in bytes {
+        for shift in (0..8).rev() {
+            code |= u32::from((byte >> shift) & 1);
+            length += 1;
+            let count = u32::from(CODE_COUNTS[length]);
+            if code >= first && code - first < count {
+                let symbol = SYMBOLS[index + (code - first) as usize];
+                if symbol == EOS {
+                    return None;
+                }
+                decoded.push(symbol as u8);
+                (code, first, index, length) = (0, 0, 0, 0);
+            } else {
+                index += count as usize;
+                first = (first + count) << 1;
+                code <<= 1;
+            }
+        }
+    }
+    // `code` has been shifted once past the last bit read.
+    if length > 7 || code >> 1 != (1 << length) - 1 {
+        return None;
+    }
+    Some(decoded)
+}
+
+/// Decodes an integer with a `prefix`-bit prefix (RFC 7541 section 5.1).
+/// The bits of the first byte above the prefix are returned alongside it.
+pub fn integer(prefix: u8) -> impl Fn(&[u8]) -> Res<&[u8], (u8, usize)> {
+    move |input| {
+        let (mut rest, first) = u8(input)?;
+        let mask = ((1u16 << prefix) - 1) as u8;
+        let mut value = usize::from(first & mask);
+        if value < usize::from(mask) {
+            return Ok((rest, (first & !mask, value)));
+        }
+        let mut shift = 0;
+        loop {
+            let (next, byte) = u8(rest)?;
+            rest = next;
+            if shift > 28 {
+                return Err(failure(input, "integer overflow"));
+            }
+            value += usize::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok((rest, (first & !mask, value)));
+            }
+        }
+    }
+}
+
+/// Decodes a string literal, Huffman-coded or not.
+pub fn string(input: &[u8]) -> Res<&[u8], String> {
+    let (rest, (flags, length)) = integer(7)(input)?;
+    let (rest, bytes) = take(length)(rest)?;
+    let bytes = if flags & 0x80 != 0 {
+        huffman_decode(bytes).ok_or_else(|| failure(input, "invalid huffman code"))?
+    } else {
+        bytes.to_vec()
+    };
+    String::from_utf8(bytes)
+        .map(|string| (rest, string))
+        .map_err(|_| failure(input, "invalid utf-8"))
+}
+
+/// Decoding state for one direction of an HTTP/2 connection. The dynamic
+/// table carries over from one header block to the next, so blocks must be
+/// decoded in the order they were sent.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    entries: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+    /// The limit from `SETTINGS_HEADER_TABLE_SIZE`, which size updates may
+    /// not exceed.
+    size_limit: usize,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Decoder::new(4096)
+    }
+}
+
+impl Decoder {
+    pub fn new(size_limit: usize) -> Self {
+        Decoder {
+            entries: VecDeque::new(),
+            size: 0,
+            max_size: size_limit,
+            size_limit,
+        }
+    }
+
+    /// The size of the dynamic table as defined by RFC 7541 section 4.1.
+    pub fn table_size(&self) -> usize {
+        self.size
+    }
+
+    /// The dynamic table, newest entry first.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    fn get(&self, index: usize) -> Option<(&str, &str)> {
+        match index {
+            0 => None,
+            1..=61 => Some(STATIC_TABLE[index - 1]),
+            _ => self
+                .entries
+                .get(index - 62)
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.max_size {
+            let (name, value) = self.entries.pop_back().unwrap();
+            self.size -= name.len() + value.len() + 32;
+        }
+    }
+
+    fn insert(&mut self, name: String, value: String) {
+        self.size += name.len() + value.len() + 32;
+        self.entries.push_front((name, value));
+        self.evict();
+    }
+
+    fn literal<'a>(&self, prefix: u8, input: &'a [u8]) -> Res<&'a [u8], (String, String)> {
+        let (rest, (_, index)) = integer(prefix)(input)?;
+        let (rest, name) = match index {
+            0 => string(rest)?,
+            _ => match self.get(index) {
+                Some((name, _)) => (rest, name.to_string()),
+                None => return Err(failure(input, "invalid table index")),
+            },
+        };
+        let (rest, value) = string(rest)?;
+        Ok((rest, (name, value)))
+    }
+
+    /// Decodes a complete header block, updating the dynamic table.
+    pub fn decode<'a>(&mut self, block: &'a [u8]) -> Res<&'a [u8], Vec<Header>> {
+        let mut headers = Vec::new();
+        let mut input = block;
+        while let Some(&first) = input.first() {
+            let start = input;
+            if first & 0x80 != 0 {
+                let (rest, (_, index)) = context("indexed header", integer(7))(input)?;
+                let (name, value) = self
+                    .get(index)
+                    .ok_or_else(|| failure(start, "invalid table index"))?;
+                headers.push(Header {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                    sensitive: false,
+                });
+                input = rest;
+            } else if first & 0xc0 == 0x40 {
+                let (rest, (name, value)) = self.literal(6, input)?;
+                self.insert(name.clone(), value.clone());
+                headers.push(Header {
+                    name,
+                    value,
+                    sensitive: false,
+                });
+                input = rest;
+            } else if first & 0xe0 == 0x20 {
+                let (rest, (_, size)) = context("table size update", integer(5))(input)?;
+                if !headers.is_empty() {
+                    return Err(failure(start, "table size update after header"));
+                }
+                if size > self.size_limit {
+                    return Err(failure(start, "table size update too large"));
+                }
+                self.max_size = size;
+                self.evict();
+                input = rest;
+            } else {
+                let (rest, (name, value)) = self.literal(4, input)?;
+                headers.push(Header {
+                    name,
+                    value,
+                    sensitive: first & 0x10 != 0,
+                });
+                input = rest;
+            }
+        }
+        Ok((input, headers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        let digits: Vec<u8> = s.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+        digits
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    fn pairs(headers: &[Header]) -> Vec<(&str, &str)> {
+        headers
+            .iter()
+            .map(|h| (h.name.as_str(), h.value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_integer() {
+        assert_eq!(integer(5)(&[0x0a]), Ok((&b""[..], (0, 10))));
+        assert_eq!(
+            integer(5)(&[0xff, 0x9a, 0x0a]),
+            Ok((&b""[..], (0xe0, 1337)))
+        );
+        assert_eq!(integer(8)(&[42]), Ok((&b""[..], (0, 42))));
+        let overflow = [0x1f, 0xff, 0xff, 0xff, 0xff, 0xff, 0x0f];
+        assert_eq!(
+            integer(5)(&overflow),
+            Err(failure(&overflow, "integer overflow"))
+        );
+    }
+
+    #[test]
+    fn test_huffman() {
+        assert_eq!(
+            huffman_decode(&hex("f1e3 c2e5 f23a 6ba0 ab90 f4ff")),
+            Some(b"www.example.com".to_vec())
+        );
+        assert_eq!(
+            huffman_decode(&hex("a8eb 1064 9cbf")),
+            Some(b"no-cache".to_vec())
+        );
+        assert_eq!(huffman_decode(&[]), Some(vec![]));
+        // Eight bits of padding.
+        assert_eq!(huffman_decode(&hex("a8eb 1064 9cbf ff")), None);
+        // Padding that isn't all ones.
+        assert_eq!(huffman_decode(&[0x00]), None);
+        // EOS.
+        assert_eq!(huffman_decode(&hex("ffff fffc")), None);
+    }
+
+    #[test]
+    fn test_requests() {
+        let mut decoder = Decoder::default();
+        let (_, headers) = decoder
+            .decode(&hex("8286 8441 0f77 7777 2e65 7861 6d70 6c65 2e63 6f6d"))
+            .unwrap();
+        assert_eq!(
+            pairs(&headers),
+            vec![
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+            ]
+        );
+        assert_eq!(decoder.table_size(), 57);
+        let (_, headers) = decoder
+            .decode(&hex("8286 84be 5886 a8eb 1064 9cbf"))
+            .unwrap();
+        assert_eq!(headers[3].value, "www.example.com");
+        assert_eq!(headers[4].value, "no-cache");
+        assert_eq!(decoder.table_size(), 110);
+        let (_, headers) = decoder
+            .decode(&hex(
+                "8287 85bf 4088 25a8 49e9 5ba9 7d7f 8925 a849 e95b b8e8 b4bf",
+            ))
+            .unwrap();
+        assert_eq!(
+            pairs(&headers),
+            vec![
+                (":method", "GET"),
+                (":scheme", "https"),
+                (":path", "/index.html"),
+                (":authority", "www.example.com"),
+                ("custom-key", "custom-value"),
+            ]
+        );
+        assert_eq!(
+            decoder.entries().collect::<Vec<_>>(),
+            vec![
+                ("custom-key", "custom-value"),
+                ("cache-control", "no-cache"),
+                (":authority", "www.example.com"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eviction_and_size_update() {
+        let mut decoder = Decoder::new(100);
+        decoder
+            .decode(&hex("418c f1e3 c2e5 f23a 6ba0 ab90 f4ff"))
+            .unwrap();
+        decoder.decode(&hex("5886 a8eb 1064 9cbf")).unwrap();
+        assert_eq!(
+            decoder.entries().collect::<Vec<_>>(),
+            vec![("cache-control", "no-cache")]
+        );
+        assert_eq!(decoder.table_size(), 53);
+        decoder.decode(&[0x20]).unwrap();
+        assert_eq!(decoder.table_size(), 0);
+        let block = [0x3f, 0x50];
+        assert_eq!(
+            decoder.decode(&block),
+            Err(failure(&block, "table size update too large"))
+        );
+        let block = [0x82, 0x20];
+        assert_eq!(
+            decoder.decode(&block),
+            Err(failure(&block[1..], "table size update after header"))
+        );
+    }
+
+    #[test]
+    fn test_never_indexed() {
+        let mut decoder = Decoder::default();
+        let (_, headers) = decoder
+            .decode(&hex("1008 7061 7373 776f 7264 0673 6563 7265 74"))
+            .unwrap();
+        assert_eq!(
+            headers,
+            vec![Header {
+                name: "password".to_string(),
+                value: "secret".to_string(),
+                sensitive: true,
+            }]
+        );
+        assert_eq!(decoder.table_size(), 0);
+        let block = [0xbe];
+        assert_eq!(
+            decoder.decode(&block),
+            Err(failure(&block, "invalid table index"))
+        );
+    }
+}
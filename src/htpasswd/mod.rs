@@ -0,0 +1,236 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{take_till1, take_while_m_n},
+    character::complete::{char, line_ending, multispace0, not_line_ending, space0},
+    combinator::{eof, map, recognize},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::many0,
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    Err as NomErr, IResult,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HashKind {
+    /// `$2y$`, `$2a$` or `$2b$`.
+    Bcrypt,
+    /// Apache's MD5 variant, `$apr1$`.
+    Apr1,
+    /// `{SHA}` followed by a base64 SHA-1 digest.
+    Sha1,
+    /// glibc `$5$` crypt.
+    Sha256Crypt,
+    /// glibc `$6$` crypt.
+    Sha512Crypt,
+    /// Traditional 13-character DES crypt.
+    Crypt,
+    /// Anything else, such as a plaintext password.
+    Unknown,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct HtpasswdEntry<'a> {
+    pub user: &'a str,
+    pub hash: &'a str,
+    pub kind: HashKind,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct HtdigestEntry<'a> {
+    pub user: &'a str,
+    pub realm: &'a str,
+    /// The hex MD5 of `user:realm:password`.
+    pub hash: &'a str,
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+fn is_crypt_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.' || c == '/'
+}
+
+/// Identifies a hash by its prefix, or returns `None` when the prefix is
+/// recognised but the rest of the hash is malformed.
+pub fn hash_kind(hash: &str) -> Option<HashKind> {
+    let crypt = |rest: &str| !rest.is_empty() && rest.chars().all(|c| is_crypt_char(c) || c == '$');
+    if let Some(rest) = ["$2y$", "$2a$", "$2b$"]
+        .iter()
+        .find_map(|prefix| hash.strip_prefix(prefix))
+    {
+        let (cost, digest) = rest.split_at(rest.len().min(3));
+        let valid = cost.len() == 3
+            && cost[..2].chars().all(|c| c.is_ascii_digit())
+            && cost.ends_with('$')
+            && digest.len() == 53
+            && digest.chars().all(is_crypt_char);
+        return valid.then_some(HashKind::Bcrypt);
+    }
+    for (prefix, kind) in [
+        ("$apr1$", HashKind::Apr1),
+        ("$5$", HashKind::Sha256Crypt),
+        ("$6$", HashKind::Sha512Crypt),
+    ] {
+        if let Some(rest) = hash.strip_prefix(prefix) {
+            return crypt(rest).then_some(kind);
+        }
+    }
+    if let Some(digest) = hash.strip_prefix("{SHA}") {
+        let valid = digest.len() == 28
+            && digest
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+/=".contains(c));
+        return valid.then_some(HashKind::Sha1);
+    }
+    if hash.len() == 13 && hash.chars().all(is_crypt_char) {
+        return Some(HashKind::Crypt);
+    }
+    Some(HashKind::Unknown)
+}
+
+fn field(input: &str) -> Res<&str, &str> {
+    take_till1(|c: char| c == ':' || c == '\r' || c == '\n')(input)
+}
+
+fn end_of_line(input: &str) -> Res<&str, &str> {
+    preceded(space0, alt((line_ending, eof)))(input)
+}
+
+/// Blank lines and `#` comments.
+fn ignored(input: &str) -> Res<&str, ()> {
+    map(
+        many0(alt((
+            recognize(pair(multispace0, pair(char('#'), not_line_ending))),
+            recognize(pair(space0, line_ending)),
+        ))),
+        |_| (),
+    )(input)
+}
+
+/// Parses one `user:hash` line.
+pub fn htpasswd_entry(input: &str) -> Res<&str, HtpasswdEntry<'_>> {
+    let (next_input, (user, hash)) = context(
+        "htpasswd entry",
+        terminated(
+            pair(
+                field,
+                preceded(char(':'), take_till1(|c: char| c.is_whitespace())),
+            ),
+            end_of_line,
+        ),
+    )(input)?;
+    let kind = hash_kind(hash).ok_or_else(|| failure(input, "malformed hash"))?;
+    Ok((next_input, HtpasswdEntry { user, hash, kind }))
+}
+
+/// Parses an htpasswd file, skipping blank lines and comments.
+pub fn htpasswd(input: &str) -> Res<&str, Vec<HtpasswdEntry<'_>>> {
+    context(
+        "htpasswd",
+        delimited(
+            ignored,
+            many0(terminated(htpasswd_entry, ignored)),
+            multispace0,
+        ),
+    )(input)
+}
+
+/// Parses one `user:realm:hash` line.
+pub fn htdigest_entry(input: &str) -> Res<&str, HtdigestEntry<'_>> {
+    context(
+        "htdigest entry",
+        terminated(
+            tuple((
+                field,
+                preceded(char(':'), field),
+                preceded(
+                    char(':'),
+                    take_while_m_n(32, 32, |c: char| c.is_ascii_hexdigit()),
+                ),
+            )),
+            end_of_line,
+        ),
+    )(input)
+    .map(|(next_input, (user, realm, hash))| (next_input, HtdigestEntry { user, realm, hash }))
+}
+
+/// Parses an htdigest file, skipping blank lines and comments.
+pub fn htdigest(input: &str) -> Res<&str, Vec<HtdigestEntry<'_>>> {
+    context(
+        "htdigest",
+        delimited(
+            ignored,
+            many0(terminated(htdigest_entry, ignored)),
+            multispace0,
+        ),
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BCRYPT: &str = "$2y$05$c4WoMPo3SXsafkva.HHa6uXQZWr7oboPiC2bT/r7q1BB8I2s0BRqC";
+
+    #[test]
+    fn test_htpasswd() {
+        let input = format!(
+            "# users\nalice:{}\nbob:$apr1$r31.....$HqJZimcKQFAMYayBlzkrA/\r\n\ncarol:{{SHA}}W6ph5Mm5Pz8GgiULbPgzG37mj9g=\ndave:rqXexS6ZhobKA\neve:plain\n",
+            BCRYPT
+        );
+        let (rest, entries) = htpasswd(&input).unwrap();
+        assert_eq!(rest, "");
+        let kinds: Vec<_> = entries.iter().map(|e| (e.user, e.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("alice", HashKind::Bcrypt),
+                ("bob", HashKind::Apr1),
+                ("carol", HashKind::Sha1),
+                ("dave", HashKind::Crypt),
+                ("eve", HashKind::Unknown),
+            ]
+        );
+        assert_eq!(entries[0].hash, BCRYPT);
+    }
+
+    #[test]
+    fn test_hash_kind() {
+        assert_eq!(hash_kind("$6$salt$abc"), Some(HashKind::Sha512Crypt));
+        assert_eq!(hash_kind("$5$salt$abc"), Some(HashKind::Sha256Crypt));
+        assert_eq!(hash_kind("$2y$05$short"), None);
+        assert_eq!(hash_kind("{SHA}tooshort"), None);
+        assert_eq!(hash_kind("$apr1$"), None);
+    }
+
+    #[test]
+    fn test_htpasswd_errors() {
+        assert_eq!(
+            htpasswd_entry("alice:$2y$05$short\n"),
+            Err(failure("alice:$2y$05$short\n", "malformed hash"))
+        );
+        assert!(htpasswd_entry("alice\n").is_err());
+        assert!(htpasswd_entry("alice:hash extra\n").is_err());
+    }
+
+    #[test]
+    fn test_htdigest() {
+        let input = "# digest\nalice:Private Area:5f4dcc3b5aa765d61d8327deb882cf99\nbob:api:0123456789abcdef0123456789ABCDEF";
+        let (rest, entries) = htdigest(input).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            entries[0],
+            HtdigestEntry {
+                user: "alice",
+                realm: "Private Area",
+                hash: "5f4dcc3b5aa765d61d8327deb882cf99",
+            }
+        );
+        assert_eq!(entries[1].realm, "api");
+        assert!(htdigest_entry("alice:realm:nothex").is_err());
+    }
+}
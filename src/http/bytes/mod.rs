@@ -0,0 +1,221 @@
+//! The HTTP parsers over `&[u8]`, for buffers read straight from a socket.
+//! Message heads and URIs are text, so they're parsed from the longest
+//! UTF-8 prefix of the input by the `&str` parsers; nothing is converted
+//! lossily, and a byte that isn't UTF-8 ends the text there. Bodies are
+//! returned as bytes.
+
+use std::str;
+
+use nom::{
+    error::{VerboseError, VerboseErrorKind},
+    sequence::pair,
+    Err as NomErr, IResult,
+};
+
+use super::message::{request_line, split_body, status_line};
+use super::{HeaderMap, Request, Response, URI};
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a [u8], reason: &'static str) -> NomErr<VerboseError<&'a [u8]>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+/// The longest prefix of `input` that is UTF-8.
+fn text_prefix(input: &[u8]) -> &str {
+    match str::from_utf8(input) {
+        Ok(text) => text,
+        Err(error) => str::from_utf8(&input[..error.valid_up_to()]).expect("valid up to here"),
+    }
+}
+
+/// Runs a `&str` parser over the text at the start of `input`, moving the
+/// rest and any error positions back to `input`. An error on the line cut
+/// short by a byte that isn't UTF-8 is blamed on that byte.
+fn over_text<'a, O>(
+    input: &'a [u8],
+    parser: impl FnOnce(&'a str) -> Res<&'a str, O>,
+) -> Res<&'a [u8], O> {
+    let text = text_prefix(input);
+    let at = |rest: &str| &input[text.len() - rest.len()..];
+    match parser(text) {
+        Ok((rest, output)) => Ok((at(rest), output)),
+        Err(error) => Err(error.map(|error| {
+            let cut_short = text.len() < input.len()
+                && error
+                    .errors
+                    .first()
+                    .is_some_and(|(rest, _)| !rest.contains('\n'));
+            let mut errors: Vec<_> = error
+                .errors
+                .into_iter()
+                .map(|(rest, kind)| (at(rest), kind))
+                .collect();
+            if cut_short {
+                errors.insert(
+                    0,
+                    (&input[text.len()..], VerboseErrorKind::Context("UTF-8")),
+                );
+            }
+            VerboseError { errors }
+        })),
+    }
+}
+
+/// The body after a message's head, as `message::split_body` frames it.
+fn body<'a>(input: &'a [u8], headers: &HeaderMap, status: Option<u16>) -> Res<&'a [u8], &'a [u8]> {
+    split_body(input, headers, status)
+        .map(|(body, rest)| (rest, body))
+        .map_err(|reason| failure(input, reason))
+}
+
+pub fn headers(input: &[u8]) -> Res<&[u8], HeaderMap<'_>> {
+    over_text(input, super::headers)
+}
+
+pub fn uri(input: &[u8]) -> Res<&[u8], URI<'_>> {
+    over_text(input, super::uri)
+}
+
+/// Like `http::request`, with the body as bytes.
+pub fn request(input: &[u8]) -> Res<&[u8], Request<'_, [u8]>> {
+    let (input, ((method, target, version), headers)) =
+        over_text(input, pair(request_line, super::headers))?;
+    let (input, body) = body(input, &headers, None)?;
+    let request = Request {
+        method,
+        target,
+        version,
+        headers,
+        body,
+    };
+    Ok((input, request))
+}
+
+/// Like `http::response`, with the body as bytes.
+pub fn response(input: &[u8]) -> Res<&[u8], Response<'_, [u8]>> {
+    let (input, ((version, status, reason), headers)) =
+        over_text(input, pair(status_line, super::headers))?;
+    let (input, body) = body(input, &headers, Some(status))?;
+    let response = Response {
+        version,
+        status,
+        reason,
+        headers,
+        body,
+    };
+    Ok((input, response))
+}
+
+/// Variants of the byte parsers that return `Err::Incomplete` until the
+/// buffer holds a whole message, as `http::streaming` does for text.
+pub mod streaming {
+    use nom::{sequence::pair, Err as NomErr, Needed};
+
+    use super::{over_text, request_line, status_line, Request, Res, Response};
+    use crate::http::message::{body_needed, has_head};
+
+    pub fn request(input: &[u8]) -> Res<&[u8], Request<'_, [u8]>> {
+        if !has_head(input) {
+            if input.contains(&b'\n') {
+                over_text(input, request_line)?;
+            }
+            return Err(NomErr::Incomplete(Needed::Unknown));
+        }
+        let (rest, (_, headers)) = over_text(input, pair(request_line, super::super::headers))?;
+        if let Some(needed) = body_needed(rest, &headers, None) {
+            return Err(NomErr::Incomplete(needed));
+        }
+        super::request(input)
+    }
+
+    /// A body without a `Content-Length` runs until the connection closes,
+    /// so it's never complete here.
+    pub fn response(input: &[u8]) -> Res<&[u8], Response<'_, [u8]>> {
+        if !has_head(input) {
+            if input.contains(&b'\n') {
+                over_text(input, status_line)?;
+            }
+            return Err(NomErr::Incomplete(Needed::Unknown));
+        }
+        let (rest, ((_, status, _), headers)) =
+            over_text(input, pair(status_line, super::super::headers))?;
+        if let Some(needed) = body_needed(rest, &headers, Some(status)) {
+            return Err(NomErr::Incomplete(needed));
+        }
+        super::response(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request() {
+        let input = b"POST /upload HTTP/1.1\r\nContent-Length: 4\r\n\r\n\xff\x00\xfe\x01GET";
+        let (rest, parsed) = request(input).unwrap();
+        assert_eq!(rest, b"GET");
+        assert_eq!(parsed.target, "/upload");
+        assert_eq!(parsed.body, b"\xff\x00\xfe\x01");
+        assert_eq!(parsed.clone().header("content-length"), Some("4"));
+
+        let bad = b"GET / HTTP/1.1\r\nX-Name: caf\xe9\r\n\r\n";
+        let error = request(bad).unwrap_err();
+        let NomErr::Failure(error) = error else {
+            panic!("expected a failure, got {:?}", error);
+        };
+        assert_eq!(error.errors[0].0, b"\xe9\r\n\r\n");
+        assert!(request(b"GET / HTTP/1.1\r\nContent-Length: 9\r\n\r\nshort").is_err());
+    }
+
+    #[test]
+    fn test_response() {
+        let input = b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\n\r\n\x89PNG\r\n";
+        let (rest, parsed) = response(input).unwrap();
+        assert_eq!(rest, b"");
+        assert_eq!(parsed.body, b"\x89PNG\r\n");
+        let (_, parsed) = response(b"HTTP/1.1 304 Not Modified\r\n\r\n").unwrap();
+        assert_eq!(parsed.body, b"");
+    }
+
+    #[test]
+    fn test_streaming() {
+        use nom::Needed;
+
+        let input = b"POST / HTTP/1.1\r\nContent-Length: 3\r\n\r\n\xff\xfe\xfd";
+        assert_eq!(
+            streaming::request(&input[..20]),
+            Err(NomErr::Incomplete(Needed::Unknown))
+        );
+        assert_eq!(
+            streaming::request(&input[..input.len() - 1]),
+            Err(NomErr::Incomplete(Needed::new(1)))
+        );
+        assert_eq!(streaming::request(input).unwrap().1.body, b"\xff\xfe\xfd");
+        assert!(matches!(
+            streaming::response(b"HTTP/1.1 200 OK\r\n\r\nbody"),
+            Err(NomErr::Incomplete(Needed::Unknown))
+        ));
+        // Framing that can't be trusted fails instead of waiting for more.
+        assert!(matches!(
+            streaming::request(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\n"),
+            Err(NomErr::Failure(_))
+        ));
+        assert!(matches!(
+            streaming::request(b"POST / HTTP/1.1\r\nContent-Length: +3\r\n\r\n"),
+            Err(NomErr::Failure(_))
+        ));
+    }
+
+    #[test]
+    fn test_headers_and_uri() {
+        let (rest, parsed) = headers(b"Host: a\r\n\r\n\x80").unwrap();
+        assert_eq!((rest, parsed.get("host")), (&b"\x80"[..], Some("a")));
+        let (rest, parsed) = uri(b"http://example.org/a\xff").unwrap();
+        assert_eq!(rest, b"\xff");
+        assert_eq!(parsed.path_segments(), ["a"]);
+    }
+}
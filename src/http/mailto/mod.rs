@@ -0,0 +1,137 @@
+//! `mailto:` URIs, as in RFC 6068. The generic URI parser sees a path and a
+//! query; this gives the recipients and header fields they stand for.
+
+use nom::{
+    bytes::complete::{tag_no_case, take_while},
+    character::complete::char,
+    combinator::opt,
+    error::{context, VerboseError, VerboseErrorKind},
+    sequence::{pair, preceded},
+    Err as NomErr, IResult,
+};
+
+use super::percent_decode;
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+/// A decoded `mailto:` URI. Recipients given as `to`, `cc` and `bcc`
+/// fields are in their lists rather than `headers`, which holds any other
+/// fields in order.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Mailto {
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub subject: Option<String>,
+    pub body: Option<String>,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Mailto {
+    /// The first other header field called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Splits a comma-separated list of addresses, decoding each. A `%2C` in
+/// one stays part of it.
+fn addresses<'a>(
+    input: &'a str,
+    list: &'a str,
+) -> Result<Vec<String>, NomErr<VerboseError<&'a str>>> {
+    list.split(',')
+        .filter(|address| !address.is_empty())
+        .map(|address| {
+            let decoded = percent_decode(address).into_owned();
+            match decoded.rsplit_once('@') {
+                Some((local, domain)) if !local.is_empty() && !domain.is_empty() => Ok(decoded),
+                _ => Err(failure(input, "an address with a local part and a domain")),
+            }
+        })
+        .collect()
+}
+
+/// Parses a `mailto:` URI, up to a `#` or whitespace. `+` is a plus sign
+/// here, not a space.
+pub fn mailto(input: &str) -> Res<&str, Mailto> {
+    let uri_text = |c: char| c != '#' && !c.is_whitespace();
+    let (next_input, (to, fields)) = context(
+        "mailto",
+        preceded(
+            tag_no_case("mailto:"),
+            pair(
+                take_while(move |c| c != '?' && uri_text(c)),
+                opt(preceded(char('?'), take_while(uri_text))),
+            ),
+        ),
+    )(input)?;
+    let mut mailto = Mailto {
+        to: addresses(input, to)?,
+        ..Mailto::default()
+    };
+    for field in fields.unwrap_or("").split('&').filter(|f| !f.is_empty()) {
+        let (name, value) = field.split_once('=').unwrap_or((field, ""));
+        let name = percent_decode(name);
+        match name.to_ascii_lowercase().as_str() {
+            "to" => mailto.to.extend(addresses(input, value)?),
+            "cc" => mailto.cc.extend(addresses(input, value)?),
+            "bcc" => mailto.bcc.extend(addresses(input, value)?),
+            "subject" => mailto.subject = Some(percent_decode(value).into_owned()),
+            "body" => mailto.body = Some(percent_decode(value).into_owned()),
+            _ => mailto
+                .headers
+                .push((name.into_owned(), percent_decode(value).into_owned())),
+        }
+    }
+    Ok((next_input, mailto))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mailto() {
+        let (rest, parsed) = mailto(
+            "MAILTO:joe@example.com,%22not%2Cme%22@example.org?cc=bob@example.com\
+             &subject=Hello%20there%3F&body=a+b%0D%0Ac&In-Reply-To=%3C3469A91.D10AF4C@example.com%3E&to=al@example.net end",
+        )
+        .unwrap();
+        assert_eq!(rest, " end");
+        assert_eq!(
+            parsed.to,
+            [
+                "joe@example.com",
+                "\"not,me\"@example.org",
+                "al@example.net"
+            ]
+        );
+        assert_eq!(parsed.cc, ["bob@example.com"]);
+        assert!(parsed.bcc.is_empty());
+        assert_eq!(parsed.subject.as_deref(), Some("Hello there?"));
+        assert_eq!(parsed.body.as_deref(), Some("a+b\r\nc"));
+        assert_eq!(
+            parsed.header("in-reply-to"),
+            Some("<3469A91.D10AF4C@example.com>")
+        );
+
+        let (_, parsed) = mailto("mailto:?to=a@b.c&keywords=x").unwrap();
+        assert_eq!(parsed.to, ["a@b.c"]);
+        assert_eq!(parsed.headers, [("keywords".to_string(), "x".to_string())]);
+        assert_eq!(mailto("mailto:").unwrap().1, Mailto::default());
+
+        assert!(mailto("mailto:nobody").is_err());
+        assert!(mailto("mailto:?cc=@example.com").is_err());
+        assert!(mailto("http://example.com").is_err());
+    }
+}
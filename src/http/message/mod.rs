@@ -0,0 +1,535 @@
+//! HTTP/1.1 request and response messages, as in RFC 7230.
+
+use std::{borrow::Cow, fmt};
+
+use nom::{
+    bytes::complete::{tag, take_while, take_while1, take_while_m_n},
+    character::complete::{char, line_ending, satisfy},
+    combinator::{cut, map},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::many0,
+    sequence::{delimited, pair, separated_pair, terminated, tuple},
+    Err as NomErr, IResult, Needed,
+};
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HTTP/{}.{}", self.major, self.minor)
+    }
+}
+
+/// A request. The body is text, or bytes when parsed by `http::bytes`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Request<'a, B: ?Sized = str> {
+    pub method: &'a str,
+    /// The request target as sent: usually a path and query, but possibly
+    /// an absolute URI, an authority for `CONNECT`, or `*`.
+    pub target: &'a str,
+    pub version: Version,
+    pub headers: HeaderMap<'a>,
+    pub body: &'a B,
+}
+
+/// A response. The body is text, or bytes when parsed by `http::bytes`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Response<'a, B: ?Sized = str> {
+    pub version: Version,
+    pub status: u16,
+    pub reason: &'a str,
+    pub headers: HeaderMap<'a>,
+    pub body: &'a B,
+}
+
+// Derived, these would need `B: Clone`, which unsized bodies aren't.
+impl<B: ?Sized> Clone for Request<'_, B> {
+    fn clone(&self) -> Self {
+        Request {
+            headers: self.headers.clone(),
+            ..*self
+        }
+    }
+}
+
+impl<B: ?Sized> Clone for Response<'_, B> {
+    fn clone(&self) -> Self {
+        Response {
+            headers: self.headers.clone(),
+            ..*self
+        }
+    }
+}
+
+/// Header fields in the order they came, looked up by name ignoring case.
+/// A name can appear more than once.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct HeaderMap<'a> {
+    fields: Vec<(&'a str, Cow<'a, str>)>,
+}
+
+impl<'a> HeaderMap<'a> {
+    pub fn new() -> Self {
+        HeaderMap::default()
+    }
+
+    /// Adds a field after the others, keeping any with the same name.
+    pub fn append(&mut self, name: &'a str, value: impl Into<Cow<'a, str>>) {
+        self.fields.push((name, value.into()));
+    }
+
+    /// The value of the first field called `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_ref())
+    }
+
+    /// The values of every field called `name`, in order.
+    pub fn get_all<'m>(&'m self, name: &'m str) -> impl Iterator<Item = &'m str> + 'm {
+        self.fields
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_ref())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Every field as sent, names in their original case.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &str)> + '_ {
+        self.fields
+            .iter()
+            .map(|(name, value)| (*name, value.as_ref()))
+    }
+}
+
+impl<B: ?Sized> Request<'_, B> {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+}
+
+impl<B: ?Sized> Response<'_, B> {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+}
+
+fn is_tchar(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
+/// An RFC 7230 token, such as a method or header name.
+pub(crate) fn token(input: &str) -> Res<&str, &str> {
+    take_while1(is_tchar)(input)
+}
+
+fn digit(input: &str) -> Res<&str, u8> {
+    map(satisfy(|c| c.is_ascii_digit()), |c| c as u8 - b'0')(input)
+}
+
+fn version(input: &str) -> Res<&str, Version> {
+    context(
+        "version",
+        map(
+            pair(tag("HTTP/"), cut(separated_pair(digit, char('.'), digit))),
+            |(_, (major, minor))| Version { major, minor },
+        ),
+    )(input)
+}
+
+fn is_ows(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn field_line(input: &str) -> Res<&str, &str> {
+    terminated(take_while(|c| c != '\r' && c != '\n'), line_ending)(input)
+        .map(|(next_input, line)| (next_input, line.trim_matches(is_ows)))
+}
+
+/// One `name: value` field, with the whitespace around the value removed.
+/// Lines folded onto the next (obs-fold) are joined with a space when
+/// `lenient`, and an error otherwise.
+fn header(lenient: bool) -> impl Fn(&str) -> Res<&str, (&str, Cow<'_, str>)> {
+    move |input| {
+        let (mut input, (name, value)) = context(
+            "header",
+            separated_pair(
+                token,
+                cut(context("`:` right after the name", char(':'))),
+                field_line,
+            ),
+        )(input)?;
+        let mut value = Cow::Borrowed(value);
+        while input.starts_with(is_ows) {
+            if !lenient {
+                return Err(failure(input, "a header on one line"));
+            }
+            let (next_input, more) = field_line(input)?;
+            input = next_input;
+            value = match (value.is_empty(), more.is_empty()) {
+                (_, true) => value,
+                (true, false) => Cow::Borrowed(more),
+                (false, false) => Cow::Owned(format!("{} {}", value, more)),
+            };
+        }
+        Ok((input, (name, value)))
+    }
+}
+
+fn header_block(lenient: bool) -> impl Fn(&str) -> Res<&str, HeaderMap<'_>> {
+    move |input| {
+        context(
+            "headers",
+            terminated(
+                many0(header(lenient)),
+                cut(context("end of headers", line_ending)),
+            ),
+        )(input)
+        .map(|(next_input, fields)| (next_input, HeaderMap { fields }))
+    }
+}
+
+/// Parses header fields up to and including the empty line after them.
+/// Names must be RFC 7230 tokens, and a field folded over several lines is
+/// an error.
+pub fn headers(input: &str) -> Res<&str, HeaderMap<'_>> {
+    header_block(false)(input)
+}
+
+/// Like `headers`, but joins folded fields with a space, as RFC 7230
+/// allows a user agent to.
+pub fn headers_lenient(input: &str) -> Res<&str, HeaderMap<'_>> {
+    header_block(true)(input)
+}
+
+/// Whether `input` holds the empty line that ends a message's head.
+pub(crate) fn has_head(input: &[u8]) -> bool {
+    input.starts_with(b"\n")
+        || input.starts_with(b"\r\n")
+        || input.windows(2).any(|w| w == b"\n\n")
+        || input.windows(3).any(|w| w == b"\n\r\n")
+}
+
+/// How long the body after a message's head is, as in RFC 7230 section
+/// 3.3.3: `Some` for a known length and `None` when it runs until the
+/// connection closes. `status` is `None` for a request, whose body is
+/// empty without a `Content-Length`.
+///
+/// Bodies are borrowed from the input, so chunks can't be joined into one
+/// and any `Transfer-Encoding` is an error, as are a `Content-Length` given
+/// more than once and one that isn't all digits. Guessing at any of these
+/// would let the next message start somewhere a peer doesn't expect.
+pub(crate) fn body_length(
+    headers: &HeaderMap,
+    status: Option<u16>,
+) -> Result<Option<usize>, &'static str> {
+    if matches!(status, Some(100..=199 | 204 | 304)) {
+        return Ok(Some(0));
+    }
+    if headers.contains("transfer-encoding") {
+        return Err("a message without Transfer-Encoding");
+    }
+    let mut lengths = headers.get_all("content-length");
+    let Some(length) = lengths.next() else {
+        return Ok(status.map_or(Some(0), |_| None));
+    };
+    if lengths.next().is_some() {
+        return Err("a single Content-Length");
+    }
+    if length.is_empty() || !length.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("a valid Content-Length");
+    }
+    length
+        .parse()
+        .map(Some)
+        .map_err(|_| "a valid Content-Length")
+}
+
+/// Splits the body of a message off the `input` after its head, returning
+/// it and what follows.
+pub(crate) fn split_body<'a>(
+    input: &'a [u8],
+    headers: &HeaderMap,
+    status: Option<u16>,
+) -> Result<(&'a [u8], &'a [u8]), &'static str> {
+    match body_length(headers, status)? {
+        None => Ok((input, &input[input.len()..])),
+        Some(length) if length <= input.len() => Ok(input.split_at(length)),
+        Some(_) => Err("a body as long as its Content-Length"),
+    }
+}
+
+/// How much more input a streaming parser needs for the body after a
+/// head, or `None` once it's all there. Framing errors are left for the
+/// complete parser to report.
+pub(crate) fn body_needed(rest: &[u8], headers: &HeaderMap, status: Option<u16>) -> Option<Needed> {
+    match body_length(headers, status) {
+        Ok(Some(length)) => length
+            .checked_sub(rest.len())
+            .filter(|&n| n > 0)
+            .map(Needed::new),
+        Ok(None) => Some(Needed::Unknown),
+        Err(_) => None,
+    }
+}
+
+/// `split_body` over text. The body must end on a character boundary.
+fn body<'a>(input: &'a str, headers: &HeaderMap, status: Option<u16>) -> Res<&'a str, &'a str> {
+    let (body, _) =
+        split_body(input.as_bytes(), headers, status).map_err(|reason| failure(input, reason))?;
+    match (input.get(..body.len()), input.get(body.len()..)) {
+        (Some(body), Some(rest)) => Ok((rest, body)),
+        _ => Err(failure(input, "a body as long as its Content-Length")),
+    }
+}
+
+pub(crate) fn request_line(input: &str) -> Res<&str, (&str, &str, Version)> {
+    context(
+        "request line",
+        tuple((
+            terminated(token, char(' ')),
+            terminated(take_while1(|c: char| c.is_ascii_graphic()), char(' ')),
+            terminated(version, line_ending),
+        )),
+    )(input)
+}
+
+/// Parses a request. The body is `Content-Length` bytes long, or empty
+/// without that header; anything after it is left for the next message.
+/// A request with a `Transfer-Encoding`, such as `chunked`, is an error.
+pub fn request(input: &str) -> Res<&str, Request<'_>> {
+    let (input, ((method, target, version), headers)) =
+        context("request", pair(request_line, headers))(input)?;
+    let (input, body) = body(input, &headers, None)?;
+    let request = Request {
+        method,
+        target,
+        version,
+        headers,
+        body,
+    };
+    Ok((input, request))
+}
+
+fn status(input: &str) -> Res<&str, u16> {
+    let (next_input, code) =
+        context("status", take_while_m_n(3, 3, |c: char| c.is_ascii_digit()))(input)?;
+    match code.parse() {
+        Ok(code @ 100..=599) => Ok((next_input, code)),
+        _ => Err(failure(input, "a status between 100 and 599")),
+    }
+}
+
+pub(crate) fn status_line(input: &str) -> Res<&str, (Version, u16, &str)> {
+    context(
+        "status line",
+        tuple((
+            terminated(version, char(' ')),
+            cut(status),
+            delimited(
+                // The space before an empty reason is often left out.
+                take_while_m_n(0, 1, |c| c == ' '),
+                take_while(|c| c != '\r' && c != '\n'),
+                line_ending,
+            ),
+        )),
+    )(input)
+}
+
+/// Parses a response. Responses that can't have a body (1xx, 204 and 304)
+/// get an empty one; otherwise it is `Content-Length` bytes long, or the
+/// rest of the input when the length isn't given, as when the server closes
+/// the connection to end it. As for requests, `Transfer-Encoding` is an
+/// error.
+pub fn response(input: &str) -> Res<&str, Response<'_>> {
+    let (input, ((version, status, reason), headers)) =
+        context("response", pair(status_line, headers))(input)?;
+    let (input, body) = body(input, &headers, Some(status))?;
+    let response = Response {
+        version,
+        status,
+        reason,
+        headers,
+        body,
+    };
+    Ok((input, response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request() {
+        let input = "POST /submit?x=1 HTTP/1.1\r\n\
+                     Host: example.org\r\n\
+                     content-length:  5 \r\n\
+                     \r\n\
+                     helloGET / HTTP/1.0\r\n\r\n";
+        let (rest, request) = request(input).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.target, "/submit?x=1");
+        assert_eq!(request.version, Version { major: 1, minor: 1 });
+        assert_eq!(request.header("HOST"), Some("example.org"));
+        assert_eq!(request.body, "hello");
+
+        let (rest, request) = super::request(rest).unwrap();
+        assert_eq!((rest, request.method, request.body), ("", "GET", ""));
+        assert_eq!(request.version.to_string(), "HTTP/1.0");
+
+        assert!(super::request("GET / HTTP/1.1\r\nContent-Length: 9\r\n\r\nshort").is_err());
+        assert!(super::request("GET / HTTP/1.1\r\nBad Header: x\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn test_framing() {
+        for (head, reason) in [
+            (
+                "Transfer-Encoding: chunked",
+                "a message without Transfer-Encoding",
+            ),
+            (
+                "Content-Length: 5\r\nTransfer-Encoding: chunked",
+                "a message without Transfer-Encoding",
+            ),
+            (
+                "Content-Length: 5\r\nContent-Length: 6",
+                "a single Content-Length",
+            ),
+            (
+                "Content-Length: 5\r\nContent-Length: 5",
+                "a single Content-Length",
+            ),
+            ("Content-Length: +5", "a valid Content-Length"),
+            ("Content-Length: 5, 5", "a valid Content-Length"),
+            ("Content-Length:", "a valid Content-Length"),
+        ] {
+            let input = format!("POST / HTTP/1.1\r\n{}\r\n\r\n5\r\nhello\r\n0\r\n\r\n", head);
+            let Err(NomErr::Failure(error)) = request(&input) else {
+                panic!("expected a failure for {:?}", head);
+            };
+            assert_eq!(
+                error.errors[0].1,
+                VerboseErrorKind::Context(reason),
+                "{:?}",
+                head
+            );
+        }
+        let chunked = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhi\r\n0\r\n\r\n";
+        assert!(response(chunked).is_err());
+        let (_, response) =
+            super::response("HTTP/1.1 304 Not Modified\r\nTransfer-Encoding: chunked\r\n\r\n")
+                .unwrap();
+        assert_eq!(response.body, "");
+    }
+
+    #[test]
+    fn test_headers() {
+        let input =
+            "Accept: text/html\r\nSet-Cookie: a=1\r\nset-cookie:b=2 \r\nX-Empty:\r\n\r\nbody";
+        let (rest, headers) = headers(input).unwrap();
+        assert_eq!(rest, "body");
+        assert_eq!(headers.len(), 4);
+        assert_eq!(headers.get("ACCEPT"), Some("text/html"));
+        assert_eq!(
+            headers.get_all("Set-Cookie").collect::<Vec<_>>(),
+            ["a=1", "b=2"]
+        );
+        assert_eq!(headers.get("x-empty"), Some(""));
+        assert!(!headers.contains("Host"));
+        assert_eq!(headers.iter().nth(2), Some(("set-cookie", "b=2")));
+
+        let folded = "X-Long: a\r\n  b\r\n\tc\r\n\r\n";
+        assert_eq!(
+            super::headers(folded),
+            Err(NomErr::Failure(VerboseError {
+                errors: vec![
+                    (
+                        "  b\r\n\tc\r\n\r\n",
+                        VerboseErrorKind::Context("a header on one line")
+                    ),
+                    (folded, VerboseErrorKind::Context("headers")),
+                ]
+            }))
+        );
+        let (_, headers) = headers_lenient(folded).unwrap();
+        assert_eq!(headers.get("x-long"), Some("a b c"));
+
+        for invalid in ["Bad Name: x\r\n\r\n", "Name : x\r\n\r\n", "(x): y\r\n\r\n"] {
+            assert!(
+                matches!(super::headers(invalid), Err(NomErr::Failure(_))),
+                "{}",
+                invalid
+            );
+        }
+        assert!(super::headers("A: b\r\n").is_err());
+
+        let mut map = HeaderMap::new();
+        map.append("Vary", "a");
+        map.append("vary", String::from("b"));
+        assert_eq!(map.get_all("VARY").count(), 2);
+    }
+
+    #[test]
+    fn test_response() {
+        let input = "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\r\nhi";
+        let (rest, response) = response(input).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!((response.status, response.reason), (200, "OK"));
+        assert_eq!(response.header("content-type"), Some("text/plain"));
+        assert_eq!(response.body, "hi");
+
+        let (_, response) = super::response("HTTP/1.0 404 Not Found\n\nuntil close").unwrap();
+        assert_eq!(response.reason, "Not Found");
+        assert_eq!(response.body, "until close");
+
+        let (rest, response) = super::response("HTTP/1.1 204\r\n\r\nHTTP/1.1").unwrap();
+        assert_eq!((response.reason, response.body, rest), ("", "", "HTTP/1.1"));
+
+        assert_eq!(
+            super::response("HTTP/1.1 600 Nope\r\n\r\n"),
+            Err(NomErr::Failure(VerboseError {
+                errors: vec![
+                    (
+                        "600 Nope\r\n\r\n",
+                        VerboseErrorKind::Context("a status between 100 and 599")
+                    ),
+                    (
+                        "HTTP/1.1 600 Nope\r\n\r\n",
+                        VerboseErrorKind::Context("status line")
+                    ),
+                    (
+                        "HTTP/1.1 600 Nope\r\n\r\n",
+                        VerboseErrorKind::Context("response")
+                    ),
+                ]
+            }))
+        );
+        assert!(super::response("HTTP/1.1 099 Low\r\n\r\n").is_err());
+        assert!(super::response("HTTP/1.1 20 OK\r\n\r\n").is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! `multipart/form-data` and other multipart bodies, as in RFC 2046 and
+//! RFC 7578. Payloads are bytes, since a part can be a file of any kind.
+
+use std::borrow::Cow;
+
+use nom::{
+    error::{VerboseError, VerboseErrorKind},
+    Err as NomErr, IResult,
+};
+
+use super::header::{content_disposition, media_type, ContentDisposition, MediaType};
+use super::{bytes, HeaderMap};
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a [u8], reason: &'static str) -> NomErr<VerboseError<&'a [u8]>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+/// One part of a multipart body.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Part<'a> {
+    pub headers: HeaderMap<'a>,
+    pub body: &'a [u8],
+}
+
+impl<'a> Part<'a> {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// The part's `Content-Type`. RFC 7578 makes it `text/plain` when
+    /// absent, which is left to the caller.
+    pub fn content_type(&self) -> Option<MediaType<'_>> {
+        match media_type(self.header("content-type")?) {
+            Ok((_, media)) => Some(media),
+            Err(_) => None,
+        }
+    }
+
+    pub fn content_disposition(&self) -> Option<ContentDisposition<'_>> {
+        match content_disposition(self.header("content-disposition")?) {
+            Ok((_, disposition)) => Some(disposition),
+            Err(_) => None,
+        }
+    }
+
+    /// The form field's name, from the `Content-Disposition`.
+    pub fn name(&self) -> Option<Cow<'_, str>> {
+        self.content_disposition()?.name()
+    }
+
+    /// The name of the file uploaded in this part, if it is one, preferring
+    /// an RFC 5987 `filename*`.
+    pub fn filename(&self) -> Option<Cow<'_, str>> {
+        self.content_disposition()?.filename()
+    }
+
+    /// The parts of a nested `multipart/mixed` body, which older clients
+    /// send for a field holding several files. Each inner part gives its
+    /// file's name as a `Content-Disposition: file` parameter.
+    pub fn parts(&self) -> Option<Vec<Part<'a>>> {
+        let media = self.content_type()?;
+        if !media.type_.eq_ignore_ascii_case("multipart") {
+            return None;
+        }
+        let (_, parts) = multipart_body(self.body, media.param("boundary")?.as_bytes()).ok()?;
+        Some(parts)
+    }
+}
+
+/// Parses a multipart body whose parts are separated by `boundary`, the
+/// parameter of the same name on its `Content-Type`. The preamble before
+/// the first boundary and the epilogue after the last are skipped.
+pub fn multipart(boundary: &str) -> impl Fn(&[u8]) -> Res<&[u8], Vec<Part<'_>>> + '_ {
+    move |input| multipart_body(input, boundary.as_bytes())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn multipart_body<'a>(input: &'a [u8], boundary: &[u8]) -> Res<&'a [u8], Vec<Part<'a>>> {
+    let delimiter = [b"--", boundary].concat();
+    // Every delimiter but one opening the body starts on a new line, and
+    // the line ending before it belongs to the delimiter, not the part.
+    let newline = [b"\n", &delimiter[..]].concat();
+    let mut at = if input.starts_with(&delimiter) {
+        delimiter.len()
+    } else {
+        match find(input, &newline) {
+            Some(start) => start + newline.len(),
+            None => return Err(failure(input, "a multipart boundary")),
+        }
+    };
+    let mut parts = Vec::new();
+    loop {
+        let rest = &input[at..];
+        if rest.starts_with(b"--") {
+            return Ok((&input[input.len()..], parts));
+        }
+        let padding = rest
+            .iter()
+            .take_while(|&&b| b == b' ' || b == b'\t')
+            .count();
+        let rest = &rest[padding..];
+        let rest = rest.strip_prefix(b"\r").unwrap_or(rest);
+        let Some(rest) = rest.strip_prefix(b"\n") else {
+            return Err(failure(rest, "a line ending after the boundary"));
+        };
+        let (rest, headers) = bytes::headers(rest)?;
+        let Some(end) = find(rest, &newline) else {
+            return Err(failure(rest, "a closing multipart boundary"));
+        };
+        let body = &rest[..end];
+        parts.push(Part {
+            headers,
+            body: body.strip_suffix(b"\r").unwrap_or(body),
+        });
+        at = input.len() - rest.len() + end + newline.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multipart() {
+        let body = b"preamble\r\n--XyZ\r\n\
+            Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+            Hello\r\n--XyZ  \r\n\
+            Content-Disposition: form-data; name=\"upload\"; filename=\"a b.png\"\r\n\
+            Content-Type: image/png\r\n\r\n\
+            \x89PNG\r\n\x00\r\n--XyZ--\r\nepilogue";
+        let (rest, parts) = multipart("XyZ")(body).unwrap();
+        assert_eq!(rest, b"");
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name().as_deref(), Some("title"));
+        assert_eq!(parts[0].body, b"Hello");
+        assert_eq!(parts[0].filename(), None);
+        assert_eq!(parts[1].filename().as_deref(), Some("a b.png"));
+        assert!(parts[1].content_type().unwrap().is("image", "png"));
+        assert_eq!(parts[1].body, b"\x89PNG\r\n\x00");
+
+        let (_, parts) = multipart("b")(b"--b\r\n\r\n\r\n--b--").unwrap();
+        assert_eq!((parts[0].headers.len(), parts[0].body), (0, &b""[..]));
+        assert!(multipart("b")(b"no boundary here").is_err());
+        assert!(multipart("b")(b"--b\r\n\r\nnever closed").is_err());
+        assert!(multipart("b")(b"--bx\r\n\r\n--b--").is_err());
+    }
+
+    #[test]
+    fn test_nested() {
+        let body = b"--outer\r\n\
+            Content-Disposition: form-data; name=\"files\"\r\n\
+            Content-Type: multipart/mixed; boundary=inner\r\n\r\n\
+            --inner\r\n\
+            Content-Disposition: file; filename=\"one.txt\"\r\n\r\n\
+            1\r\n--inner\r\n\
+            Content-Disposition: file; filename*=UTF-8''tw%C3%B6.txt\r\n\r\n\
+            2\r\n--inner--\r\n--outer--";
+        let (_, parts) = multipart("outer")(body).unwrap();
+        let files = parts[0].parts().unwrap();
+        let names: Vec<_> = files.iter().map(|part| part.filename().unwrap()).collect();
+        assert_eq!(names, ["one.txt", "tw\u{f6}.txt"]);
+        assert_eq!(files[1].body, b"2");
+        assert_eq!(files[0].parts(), None);
+    }
+}
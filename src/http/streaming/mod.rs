@@ -0,0 +1,119 @@
+//! Variants of the HTTP parsers that return `Err::Incomplete` until the
+//! buffer holds a whole message or URI, for reading from a socket. Errors
+//! are still reported as soon as the buffer shows one: a bad start line
+//! fails once its line ending arrives.
+
+use nom::{error::VerboseError, sequence::pair, Err as NomErr, IResult, Needed};
+
+use super::message::{body_needed, has_head, headers, request_line, status_line};
+use super::{Request, Response, URI};
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+/// Parses a request once its headers and `Content-Length` body have
+/// arrived.
+pub fn request(input: &str) -> Res<&str, Request<'_>> {
+    if !has_head(input.as_bytes()) {
+        if input.contains('\n') {
+            request_line(input)?;
+        }
+        return Err(NomErr::Incomplete(Needed::Unknown));
+    }
+    let (rest, (_, headers)) = pair(request_line, headers)(input)?;
+    if let Some(needed) = body_needed(rest.as_bytes(), &headers, None) {
+        return Err(NomErr::Incomplete(needed));
+    }
+    super::request(input)
+}
+
+/// Parses a response once its headers and body have arrived. A body without
+/// a `Content-Length` runs until the connection closes, so it's never
+/// complete here; parse it with `http::response` at end of input.
+pub fn response(input: &str) -> Res<&str, Response<'_>> {
+    if !has_head(input.as_bytes()) {
+        if input.contains('\n') {
+            status_line(input)?;
+        }
+        return Err(NomErr::Incomplete(Needed::Unknown));
+    }
+    let (rest, ((_, status, _), headers)) = pair(status_line, headers)(input)?;
+    if let Some(needed) = body_needed(rest.as_bytes(), &headers, Some(status)) {
+        return Err(NomErr::Incomplete(needed));
+    }
+    super::response(input)
+}
+
+/// Parses a URI once a character that can't be part of it, such as
+/// whitespace, shows where it ends.
+pub fn uri(input: &str) -> Res<&str, URI<'_>> {
+    let ends = |c: char| c.is_whitespace() || c.is_control() || "\"<>".contains(c);
+    if !input.contains(ends) {
+        return Err(NomErr::Incomplete(Needed::Unknown));
+    }
+    super::uri(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request() {
+        let message = "POST /a HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\n\r\nhello";
+        for end in 0..message.len() - 5 {
+            assert_eq!(
+                request(&message[..end]),
+                Err(NomErr::Incomplete(Needed::Unknown)),
+                "{:?}",
+                &message[..end]
+            );
+        }
+        let partial = &message[..message.len() - 2];
+        assert_eq!(request(partial), Err(NomErr::Incomplete(Needed::new(2))));
+        let (rest, parsed) = request(message).unwrap();
+        assert_eq!((rest, parsed.body), ("", "hello"));
+
+        assert_eq!(request("GET / HTTP/1.1\n\nGET").unwrap().0, "GET");
+        assert!(matches!(request("BAD LINE\r\n"), Err(NomErr::Error(_))));
+        assert!(matches!(
+            request("POST / HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\n"),
+            Err(NomErr::Failure(_))
+        ));
+    }
+
+    #[test]
+    fn test_response() {
+        let message = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nhi";
+        assert!(matches!(
+            response(&message[..20]),
+            Err(NomErr::Incomplete(_))
+        ));
+        assert_eq!(
+            response(&message[..message.len() - 1]),
+            Err(NomErr::Incomplete(Needed::new(1)))
+        );
+        assert_eq!(response(message).unwrap().1.body, "hi");
+
+        let until_close = "HTTP/1.0 200 OK\r\n\r\nbody";
+        assert_eq!(
+            response(until_close),
+            Err(NomErr::Incomplete(Needed::Unknown))
+        );
+        assert_eq!(response("HTTP/1.1 304 \r\n\r\n").unwrap().1.status, 304);
+        assert!(matches!(
+            response("HTTP/1.1 700 Nope\r\n"),
+            Err(NomErr::Failure(_))
+        ));
+    }
+
+    #[test]
+    fn test_uri() {
+        assert_eq!(
+            uri("http://example.org/pa"),
+            Err(NomErr::Incomplete(Needed::Unknown))
+        );
+        let (rest, parsed) = uri("http://example.org/path HTTP/1.1").unwrap();
+        assert_eq!(rest, " HTTP/1.1");
+        assert_eq!(parsed.path_segments(), ["path"]);
+    }
+}
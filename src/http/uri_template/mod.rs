@@ -0,0 +1,654 @@
+//! URI Templates, as in RFC 6570: `/users/{id}/posts{?page,limit}`
+//! expanded from variables up to level 4, and matched against a URI to get
+//! the variables back, as a router does.
+
+use std::fmt;
+
+use nom::{
+    branch::alt,
+    bytes::complete::take_while1,
+    character::complete::{char, one_of, satisfy},
+    combinator::{cut, map, map_opt, opt, recognize},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::{many0, separated_list1},
+    sequence::{pair, preceded, terminated, tuple},
+    Err as NomErr, IResult,
+};
+
+use super::{is_unreserved, percent_decode};
+use crate::json::JsonValue;
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+/// The operator at the start of an expression, which decides how its
+/// variables are joined and encoded.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Operator {
+    /// `{var}`
+    Simple,
+    /// `{+var}`, keeping reserved characters.
+    Reserved,
+    /// `{#var}`
+    Fragment,
+    /// `{.var}`
+    Label,
+    /// `{/var}`
+    Path,
+    /// `{;var}`
+    PathParam,
+    /// `{?var}`
+    Query,
+    /// `{&var}`
+    QueryContinuation,
+}
+
+impl Operator {
+    fn first(self) -> &'static str {
+        match self {
+            Operator::Simple | Operator::Reserved => "",
+            Operator::Fragment => "#",
+            Operator::Label => ".",
+            Operator::Path => "/",
+            Operator::PathParam => ";",
+            Operator::Query => "?",
+            Operator::QueryContinuation => "&",
+        }
+    }
+
+    fn separator(self) -> char {
+        match self {
+            Operator::Simple | Operator::Reserved | Operator::Fragment => ',',
+            Operator::Label => '.',
+            Operator::Path => '/',
+            Operator::PathParam => ';',
+            Operator::Query | Operator::QueryContinuation => '&',
+        }
+    }
+
+    fn is_named(self) -> bool {
+        matches!(
+            self,
+            Operator::PathParam | Operator::Query | Operator::QueryContinuation
+        )
+    }
+
+    /// What a named variable with an empty value expands to after its name.
+    fn if_empty(self) -> &'static str {
+        match self {
+            Operator::Query | Operator::QueryContinuation => "=",
+            _ => "",
+        }
+    }
+
+    fn allows_reserved(self) -> bool {
+        matches!(self, Operator::Reserved | Operator::Fragment)
+    }
+
+    /// Whether `c` can appear in this expression's expansion.
+    fn allows(self, c: char) -> bool {
+        is_unreserved(c)
+            || c == '%'
+            || match self {
+                Operator::Simple => c == ',',
+                Operator::Reserved | Operator::Fragment => is_reserved(c),
+                Operator::Label => c == ',',
+                Operator::Path => c == '/' || c == ',',
+                Operator::PathParam => ";=,".contains(c),
+                Operator::Query | Operator::QueryContinuation => "&=,".contains(c),
+            }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Modifier {
+    None,
+    /// `{var:3}`, the first 3 characters of a string.
+    Prefix(u16),
+    /// `{var*}`, each item of a list or pair of a map separately.
+    Explode,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct VarSpec<'a> {
+    pub name: &'a str,
+    pub modifier: Modifier,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Part<'a> {
+    Literal(&'a str),
+    Expression {
+        operator: Operator,
+        vars: Vec<VarSpec<'a>>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UriTemplate<'a> {
+    pub parts: Vec<Part<'a>>,
+}
+
+fn is_reserved(c: char) -> bool {
+    ":/?#[]@!$&'()*+,;=".contains(c)
+}
+
+fn pct_encoded(input: &str) -> Res<&str, &str> {
+    recognize(tuple((
+        char('%'),
+        satisfy(|c| c.is_ascii_hexdigit()),
+        satisfy(|c| c.is_ascii_hexdigit()),
+    )))(input)
+}
+
+fn literal(input: &str) -> Res<&str, &str> {
+    take_while1(|c: char| {
+        !c.is_ascii_control() && !c.is_ascii_whitespace() && !"\"'<>\\^`{|}".contains(c)
+    })(input)
+}
+
+fn varname(input: &str) -> Res<&str, &str> {
+    let varchars = || {
+        recognize(many0(alt((
+            take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+            pct_encoded,
+        ))))
+    };
+    let (rest, name) = recognize(pair(varchars(), many0(pair(char('.'), varchars()))))(input)?;
+    if name.is_empty() || name.starts_with('.') || name.ends_with('.') || name.contains("..") {
+        return Err(NomErr::Error(VerboseError {
+            errors: vec![(input, VerboseErrorKind::Context("variable name"))],
+        }));
+    }
+    Ok((rest, name))
+}
+
+fn varspec(input: &str) -> Res<&str, VarSpec<'_>> {
+    let prefix = map_opt(
+        take_while1(|c: char| c.is_ascii_digit()),
+        |length: &str| match length.parse() {
+            Ok(length @ 1..=9999) if !length.to_string().starts_with('0') => {
+                Some(Modifier::Prefix(length))
+            }
+            _ => None,
+        },
+    );
+    let modifier = alt((
+        preceded(char(':'), cut(prefix)),
+        map(char('*'), |_| Modifier::Explode),
+    ));
+    map(pair(varname, opt(modifier)), |(name, modifier)| VarSpec {
+        name,
+        modifier: modifier.unwrap_or(Modifier::None),
+    })(input)
+}
+
+fn expression(input: &str) -> Res<&str, Part<'_>> {
+    let operator = map(one_of("+#./;?&"), |c| match c {
+        '+' => Operator::Reserved,
+        '#' => Operator::Fragment,
+        '.' => Operator::Label,
+        '/' => Operator::Path,
+        ';' => Operator::PathParam,
+        '?' => Operator::Query,
+        _ => Operator::QueryContinuation,
+    });
+    let (after_brace, _) = char('{')(input)?;
+    if after_brace.starts_with(|c| "=,!@|".contains(c)) {
+        return Err(failure(after_brace, "an operator RFC 6570 defines"));
+    }
+    context(
+        "expression",
+        cut(terminated(
+            pair(opt(operator), separated_list1(char(','), varspec)),
+            char('}'),
+        )),
+    )(after_brace)
+    .map(|(next_input, (operator, vars))| {
+        let operator = operator.unwrap_or(Operator::Simple);
+        (next_input, Part::Expression { operator, vars })
+    })
+}
+
+/// Parses a template. Parsing stops at a character that can't appear in
+/// one, such as whitespace or an unmatched `}`.
+pub fn uri_template(input: &str) -> Res<&str, UriTemplate<'_>> {
+    context(
+        "uri template",
+        many0(alt((map(literal, Part::Literal), expression))),
+    )(input)
+    .map(|(next_input, parts)| (next_input, UriTemplate { parts }))
+}
+
+/// Encodes `text`, leaving unreserved characters and, if `allow_reserved`,
+/// reserved characters and escapes as they are.
+fn encode(text: &str, allow_reserved: bool, out: &mut String) {
+    for (i, c) in text.char_indices() {
+        let escape = allow_reserved && pct_encoded(&text[i..]).is_ok();
+        if is_unreserved(c) || (allow_reserved && is_reserved(c)) || escape {
+            out.push(c);
+        } else {
+            let mut buffer = [0; 4];
+            for byte in c.encode_utf8(&mut buffer).bytes() {
+                out.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+}
+
+/// The text of a value that isn't a list or map.
+fn scalar(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::String(s) => Some(s.clone()),
+        JsonValue::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => Some((*n as i64).to_string()),
+        JsonValue::Number(n) => Some(n.to_string()),
+        JsonValue::Decimal(n) => Some(n.to_string()),
+        JsonValue::Boolean(b) => Some(b.to_string()),
+        JsonValue::Null | JsonValue::Array(_) | JsonValue::Object(_) => None,
+    }
+}
+
+fn lookup<'v>(vars: &'v JsonValue, name: &str) -> Option<&'v JsonValue> {
+    let JsonValue::Object(pairs) = vars else {
+        return None;
+    };
+    let value = pairs.iter().find(|(key, _)| key == name).map(|(_, v)| v)?;
+    match value {
+        JsonValue::Null => None,
+        JsonValue::Array(items) if items.is_empty() => None,
+        JsonValue::Object(pairs) if pairs.is_empty() => None,
+        value => Some(value),
+    }
+}
+
+/// Expands one defined variable.
+fn expand_var(operator: Operator, spec: &VarSpec, value: &JsonValue) -> String {
+    let reserved = operator.allows_reserved();
+    let named = operator.is_named();
+    let separator = operator.separator();
+    let mut out = String::new();
+    let name_and = |out: &mut String, empty: bool| {
+        out.push_str(spec.name);
+        out.push_str(if empty { operator.if_empty() } else { "=" });
+    };
+    let explode = spec.modifier == Modifier::Explode;
+    match value {
+        JsonValue::Array(items) => {
+            if named && !explode {
+                name_and(&mut out, false);
+            }
+            let items = items.iter().filter_map(scalar);
+            for (i, item) in items.enumerate() {
+                if i > 0 {
+                    out.push(if explode { separator } else { ',' });
+                }
+                if named && explode {
+                    name_and(&mut out, item.is_empty());
+                }
+                encode(&item, reserved, &mut out);
+            }
+        }
+        JsonValue::Object(pairs) => {
+            if named && !explode {
+                name_and(&mut out, false);
+            }
+            let pairs = pairs
+                .iter()
+                .filter_map(|(key, value)| Some((key, scalar(value)?)));
+            for (i, (key, value)) in pairs.enumerate() {
+                if i > 0 {
+                    out.push(if explode { separator } else { ',' });
+                }
+                encode(key, reserved, &mut out);
+                out.push(if explode { '=' } else { ',' });
+                encode(&value, reserved, &mut out);
+            }
+        }
+        value => {
+            let text = scalar(value).unwrap_or_default();
+            let text = match spec.modifier {
+                Modifier::Prefix(length) => text.chars().take(length as usize).collect(),
+                _ => text,
+            };
+            if named {
+                name_and(&mut out, text.is_empty());
+            }
+            encode(&text, reserved, &mut out);
+        }
+    }
+    out
+}
+
+/// A matched value: a list when a comma joined several, which only an
+/// expanded list can contain unencoded.
+fn matched_value(operator: Operator, text: &str) -> JsonValue {
+    if !operator.allows_reserved() && text.contains(',') {
+        return JsonValue::Array(
+            text.split(',')
+                .map(|item| JsonValue::String(percent_decode(item).into_owned()))
+                .collect(),
+        );
+    }
+    JsonValue::String(percent_decode(text).into_owned())
+}
+
+fn set(vars: &mut Vec<(String, JsonValue)>, name: &str, value: JsonValue) {
+    vars.push((percent_decode(name).into_owned(), value));
+}
+
+/// Reads the variables of one expression back out of its expansion.
+fn match_expression(
+    operator: Operator,
+    specs: &[VarSpec],
+    expansion: &str,
+    vars: &mut Vec<(String, JsonValue)>,
+) {
+    let separator = operator.separator();
+    if operator.is_named() {
+        let explode = specs.iter().find(|spec| spec.modifier == Modifier::Explode);
+        let mut exploded = Vec::new();
+        for item in expansion.split(separator).filter(|item| !item.is_empty()) {
+            let (name, value) = item.split_once('=').unwrap_or((item, ""));
+            let Some(spec) = specs.iter().find(|spec| spec.name == name) else {
+                let value = JsonValue::String(percent_decode(value).into_owned());
+                exploded.push((percent_decode(name).into_owned(), value));
+                continue;
+            };
+            match spec.modifier {
+                Modifier::Explode => {
+                    let value = JsonValue::String(percent_decode(value).into_owned());
+                    match vars.iter_mut().find(|(key, _)| key == name) {
+                        Some((_, JsonValue::Array(items))) => items.push(value),
+                        _ => set(vars, name, JsonValue::Array(vec![value])),
+                    }
+                }
+                _ => set(vars, name, matched_value(operator, value)),
+            }
+        }
+        if let (Some(spec), false) = (explode, exploded.is_empty()) {
+            set(vars, spec.name, JsonValue::Object(exploded));
+        }
+        return;
+    }
+    if let [spec] = specs {
+        if spec.modifier != Modifier::Explode {
+            set(vars, spec.name, matched_value(operator, expansion));
+            return;
+        }
+    }
+    let mut items = expansion.split(separator);
+    for spec in specs {
+        if spec.modifier == Modifier::Explode {
+            let rest = items
+                .by_ref()
+                .map(|item| JsonValue::String(percent_decode(item).into_owned()))
+                .collect();
+            set(vars, spec.name, JsonValue::Array(rest));
+        } else if let Some(item) = items.next() {
+            set(vars, spec.name, matched_value(operator, item));
+        }
+    }
+}
+
+impl UriTemplate<'_> {
+    /// Expands the template with the variables of `vars`, a JSON object.
+    /// Strings, numbers and booleans are values, arrays are lists and
+    /// objects are maps; a missing or `null` variable, or an empty list or
+    /// map, is undefined and leaves nothing behind.
+    pub fn expand(&self, vars: &JsonValue) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            let (operator, specs) = match part {
+                Part::Literal(text) => {
+                    encode(text, true, &mut out);
+                    continue;
+                }
+                Part::Expression { operator, vars } => (*operator, vars),
+            };
+            let expansions: Vec<_> = specs
+                .iter()
+                .filter_map(|spec| Some(expand_var(operator, spec, lookup(vars, spec.name)?)))
+                .collect();
+            if !expansions.is_empty() {
+                out.push_str(operator.first());
+                out.push_str(&expansions.join(&operator.separator().to_string()));
+            }
+        }
+        out
+    }
+
+    /// Matches `uri` against the template, giving back the variables that
+    /// expansion would have needed to produce it as a JSON object. Lists
+    /// come back as arrays, and query parameters the template doesn't name
+    /// are ignored unless a `{?var*}` collects them. An expression ends at
+    /// the first character its expansion couldn't contain, at the literal
+    /// after it, or at the last place the expression after it could start,
+    /// so `{/path*}{.ext}` splits off the last extension. Two simple
+    /// expressions in a row can't be told apart; the first takes
+    /// everything.
+    pub fn matches(&self, uri: &str) -> Option<JsonValue> {
+        let mut vars = Vec::new();
+        let mut rest = uri;
+        for (i, part) in self.parts.iter().enumerate() {
+            let (operator, specs) = match part {
+                Part::Literal(text) => {
+                    rest = rest.strip_prefix(text)?;
+                    continue;
+                }
+                Part::Expression { operator, vars } => (*operator, vars),
+            };
+            let Some(after_first) = rest.strip_prefix(operator.first()) else {
+                continue;
+            };
+            let mut end = after_first
+                .find(|c| !operator.allows(c))
+                .unwrap_or(after_first.len());
+            let before_next = match self.parts.get(i + 1) {
+                Some(Part::Literal(next)) => after_first[..end].find(next),
+                Some(Part::Expression { operator, .. }) if !operator.first().is_empty() => {
+                    after_first[..end].rfind(operator.first())
+                }
+                _ => None,
+            };
+            end = before_next.unwrap_or(end);
+            match_expression(operator, specs, &after_first[..end], &mut vars);
+            rest = &after_first[end..];
+        }
+        rest.is_empty().then_some(JsonValue::Object(vars))
+    }
+}
+
+impl fmt::Display for UriTemplate<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in &self.parts {
+            let (operator, vars) = match part {
+                Part::Literal(text) => {
+                    f.write_str(text)?;
+                    continue;
+                }
+                Part::Expression { operator, vars } => (operator, vars),
+            };
+            f.write_str("{")?;
+            if *operator != Operator::Simple {
+                f.write_str(match operator {
+                    Operator::Reserved => "+",
+                    operator => operator.first(),
+                })?;
+            }
+            for (i, var) in vars.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(",")?;
+                }
+                f.write_str(var.name)?;
+                match var.modifier {
+                    Modifier::None => {}
+                    Modifier::Prefix(length) => write!(f, ":{}", length)?,
+                    Modifier::Explode => f.write_str("*")?,
+                }
+            }
+            f.write_str("}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::parse_json;
+
+    fn vars() -> JsonValue {
+        let json = r#"{
+            "count": ["one", "two", "three"], "dom": ["example", "com"],
+            "dub": "me/too", "hello": "Hello World!", "half": "50%",
+            "var": "value", "who": "fred", "base": "http://example.com/home/",
+            "path": "/foo/bar", "list": ["red", "green", "blue"],
+            "keys": {"semi": ";", "dot": ".", "comma": ","},
+            "v": "6", "x": 1024, "y": "768", "empty_keys": {}, "undef": null,
+            "empty": ""
+        }"#;
+        parse_json(json).unwrap().1
+    }
+
+    fn expand(template: &str) -> String {
+        let (rest, parsed) = uri_template(template).unwrap();
+        assert_eq!(rest, "", "{}", template);
+        assert_eq!(parsed.to_string(), template);
+        parsed.expand(&vars())
+    }
+
+    #[test]
+    fn test_expand() {
+        // Examples from RFC 6570, section 3.2.
+        let cases = [
+            ("{var}", "value"),
+            ("{hello}", "Hello%20World%21"),
+            ("{half}", "50%25"),
+            ("O{empty}X", "OX"),
+            ("O{undef}X", "OX"),
+            ("{x,y}", "1024,768"),
+            ("?{x,empty}", "?1024,"),
+            ("?{undef,y}", "?768"),
+            ("{var:3}", "val"),
+            ("{var:30}", "value"),
+            ("{list*}", "red,green,blue"),
+            ("{keys}", "semi,%3B,dot,.,comma,%2C"),
+            ("{keys*}", "semi=%3B,dot=.,comma=%2C"),
+            ("{empty_keys}", ""),
+            ("{+hello}", "Hello%20World!"),
+            ("{base}index", "http%3A%2F%2Fexample.com%2Fhome%2Findex"),
+            ("{+base}index", "http://example.com/home/index"),
+            ("up{+path}{var}/here", "up/foo/barvalue/here"),
+            ("{+path:6}/here", "/foo/b/here"),
+            ("{+keys}", "semi,;,dot,.,comma,,"),
+            ("foo{#empty}", "foo#"),
+            ("{#path,x}/here", "#/foo/bar,1024/here"),
+            ("{#keys*}", "#semi=;,dot=.,comma=,"),
+            ("www{.dom*}", "www.example.com"),
+            ("X{.empty}", "X."),
+            ("X{.undef}", "X"),
+            ("X{.list}", "X.red,green,blue"),
+            ("X{.keys*}", "X.semi=%3B.dot=..comma=%2C"),
+            ("{/who,dub}", "/fred/me%2Ftoo"),
+            ("{/var,empty}", "/value/"),
+            ("{/var:1,var}", "/v/value"),
+            ("{/list*,path:4}", "/red/green/blue/%2Ffoo"),
+            ("{;v,empty,who}", ";v=6;empty;who=fred"),
+            ("{;hello:5}", ";hello=Hello"),
+            ("{;list*}", ";list=red;list=green;list=blue"),
+            ("{;keys}", ";keys=semi,%3B,dot,.,comma,%2C"),
+            ("{?x,y,empty}", "?x=1024&y=768&empty="),
+            ("{?list}", "?list=red,green,blue"),
+            ("{?list*}", "?list=red&list=green&list=blue"),
+            ("{?keys*}", "?semi=%3B&dot=.&comma=%2C"),
+            ("?fixed=yes{&x}", "?fixed=yes&x=1024"),
+            ("/caf\u{e9}/{who}", "/caf%C3%A9/fred"),
+        ];
+        for (template, expected) in cases {
+            assert_eq!(expand(template), expected, "{}", template);
+        }
+    }
+
+    #[test]
+    fn test_uri_template() {
+        let (rest, parsed) = uri_template("/users/{id}/posts{?page,limit} x").unwrap();
+        assert_eq!(rest, " x");
+        assert_eq!(
+            parsed.parts[3],
+            Part::Expression {
+                operator: Operator::Query,
+                vars: vec![
+                    VarSpec {
+                        name: "page",
+                        modifier: Modifier::None
+                    },
+                    VarSpec {
+                        name: "limit",
+                        modifier: Modifier::None
+                    }
+                ]
+            }
+        );
+        assert!(uri_template("{var").is_err());
+        assert!(uri_template("{=var}").is_err());
+        assert!(uri_template("{var:0}").is_err());
+        assert!(uri_template("{var:10000}").is_err());
+        assert!(uri_template("{a..b}").is_err());
+        assert!(uri_template("{}").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let string = |s: &str| JsonValue::String(s.to_string());
+        let (_, route) = uri_template("/users/{id}/posts{?page,limit}").unwrap();
+        assert_eq!(
+            route.matches("/users/42/posts?limit=10&page=2&other=x"),
+            Some(JsonValue::Object(vec![
+                ("id".to_string(), string("42")),
+                ("limit".to_string(), string("10")),
+                ("page".to_string(), string("2")),
+            ]))
+        );
+        assert_eq!(
+            route.matches("/users/a%20b/posts"),
+            Some(JsonValue::Object(vec![("id".to_string(), string("a b"))]))
+        );
+        assert_eq!(route.matches("/users/42/extra/posts"), None);
+        assert_eq!(route.matches("/groups/42/posts"), None);
+
+        let (_, files) = uri_template("/files{/path*}{.ext}").unwrap();
+        assert_eq!(
+            files.matches("/files/a/b/c.txt"),
+            Some(JsonValue::Object(vec![
+                (
+                    "path".to_string(),
+                    JsonValue::Array(vec![string("a"), string("b"), string("c")])
+                ),
+                ("ext".to_string(), string("txt")),
+            ]))
+        );
+
+        let (_, search) = uri_template("/search{?q,tags,opts*}").unwrap();
+        let vars = search.matches("/search?q=a%20b&tags=x,y&sort=new").unwrap();
+        assert_eq!(
+            vars,
+            JsonValue::Object(vec![
+                ("q".to_string(), string("a b")),
+                (
+                    "tags".to_string(),
+                    JsonValue::Array(vec![string("x"), string("y")])
+                ),
+                (
+                    "opts".to_string(),
+                    JsonValue::Object(vec![("sort".to_string(), string("new"))])
+                ),
+            ])
+        );
+        assert_eq!(search.expand(&vars), "/search?q=a%20b&tags=x,y&sort=new");
+    }
+}
@@ -0,0 +1,303 @@
+use std::fmt;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{take_while, take_while1, take_while_m_n},
+    character::complete::{char, line_ending, space0, space1},
+    combinator::{opt, recognize},
+    error::{context, VerboseError},
+    multi::{many0, separated_list1},
+    sequence::{pair, preceded, terminated, tuple},
+    IResult,
+};
+
+/// An IRCv3 message tag. Values are unescaped.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Tag<'a> {
+    pub key: &'a str,
+    pub value: Option<String>,
+}
+
+/// The source of a message: a server name, or `nick!user@host`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Prefix<'a> {
+    pub name: &'a str,
+    pub user: Option<&'a str>,
+    pub host: Option<&'a str>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Message<'a> {
+    pub tags: Vec<Tag<'a>>,
+    pub prefix: Option<Prefix<'a>>,
+    pub command: &'a str,
+    /// Middle parameters followed by the trailing parameter, if any.
+    pub params: Vec<&'a str>,
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => unescaped.push(';'),
+            Some('s') => unescaped.push(' '),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            // A trailing backslash is dropped.
+            None => {}
+        }
+    }
+    unescaped
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ';' => escaped.push_str("\\:"),
+            ' ' => escaped.push_str("\\s"),
+            '\\' => escaped.push_str("\\\\"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn tag(input: &str) -> Res<&str, Tag<'_>> {
+    context(
+        "tag",
+        pair(
+            recognize(pair(
+                opt(char('+')),
+                take_while1(|c: char| c.is_ascii_alphanumeric() || "-./".contains(c)),
+            )),
+            opt(preceded(
+                char('='),
+                take_while(|c: char| !"; \r\n\0".contains(c)),
+            )),
+        ),
+    )(input)
+    .map(|(next_input, (key, value))| {
+        (
+            next_input,
+            Tag {
+                key,
+                value: value.filter(|v| !v.is_empty()).map(unescape),
+            },
+        )
+    })
+}
+
+fn tags(input: &str) -> Res<&str, Vec<Tag<'_>>> {
+    context(
+        "tags",
+        terminated(preceded(char('@'), separated_list1(char(';'), tag)), space1),
+    )(input)
+}
+
+fn prefix(input: &str) -> Res<&str, Prefix<'_>> {
+    let part = |stop: &'static str| take_while1(move |c: char| !stop.contains(c));
+    context(
+        "prefix",
+        terminated(
+            preceded(
+                char(':'),
+                tuple((
+                    part("!@ \r\n"),
+                    opt(preceded(char('!'), part("@ \r\n"))),
+                    opt(preceded(char('@'), part(" \r\n"))),
+                )),
+            ),
+            space1,
+        ),
+    )(input)
+    .map(|(next_input, (name, user, host))| (next_input, Prefix { name, user, host }))
+}
+
+fn command(input: &str) -> Res<&str, &str> {
+    context(
+        "command",
+        alt((
+            take_while1(|c: char| c.is_ascii_alphabetic()),
+            take_while_m_n(3, 3, |c: char| c.is_ascii_digit()),
+        )),
+    )(input)
+}
+
+fn middle(input: &str) -> Res<&str, &str> {
+    recognize(pair(
+        take_while_m_n(1, 1, |c: char| !": \r\n\0".contains(c)),
+        take_while(|c: char| !" \r\n\0".contains(c)),
+    ))(input)
+}
+
+fn trailing(input: &str) -> Res<&str, &str> {
+    preceded(char(':'), take_while(|c: char| !"\r\n\0".contains(c)))(input)
+}
+
+fn params(input: &str) -> Res<&str, Vec<&str>> {
+    let (input, mut params) = many0(preceded(space1, middle))(input)?;
+    let (input, trailing) = opt(preceded(space1, trailing))(input)?;
+    params.extend(trailing);
+    Ok((input, params))
+}
+
+/// Parses one IRC protocol line, with or without its line ending.
+pub fn message(input: &str) -> Res<&str, Message<'_>> {
+    context(
+        "message",
+        tuple((
+            opt(tags),
+            opt(prefix),
+            command,
+            params,
+            preceded(space0, opt(line_ending)),
+        )),
+    )(input)
+    .map(|(next_input, (tags, prefix, command, params, _))| {
+        (
+            next_input,
+            Message {
+                tags: tags.unwrap_or_default(),
+                prefix,
+                command,
+                params,
+            },
+        )
+    })
+}
+
+impl fmt::Display for Prefix<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(user) = self.user {
+            write!(f, "!{}", user)?;
+        }
+        if let Some(host) = self.host {
+            write!(f, "@{}", host)?;
+        }
+        Ok(())
+    }
+}
+
+/// Serializes the message without a line ending. The last parameter is
+/// written as a trailing parameter when it needs to be.
+impl fmt::Display for Message<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.tags.is_empty() {
+            let tags: Vec<_> = self
+                .tags
+                .iter()
+                .map(|tag| match &tag.value {
+                    Some(value) => format!("{}={}", tag.key, escape(value)),
+                    None => tag.key.to_owned(),
+                })
+                .collect();
+            write!(f, "@{} ", tags.join(";"))?;
+        }
+        if let Some(prefix) = &self.prefix {
+            write!(f, ":{} ", prefix)?;
+        }
+        write!(f, "{}", self.command)?;
+        if let Some((last, middle)) = self.params.split_last() {
+            for param in middle {
+                write!(f, " {}", param)?;
+            }
+            if last.is_empty() || last.starts_with(':') || last.contains(' ') {
+                write!(f, " :{}", last)?;
+            } else {
+                write!(f, " {}", last)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        let line = "@time=2023-01-01T00:00:00Z;msgid=a\\sb\\:c;+draft/flag :nick!user@host PRIVMSG #chan :hello there\r\nNEXT";
+        assert_eq!(
+            message(line),
+            Ok((
+                "NEXT",
+                Message {
+                    tags: vec![
+                        Tag {
+                            key: "time",
+                            value: Some("2023-01-01T00:00:00Z".to_owned())
+                        },
+                        Tag {
+                            key: "msgid",
+                            value: Some("a b;c".to_owned())
+                        },
+                        Tag {
+                            key: "+draft/flag",
+                            value: None
+                        },
+                    ],
+                    prefix: Some(Prefix {
+                        name: "nick",
+                        user: Some("user"),
+                        host: Some("host")
+                    }),
+                    command: "PRIVMSG",
+                    params: vec!["#chan", "hello there"],
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_message_forms() {
+        let (_, msg) = message("PING").unwrap();
+        assert_eq!((msg.command, msg.params.len()), ("PING", 0));
+        let (_, msg) = message(":irc.example.net 001 ada :Welcome").unwrap();
+        assert_eq!(
+            msg.prefix,
+            Some(Prefix {
+                name: "irc.example.net",
+                user: None,
+                host: None
+            })
+        );
+        assert_eq!((msg.command, msg.params), ("001", vec!["ada", "Welcome"]));
+        let (_, msg) = message("MODE #chan +o  ada :").unwrap();
+        assert_eq!(msg.params, vec!["#chan", "+o", "ada", ""]);
+        assert!(message("12 x").is_err());
+        assert!(message(":prefixonly").is_err());
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("a\\\\b\\r\\n\\x\\"), "a\\b\r\nx");
+        let (_, msg) = message("@k= CMD").unwrap();
+        assert_eq!(msg.tags[0].value, None);
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for line in [
+            "@a=b\\sc;d :n!u@h PRIVMSG #c :hi there",
+            "PING :",
+            "PRIVMSG #c ::)",
+            "JOIN #c",
+        ] {
+            let (_, msg) = message(line).unwrap();
+            assert_eq!(msg.to_string(), line);
+        }
+    }
+}
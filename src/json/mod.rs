@@ -1,46 +1,478 @@
-/// Not working yet. Still some issues to Iron out.
+//! JSON as in RFC 8259. `parse_json` reads a whole document,
+//! `parse_json_recover` carries on past errors, `parse_json_cst` keeps
+//! every byte for editing, and `JsonLexer` reads a document fed in pieces.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read};
+use std::ops::Index;
+use std::str::FromStr;
+use std::sync::Arc;
+
 use nom::{
     branch::alt,
-    bytes::complete::{escaped, is_not, tag},
-    character::complete::{char, digit1, multispace0},
-    combinator::{map, map_res, recognize},
-    multi::separated_list0,
-    sequence::{delimited, preceded, tuple},
-    IResult, Parser,
+    bytes::complete::tag,
+    character::complete::{char, digit0, digit1, multispace0, one_of},
+    combinator::{all_consuming, map, map_res, not, opt, peek, recognize},
+    error::ErrorKind,
+    sequence::{pair, preceded, terminated, tuple},
+    Err as NomErr, IResult,
 };
 
-#[derive(Debug, PartialEq)]
+use crate::cst::{GreenElement, GreenNode, SyntaxKind, SyntaxNode, TextEdit, TokenParser};
+use crate::error::Recovered;
+use crate::limits::Depth;
+use crate::pretty::{Indent, PrettyPrint};
+use crate::span::{spanned, Spanned};
+use crate::stream::Step;
+use crate::value::{FromValue, ToValue, Value, ValueError};
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum JsonValue {
     Object(Vec<(String, JsonValue)>),
     Array(Vec<JsonValue>),
     String(String),
     Number(f64),
+    /// A number as written, from `parse_json_with` when `lossless_numbers`
+    /// is set.
+    Decimal(JsonNumber),
     Boolean(bool),
     Null,
 }
 
+/// An object's members in order, with lookup by key. Where a key is given
+/// more than once, lookups find the last, as most JSON readers do.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct JsonObject<'a> {
+    members: &'a [(String, JsonValue)],
+}
+
+impl<'a> JsonObject<'a> {
+    pub fn get(&self, key: &str) -> Option<&'a JsonValue> {
+        self.members
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a JsonValue)> {
+        self.members
+            .iter()
+            .map(|(key, value)| (key.as_str(), value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &'a str> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+impl JsonValue {
+    pub fn as_object(&self) -> Option<JsonObject<'_>> {
+        match self {
+            JsonValue::Object(members) => Some(JsonObject { members }),
+            _ => None,
+        }
+    }
+
+    /// The member named `key`, the last one if there are several.
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        self.as_object()?.get(key)
+    }
+
+    pub fn get_index(&self, index: usize) -> Option<&JsonValue> {
+        self.as_array()?.get(index)
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(n) => Some(*n),
+            JsonValue::Decimal(n) => Some(n.as_f64()),
+            _ => None,
+        }
+    }
+
+    /// The number, if it's a whole number that fits in an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            JsonValue::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+                Some(*n as i64)
+            }
+            JsonValue::Decimal(n) => n.as_i64(),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            JsonValue::Number(n) if n.fract() == 0.0 && (0.0..u64::MAX as f64).contains(n) => {
+                Some(*n as u64)
+            }
+            JsonValue::Decimal(n) => n.as_u64(),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, JsonValue::Null)
+    }
+
+    /// The value at an RFC 6901 JSON Pointer such as `/address/city`, where
+    /// `~1` stands for `/` and `~0` for `~` in a key. `""` is the whole
+    /// value.
+    pub fn pointer(&self, pointer: &str) -> Option<&JsonValue> {
+        pointer_tokens(pointer)?
+            .into_iter()
+            .try_fold(self, |value, token| match value {
+                JsonValue::Object(members) => members
+                    .iter()
+                    .rev()
+                    .find(|(key, _)| *key == token)
+                    .map(|(_, value)| value),
+                JsonValue::Array(items) => items.get(array_index(&token)?),
+                _ => None,
+            })
+    }
+
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut JsonValue> {
+        pointer_tokens(pointer)?
+            .into_iter()
+            .try_fold(self, |value, token| match value {
+                JsonValue::Object(members) => members
+                    .iter_mut()
+                    .rev()
+                    .find(|(key, _)| *key == token)
+                    .map(|(_, value)| value),
+                JsonValue::Array(items) => items.get_mut(array_index(&token)?),
+                _ => None,
+            })
+    }
+
+    /// Applies an RFC 7386 merge patch: an object patch merges into this
+    /// value member by member, with `null` removing a member, and any
+    /// other patch replaces it.
+    pub fn merge_patch(&mut self, patch: &JsonValue) {
+        let JsonValue::Object(changes) = patch else {
+            *self = patch.clone();
+            return;
+        };
+        if !matches!(self, JsonValue::Object(_)) {
+            *self = JsonValue::Object(vec![]);
+        }
+        let JsonValue::Object(members) = self else {
+            unreachable!("just made an object");
+        };
+        for (key, change) in changes {
+            if change.is_null() {
+                members.retain(|(k, _)| k != key);
+                continue;
+            }
+            match members.iter_mut().rev().find(|(k, _)| k == key) {
+                Some((_, value)) => value.merge_patch(change),
+                None => {
+                    let mut value = JsonValue::Null;
+                    value.merge_patch(change);
+                    members.push((key.clone(), value));
+                }
+            }
+        }
+    }
+
+    /// The value as JSON with one member or element per line, `indent`
+    /// spaces deeper per level.
+    pub fn to_string_pretty(&self, indent: usize) -> String {
+        self.to_pretty_string(Indent::spaces(indent))
+    }
+}
+
+/// The unescaped reference tokens of a JSON Pointer, or `None` if it's
+/// neither empty nor starts with `/`, or has a `~` not followed by `0` or
+/// `1`.
+fn pointer_tokens(pointer: &str) -> Option<Vec<Cow<'_, str>>> {
+    if pointer.is_empty() {
+        return Some(vec![]);
+    }
+    pointer
+        .strip_prefix('/')?
+        .split('/')
+        .map(|token| {
+            let escapes_valid = token
+                .match_indices('~')
+                .all(|(i, _)| matches!(token.as_bytes().get(i + 1), Some(b'0' | b'1')));
+            match (escapes_valid, token.contains('~')) {
+                (false, _) => None,
+                (true, true) => Some(Cow::Owned(token.replace("~1", "/").replace("~0", "~"))),
+                (true, false) => Some(Cow::Borrowed(token)),
+            }
+        })
+        .collect()
+}
+
+/// An array index as a pointer gives it: digits without leading zeros.
+/// `-`, for the element after the last, never names an existing one.
+fn array_index(token: &str) -> Option<usize> {
+    let digits = token.bytes().all(|b| b.is_ascii_digit());
+    let leading_zero = token.len() > 1 && token.starts_with('0');
+    match digits && !leading_zero {
+        true => token.parse().ok(),
+        false => None,
+    }
+}
+
+static NULL: JsonValue = JsonValue::Null;
+
+/// `value["key"]` looks up a member as `get` does, giving `Null` when there
+/// isn't one or `value` isn't an object, so lookups can be chained.
+impl Index<&str> for JsonValue {
+    type Output = JsonValue;
+
+    fn index(&self, key: &str) -> &JsonValue {
+        self.get(key).unwrap_or(&NULL)
+    }
+}
+
+/// `value[i]`, giving `Null` past the end or for anything but an array.
+impl Index<usize> for JsonValue {
+    type Output = JsonValue;
+
+    fn index(&self, index: usize) -> &JsonValue {
+        self.get_index(index).unwrap_or(&NULL)
+    }
+}
+
+/// Compact JSON, which parses back to the same value. Numbers JSON can't
+/// represent, such as NaN, are written as `null`.
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.pretty_print(f, Indent::compact())
+    }
+}
+
+/// A number kept as it was written, so integers beyond the 53 bits an
+/// `f64` holds exactly, such as 64-bit IDs, survive parsing.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct JsonNumber(String);
+
+impl JsonNumber {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The number, if it's an integer that fits. `1.0` and `1e2` aren't
+    /// integers here, as they aren't written as one.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.0.parse().ok()
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        self.0.parse().ok()
+    }
+
+    /// The nearest `f64`, which is exact for integers up to 2^53.
+    pub fn as_f64(&self) -> f64 {
+        self.0.parse().expect("a JSON number is a valid f64")
+    }
+}
+
+impl fmt::Display for JsonNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for JsonNumber {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        all_consuming(number_lexeme)(s)
+            .map(|(_, lexeme)| JsonNumber(lexeme.to_string()))
+            .map_err(|error| crate::Error::from_nom("json", s, error))
+    }
+}
+
+/// How `parse_json_with` reads a document.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct JsonOptions {
+    /// Keep numbers as `JsonValue::Decimal` instead of converting them to
+    /// `f64`.
+    pub lossless_numbers: bool,
+    pub duplicate_keys: DuplicateKeys,
+}
+
+/// What to do with a key given twice in one object.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Keep every member, as written.
+    #[default]
+    Keep,
+    /// Fail at the second occurrence.
+    Error,
+    FirstWins,
+    /// Keep the last value, where the key first appeared.
+    LastWins,
+}
+
+/// Parses like `parse_json`, with `options`.
+pub fn parse_json_with(input: &str, options: JsonOptions) -> IResult<&str, JsonValue> {
+    let (value, rest) = parse_prefix(input, options).map_err(|error| failure(input, &error))?;
+    match whitespace(rest) {
+        "" => Ok(("", value)),
+        rest => Err(NomErr::Error(nom::error::Error::new(rest, ErrorKind::Eof))),
+    }
+}
+
+/// The value at the start of `input`, read with `JsonLexer` and
+/// `JsonBuilder`, and what follows it.
+fn parse_prefix(input: &str, options: JsonOptions) -> Result<(JsonValue, &str), crate::Error> {
+    let mut lexer = JsonLexer::new();
+    lexer.feed(input.as_bytes());
+    lexer.finish();
+    let mut builder = JsonBuilder::with_options(options);
+    loop {
+        let event = match lexer.next_event() {
+            Step::Item(event) => event,
+            Step::Error(error) => return Err(error),
+            Step::NeedMore => return Err(lexer.incomplete()),
+        };
+        match builder.push(event) {
+            Ok(Some(value)) => return Ok((value, &input[lexer.start..])),
+            Ok(None) => {}
+            Err(error) => return Err(lexer.locate(error)),
+        }
+    }
+}
+
+/// `error` as a nom failure where it starts in `input`. Only `FromStr`
+/// keeps what was expected there.
+fn failure<'a>(input: &'a str, error: &crate::Error) -> NomErr<nom::error::Error<&'a str>> {
+    let at = input
+        .get(error.span.start..)
+        .unwrap_or(&input[input.len()..]);
+    NomErr::Failure(nom::error::Error::new(at, ErrorKind::Char))
+}
+
+/// A string with its escapes decoded, as in RFC 8259 section 7. A `\uXXXX`
+/// escape for half of a UTF-16 surrogate pair must be followed by the other
+/// half, and the two become one character. Control characters, U+0000 to
+/// U+001F, are only allowed escaped.
 pub fn parse_string(input: &str) -> IResult<&str, String> {
-    let (input, string) = delimited(
-        char('"'),
-        escaped(is_not("\\\""), '\\', char('"')),
-        char('"'),
-    )(input)?;
-    Ok((input, string.to_owned()))
+    let (mut rest, _) = char('"')(input)?;
+    let mut string = String::new();
+    loop {
+        let end = rest
+            .find(|c| matches!(c, '"' | '\\' | '\0'..='\u{1f}'))
+            .unwrap_or(rest.len());
+        string.push_str(&rest[..end]);
+        rest = &rest[end..];
+        if let Some(after) = rest.strip_prefix('"') {
+            return Ok((after, string));
+        }
+        if !rest.starts_with('\\') {
+            return Err(NomErr::Failure(nom::error::Error::new(
+                rest,
+                ErrorKind::Char,
+            )));
+        }
+        let (after, c) = escape(rest)?;
+        string.push(c);
+        rest = after;
+    }
 }
 
-pub fn parse_number(input: &str) -> IResult<&str, f64> {
-    let integer_parser = map_res(digit1, |s: &str| s.parse::<f64>());
-    let integer_parser_2 = map_res(digit1, |s: &str| s.parse::<f64>());
+/// The escape at the start of `input`, which begins with a backslash.
+fn escape(input: &str) -> IResult<&str, char> {
+    let invalid = || NomErr::Failure(nom::error::Error::new(input, ErrorKind::Escaped));
+    let c = match input[1..].chars().next() {
+        Some(c @ ('"' | '\\' | '/')) => c,
+        Some('b') => '\u{8}',
+        Some('f') => '\u{c}',
+        Some('n') => '\n',
+        Some('r') => '\r',
+        Some('t') => '\t',
+        Some('u') => {
+            let hex4 = |s: &str| {
+                s.get(..4)
+                    .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+                    .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+            };
+            let unit = hex4(&input[2..]).ok_or_else(invalid)?;
+            let rest = &input[6..];
+            return match unit {
+                0xd800..=0xdbff => {
+                    let low = rest
+                        .strip_prefix("\\u")
+                        .and_then(hex4)
+                        .filter(|low| (0xdc00..=0xdfff).contains(low))
+                        .ok_or_else(invalid)?;
+                    let code =
+                        0x10000 + ((u32::from(unit) - 0xd800) << 10) + u32::from(low) - 0xdc00;
+                    Ok((
+                        &rest[6..],
+                        char::from_u32(code).expect("a surrogate pair is a char"),
+                    ))
+                }
+                0xdc00..=0xdfff => Err(invalid()),
+                unit => Ok((rest, char::from_u32(unit.into()).expect("not a surrogate"))),
+            };
+        }
+        _ => return Err(invalid()),
+    };
+    Ok((&input[2..], c))
+}
 
-    let fractional_parser = map_res(digit1, |s: &str| s.parse::<f64>())
-        .map(|fractional| fractional / 10f64.powi(fractional.to_string().len() as i32));
+/// A number as in RFC 8259 section 6: an optional minus, an integer part
+/// without leading zeros, then an optional fraction and exponent.
+pub fn parse_number(input: &str) -> IResult<&str, f64> {
+    map_res(number_lexeme, str::parse)(input)
+}
 
-    let mut number_parser = alt((
-        recognize(tuple((integer_parser, char('.'), fractional_parser))),
-        recognize(integer_parser_2),
+fn number_lexeme(input: &str) -> IResult<&str, &str> {
+    let integer = alt((
+        terminated(tag("0"), not(digit1)),
+        recognize(pair(one_of("123456789"), digit0)),
     ));
-
-    number_parser(input).map(|(remaining, number)| (remaining, number.parse().unwrap()))
+    let fraction = preceded(char('.'), digit1);
+    let exponent = tuple((one_of("eE"), opt(one_of("+-")), digit1));
+    recognize(tuple((
+        opt(char('-')),
+        integer,
+        opt(fraction),
+        opt(exponent),
+    )))(input)
 }
 
 pub fn parse_boolean(input: &str) -> IResult<&str, bool> {
@@ -51,37 +483,21 @@ pub fn parse_null(input: &str) -> IResult<&str, ()> {
     map(tag("null"), |_| ())(input)
 }
 
+/// Parses the value after any whitespace, leaving what follows it.
 pub fn parse_value(input: &str) -> IResult<&str, JsonValue> {
-    preceded(
-        multispace0,
-        alt((
-            parse_object,
-            parse_array,
-            map(parse_string, JsonValue::String),
-            map(parse_number, JsonValue::Number),
-            map(parse_boolean, JsonValue::Boolean),
-            map(parse_null, |_| JsonValue::Null),
-        )),
-    )(input)
+    parse_prefix(input, JsonOptions::default())
+        .map(|(value, rest)| (rest, value))
+        .map_err(|error| failure(input, &error))
 }
 
 pub fn parse_object(input: &str) -> IResult<&str, JsonValue> {
-    let parse_opening_brace = preceded(multispace0, char('{'));
-    let parse_closing_brace = preceded(multispace0, char('}'));
-    let parse_comma = preceded(multispace0, char(','));
-    // let parse_quoted_string = preceded(multispace0, parse_string);
-
-    let parser = map(separated_list0(parse_comma, parse_key_value), |pairs| {
-        JsonValue::Object(pairs)
-    });
-
-    delimited(parse_opening_brace, parser, parse_closing_brace)(input)
+    preceded(multispace0, peek(char('{')))(input)?;
+    parse_value(input)
 }
 
 pub fn parse_key_value(input: &str) -> IResult<&str, (String, JsonValue)> {
-    let parse_key = parse_string;
+    let parse_key = preceded(multispace0, parse_string);
     let parse_separator = preceded(multispace0, char(':'));
-    let parse_value = parse_value;
 
     let mut parser = tuple((parse_key, parse_separator, parse_value));
 
@@ -89,16 +505,1193 @@ pub fn parse_key_value(input: &str) -> IResult<&str, (String, JsonValue)> {
 }
 
 pub fn parse_array(input: &str) -> IResult<&str, JsonValue> {
-    let parse_array = delimited(
-        preceded(multispace0, char('[')),
-        separated_list0(preceded(multispace0, char(',')), parse_value),
-        preceded(multispace0, char(']')),
-    );
-    map(parse_array, JsonValue::Array)(input)
+    preceded(multispace0, peek(char('[')))(input)?;
+    parse_value(input)
 }
 
+/// Parses a whole document: one value with optional whitespace around it.
+/// Anything else after the value is an `ErrorKind::Eof` error there.
 pub fn parse_json(input: &str) -> IResult<&str, JsonValue> {
-    preceded(multispace0, parse_value)(input)
+    parse_json_with(input, JsonOptions::default())
+}
+
+/// Parses a value after any whitespace, returning where it is in `input`.
+/// Unlike `parse_json`, what follows the value is left for the caller.
+pub fn parse_json_spanned(input: &str) -> IResult<&str, Spanned<'_, JsonValue>> {
+    preceded(multispace0, spanned(input, parse_value))(input)
+}
+
+/// `parse_json` returning an `Error`, whose span starts at the first byte
+/// left over when there's more after the value.
+pub fn parse_json_complete(input: &str) -> Result<JsonValue, crate::Error> {
+    input.parse()
+}
+
+impl FromStr for JsonValue {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::error::traced("json", s.len(), || {
+            crate::limits::check_len("json", s)?;
+            match parse_prefix(s, JsonOptions::default())? {
+                (value, rest) if whitespace(rest).is_empty() => Ok(value),
+                (_, rest) => Err(crate::Error::trailing("json", s, whitespace(rest))),
+            }
+        })
+    }
+}
+
+/// Parses `s` as JSON into any type implementing `Deserialize`.
+#[cfg(feature = "serde")]
+pub fn from_str<T: serde::de::DeserializeOwned>(s: &str) -> Result<T, crate::serde_value::Error> {
+    crate::serde_value::from_value(s.parse::<JsonValue>()?.to_value())
+}
+
+/// Serializes `value` as compact JSON.
+#[cfg(feature = "serde")]
+pub fn to_string<T: serde::Serialize + ?Sized>(
+    value: &T,
+) -> Result<String, crate::serde_value::Error> {
+    to_string_with(value, Indent::compact())
+}
+
+/// Serializes `value` as JSON indented by two spaces.
+#[cfg(feature = "serde")]
+pub fn to_string_pretty<T: serde::Serialize + ?Sized>(
+    value: &T,
+) -> Result<String, crate::serde_value::Error> {
+    to_string_with(value, Indent::default())
+}
+
+#[cfg(feature = "serde")]
+fn to_string_with<T: serde::Serialize + ?Sized>(
+    value: &T,
+    indent: Indent,
+) -> Result<String, crate::serde_value::Error> {
+    let value = crate::serde_value::to_value(value)?;
+    let json = JsonValue::from_value(value)
+        .map_err(|error| crate::serde_value::Error::Message(error.to_string()))?;
+    Ok(json.to_pretty_string(indent))
+}
+
+/// Skips the rest of a malformed value, up to the `,`, `]` or `}` that
+/// ends it. Brackets and strings inside the skipped text are balanced, so
+/// a broken nested value is skipped as a whole.
+fn skip_malformed(input: &str) -> &str {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in input.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '[' | '{' => depth += 1,
+            ',' | ']' | '}' if depth == 0 => return &input[i..],
+            ']' | '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    &input[input.len()..]
+}
+
+fn whitespace(input: &str) -> &str {
+    input.trim_start_matches([' ', '\t', '\r', '\n'])
+}
+
+/// State for `parse_json_recover`.
+struct Recovery<'a> {
+    source: &'a str,
+    errors: Vec<crate::Error>,
+}
+
+impl<'a> Recovery<'a> {
+    /// Records an error, unless one was already reported at the same spot.
+    fn error(&mut self, at: &'a str, expected: &str) {
+        let error = crate::Error::expected("json", self.source, at, expected);
+        if self.errors.last().map(|last| last.span.start) != Some(error.span.start) {
+            self.errors.push(error);
+        }
+    }
+
+    fn value(&mut self, input: &'a str) -> (&'a str, JsonValue) {
+        let input = whitespace(input);
+        if let Some(rest) = input.strip_prefix('[') {
+            let Some(_depth) = Depth::enter() else {
+                return self.too_deep(input);
+            };
+            return self.array(rest);
+        }
+        if let Some(rest) = input.strip_prefix('{') {
+            let Some(_depth) = Depth::enter() else {
+                return self.too_deep(input);
+            };
+            return self.object(rest);
+        }
+        let scalar = alt((
+            map(parse_string, JsonValue::String),
+            map(parse_number, JsonValue::Number),
+            map(parse_boolean, JsonValue::Boolean),
+            map(parse_null, |_| JsonValue::Null),
+        ))(input);
+        scalar.unwrap_or_else(|_| {
+            self.error(input, "value");
+            (skip_malformed(input), JsonValue::Null)
+        })
+    }
+
+    /// Skips a container nested past `limits::MAX_DEPTH`.
+    fn too_deep(&mut self, input: &'a str) -> (&'a str, JsonValue) {
+        self.error(input, "less deeply nested value");
+        (skip_malformed(input), JsonValue::Null)
+    }
+
+    /// Handles what follows an element: `Ok` with the next element after a
+    /// comma, or `Err` with the input after the container is closed. A
+    /// container that is never closed ends where recovery stopped.
+    fn separator(&mut self, input: &'a str, close: char) -> Result<&'a str, &'a str> {
+        let mut input = whitespace(input);
+        let mut reported = false;
+        loop {
+            if let Some(rest) = input.strip_prefix(',') {
+                let next = whitespace(rest);
+                return match next.strip_prefix(close) {
+                    Some(after) => {
+                        self.error(next, "value");
+                        Err(after)
+                    }
+                    None => Ok(rest),
+                };
+            }
+            if let Some(rest) = input.strip_prefix(close) {
+                return Err(rest);
+            }
+            if !reported {
+                self.error(input, &format!("',' or '{}'", close));
+                reported = true;
+            }
+            let skipped = skip_malformed(input);
+            if skipped.len() == input.len() {
+                return Err(input);
+            }
+            input = skipped;
+        }
+    }
+
+    fn array(&mut self, input: &'a str) -> (&'a str, JsonValue) {
+        let mut items = vec![];
+        let mut input = whitespace(input);
+        if let Some(rest) = input.strip_prefix(']') {
+            return (rest, JsonValue::Array(items));
+        }
+        loop {
+            let (rest, item) = self.value(input);
+            items.push(item);
+            match self.separator(rest, ']') {
+                Ok(next) => input = next,
+                Err(rest) => return (rest, JsonValue::Array(items)),
+            }
+        }
+    }
+
+    fn object(&mut self, input: &'a str) -> (&'a str, JsonValue) {
+        let mut members = vec![];
+        let mut input = whitespace(input);
+        if let Some(rest) = input.strip_prefix('}') {
+            return (rest, JsonValue::Object(members));
+        }
+        loop {
+            let start = whitespace(input);
+            input = match parse_string(start) {
+                Ok((rest, key)) => match whitespace(rest).strip_prefix(':') {
+                    Some(rest) => {
+                        let (rest, value) = self.value(rest);
+                        members.push((key, value));
+                        rest
+                    }
+                    None => {
+                        self.error(whitespace(rest), "':'");
+                        skip_malformed(rest)
+                    }
+                },
+                Err(_) => {
+                    self.error(start, "string");
+                    skip_malformed(start)
+                }
+            };
+            match self.separator(input, '}') {
+                Ok(next) => input = next,
+                Err(rest) => return (rest, JsonValue::Object(members)),
+            }
+        }
+    }
+}
+
+/// Parses a whole document, carrying on past errors instead of stopping at
+/// the first. A malformed value is skipped up to the next `,`, `]` or `}`
+/// and replaced with `null`, and members with a malformed key are dropped,
+/// so the value is always as complete as the input allows.
+pub fn parse_json_recover(input: &str) -> Recovered<JsonValue> {
+    let mut recovery = Recovery {
+        source: input,
+        errors: vec![],
+    };
+    let (rest, value) = recovery.value(input);
+    let rest = whitespace(rest);
+    if !rest.is_empty() {
+        recovery.error(rest, "end of input");
+    }
+    Recovered {
+        value,
+        errors: recovery.errors,
+    }
+}
+
+#[deprecated(note = "renamed to `parse_json_recover`, after `cargo_lock::lockfile_recover`")]
+pub fn parse_json_lenient(input: &str) -> Recovered<JsonValue> {
+    parse_json_recover(input)
+}
+
+/// Splits `input` into tokens for the syntax tree. Never fails: anything
+/// unrecognised becomes an `Error` token.
+fn lex(input: &str) -> Vec<(SyntaxKind, &str)> {
+    let mut tokens = vec![];
+    let mut rest = input;
+    while let Some(c) = rest.chars().next() {
+        let (kind, len) = match c {
+            ' ' | '\t' => (
+                SyntaxKind::Whitespace,
+                rest.len() - rest.trim_start_matches([' ', '\t']).len(),
+            ),
+            '\n' => (SyntaxKind::Newline, 1),
+            '\r' if rest.starts_with("\r\n") => (SyntaxKind::Newline, 2),
+            '{' => (SyntaxKind::LBrace, 1),
+            '}' => (SyntaxKind::RBrace, 1),
+            '[' => (SyntaxKind::LBracket, 1),
+            ']' => (SyntaxKind::RBracket, 1),
+            ',' => (SyntaxKind::Comma, 1),
+            ':' => (SyntaxKind::Colon, 1),
+            '"' => {
+                let mut escaped = false;
+                let end = rest[1..].char_indices().find_map(|(i, c)| match c {
+                    _ if escaped => {
+                        escaped = false;
+                        None
+                    }
+                    '\\' => {
+                        escaped = true;
+                        None
+                    }
+                    '"' => Some(i + 2),
+                    '\n' => Some(i + 1),
+                    _ => None,
+                });
+                match end {
+                    Some(end) if rest[..end].ends_with('"') && end > 1 => (SyntaxKind::String, end),
+                    Some(end) => (SyntaxKind::Error, end),
+                    None => (SyntaxKind::Error, rest.len()),
+                }
+            }
+            '-' | '0'..='9' => (
+                SyntaxKind::Number,
+                rest.find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+                    .unwrap_or(rest.len()),
+            ),
+            c if c.is_ascii_alphabetic() => {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric())
+                    .unwrap_or(rest.len());
+                match &rest[..len] {
+                    "true" | "false" | "null" => (SyntaxKind::Keyword, len),
+                    _ => (SyntaxKind::Error, len),
+                }
+            }
+            c => (SyntaxKind::Error, c.len_utf8()),
+        };
+        tokens.push((kind, &rest[..len]));
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+fn cst_value(p: &mut TokenParser) {
+    // Past the nesting limit, brackets are left as plain tokens.
+    let _depth = match p.peek() {
+        Some(SyntaxKind::LBrace | SyntaxKind::LBracket) => match Depth::enter() {
+            Some(depth) => Some(depth),
+            None => return p.bump(),
+        },
+        _ => None,
+    };
+    match p.peek() {
+        Some(SyntaxKind::LBrace) => {
+            p.builder.start_node(SyntaxKind::Object);
+            p.bump();
+            loop {
+                p.trivia();
+                match p.peek() {
+                    None | Some(SyntaxKind::RBracket) => break,
+                    Some(SyntaxKind::RBrace) => {
+                        p.bump();
+                        break;
+                    }
+                    Some(SyntaxKind::Comma) => p.bump(),
+                    Some(_) => {
+                        p.builder.start_node(SyntaxKind::Member);
+                        if p.peek() == Some(SyntaxKind::String) {
+                            p.bump();
+                            p.trivia();
+                        }
+                        if p.peek() == Some(SyntaxKind::Colon) {
+                            p.bump();
+                            p.trivia();
+                        }
+                        if !matches!(
+                            p.peek(),
+                            None | Some(
+                                SyntaxKind::Comma | SyntaxKind::RBrace | SyntaxKind::RBracket
+                            )
+                        ) {
+                            cst_value(p);
+                        }
+                        p.builder.finish_node();
+                    }
+                }
+            }
+            p.builder.finish_node();
+        }
+        Some(SyntaxKind::LBracket) => {
+            p.builder.start_node(SyntaxKind::Array);
+            p.bump();
+            loop {
+                p.trivia();
+                match p.peek() {
+                    None | Some(SyntaxKind::RBrace) => break,
+                    Some(SyntaxKind::RBracket) => {
+                        p.bump();
+                        break;
+                    }
+                    Some(SyntaxKind::Comma) => p.bump(),
+                    Some(_) => cst_value(p),
+                }
+            }
+            p.builder.finish_node();
+        }
+        Some(_) => p.bump(),
+        None => {}
+    }
+}
+
+/// Parses `input` into a lossless syntax tree, which prints back as
+/// exactly `input`. Malformed input still produces a tree, with `Error`
+/// tokens and unclosed containers where the input is broken; use
+/// `parse_json` or `parse_json_recover` to find out what is wrong.
+pub fn parse_json_cst(input: &str) -> GreenNode {
+    let mut p = TokenParser::new(lex(input));
+    p.builder.start_node(SyntaxKind::Root);
+    p.trivia();
+    cst_value(&mut p);
+    while p.peek().is_some() {
+        p.bump();
+    }
+    p.builder.finish_node();
+    p.finish()
+}
+
+/// Re-parses `root`, a tree from `parse_json_cst`, after `edit`. Only the
+/// innermost object or array around the edit is re-parsed, when its
+/// brackets are untouched and it still parses as a whole; everything
+/// outside it is shared with `root`. Otherwise the whole text is re-parsed.
+///
+/// # Panics
+///
+/// If the edit's range isn't within `root`'s text on character boundaries.
+pub fn reparse_json_cst(root: &SyntaxNode, edit: &TextEdit) -> SyntaxNode {
+    let mut node = root.clone();
+    let mut container = None;
+    loop {
+        let range = node.text_range();
+        if matches!(node.kind(), SyntaxKind::Object | SyntaxKind::Array)
+            && range.start < edit.range.start
+            && edit.range.end < range.end
+        {
+            container = Some(node.clone());
+        }
+        let child = node.children().into_iter().find(|child| {
+            let range = child.text_range();
+            range.start <= edit.range.start && edit.range.end <= range.end
+        });
+        match child {
+            Some(child) => node = child,
+            None => break,
+        }
+    }
+
+    if let Some(container) = container {
+        let start = container.text_range().start;
+        let local = TextEdit {
+            range: edit.range.start - start..edit.range.end - start,
+            replacement: edit.replacement.clone(),
+        };
+        let text = local.apply(&container.to_string());
+        if let Some(green) = reparse_container(&text, container.kind()) {
+            return container.replace_with(green);
+        }
+    }
+    SyntaxNode::new_root(parse_json_cst(&edit.apply(&root.to_string())))
+}
+
+/// Parses `text` as a single, closed object or array of the given kind.
+/// Anything else would parse differently in context, so gives `None`.
+fn reparse_container(text: &str, kind: SyntaxKind) -> Option<GreenNode> {
+    let (open, close) = match kind {
+        SyntaxKind::Object => (SyntaxKind::LBrace, SyntaxKind::RBrace),
+        _ => (SyntaxKind::LBracket, SyntaxKind::RBracket),
+    };
+    let mut p = TokenParser::new(lex(text));
+    if p.peek() != Some(open) {
+        return None;
+    }
+    cst_value(&mut p);
+    if p.peek().is_some() {
+        return None;
+    }
+    let green = p.finish();
+    match green.children().last() {
+        Some(GreenElement::Token(token)) if token.kind() == close => Some(green),
+        _ => None,
+    }
+}
+
+/// Whole numbers become `Value::Integer` when they fit in an `i64`.
+impl ToValue for JsonValue {
+    fn to_value(&self) -> Value {
+        match self {
+            JsonValue::Object(members) => Value::Table(
+                members
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.to_value()))
+                    .collect(),
+            ),
+            JsonValue::Array(items) => Value::Array(items.iter().map(Self::to_value).collect()),
+            JsonValue::String(s) => Value::String(s.clone()),
+            JsonValue::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+                Value::Integer(*n as i64)
+            }
+            JsonValue::Number(n) => Value::Float(*n),
+            JsonValue::Decimal(n) => n
+                .as_i64()
+                .map_or_else(|| Value::Float(n.as_f64()), Value::Integer),
+            JsonValue::Boolean(b) => Value::Boolean(*b),
+            JsonValue::Null => Value::Null,
+        }
+    }
+}
+
+/// Never fails. Non-finite floats, which JSON can't represent, become
+/// `null`.
+impl FromValue for JsonValue {
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        Ok(match value {
+            Value::Null => JsonValue::Null,
+            Value::Boolean(b) => JsonValue::Boolean(b),
+            Value::Integer(i) => JsonValue::Number(i as f64),
+            Value::Float(f) if !f.is_finite() => JsonValue::Null,
+            Value::Float(f) => JsonValue::Number(f),
+            Value::String(s) => JsonValue::String(s),
+            Value::Array(items) => JsonValue::Array(
+                items
+                    .into_iter()
+                    .map(JsonValue::from_value)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Table(entries) => JsonValue::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, JsonValue::from_value(value)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+}
+
+/// An immutable `JsonValue` that can be shared between threads. Cloning
+/// one, or any value inside it, only bumps a reference count.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SharedJson {
+    Object(Arc<[(Arc<str>, SharedJson)]>),
+    Array(Arc<[SharedJson]>),
+    String(Arc<str>),
+    Number(f64),
+    Boolean(bool),
+    Null,
+}
+
+impl SharedJson {
+    pub fn get(&self, key: &str) -> Option<&SharedJson> {
+        match self {
+            SharedJson::Object(members) => members
+                .iter()
+                .rev()
+                .find(|(k, _)| &**k == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn index(&self, index: usize) -> Option<&SharedJson> {
+        match self {
+            SharedJson::Array(items) => items.get(index),
+            _ => None,
+        }
+    }
+
+    /// A copy of `self` with `key` set to `value`. Only the members list is
+    /// copied; the other members are shared with `self`.
+    pub fn with(&self, key: &str, value: SharedJson) -> SharedJson {
+        let SharedJson::Object(members) = self else {
+            return SharedJson::Object(Arc::new([(key.into(), value)]));
+        };
+        let mut members = members.to_vec();
+        match members.iter_mut().find(|(k, _)| &**k == key) {
+            Some((_, old)) => *old = value,
+            None => members.push((key.into(), value)),
+        }
+        SharedJson::Object(members.into())
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        match self {
+            SharedJson::Object(members) => JsonValue::Object(
+                members
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_json()))
+                    .collect(),
+            ),
+            SharedJson::Array(items) => JsonValue::Array(items.iter().map(Self::to_json).collect()),
+            SharedJson::String(s) => JsonValue::String(s.to_string()),
+            SharedJson::Number(n) => JsonValue::Number(*n),
+            SharedJson::Boolean(b) => JsonValue::Boolean(*b),
+            SharedJson::Null => JsonValue::Null,
+        }
+    }
+}
+
+impl From<JsonValue> for SharedJson {
+    fn from(value: JsonValue) -> Self {
+        match value {
+            JsonValue::Object(members) => SharedJson::Object(
+                members
+                    .into_iter()
+                    .map(|(key, value)| (key.into(), value.into()))
+                    .collect(),
+            ),
+            JsonValue::Array(items) => {
+                SharedJson::Array(items.into_iter().map(SharedJson::from).collect())
+            }
+            JsonValue::String(s) => SharedJson::String(s.into()),
+            JsonValue::Number(n) => SharedJson::Number(n),
+            JsonValue::Decimal(n) => SharedJson::Number(n.as_f64()),
+            JsonValue::Boolean(b) => SharedJson::Boolean(b),
+            JsonValue::Null => SharedJson::Null,
+        }
+    }
+}
+
+impl FromStr for SharedJson {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<JsonValue>().map(SharedJson::from)
+    }
+}
+
+/// One token of a JSON document, from `JsonLexer`. Numbers are kept as
+/// written.
+#[derive(Debug, PartialEq, Clone)]
+pub enum JsonEvent {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    /// An object member's name. The events for its value follow.
+    Key(String),
+    String(String),
+    Number(JsonNumber),
+    Boolean(bool),
+    Null,
+}
+
+/// What `JsonLexer` will accept next.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Expect {
+    /// A value, or `]` straight after `[`.
+    Value {
+        or_end: bool,
+    },
+    /// A key, or `}` straight after `{`.
+    Key {
+        or_end: bool,
+    },
+    Colon,
+    /// `,` or the end of the innermost container.
+    CommaOrEnd,
+    Done,
+}
+
+/// A pull parser for one document fed in pieces, handing out an event per
+/// token as soon as the token is complete, so nothing but the open
+/// containers has to be held in memory. Error spans are offsets into the
+/// whole document. After an error the same error is returned again.
+#[derive(Debug)]
+pub struct JsonLexer {
+    buffer: Vec<u8>,
+    /// Where the next token starts in `buffer`.
+    start: usize,
+    /// Where the token of the last event starts in `buffer`.
+    token: usize,
+    /// Bytes, lines, and characters on the last of them, dropped from
+    /// the front of `buffer`.
+    offset: usize,
+    lines: usize,
+    columns: usize,
+    /// `true` for each open object, `false` for each open array.
+    open: Vec<bool>,
+    expect: Expect,
+    finished: bool,
+    error: Option<crate::Error>,
+}
+
+impl Default for JsonLexer {
+    fn default() -> Self {
+        JsonLexer {
+            buffer: vec![],
+            start: 0,
+            token: 0,
+            offset: 0,
+            lines: 0,
+            columns: 0,
+            open: vec![],
+            expect: Expect::Value { or_end: false },
+            finished: false,
+            error: None,
+        }
+    }
+}
+
+impl JsonLexer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        let (line, column) = crate::error::line_column(&self.buffer[..self.start]);
+        if line > 1 {
+            self.columns = 0;
+        }
+        self.lines += line - 1;
+        self.columns += column - 1;
+        self.offset += self.start;
+        self.buffer.drain(..self.start);
+        self.start = 0;
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Says no more input is coming, so a number at the end of what was
+    /// fed is complete and a document cut short is an error.
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Whether the whole value has been read. From then on `NeedMore`
+    /// means the document ended, once `finish` has been called.
+    pub fn is_done(&self) -> bool {
+        self.expect == Expect::Done
+    }
+
+    pub fn next_event(&mut self) -> Step<JsonEvent> {
+        if let Some(error) = &self.error {
+            return Step::Error(error.clone());
+        }
+        match self.step() {
+            Ok(Some(event)) => Step::Item(event),
+            Ok(None) => Step::NeedMore,
+            Err(mut error) => {
+                error.relocate(self.offset, self.lines + 1, self.columns + 1);
+                self.error = Some(error.clone());
+                Step::Error(error)
+            }
+        }
+    }
+
+    /// The next event, or `None` if it isn't all buffered yet.
+    fn step(&mut self) -> Result<Option<JsonEvent>, crate::Error> {
+        loop {
+            self.start += self.buffer[self.start..]
+                .iter()
+                .take_while(|b| b" \t\r\n".contains(b))
+                .count();
+            let at = self.start;
+            self.token = at;
+            let Some(&byte) = self.buffer.get(at) else {
+                if self.finished && !self.is_done() {
+                    return Err(self.error_at(at, "more input"));
+                }
+                return Ok(None);
+            };
+            let in_object = self.open.last() == Some(&true);
+            match (self.expect, byte) {
+                (Expect::Colon, b':') => {
+                    self.start += 1;
+                    self.expect = Expect::Value { or_end: false };
+                }
+                (Expect::CommaOrEnd, b',') => {
+                    self.start += 1;
+                    self.expect = match in_object {
+                        true => Expect::Key { or_end: false },
+                        false => Expect::Value { or_end: false },
+                    };
+                }
+                (Expect::CommaOrEnd, b'}') | (Expect::Key { or_end: true }, b'}') if in_object => {
+                    return Ok(Some(self.close(JsonEvent::EndObject)));
+                }
+                (Expect::CommaOrEnd, b']') | (Expect::Value { or_end: true }, b']')
+                    if !in_object =>
+                {
+                    return Ok(Some(self.close(JsonEvent::EndArray)));
+                }
+                (Expect::Value { .. }, b'{' | b'[') => {
+                    // Without `hardened` the limit is `usize::MAX`, which
+                    // `open` can't reach.
+                    #[cfg(feature = "hardened")]
+                    if self.open.len() >= crate::limits::MAX_DEPTH {
+                        return Err(self.error_at(at, "less deeply nested input"));
+                    }
+                    self.start += 1;
+                    self.open.push(byte == b'{');
+                    return Ok(Some(if byte == b'{' {
+                        self.expect = Expect::Key { or_end: true };
+                        JsonEvent::StartObject
+                    } else {
+                        self.expect = Expect::Value { or_end: true };
+                        JsonEvent::StartArray
+                    }));
+                }
+                (Expect::Key { .. }, b'"') => {
+                    let key = self.string()?.map(JsonEvent::Key);
+                    if key.is_some() {
+                        self.expect = Expect::Colon;
+                    }
+                    return Ok(key);
+                }
+                (Expect::Value { .. }, _) => {
+                    let value = match byte {
+                        b'"' => self.string()?.map(JsonEvent::String),
+                        b'-' | b'0'..=b'9' => self.number()?,
+                        _ => self.literal()?,
+                    };
+                    if value.is_some() {
+                        self.expect = self.after_value();
+                    }
+                    return Ok(value);
+                }
+                (expect, _) => {
+                    let expected = match expect {
+                        Expect::Key { .. } => "string",
+                        Expect::Colon => "':'",
+                        Expect::CommaOrEnd if in_object => "',' or '}'",
+                        Expect::CommaOrEnd => "',' or ']'",
+                        Expect::Value { .. } | Expect::Done => "end of input",
+                    };
+                    return Err(self.error_at(at, expected));
+                }
+            }
+        }
+    }
+
+    fn after_value(&self) -> Expect {
+        match self.open.is_empty() {
+            true => Expect::Done,
+            false => Expect::CommaOrEnd,
+        }
+    }
+
+    fn close(&mut self, event: JsonEvent) -> JsonEvent {
+        self.start += 1;
+        self.open.pop();
+        self.expect = self.after_value();
+        event
+    }
+
+    fn string(&mut self) -> Result<Option<String>, crate::Error> {
+        let rest = &self.buffer[self.start..];
+        let mut i = 1;
+        let end = loop {
+            match rest.get(i) {
+                Some(b'"') => break i + 1,
+                Some(b'\\') => i += 2,
+                Some(_) => i += 1,
+                // Let `parse_string` report the string as unterminated.
+                None if self.finished => break rest.len(),
+                None => return Ok(None),
+            }
+        };
+        let text = match std::str::from_utf8(&rest[..end]) {
+            Ok(text) => text,
+            Err(error) => {
+                return Err(self.error_at(self.start + error.valid_up_to(), "utf-8 text"));
+            }
+        };
+        match parse_string(text) {
+            Ok((_, string)) => {
+                self.start += end;
+                Ok(Some(string))
+            }
+            Err(error) => Err(self.nom_error(text, error)),
+        }
+    }
+
+    fn number(&mut self) -> Result<Option<JsonEvent>, crate::Error> {
+        let rest = &self.buffer[self.start..];
+        let len = rest
+            .iter()
+            .take_while(|b| b"0123456789+-.eE".contains(b))
+            .count();
+        if len == rest.len() && !self.finished {
+            return Ok(None);
+        }
+        let text = std::str::from_utf8(&rest[..len]).expect("ascii");
+        // What `number_lexeme` leaves, as in `1-`, is reported as
+        // unexpected by the next call.
+        match number_lexeme(text) {
+            Ok((_, lexeme)) => {
+                self.start += lexeme.len();
+                Ok(Some(JsonEvent::Number(JsonNumber(lexeme.to_string()))))
+            }
+            Err(error) => Err(self.nom_error(text, error)),
+        }
+    }
+
+    fn literal(&mut self) -> Result<Option<JsonEvent>, crate::Error> {
+        let rest = &self.buffer[self.start..];
+        for (word, event) in [
+            ("true", JsonEvent::Boolean(true)),
+            ("false", JsonEvent::Boolean(false)),
+            ("null", JsonEvent::Null),
+        ] {
+            if rest.starts_with(word.as_bytes()) {
+                self.start += word.len();
+                return Ok(Some(event));
+            }
+            if !self.finished && word.as_bytes().starts_with(rest) {
+                return Ok(None);
+            }
+        }
+        Err(self.error_at(self.start, "value"))
+    }
+
+    /// The error for a document that ends before its value does.
+    fn incomplete(&self) -> crate::Error {
+        let mut error = self.error_at(self.buffer.len(), "more input");
+        error.relocate(self.offset, self.lines + 1, self.columns + 1);
+        error
+    }
+
+    /// An error from `JsonBuilder`, moved to the token of the last event.
+    fn locate(&self, mut error: crate::Error) -> crate::Error {
+        let (line, column) = crate::error::line_column(&self.buffer[..self.token]);
+        error.relocate(self.token, line, column);
+        error.relocate(self.offset, self.lines + 1, self.columns + 1);
+        error
+    }
+
+    /// An error at `at` in `buffer`, with spans relative to it.
+    fn error_at(&self, at: usize, expected: &str) -> crate::Error {
+        let text = match std::str::from_utf8(&self.buffer) {
+            Ok(text) => text,
+            Err(error) => {
+                std::str::from_utf8(&self.buffer[..error.valid_up_to()]).expect("valid up to there")
+            }
+        };
+        match text.get(at..) {
+            Some(rest) if !rest.is_empty() || at == self.buffer.len() => {
+                crate::Error::expected("json", text, rest, expected)
+            }
+            _ => crate::Error::expected("json", &self.buffer[..], &self.buffer[at..], expected),
+        }
+    }
+
+    /// An error from parsing `token`, which starts at `start`.
+    fn nom_error(&self, token: &str, error: NomErr<nom::error::Error<&str>>) -> crate::Error {
+        let mut error = crate::Error::from_nom("json", token, error);
+        let (line, column) = crate::error::line_column(&self.buffer[..self.start]);
+        error.relocate(self.start, line, column);
+        error
+    }
+}
+
+/// The events of a document read from `reader` a chunk at a time.
+#[derive(Debug)]
+pub struct JsonTokenStream<R> {
+    reader: R,
+    lexer: JsonLexer,
+    failed: bool,
+}
+
+impl<R: Read> JsonTokenStream<R> {
+    pub fn new(reader: R) -> Self {
+        JsonTokenStream {
+            reader,
+            lexer: JsonLexer::new(),
+            failed: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for JsonTokenStream<R> {
+    type Item = Result<JsonEvent, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            match self.lexer.next_event() {
+                Step::Item(event) => return Some(Ok(event)),
+                Step::Error(error) => {
+                    self.failed = true;
+                    return Some(Err(error.into()));
+                }
+                Step::NeedMore if self.lexer.finished => return None,
+                Step::NeedMore => {
+                    let mut chunk = [0; 8192];
+                    match self.reader.read(&mut chunk) {
+                        Ok(0) => self.lexer.finish(),
+                        Ok(n) => self.lexer.feed(&chunk[..n]),
+                        Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                        Err(error) => {
+                            self.failed = true;
+                            return Some(Err(error.into()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Parse(Box<crate::Error>),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(error) => write!(f, "{}", error),
+            ReadError::Parse(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(error) => Some(error),
+            ReadError::Parse(error) => Some(error.as_ref()),
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> Self {
+        ReadError::Io(error)
+    }
+}
+
+impl From<crate::Error> for ReadError {
+    fn from(error: crate::Error) -> Self {
+        ReadError::Parse(Box::new(error))
+    }
+}
+
+/// Puts events back together into values, as `parse_json_with` builds
+/// them.
+#[derive(Debug, Default)]
+pub struct JsonBuilder {
+    options: JsonOptions,
+    open: Vec<Open>,
+}
+
+/// A container `JsonBuilder` is still adding to.
+#[derive(Debug)]
+struct Open {
+    value: JsonValue,
+    /// The key the next member of an object goes under.
+    key: Option<String>,
+    /// Where each key is in an object, unless duplicates are kept.
+    seen: HashMap<String, usize>,
+}
+
+impl JsonBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: JsonOptions) -> Self {
+        JsonBuilder {
+            options,
+            open: vec![],
+        }
+    }
+
+    /// Adds the next event, returning the value once it's complete. An
+    /// event that can't come next, such as a member without its key, is
+    /// an error, as is a repeated key under `DuplicateKeys::Error`. Events
+    /// don't say where they were, so the error's span is empty at the
+    /// start; `parse_json_with` moves it to the event's token.
+    pub fn push(&mut self, event: JsonEvent) -> Result<Option<JsonValue>, crate::Error> {
+        let is_key = matches!(event, JsonEvent::Key(_));
+        let closes = matches!(event, JsonEvent::EndObject | JsonEvent::EndArray);
+        let (expected, allowed) = match self.open.last() {
+            None => ("value", !is_key && !closes),
+            Some(open) => match (&open.value, &open.key, &event) {
+                (JsonValue::Object(_), None, JsonEvent::Key(key)) => {
+                    let repeated = open.seen.contains_key(key);
+                    match self.options.duplicate_keys {
+                        DuplicateKeys::Error if repeated => ("new key", false),
+                        _ => ("key", true),
+                    }
+                }
+                (JsonValue::Object(_), None, event) => {
+                    ("key or '}'", *event == JsonEvent::EndObject)
+                }
+                (JsonValue::Object(_), Some(_), _) => ("value", !is_key && !closes),
+                (_, _, event) => ("value or ']'", !is_key && *event != JsonEvent::EndObject),
+            },
+        };
+        if !allowed {
+            return Err(unexpected(expected, &event));
+        }
+        let value = match event {
+            JsonEvent::StartObject => {
+                self.open.push(Open::new(JsonValue::Object(vec![])));
+                return Ok(None);
+            }
+            JsonEvent::StartArray => {
+                self.open.push(Open::new(JsonValue::Array(vec![])));
+                return Ok(None);
+            }
+            JsonEvent::Key(key) => {
+                if let Some(open) = self.open.last_mut() {
+                    open.key = Some(key);
+                }
+                return Ok(None);
+            }
+            JsonEvent::EndObject | JsonEvent::EndArray => match self.open.pop() {
+                Some(open) => open.value,
+                None => return Err(unexpected("value", &event)),
+            },
+            JsonEvent::String(s) => JsonValue::String(s),
+            JsonEvent::Number(n) if self.options.lossless_numbers => JsonValue::Decimal(n),
+            JsonEvent::Number(n) => JsonValue::Number(n.as_f64()),
+            JsonEvent::Boolean(b) => JsonValue::Boolean(b),
+            JsonEvent::Null => JsonValue::Null,
+        };
+        let policy = self.options.duplicate_keys;
+        match self.open.last_mut() {
+            None => return Ok(Some(value)),
+            Some(Open {
+                value: JsonValue::Object(members),
+                key,
+                seen,
+            }) => {
+                let key = key.take().unwrap_or_default();
+                if policy == DuplicateKeys::Keep {
+                    members.push((key, value));
+                    return Ok(None);
+                }
+                match (policy, seen.get(&key)) {
+                    (DuplicateKeys::LastWins, Some(&i)) => members[i].1 = value,
+                    // `DuplicateKeys::Error` stopped at the key.
+                    (_, Some(_)) => {}
+                    (_, None) => {
+                        seen.insert(key.clone(), members.len());
+                        members.push((key, value));
+                    }
+                }
+            }
+            Some(Open {
+                value: JsonValue::Array(items),
+                ..
+            }) => items.push(value),
+            Some(_) => {}
+        }
+        Ok(None)
+    }
+}
+
+impl Open {
+    fn new(value: JsonValue) -> Self {
+        Open {
+            value,
+            key: None,
+            seen: HashMap::new(),
+        }
+    }
+}
+
+/// An error for `event` where `expected` should have come, to be moved to
+/// where the event was.
+fn unexpected(expected: &str, event: &JsonEvent) -> crate::Error {
+    let found = match event {
+        JsonEvent::StartObject => "'{'".to_string(),
+        JsonEvent::EndObject => "'}'".to_string(),
+        JsonEvent::StartArray => "'['".to_string(),
+        JsonEvent::EndArray => "']'".to_string(),
+        JsonEvent::Key(key) => format!("key {:?}", key),
+        JsonEvent::String(s) => format!("{:?}", s),
+        JsonEvent::Number(n) => format!("`{}`", n),
+        JsonEvent::Boolean(b) => format!("`{}`", b),
+        JsonEvent::Null => "`null`".to_string(),
+    };
+    crate::Error {
+        format: "json",
+        span: 0..0,
+        line: 1,
+        column: 1,
+        expected: Some(expected.to_string()),
+        found,
+        context: vec![],
+    }
+}
+
+/// Parses a document from `reader` without reading it all into memory
+/// first.
+pub fn parse_json_reader(reader: impl Read) -> Result<JsonValue, ReadError> {
+    let mut events = JsonTokenStream::new(reader);
+    let mut builder = JsonBuilder::new();
+    while let Some(event) = events.next() {
+        let value = builder
+            .push(event?)
+            .map_err(|error| events.lexer.locate(error))?;
+        if let Some(value) = value {
+            // Anything but whitespace after the value is an error.
+            if let Some(Err(error)) = events.next() {
+                return Err(error);
+            }
+            return Ok(value);
+        }
+    }
+    Err(events.lexer.incomplete().into())
 }
 
 #[cfg(test)]
@@ -111,6 +1704,62 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_string_escapes_test() {
+        for (input, expected) in [
+            (r#""a\"b""#, "a\"b"),
+            (r#""\\ \/ \b \f \n \r \t""#, "\\ / \u{8} \u{c} \n \r \t"),
+            (r#""caf\u00e9 \u00E9""#, "café é"),
+            (r#""\ud83d\ude00!""#, "\u{1f600}!"),
+            (r#""\u0000""#, "\0"),
+        ] {
+            assert_eq!(super::parse_string(input), Ok(("", expected.to_owned())));
+        }
+        for input in [
+            r#""\x""#,
+            r#""\u12""#,
+            r#""\ud83d""#,
+            r#""\ud83dA""#,
+            r#""\ude00""#,
+            r#""open"#,
+            "\"a\nb\"",
+            "\"a\tb\"",
+            "\"\u{1f}\"",
+        ] {
+            assert!(super::parse_string(input).is_err(), "{:?}", input);
+        }
+        assert_eq!(
+            super::parse_string("\"\u{7f}\""),
+            Ok(("", "\u{7f}".to_owned()))
+        );
+        let error = "[\"line\none\"]".parse::<super::JsonValue>().unwrap_err();
+        assert_eq!((error.span.start, error.line, error.column), (6, 1, 7));
+        let error = r#"["ok", "b\q"]"#.parse::<super::JsonValue>().unwrap_err();
+        assert_eq!(error.span.start, 9);
+    }
+
+    #[test]
+    fn parse_empty_string_test() {
+        for (input, expected) in [
+            (r#""""#, ""),
+            (r#""\n\t""#, "\n\t"),
+            (r#""\\""#, "\\"),
+            (r#""a\"""#, "a\""),
+            (r#""A""#, "A"),
+        ] {
+            assert_eq!(super::parse_string(input), Ok(("", expected.to_owned())));
+        }
+        assert_eq!(super::parse_string(r#""" x"#), Ok((" x", String::new())));
+        assert!(super::parse_string(r#""\""#).is_err());
+        assert_eq!(
+            r#"{"": [""]}"#.parse(),
+            Ok(super::JsonValue::Object(vec![(
+                String::new(),
+                super::JsonValue::Array(vec![super::JsonValue::String(String::new())])
+            )]))
+        );
+    }
+
     #[test]
     fn parse_decimal_number_test() {
         assert_eq!(super::parse_number("123.456"), Ok(("", 123.456)));
@@ -121,6 +1770,389 @@ mod tests {
         assert_eq!(super::parse_number("123"), Ok(("", 123.0)));
     }
 
+    #[test]
+    fn parse_number_grammar_test() {
+        for (input, expected) in [
+            ("0", 0.0),
+            ("-0", -0.0),
+            ("-1", -1.0),
+            ("1e10", 1e10),
+            ("2.5E-3", 2.5e-3),
+            ("1E+2", 100.0),
+            ("-0.5e1", -5.0),
+            ("10", 10.0),
+            ("12.0", 12.0),
+        ] {
+            assert_eq!(super::parse_number(input), Ok(("", expected)), "{}", input);
+        }
+        assert_eq!(super::parse_number("1.x"), Ok((".x", 1.0)));
+        assert_eq!(super::parse_number("1e"), Ok(("e", 1.0)));
+        assert_eq!(super::parse_number("0.5]"), Ok(("]", 0.5)));
+        for input in ["0123", "-", "+1", ".5", "-.5", "00", "-01"] {
+            assert!(super::parse_number(input).is_err(), "{}", input);
+        }
+        assert!("[1, 0123]".parse::<super::JsonValue>().is_err());
+        assert_eq!(
+            "[-1e2]".parse(),
+            Ok(super::JsonValue::Array(vec![super::JsonValue::Number(
+                -100.0
+            )]))
+        );
+    }
+
+    #[test]
+    fn parse_json_lossless_test() {
+        use super::{JsonNumber, JsonOptions, JsonValue};
+        use crate::pretty::{Indent, PrettyPrint};
+
+        let options = JsonOptions {
+            lossless_numbers: true,
+            ..JsonOptions::default()
+        };
+        let input = "[18446744073709551615, -9007199254740993, 1.50, 2e3]";
+        let (_, value) = super::parse_json_with(input, options).unwrap();
+        let JsonValue::Array(numbers) = &value else {
+            panic!("expected an array, got {:?}", value);
+        };
+        let numbers: Vec<_> = numbers
+            .iter()
+            .map(|number| match number {
+                JsonValue::Decimal(number) => number,
+                other => panic!("expected a decimal, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(numbers[0].as_u64(), Some(u64::MAX));
+        assert_eq!(numbers[0].as_i64(), None);
+        assert_eq!(numbers[1].as_i64(), Some(-9007199254740993));
+        assert_eq!((numbers[2].as_str(), numbers[2].as_f64()), ("1.50", 1.5));
+        assert_eq!((numbers[3].as_i64(), numbers[3].as_f64()), (None, 2000.0));
+        assert_eq!(
+            value.to_pretty_string(Indent::compact()),
+            input.replace(' ', "")
+        );
+
+        assert_eq!(super::parse_json("1").unwrap().1, JsonValue::Number(1.0));
+        assert_eq!("-0.5".parse::<JsonNumber>().unwrap().as_f64(), -0.5);
+        assert!("01".parse::<JsonNumber>().is_err());
+    }
+
+    #[test]
+    fn parse_duplicate_keys_test() {
+        use super::{DuplicateKeys, JsonOptions, JsonValue::Number};
+
+        let input = r#"{"a": 1, "b": 2, "a": 3}"#;
+        let parse = |duplicate_keys| {
+            let options = JsonOptions {
+                duplicate_keys,
+                ..JsonOptions::default()
+            };
+            super::parse_json_with(input, options).map(|(_, value)| value)
+        };
+        let kept = parse(DuplicateKeys::Keep).unwrap();
+        let object = kept.as_object().unwrap();
+        assert_eq!(object.keys().collect::<Vec<_>>(), ["a", "b", "a"]);
+        assert_eq!(object.get("a"), Some(&Number(3.0)));
+        assert_eq!((object.len(), object.contains_key("c")), (3, false));
+
+        let first = parse(DuplicateKeys::FirstWins).unwrap();
+        let pairs: Vec<_> = first.as_object().unwrap().iter().collect();
+        assert_eq!(pairs, [("a", &Number(1.0)), ("b", &Number(2.0))]);
+        let last = parse(DuplicateKeys::LastWins).unwrap();
+        let pairs: Vec<_> = last.as_object().unwrap().iter().collect();
+        assert_eq!(pairs, [("a", &Number(3.0)), ("b", &Number(2.0))]);
+
+        let Err(nom::Err::Failure(error)) = parse(DuplicateKeys::Error) else {
+            panic!("expected a failure");
+        };
+        assert_eq!(error.input, &input[17..]);
+        assert_eq!(Number(1.0).as_object(), None);
+    }
+
+    #[test]
+    fn serialize_test() {
+        use super::JsonValue::{self, Array, Boolean, Null, Number, Object, String};
+
+        let value = Object(vec![
+            ("text".to_owned(), String("a\"b\\c\n\u{1}é".to_owned())),
+            (
+                "numbers".to_owned(),
+                Array(vec![Number(-1.5), Number(1e21), Number(f64::NAN)]),
+            ),
+            (
+                "".to_owned(),
+                Array(vec![Boolean(true), Null, Object(vec![])]),
+            ),
+        ]);
+        let compact = value.to_string();
+        assert_eq!(
+            compact,
+            r#"{"text":"a\"b\\c\n\u0001é","numbers":[-1.5,1000000000000000000000,null],"":[true,null,{}]}"#
+        );
+        let pretty = value.to_string_pretty(4);
+        assert!(pretty.starts_with("{\n    \"text\": "), "{}", pretty);
+        for text in [&compact, &pretty] {
+            let parsed: JsonValue = text.parse().unwrap();
+            assert_eq!(parsed.to_string(), compact);
+        }
+    }
+
+    #[test]
+    fn accessors_test() {
+        let value: super::JsonValue =
+            r#"{"config": {"port": 8080, "hosts": ["a", "b"], "debug": false, "x": null}}"#
+                .parse()
+                .unwrap();
+        assert_eq!(value["config"]["port"].as_u64(), Some(8080));
+        assert_eq!(value["config"]["hosts"][1].as_str(), Some("b"));
+        assert_eq!(value["config"]["debug"].as_bool(), Some(false));
+        assert!(value["config"]["x"].is_null());
+        assert!(value["missing"]["deeper"][3].is_null());
+        assert_eq!(
+            value
+                .get("config")
+                .and_then(|c| c.get("port"))
+                .and_then(|p| p.as_i64()),
+            Some(8080)
+        );
+        assert_eq!(value["config"]["hosts"].as_array().map(<[_]>::len), Some(2));
+        assert_eq!(value["config"]["hosts"].get_index(2), None);
+        assert_eq!(value["config"].get_index(0), None);
+        assert_eq!(value["config"]["port"].as_str(), None);
+
+        let number = |n: f64| super::JsonValue::Number(n);
+        assert_eq!(number(-3.0).as_u64(), None);
+        assert_eq!(number(-3.0).as_i64(), Some(-3));
+        assert_eq!(number(1.5).as_i64(), None);
+        assert_eq!(number(1.5).as_f64(), Some(1.5));
+    }
+
+    #[test]
+    fn pointer_test() {
+        use super::JsonValue::{Number, String};
+
+        let mut value: super::JsonValue = r#"{
+            "address": {"city": "Lagos", "zip": "100001"},
+            "a/b": 1, "m~n": 2, "": 3, "list": [10, 20, [30]]
+        }"#
+        .parse()
+        .unwrap();
+        assert_eq!(value.pointer(""), Some(&value.clone()));
+        assert_eq!(
+            value.pointer("/address/city").and_then(|v| v.as_str()),
+            Some("Lagos")
+        );
+        assert_eq!(value.pointer("/a~1b"), Some(&Number(1.0)));
+        assert_eq!(value.pointer("/m~0n"), Some(&Number(2.0)));
+        assert_eq!(value.pointer("/"), Some(&Number(3.0)));
+        assert_eq!(value.pointer("/list/2/0"), Some(&Number(30.0)));
+        for missing in [
+            "address",
+            "/nope",
+            "/list/3",
+            "/list/-",
+            "/list/01",
+            "/list/+1",
+            "/a~2b",
+            "/address/city/x",
+        ] {
+            assert_eq!(value.pointer(missing), None, "{}", missing);
+        }
+
+        *value.pointer_mut("/address/city").unwrap() = String("Abuja".to_owned());
+        *value.pointer_mut("/list/0").unwrap() = Number(11.0);
+        assert_eq!(value["address"]["city"].as_str(), Some("Abuja"));
+        assert_eq!(value["list"][0], Number(11.0));
+        assert!(value.pointer_mut("/list/9").is_none());
+    }
+
+    #[test]
+    fn merge_patch_test() {
+        let parse = |text: &str| text.parse::<super::JsonValue>().unwrap();
+        // The examples from RFC 7386 appendix A.
+        for (target, patch, result) in [
+            (r#"{"a":"b"}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+            (r#"{"a":"b"}"#, r#"{"b":"c"}"#, r#"{"a":"b","b":"c"}"#),
+            (r#"{"a":"b"}"#, r#"{"a":null}"#, r#"{}"#),
+            (r#"{"a":"b","b":"c"}"#, r#"{"a":null}"#, r#"{"b":"c"}"#),
+            (r#"{"a":["b"]}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+            (r#"{"a":"c"}"#, r#"{"a":["b"]}"#, r#"{"a":["b"]}"#),
+            (
+                r#"{"a":{"b":"c"}}"#,
+                r#"{"a":{"b":"d","c":null}}"#,
+                r#"{"a":{"b":"d"}}"#,
+            ),
+            (r#"{"a":[{"b":"c"}]}"#, r#"{"a":[1]}"#, r#"{"a":[1]}"#),
+            (r#"["a","b"]"#, r#"["c","d"]"#, r#"["c","d"]"#),
+            (r#"{"a":"b"}"#, r#"["c"]"#, r#"["c"]"#),
+            (r#"{"a":"foo"}"#, "null", "null"),
+            (r#"{"a":"foo"}"#, r#""bar""#, r#""bar""#),
+            (r#"{"e":null}"#, r#"{"a":1}"#, r#"{"e":null,"a":1}"#),
+            (r#"[1,2]"#, r#"{"a":"b","c":null}"#, r#"{"a":"b"}"#),
+            (
+                r#"{}"#,
+                r#"{"a":{"bb":{"ccc":null}}}"#,
+                r#"{"a":{"bb":{}}}"#,
+            ),
+        ] {
+            let mut value = parse(target);
+            value.merge_patch(&parse(patch));
+            assert_eq!(value, parse(result), "{} + {}", target, patch);
+        }
+    }
+
+    #[test]
+    fn lexer_test() {
+        use super::{JsonEvent, JsonLexer, JsonNumber};
+        use crate::stream::Step;
+
+        let mut lexer = JsonLexer::new();
+        let mut events = vec![];
+        for &byte in r#"{"a": [1, -2.5e3, true], "b" : null, "c":"xé"}"#.as_bytes() {
+            lexer.feed(&[byte]);
+            while let Step::Item(event) = lexer.next_event() {
+                events.push(event);
+            }
+        }
+        assert!(lexer.is_done());
+        let number = |n: &str| JsonEvent::Number(n.parse::<JsonNumber>().unwrap());
+        assert_eq!(
+            events,
+            vec![
+                JsonEvent::StartObject,
+                JsonEvent::Key("a".to_string()),
+                JsonEvent::StartArray,
+                number("1"),
+                number("-2.5e3"),
+                JsonEvent::Boolean(true),
+                JsonEvent::EndArray,
+                JsonEvent::Key("b".to_string()),
+                JsonEvent::Null,
+                JsonEvent::Key("c".to_string()),
+                JsonEvent::String("xé".to_string()),
+                JsonEvent::EndObject,
+            ]
+        );
+
+        // A number at the very end is only complete once input ends.
+        let mut lexer = JsonLexer::new();
+        lexer.feed(b" 12");
+        assert_eq!(lexer.next_event(), Step::NeedMore);
+        lexer.finish();
+        assert_eq!(lexer.next_event(), Step::Item(number("12")));
+        assert_eq!(lexer.next_event(), Step::NeedMore);
+
+        let mut lexer = JsonLexer::new();
+        lexer.feed(b"[\n1");
+        assert_eq!(lexer.next_event(), Step::Item(JsonEvent::StartArray));
+        lexer.feed(b",\n x]");
+        assert_eq!(lexer.next_event(), Step::Item(number("1")));
+        let Step::Error(error) = lexer.next_event() else {
+            panic!("expected an error");
+        };
+        assert_eq!((error.span.clone(), error.line, error.column), (6..7, 3, 2));
+        assert_eq!(lexer.next_event(), Step::Error(error));
+    }
+
+    #[test]
+    fn parse_json_reader_test() {
+        use super::{parse_json_reader, JsonValue, ReadError};
+
+        /// Hands out one byte per read.
+        struct Trickle<'a>(&'a [u8]);
+
+        impl std::io::Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let Some((&first, rest)) = self.0.split_first() else {
+                    return Ok(0);
+                };
+                buf[0] = first;
+                self.0 = rest;
+                Ok(1)
+            }
+        }
+
+        for text in [
+            r#"{"a": [1, 2.5, {"b": null}], "a": "dup", "c": [], "d": {}}"#,
+            "\n[true, false, \"\\ud83d\\ude00\\n\", -0, 1E+2]\n",
+            "0",
+            r#""""#,
+        ] {
+            let expected = text.parse::<JsonValue>().unwrap();
+            assert_eq!(
+                parse_json_reader(Trickle(text.as_bytes())).unwrap(),
+                expected
+            );
+            assert_eq!(parse_json_reader(text.as_bytes()).unwrap(), expected);
+        }
+
+        for (text, span, expected) in [
+            ("[1,]", 3..4, "value"),
+            ("[1] 2", 4..5, "end of input"),
+            ("{\"a\" 1}", 5..6, "':'"),
+            ("[1-]", 2..3, "',' or ']'"),
+            ("[1", 2..2, "more input"),
+            ("\"ab", 3..3, "character"),
+        ] {
+            let Err(ReadError::Parse(error)) = parse_json_reader(Trickle(text.as_bytes())) else {
+                panic!("expected an error for {}", text);
+            };
+            assert_eq!(
+                (error.span, error.expected.as_deref()),
+                (span, Some(expected)),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn json_builder_test() {
+        use super::{DuplicateKeys, JsonBuilder, JsonEvent, JsonOptions, JsonValue};
+
+        let mut builder = JsonBuilder::new();
+        for event in [JsonEvent::StartObject, JsonEvent::Key("a".to_string())] {
+            assert_eq!(builder.push(event), Ok(None));
+        }
+        assert_eq!(builder.push(JsonEvent::Null), Ok(None));
+        let error = builder.push(JsonEvent::Boolean(true)).unwrap_err();
+        assert_eq!(error.expected.as_deref(), Some("key or '}'"));
+        assert_eq!(
+            builder.push(JsonEvent::EndObject),
+            Ok(Some(JsonValue::Object(vec![(
+                "a".to_string(),
+                JsonValue::Null
+            )])))
+        );
+        assert!(builder.push(JsonEvent::EndArray).is_err());
+        assert!(builder.push(JsonEvent::Key("a".to_string())).is_err());
+
+        let options = JsonOptions {
+            duplicate_keys: DuplicateKeys::Error,
+            ..JsonOptions::default()
+        };
+        let input = "{\"a\": 1,\n \"a\": 2}";
+        assert!(super::parse_json_with(input, options).is_err());
+        let mut builder = JsonBuilder::with_options(options);
+        let mut lexer = super::JsonLexer::new();
+        lexer.feed(input.as_bytes());
+        let error = loop {
+            let crate::stream::Step::Item(event) = lexer.next_event() else {
+                panic!("expected an event");
+            };
+            if let Err(error) = builder.push(event) {
+                break lexer.locate(error);
+            }
+        };
+        assert_eq!(
+            (error.span.clone(), error.line, error.column),
+            (10..10, 2, 2)
+        );
+        assert_eq!(
+            error.to_string(),
+            "json error at byte 10: expected new key, found key \"a\""
+        );
+    }
+
     #[test]
     fn parse_boolean_test() {
         assert_eq!(super::parse_boolean("true"), Ok(("", true)));
@@ -157,7 +2189,7 @@ mod tests {
 
     #[test]
     fn parse_object_test() {
-        let result = super::parse_object(r#"{"a": "1", "b": "2"}"#);
+        let result = super::parse_object(r#"{"a": 1, "b": 2}"#);
         // let result = super::parse_object(r#"[1,2,3]"#);
 
         println!("{:?}", result);
@@ -221,75 +2253,255 @@ mod tests {
             ))
         );
     }
-}
 
-// #[test]
-// fn parse_json_test() {
-//     let input = r#"{
-//         "name": "John Doe",
-//         "age": 42,
-//         "isStudent": true,
-//         "grades": [90, 85, 95],
-//         "address": {
-//             "street": "123 Main St",
-//             "city": "Anytown",
-//             "state": "CA",
-//             "zip": "12345"
-//         },
-//         "phoneNumbers": [
-//             {"type": "home", "number": "555-1234"},
-//             {"type": "work", "number": "555-5678"}
-//         ]
-//     }"#;
-
-//     assert_eq!(
-//         parse_json(input),
-//         Ok((
-//             "",
-//             JsonValue::Object(vec![
-//                 ("name".to_owned(), JsonValue::String("John Doe".to_owned())),
-//                 ("age".to_owned(), JsonValue::Number(42.0)),
-//                 ("isStudent".to_owned(), JsonValue::Boolean(true)),
-//                 (
-//                     "grades".to_owned(),
-//                     JsonValue::Array(vec![
-//                         JsonValue::Number(90.0),
-//                         JsonValue::Number(85.0),
-//                         JsonValue::Number(95.0),
-//                     ])
-//                 ),
-//                 (
-//                     "address".to_owned(),
-//                     JsonValue::Object(vec![
-//                         (
-//                             "street".to_owned(),
-//                             JsonValue::String("123 Main St".to_owned())
-//                         ),
-//                         ("city".to_owned(), JsonValue::String("Anytown".to_owned())),
-//                         ("state".to_owned(), JsonValue::String("CA".to_owned())),
-//                         ("zip".to_owned(), JsonValue::String("12345".to_owned())),
-//                     ])
-//                 ),
-//                 (
-//                     "phoneNumbers".to_owned(),
-//                     JsonValue::Array(vec![
-//                         JsonValue::Object(vec![
-//                             ("type".to_owned(), JsonValue::String("home".to_owned())),
-//                             (
-//                                 "number".to_owned(),
-//                                 JsonValue::String("555-1234".to_owned())
-//                             ),
-//                         ]),
-//                         JsonValue::Object(vec![
-//                             ("type".to_owned(), JsonValue::String("work".to_owned())),
-//                             (
-//                                 "number".to_owned(),
-//                                 JsonValue::String("555-5678".to_owned())
-//                             ),
-//                         ]),
-//                     ])
-//                 ),
-//             ])
-//         ))
-//     );
-// }
+    #[test]
+    fn from_str_test() {
+        assert_eq!(
+            " [true, null] \n".parse::<super::JsonValue>(),
+            Ok(super::JsonValue::Array(vec![
+                super::JsonValue::Boolean(true),
+                super::JsonValue::Null
+            ]))
+        );
+        let error = "[1] x".parse::<super::JsonValue>().unwrap_err();
+        assert_eq!(error.span, 4..5);
+    }
+
+    #[test]
+    fn value_test() {
+        use crate::value::{FromValue, ToValue, Value};
+
+        let json: super::JsonValue = r#"{"a": [1, 2.5, null]}"#.parse().unwrap();
+        let value = json.to_value();
+        assert_eq!(
+            value,
+            Value::Table(vec![(
+                "a".to_owned(),
+                Value::Array(vec![Value::Integer(1), Value::Float(2.5), Value::Null])
+            )])
+        );
+        assert_eq!(super::JsonValue::from_value(value), Ok(json));
+        assert_eq!(
+            super::JsonValue::from_value(Value::Float(f64::NAN)),
+            Ok(super::JsonValue::Null)
+        );
+    }
+
+    #[test]
+    fn parse_json_recover_test() {
+        use super::JsonValue::{Array, Boolean, Null, Number, Object};
+
+        let result = super::parse_json_recover(r#"{"a": [1, x, 3], "b": tru, "c": true}"#);
+        assert_eq!(
+            result.value,
+            Object(vec![
+                ("a".to_owned(), Array(vec![Number(1.0), Null, Number(3.0)])),
+                ("b".to_owned(), Null),
+                ("c".to_owned(), Boolean(true)),
+            ])
+        );
+        let spans: Vec<_> = result.errors.iter().map(|e| e.span.clone()).collect();
+        assert_eq!(spans, vec![10..11, 22..25]);
+
+        let result = super::parse_json_recover("[1 2, [3,], {4: 5}");
+        assert_eq!(
+            result.value,
+            Array(vec![Number(1.0), Array(vec![Number(3.0)]), Object(vec![])])
+        );
+        let expected: Vec<_> = result
+            .errors
+            .iter()
+            .map(|e| e.expected.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            expected,
+            vec!["',' or ']'", "value", "string", "',' or ']'"]
+        );
+
+        let result = super::parse_json_recover("[1] ]");
+        assert_eq!(result.value, Array(vec![Number(1.0)]));
+        assert_eq!(result.errors[0].span, 4..5);
+        let result = super::parse_json_recover("[\n  1,\n  x\n]");
+        assert_eq!((result.errors[0].line, result.errors[0].column), (3, 3));
+        assert!(super::parse_json_recover(" [true] ").into_result().is_ok());
+    }
+
+    #[test]
+    fn parse_json_cst_test() {
+        use crate::cst::{GreenToken, SyntaxKind, SyntaxNode};
+
+        let input = "{\n  \"name\": \"parsers\",\n  \"tags\": [1, 2, ]\n}\n";
+        let root = SyntaxNode::new_root(super::parse_json_cst(input));
+        assert_eq!(root.to_string(), input);
+
+        let member = root
+            .descendants()
+            .into_iter()
+            .find(|node| node.kind() == SyntaxKind::Member && node.tokens()[0].text() == "\"name\"")
+            .unwrap();
+        let value = member.tokens().pop().unwrap();
+        assert_eq!(value.text_range(), 12..21);
+        let edited = value.replace_with(GreenToken::new(SyntaxKind::String, "\"nom\""));
+        assert_eq!(
+            edited.to_string(),
+            "{\n  \"name\": \"nom\",\n  \"tags\": [1, 2, ]\n}\n"
+        );
+
+        for broken in ["", "{\"a\" 1", "[1, }] x", "\"open", "{\"a\":: @}"] {
+            assert_eq!(super::parse_json_cst(broken).to_string(), broken);
+        }
+    }
+
+    #[test]
+    fn parse_json_complete_test() {
+        let value = super::parse_json_complete(" [1, 2]\n").unwrap();
+        assert_eq!(value, super::parse_json("[1, 2]").unwrap().1);
+        let error = super::parse_json_complete("[1, 2] 3").unwrap_err();
+        assert_eq!(error.span.start, 7);
+    }
+
+    #[test]
+    fn parse_json_trailing_test() {
+        use nom::error::{Error, ErrorKind};
+
+        assert_eq!(
+            super::parse_json(" 123 \n"),
+            Ok(("", super::JsonValue::Number(123.0)))
+        );
+        assert_eq!(
+            super::parse_json("123abc"),
+            Err(nom::Err::Error(Error::new("abc", ErrorKind::Eof)))
+        );
+        assert!(super::parse_json("[1, 2] 3").is_err());
+        assert!(super::parse_json("  ").is_err());
+        let error = "{} {}".parse::<super::JsonValue>().unwrap_err();
+        assert_eq!(
+            (error.span.start, error.expected.as_deref()),
+            (3, Some("end of input"))
+        );
+    }
+
+    #[test]
+    fn parse_json_spanned_test() {
+        let (rest, value) = super::parse_json_spanned("\n  [1, 2] ").unwrap();
+        assert_eq!(rest, " ");
+        assert_eq!(value.span.fragment, "[1, 2]");
+        assert_eq!((value.span.line, value.span.column), (2, 3));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Config {
+            name: String,
+            port: u16,
+            ratio: f64,
+            tags: Vec<String>,
+        }
+
+        let config: Config =
+            super::from_str(r#"{"name": "api", "port": 8080, "ratio": 0.5, "tags": ["a"]}"#)
+                .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                name: "api".to_string(),
+                port: 8080,
+                ratio: 0.5,
+                tags: vec!["a".to_string()],
+            }
+        );
+        assert_eq!(
+            super::to_string(&config).unwrap(),
+            r#"{"name":"api","port":8080,"ratio":0.5,"tags":["a"]}"#
+        );
+        assert_eq!(
+            super::from_str::<Config>(&super::to_string_pretty(&config).unwrap()),
+            Ok(config)
+        );
+
+        let error = super::from_str::<Config>(r#"{"name": "api"}"#).unwrap_err();
+        assert_eq!(error.to_string(), "missing field `port`");
+        let error = super::from_str::<Config>("[1] x").unwrap_err();
+        assert!(matches!(error, crate::serde_value::Error::Parse(_)));
+    }
+
+    #[test]
+    fn test_shared() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let doc: super::SharedJson = r#"{"a": [1, 2], "b": "x"}"#.parse().unwrap();
+        assert_send_sync(&doc);
+        let copy = doc.clone();
+        let (super::SharedJson::Object(left), super::SharedJson::Object(right)) = (&doc, &copy)
+        else {
+            panic!("expected objects");
+        };
+        assert!(std::sync::Arc::ptr_eq(left, right));
+
+        let changed = doc.with("b", super::SharedJson::Null);
+        assert_eq!(doc.get("b"), Some(&super::SharedJson::String("x".into())));
+        assert_eq!(changed.get("b"), Some(&super::SharedJson::Null));
+        match (doc.get("a"), changed.get("a")) {
+            (Some(super::SharedJson::Array(old)), Some(super::SharedJson::Array(new))) => {
+                assert!(std::sync::Arc::ptr_eq(old, new))
+            }
+            other => panic!("expected arrays, got {:?}", other),
+        }
+        assert_eq!(
+            changed.get("a").and_then(|a| a.index(1)),
+            Some(&super::SharedJson::Number(2.0))
+        );
+
+        let handle = std::thread::spawn(move || copy.to_json());
+        assert_eq!(
+            handle.join().unwrap(),
+            r#"{"a": [1, 2], "b": "x"}"#.parse::<super::JsonValue>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_reparse_cst() {
+        use crate::cst::{GreenElement, SyntaxNode, TextEdit};
+
+        let input = r#"{"a": [1, 2], "b": {"c": true}}"#;
+        let root = SyntaxNode::new_root(super::parse_json_cst(input));
+        let edit = TextEdit {
+            range: 10..11,
+            replacement: "20, 30".to_string(),
+        };
+        let edited = super::reparse_json_cst(&root, &edit);
+        let expected = r#"{"a": [1, 20, 30], "b": {"c": true}}"#;
+        assert_eq!(edited.to_string(), expected);
+        assert_eq!(edited.green(), &super::parse_json_cst(expected));
+        let member = |node: &SyntaxNode| match &node.green().children()[0] {
+            GreenElement::Node(object) => object.children()[4].clone(),
+            other => panic!("expected an object, got {:?}", other),
+        };
+        match (member(&root), member(&edited)) {
+            (GreenElement::Node(old), GreenElement::Node(new)) => {
+                assert!(std::rc::Rc::ptr_eq(&old, &new))
+            }
+            other => panic!("expected members, got {:?}", other),
+        }
+
+        // Removing a bracket changes the structure around it.
+        let edit = TextEdit {
+            range: 11..12,
+            replacement: String::new(),
+        };
+        let edited = super::reparse_json_cst(&root, &edit);
+        let expected = r#"{"a": [1, 2, "b": {"c": true}}"#;
+        assert_eq!(edited.green(), &super::parse_json_cst(expected));
+
+        // As does opening a string that swallows the closing bracket.
+        let edit = TextEdit {
+            range: 28..28,
+            replacement: "\"".to_string(),
+        };
+        let edited = super::reparse_json_cst(&root, &edit);
+        let expected = r#"{"a": [1, 2], "b": {"c": tru"e}}"#;
+        assert_eq!(edited.green(), &super::parse_json_cst(expected));
+    }
+}
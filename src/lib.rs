@@ -1,6 +1,58 @@
+pub mod bibtex;
+pub mod cargo_lock;
+pub mod chess;
+pub mod codec;
+pub mod crc32;
+pub mod cst;
+pub mod date;
+pub mod detect;
+pub mod encoding;
+pub mod error;
+pub mod ffi;
+pub mod format_string;
+pub mod frame;
+pub mod fuzz;
+pub mod gpx;
+pub mod grammar;
+pub mod gzip;
+pub mod hpack;
+pub mod htpasswd;
 pub mod http;
+pub mod irc;
+pub mod limits;
 pub mod json;
+pub mod mmap;
+pub mod money;
+pub mod obj;
+pub mod packet;
+pub mod pep508;
+pub mod phone;
+pub mod png;
+pub mod prelude;
+pub mod pretty;
+pub mod procfs;
+pub mod quantity;
+pub mod regex;
+pub mod registry;
+pub mod serde_value;
+pub mod span;
+pub mod stream;
+pub mod subtitles;
+pub mod template;
+pub mod testing;
+pub mod value;
+pub mod visit;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod websocket;
 pub mod yaml;
+pub mod zip;
+
+pub use detect::{detect, parse_any, Document, Format, FormatGuess};
+pub use error::{Error, ParseError, Recovered};
+pub use http::{OwnedURI, ParseMode, RelativeRef, UriBuf, UriReference, URI};
+pub use json::{JsonValue, SharedJson};
+pub use value::{convert, FromValue, ToValue, Value};
 
 pub fn add(left: usize, right: usize) -> usize {
     left + right
@@ -0,0 +1,123 @@
+//! Limits on how deeply input may nest and how long it may be, so hostile
+//! input fails with an error instead of overflowing the stack or using
+//! unbounded memory. They only apply with the `hardened` feature; without
+//! it both are `usize::MAX`.
+
+use std::cell::Cell;
+
+use nom::error::{ErrorKind, ParseError};
+use nom::{Err as NomErr, IResult};
+
+use crate::error::Fragment;
+use crate::Error;
+
+/// How many arrays, objects, groups, sections and the like may be open at
+/// once, across all the parsers running on a thread.
+pub const MAX_DEPTH: usize = if cfg!(feature = "hardened") {
+    128
+} else {
+    usize::MAX
+};
+
+/// The longest input, in bytes, that the entry points returning an `Error`
+/// accept.
+pub const MAX_INPUT_LEN: usize = if cfg!(feature = "hardened") {
+    64 << 20
+} else {
+    usize::MAX
+};
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// One level of nesting, given back when dropped.
+pub(crate) struct Depth(());
+
+impl Depth {
+    /// `None` if `MAX_DEPTH` levels are already open.
+    pub(crate) fn enter() -> Option<Depth> {
+        DEPTH.with(|depth| {
+            let current = depth.get();
+            (current < MAX_DEPTH).then(|| {
+                depth.set(current + 1);
+                Depth(())
+            })
+        })
+    }
+}
+
+impl Drop for Depth {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Runs `parser` one level deeper, failing with `ErrorKind::TooLarge` past
+/// `MAX_DEPTH`. Wraps the point where a recursive parser recurses; `open`
+/// recognises the start of a nested value, such as its opening bracket, so
+/// that only input which really nests counts against the limit.
+pub(crate) fn nested<I: Clone, T, O, E: ParseError<I>>(
+    mut open: impl FnMut(I) -> IResult<I, T, E>,
+    mut parser: impl FnMut(I) -> IResult<I, O, E>,
+) -> impl FnMut(I) -> IResult<I, O, E> {
+    move |input: I| {
+        open(input.clone())?;
+        match Depth::enter() {
+            Some(_depth) => parser(input),
+            None => Err(NomErr::Failure(E::from_error_kind(
+                input,
+                ErrorKind::TooLarge,
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "hardened")]
+pub(crate) fn check_len<I: Fragment>(format: &'static str, input: I) -> Result<(), Error> {
+    if input.byte_len() <= MAX_INPUT_LEN {
+        return Ok(());
+    }
+    let mut error = Error::expected(
+        format,
+        input,
+        input,
+        format!("at most {} bytes", MAX_INPUT_LEN),
+    );
+    error.found = format!("{} bytes", input.byte_len());
+    Err(error)
+}
+
+#[cfg(not(feature = "hardened"))]
+#[inline(always)]
+pub(crate) fn check_len<I: Fragment>(_format: &'static str, _input: I) -> Result<(), Error> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_depth() {
+        let depths: Vec<_> = (0..3).map(|_| Depth::enter()).collect();
+        assert!(depths.iter().all(Option::is_some));
+        assert_eq!(DEPTH.with(Cell::get), 3);
+        drop(depths);
+        assert_eq!(DEPTH.with(Cell::get), 0);
+    }
+
+    #[cfg(feature = "hardened")]
+    #[test]
+    fn test_hardened() {
+        let mut input = "[".repeat(MAX_DEPTH);
+        input.push_str(&"]".repeat(MAX_DEPTH));
+        input.parse::<crate::JsonValue>().unwrap();
+
+        let input = format!("[{}]", input);
+        let error = input.parse::<crate::JsonValue>().unwrap_err();
+        assert_eq!(error.span, MAX_DEPTH..MAX_DEPTH + 1);
+        assert_eq!(error.expected.as_deref(), Some("less deeply nested input"));
+        assert_eq!(DEPTH.with(Cell::get), 0);
+    }
+}
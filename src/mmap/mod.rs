@@ -0,0 +1,154 @@
+//! Parsing files through a memory map instead of reading them into a
+//! `String` first. Parsers that borrow from their input then borrow
+//! straight from the page cache, which matters for multi-gigabyte files.
+//!
+//! A mapped file must not be truncated by another process while it is
+//! mapped; on most platforms reading the missing pages raises `SIGBUS`.
+#![cfg(feature = "mmap")]
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::json::JsonValue;
+use crate::Error;
+
+#[derive(Debug)]
+pub struct MappedFile {
+    map: Mmap,
+}
+
+impl MappedFile {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the map is read-only and private to this process; the
+        // module docs cover files that change underneath it.
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(MappedFile { map })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// The contents as text, without copying. A UTF-8 byte order mark is
+    /// skipped; text in any other encoding is an error, as it would have
+    /// to be transcoded into a new buffer.
+    pub fn text(&self) -> Result<&str, Error> {
+        let bytes = self.bytes();
+        let bom = if bytes.starts_with(b"\xef\xbb\xbf") {
+            3
+        } else {
+            0
+        };
+        std::str::from_utf8(&bytes[bom..]).map_err(|error| {
+            let at = &bytes[bom + error.valid_up_to()..];
+            Error::expected("encoding", bytes, at, "valid UTF-8")
+        })
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.bytes()
+    }
+}
+
+#[derive(Debug)]
+pub enum FileError {
+    Io(io::Error),
+    Parse(Box<Error>),
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::Io(error) => write!(f, "{}", error),
+            FileError::Parse(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FileError::Io(error) => Some(error),
+            FileError::Parse(error) => Some(error.as_ref()),
+        }
+    }
+}
+
+impl From<io::Error> for FileError {
+    fn from(error: io::Error) -> Self {
+        FileError::Io(error)
+    }
+}
+
+impl From<Error> for FileError {
+    fn from(error: Error) -> Self {
+        FileError::Parse(Box::new(error))
+    }
+}
+
+/// Maps the file at `path` and runs `parse` over its text. The result
+/// can't borrow from the text, since the map is gone once this returns;
+/// use `MappedFile` directly to keep borrowed results around.
+pub fn parse_file<T>(
+    path: impl AsRef<Path>,
+    parse: impl FnOnce(&str) -> Result<T, Error>,
+) -> Result<T, FileError> {
+    let file = MappedFile::open(path)?;
+    Ok(parse(file.text()?)?)
+}
+
+pub fn parse_json_file(path: impl AsRef<Path>) -> Result<JsonValue, FileError> {
+    parse_file(path, str::parse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(name: &str, contents: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("parsers-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let path = write("ok.json", b"\xef\xbb\xbf[true, null]\n");
+        assert_eq!(
+            parse_json_file(&path).unwrap(),
+            JsonValue::Array(vec![JsonValue::Boolean(true), JsonValue::Null])
+        );
+        let file = MappedFile::open(&path).unwrap();
+        assert_eq!(file.len(), 16);
+        std::fs::remove_file(path).unwrap();
+
+        let path = write("bad.json", b"[1] x");
+        let Err(FileError::Parse(error)) = parse_json_file(&path) else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(error.span, 4..5);
+        std::fs::remove_file(path).unwrap();
+
+        let path = write("latin1.json", b"\"caf\xe9\"");
+        let Err(FileError::Parse(error)) = parse_json_file(&path) else {
+            panic!("expected an encoding error");
+        };
+        assert_eq!(error.span, 4..5);
+        std::fs::remove_file(path).unwrap();
+
+        assert!(matches!(
+            parse_json_file("/nonexistent/file.json"),
+            Err(FileError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,307 @@
+use std::fmt;
+use std::str::FromStr;
+
+use nom::{
+    branch::alt,
+    character::complete::{char, digit1, one_of, space0},
+    combinator::{opt, recognize},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::many0,
+    sequence::pair,
+    Err as NomErr, IResult,
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Currency {
+    /// The ISO 4217 code.
+    pub code: &'static str,
+    pub symbols: &'static [&'static str],
+    /// The number of digits after the decimal point, e.g. 2 for cents.
+    pub exponent: u32,
+}
+
+/// An amount of money in the currency's minor unit, so `$1.50` is 150.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Money {
+    pub minor_units: i64,
+    pub currency: &'static Currency,
+}
+
+pub const CURRENCIES: &[Currency] = &[
+    Currency {
+        code: "USD",
+        symbols: &["$", "US$"],
+        exponent: 2,
+    },
+    Currency {
+        code: "EUR",
+        symbols: &["€"],
+        exponent: 2,
+    },
+    Currency {
+        code: "GBP",
+        symbols: &["£"],
+        exponent: 2,
+    },
+    Currency {
+        code: "JPY",
+        symbols: &["¥", "円"],
+        exponent: 0,
+    },
+    Currency {
+        code: "CHF",
+        symbols: &[],
+        exponent: 2,
+    },
+    Currency {
+        code: "CAD",
+        symbols: &["C$", "CA$"],
+        exponent: 2,
+    },
+    Currency {
+        code: "AUD",
+        symbols: &["A$", "AU$"],
+        exponent: 2,
+    },
+    Currency {
+        code: "BRL",
+        symbols: &["R$"],
+        exponent: 2,
+    },
+    Currency {
+        code: "INR",
+        symbols: &["₹"],
+        exponent: 2,
+    },
+    Currency {
+        code: "NGN",
+        symbols: &["₦"],
+        exponent: 2,
+    },
+    Currency {
+        code: "KRW",
+        symbols: &["₩"],
+        exponent: 0,
+    },
+    Currency {
+        code: "KWD",
+        symbols: &[],
+        exponent: 3,
+    },
+];
+
+/// Looks up a currency by its ISO 4217 code.
+pub fn currency(code: &str) -> Option<&'static Currency> {
+    CURRENCIES.iter().find(|currency| currency.code == code)
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.minor_units < 0 { "-" } else { "" };
+        let units = self.minor_units.unsigned_abs();
+        match self.currency.exponent {
+            0 => write!(f, "{} {}{}", self.currency.code, sign, units),
+            exponent => {
+                let scale = 10u64.pow(exponent);
+                write!(
+                    f,
+                    "{} {}{}.{:0width$}",
+                    self.currency.code,
+                    sign,
+                    units / scale,
+                    units % scale,
+                    width = exponent as usize
+                )
+            }
+        }
+    }
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+/// Matches a currency code or symbol, preferring the longest symbol so that
+/// `US$` isn't read as `$` followed by garbage.
+fn currency_marker(input: &str) -> Res<&str, &'static Currency> {
+    let by_code = CURRENCIES
+        .iter()
+        .filter(|currency| input.starts_with(currency.code))
+        .map(|currency| (currency, currency.code.len()));
+    let by_symbol = CURRENCIES.iter().flat_map(|currency| {
+        currency
+            .symbols
+            .iter()
+            .filter(|symbol| input.starts_with(**symbol))
+            .map(move |symbol| (currency, symbol.len()))
+    });
+    match by_code.chain(by_symbol).max_by_key(|(_, len)| *len) {
+        Some((currency, len)) => Ok((&input[len..], currency)),
+        None => Err(NomErr::Error(VerboseError { errors: vec![] })),
+    }
+}
+
+/// Digits with `.` or `,` separators, without a trailing separator.
+fn number(input: &str) -> Res<&str, &str> {
+    recognize(pair(digit1, many0(pair(one_of(".,"), digit1))))(input)
+}
+
+/// Works out which separator is the decimal point and converts the number
+/// to minor units. A lone separator followed by exactly three digits is
+/// read as a thousands separator, unless the currency has three decimals.
+fn minor_units(number: &str, exponent: u32) -> Result<i64, &'static str> {
+    let decimal = match (number.rfind('.'), number.rfind(',')) {
+        (Some(dot), Some(comma)) => Some(dot.max(comma)),
+        (Some(i), None) | (None, Some(i)) => {
+            let separator = number.as_bytes()[i];
+            let single = number.bytes().filter(|&b| b == separator).count() == 1;
+            let grouping = number.len() - i - 1 == 3 && exponent != 3;
+            (single && !grouping).then_some(i)
+        }
+        (None, None) => None,
+    };
+    let (whole, fraction) = match decimal {
+        Some(i) => (&number[..i], &number[i + 1..]),
+        None => (number, ""),
+    };
+    if whole.contains('.') && whole.contains(',') {
+        return Err("mixed thousands separators");
+    }
+    let groups: Vec<_> = whole.split(['.', ',']).collect();
+    if groups.len() > 1
+        && (groups[0].len() > 3
+            || groups[0].starts_with('0')
+            || groups[1..].iter().any(|g| g.len() != 3))
+    {
+        return Err("invalid digit grouping");
+    }
+    if fraction.len() > exponent as usize {
+        return Err("too many decimal places");
+    }
+    let digits = format!(
+        "{}{}{}",
+        groups.concat(),
+        fraction,
+        "0".repeat(exponent as usize - fraction.len())
+    );
+    digits.parse().map_err(|_| "amount out of range")
+}
+
+/// Parses an amount such as `$1,234.56`, `1.234,56 €` or `USD 42`. The
+/// currency may come before or after the number, as a symbol or a code.
+pub fn money(input: &str) -> Res<&str, Money> {
+    let prefixed = |input| {
+        let (input, sign) = opt(char('-'))(input)?;
+        let (input, currency) = currency_marker(input)?;
+        let (input, _) = space0(input)?;
+        let (input, inner_sign) = opt(char('-'))(input)?;
+        let (input, number) = number(input)?;
+        Ok((input, (sign.or(inner_sign), number, currency)))
+    };
+    let suffixed = |input| {
+        let (input, sign) = opt(char('-'))(input)?;
+        let (input, number) = number(input)?;
+        let (input, _) = space0(input)?;
+        let (input, currency) = currency_marker(input)?;
+        Ok((input, (sign, number, currency)))
+    };
+    let (next_input, (sign, number, currency)) =
+        context("money", alt((prefixed, suffixed)))(input)?;
+    let units = minor_units(number, currency.exponent).map_err(|reason| failure(input, reason))?;
+    Ok((
+        next_input,
+        Money {
+            minor_units: if sign.is_some() { -units } else { units },
+            currency,
+        },
+    ))
+}
+
+impl FromStr for Money {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::error::parse_all("money", s, money)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> (i64, &'static str) {
+        let (rest, money) = money(input).unwrap();
+        assert_eq!(rest, "");
+        (money.minor_units, money.currency.code)
+    }
+
+    #[test]
+    fn test_money() {
+        assert_eq!(parse("$1,234.56"), (123456, "USD"));
+        assert_eq!(parse("1.234,56 €"), (123456, "EUR"));
+        assert_eq!(parse("USD 42"), (4200, "USD"));
+        assert_eq!(parse("42USD"), (4200, "USD"));
+        assert_eq!(parse("US$ 0.5"), (50, "USD"));
+        assert_eq!(parse("R$ 10,5"), (1050, "BRL"));
+        assert_eq!(parse("¥1,000"), (1000, "JPY"));
+        assert_eq!(parse("-£3.10"), (-310, "GBP"));
+        assert_eq!(parse("€-3"), (-300, "EUR"));
+        assert_eq!(parse("KWD 1.250"), (1250, "KWD"));
+        assert_eq!(parse("1.000.000 €"), (100000000, "EUR"));
+        assert_eq!("$1.50".parse::<Money>().unwrap().minor_units, 150);
+        assert!("$1.50 and change".parse::<Money>().is_err());
+    }
+
+    #[test]
+    fn test_money_errors() {
+        assert_eq!(
+            money("$1.234"),
+            Ok((
+                "",
+                Money {
+                    minor_units: 123400,
+                    currency: currency("USD").unwrap()
+                }
+            ))
+        );
+        assert_eq!(
+            money("$1.999"),
+            Ok((
+                "",
+                Money {
+                    minor_units: 199900,
+                    currency: currency("USD").unwrap()
+                }
+            ))
+        );
+        assert_eq!(
+            money("$1.5.5"),
+            Err(failure("$1.5.5", "invalid digit grouping"))
+        );
+        assert_eq!(
+            money("$1,2.345"),
+            Err(failure("$1,2.345", "invalid digit grouping"))
+        );
+        assert_eq!(
+            money("¥1.5"),
+            Err(failure("¥1.5", "too many decimal places"))
+        );
+        assert_eq!(
+            money("$99999999999999999999"),
+            Err(failure("$99999999999999999999", "amount out of range"))
+        );
+        assert!(money("42").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let (_, amount) = money("-$1,234.05").unwrap();
+        assert_eq!(amount.to_string(), "USD -1234.05");
+        let (_, amount) = money("¥500").unwrap();
+        assert_eq!(amount.to_string(), "JPY 500");
+    }
+}
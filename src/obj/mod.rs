@@ -0,0 +1,331 @@
+use nom::{
+    branch::alt,
+    bytes::complete::take_till1,
+    character::complete::{char, i64, line_ending, not_line_ending, space1},
+    combinator::{eof, map, opt, recognize, value},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::{count, many0, many1},
+    number::complete::double,
+    sequence::{pair, preceded, tuple},
+    Err as NomErr, IResult,
+};
+
+/// A corner of a face. Indices are resolved to zero-based positions in the
+/// mesh's vertex attribute lists.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FaceVertex {
+    pub position: usize,
+    pub texture: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Face {
+    pub vertices: Vec<FaceVertex>,
+    /// Index into `Mesh::objects`.
+    pub object: Option<usize>,
+    /// Indices into `Mesh::groups`.
+    pub groups: Vec<usize>,
+    /// Index into `Mesh::materials`.
+    pub material: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Mesh {
+    /// `v` lines. The optional `w` component is dropped.
+    pub positions: Vec<[f64; 3]>,
+    /// `vt` lines, with missing components set to 0.
+    pub texture_coords: Vec<[f64; 3]>,
+    pub normals: Vec<[f64; 3]>,
+    pub faces: Vec<Face>,
+    pub objects: Vec<String>,
+    pub groups: Vec<String>,
+    pub materials: Vec<String>,
+    /// Files named by `mtllib`.
+    pub material_libraries: Vec<String>,
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+/// The raw `v/vt/vn` indices of a face corner, before resolution.
+type RawIndex = (i64, Option<i64>, Option<i64>);
+
+#[derive(Debug, PartialEq, Clone)]
+enum Statement<'a> {
+    Position([f64; 3]),
+    TextureCoord([f64; 3]),
+    Normal([f64; 3]),
+    Face(Vec<RawIndex>),
+    Object(&'a str),
+    Group(Vec<&'a str>),
+    UseMaterial(&'a str),
+    MaterialLibrary(Vec<&'a str>),
+    Other,
+}
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+/// A backslash at the end of a line joins it with the next one.
+fn continuation(input: &str) -> Res<&str, &str> {
+    recognize(pair(char('\\'), line_ending))(input)
+}
+
+fn ws1(input: &str) -> Res<&str, &str> {
+    recognize(many1(alt((space1, continuation))))(input)
+}
+
+fn ws0(input: &str) -> Res<&str, &str> {
+    recognize(many0(alt((space1, continuation))))(input)
+}
+
+fn end_of_statement(input: &str) -> Res<&str, ()> {
+    value(
+        (),
+        tuple((
+            ws0,
+            opt(pair(char('#'), not_line_ending)),
+            alt((line_ending, eof)),
+        )),
+    )(input)
+}
+
+fn name(input: &str) -> Res<&str, &str> {
+    take_till1(|c: char| c.is_whitespace() || c == '#')(input)
+}
+
+fn names(input: &str) -> Res<&str, Vec<&str>> {
+    many1(preceded(ws1, name))(input)
+}
+
+fn number(input: &str) -> Res<&str, f64> {
+    preceded(ws1, double)(input)
+}
+
+fn vector(input: &str) -> Res<&str, [f64; 3]> {
+    map(count(number, 3), |v| [v[0], v[1], v[2]])(input)
+}
+
+fn position(input: &str) -> Res<&str, [f64; 3]> {
+    let (input, xyz) = vector(input)?;
+    let (input, _w) = opt(number)(input)?;
+    Ok((input, xyz))
+}
+
+fn texture_coord(input: &str) -> Res<&str, [f64; 3]> {
+    map(tuple((number, opt(number), opt(number))), |(u, v, w)| {
+        [u, v.unwrap_or(0.0), w.unwrap_or(0.0)]
+    })(input)
+}
+
+/// `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+fn face_vertex(input: &str) -> Res<&str, RawIndex> {
+    map(
+        pair(
+            i64,
+            opt(preceded(
+                char('/'),
+                pair(opt(i64), opt(preceded(char('/'), i64))),
+            )),
+        ),
+        |(v, rest)| match rest {
+            Some((vt, vn)) => (v, vt, vn),
+            None => (v, None, None),
+        },
+    )(input)
+}
+
+fn statement(input: &str) -> Res<&str, Statement<'_>> {
+    let (input, keyword) = name(input)?;
+    let (input, statement) = match keyword {
+        "v" => map(position, Statement::Position)(input)?,
+        "vt" => map(texture_coord, Statement::TextureCoord)(input)?,
+        "vn" => map(vector, Statement::Normal)(input)?,
+        "f" => map(many1(preceded(ws1, face_vertex)), Statement::Face)(input)?,
+        "o" => map(preceded(ws1, name), Statement::Object)(input)?,
+        "g" => map(names, Statement::Group)(input)?,
+        "usemtl" => map(preceded(ws1, name), Statement::UseMaterial)(input)?,
+        "mtllib" => map(names, Statement::MaterialLibrary)(input)?,
+        // Smoothing groups, free-form geometry and the like.
+        _ => value(Statement::Other, not_line_ending)(input)?,
+    };
+    let (input, _) = end_of_statement(input)?;
+    Ok((input, statement))
+}
+
+/// Blank lines and `#` comments.
+fn ignored(input: &str) -> Res<&str, ()> {
+    value(
+        (),
+        many0(alt((
+            recognize(pair(ws0, line_ending)),
+            recognize(tuple((ws0, char('#'), not_line_ending, opt(line_ending)))),
+            recognize(pair(space1, eof)),
+        ))),
+    )(input)
+}
+
+/// Resolves a one-based or negative (relative to the end) index.
+fn resolve(index: i64, len: usize) -> Option<usize> {
+    match index {
+        0 => None,
+        i if i > 0 => Some(i as usize - 1).filter(|&i| i < len),
+        i => len.checked_sub(i.unsigned_abs() as usize),
+    }
+}
+
+fn intern(names: &mut Vec<String>, name: &str) -> usize {
+    match names.iter().position(|n| n == name) {
+        Some(index) => index,
+        None => {
+            names.push(name.to_string());
+            names.len() - 1
+        }
+    }
+}
+
+/// Parses an OBJ file into an indexed mesh.
+pub fn obj(input: &str) -> Res<&str, Mesh> {
+    let mut mesh = Mesh::default();
+    let mut object = None;
+    let mut groups = Vec::new();
+    let mut material = None;
+    let (mut input, _) = ignored(input)?;
+    while !input.is_empty() {
+        let start = input;
+        let (next_input, statement) = context("statement", statement)(input)?;
+        match statement {
+            Statement::Position(v) => mesh.positions.push(v),
+            Statement::TextureCoord(vt) => mesh.texture_coords.push(vt),
+            Statement::Normal(vn) => mesh.normals.push(vn),
+            Statement::Face(raw) => {
+                if raw.len() < 3 {
+                    return Err(failure(start, "face needs at least three vertices"));
+                }
+                let vertices = raw
+                    .into_iter()
+                    .map(|(v, vt, vn)| {
+                        Some(FaceVertex {
+                            position: resolve(v, mesh.positions.len())?,
+                            texture: match vt {
+                                Some(vt) => Some(resolve(vt, mesh.texture_coords.len())?),
+                                None => None,
+                            },
+                            normal: match vn {
+                                Some(vn) => Some(resolve(vn, mesh.normals.len())?),
+                                None => None,
+                            },
+                        })
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| failure(start, "index out of range"))?;
+                mesh.faces.push(Face {
+                    vertices,
+                    object,
+                    groups: groups.clone(),
+                    material,
+                });
+            }
+            Statement::Object(name) => object = Some(intern(&mut mesh.objects, name)),
+            Statement::Group(names) => {
+                groups = names
+                    .into_iter()
+                    .map(|name| intern(&mut mesh.groups, name))
+                    .collect();
+            }
+            Statement::UseMaterial(name) => material = Some(intern(&mut mesh.materials, name)),
+            Statement::MaterialLibrary(files) => mesh
+                .material_libraries
+                .extend(files.into_iter().map(String::from)),
+            Statement::Other => {}
+        }
+        let (next_input, _) = ignored(next_input)?;
+        input = next_input;
+    }
+    Ok((input, mesh))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE_FACE: &str = "# a quad split into two triangles\n\
+mtllib scene.mtl\n\
+o Quad\n\
+v 0 0 0\n\
+v 1 0 0\n\
+v 1 1 0 1.0\n\
+v 0 1 0\n\
+vt 0 0\n\
+vt 1 1\n\
+vn 0 0 1\n\
+g front \\\n  visible\n\
+usemtl Red\n\
+s off\n\
+f 1/1/1 2/2/1 3//1\n\
+f -4 -2 -1 # negative indices\n";
+
+    #[test]
+    fn test_obj() {
+        let (rest, mesh) = obj(CUBE_FACE).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.positions[2], [1.0, 1.0, 0.0]);
+        assert_eq!(mesh.texture_coords[1], [1.0, 1.0, 0.0]);
+        assert_eq!(mesh.normals, vec![[0.0, 0.0, 1.0]]);
+        assert_eq!(mesh.objects, vec!["Quad"]);
+        assert_eq!(mesh.groups, vec!["front", "visible"]);
+        assert_eq!(mesh.materials, vec!["Red"]);
+        assert_eq!(mesh.material_libraries, vec!["scene.mtl"]);
+        assert_eq!(
+            mesh.faces[0],
+            Face {
+                vertices: vec![
+                    FaceVertex {
+                        position: 0,
+                        texture: Some(0),
+                        normal: Some(0),
+                    },
+                    FaceVertex {
+                        position: 1,
+                        texture: Some(1),
+                        normal: Some(0),
+                    },
+                    FaceVertex {
+                        position: 2,
+                        texture: None,
+                        normal: Some(0),
+                    },
+                ],
+                object: Some(0),
+                groups: vec![0, 1],
+                material: Some(0),
+            }
+        );
+        let positions: Vec<_> = mesh.faces[1].vertices.iter().map(|v| v.position).collect();
+        assert_eq!(positions, vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn test_face_vertex() {
+        assert_eq!(face_vertex("3"), Ok(("", (3, None, None))));
+        assert_eq!(face_vertex("3/4"), Ok(("", (3, Some(4), None))));
+        assert_eq!(face_vertex("3//-1"), Ok(("", (3, None, Some(-1)))));
+        assert_eq!(face_vertex("3/4/5"), Ok(("", (3, Some(4), Some(5)))));
+    }
+
+    #[test]
+    fn test_errors() {
+        let input = "v 0 0 0\nf 1 2 3\n";
+        assert_eq!(obj(input), Err(failure(&input[8..], "index out of range")));
+        let input = "v 0 0 0\nf 1 1\n";
+        assert_eq!(
+            obj(input),
+            Err(failure(&input[8..], "face needs at least three vertices"))
+        );
+        assert!(obj("v 0 0\n").is_err());
+    }
+}
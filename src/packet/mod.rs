@@ -0,0 +1,469 @@
+use nom::{
+    bytes::complete::take,
+    combinator::{map, rest},
+    error::{context, VerboseError, VerboseErrorKind},
+    number::complete::{be_u16, be_u32, u8},
+    sequence::tuple,
+    Err as NomErr, IResult,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EtherType {
+    Ipv4,
+    Ipv6,
+    Arp,
+    Other(u16),
+}
+
+impl From<u16> for EtherType {
+    fn from(value: u16) -> Self {
+        match value {
+            0x0800 => EtherType::Ipv4,
+            0x86dd => EtherType::Ipv6,
+            0x0806 => EtherType::Arp,
+            other => EtherType::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Ethernet<'a> {
+    pub destination: [u8; 6],
+    pub source: [u8; 6],
+    /// The VLAN identifier from an 802.1Q tag, if the frame has one.
+    pub vlan: Option<u16>,
+    pub ether_type: EtherType,
+    pub payload: &'a [u8],
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Ipv4<'a> {
+    pub dscp: u8,
+    pub ecn: u8,
+    pub total_length: u16,
+    pub identification: u16,
+    pub dont_fragment: bool,
+    pub more_fragments: bool,
+    /// In units of eight bytes.
+    pub fragment_offset: u16,
+    pub ttl: u8,
+    pub protocol: u8,
+    pub checksum: u16,
+    pub source: [u8; 4],
+    pub destination: [u8; 4],
+    pub options: &'a [u8],
+    pub payload: &'a [u8],
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct TcpFlags {
+    pub ns: bool,
+    pub cwr: bool,
+    pub ece: bool,
+    pub urg: bool,
+    pub ack: bool,
+    pub psh: bool,
+    pub rst: bool,
+    pub syn: bool,
+    pub fin: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Tcp<'a> {
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub sequence: u32,
+    pub acknowledgment: u32,
+    pub flags: TcpFlags,
+    pub window: u16,
+    pub checksum: u16,
+    pub urgent_pointer: u16,
+    /// The raw options. Use [`tcp_options`] to decode them.
+    pub options: &'a [u8],
+    pub payload: &'a [u8],
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TcpOption<'a> {
+    EndOfList,
+    Nop,
+    MaximumSegmentSize(u16),
+    WindowScale(u8),
+    SackPermitted,
+    /// Pairs of left and right edges of the acknowledged blocks.
+    Sack(&'a [u8]),
+    Timestamps(u32, u32),
+    Unknown(u8, &'a [u8]),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Udp<'a> {
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub length: u16,
+    pub checksum: u16,
+    pub payload: &'a [u8],
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Transport<'a> {
+    Tcp(Tcp<'a>),
+    Udp(Udp<'a>),
+    Other(u8, &'a [u8]),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Network<'a> {
+    Ipv4(Ipv4<'a>),
+    Other(EtherType, &'a [u8]),
+}
+
+/// A frame dissected as far as this module understands it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Packet<'a> {
+    pub ethernet: Ethernet<'a>,
+    pub network: Network<'a>,
+    pub transport: Option<Transport<'a>>,
+}
+
+pub const PROTOCOL_TCP: u8 = 6;
+pub const PROTOCOL_UDP: u8 = 17;
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a [u8], reason: &'static str) -> NomErr<VerboseError<&'a [u8]>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+fn mac(input: &[u8]) -> Res<&[u8], [u8; 6]> {
+    map(take(6usize), |b: &[u8]| {
+        [b[0], b[1], b[2], b[3], b[4], b[5]]
+    })(input)
+}
+
+fn ipv4_address(input: &[u8]) -> Res<&[u8], [u8; 4]> {
+    map(take(4usize), |b: &[u8]| [b[0], b[1], b[2], b[3]])(input)
+}
+
+/// Parses an Ethernet II header, including a single 802.1Q VLAN tag. The
+/// payload is the rest of the frame.
+pub fn ethernet(input: &[u8]) -> Res<&[u8], Ethernet<'_>> {
+    let (input, (destination, source, mut ether_type)) =
+        context("ethernet header", tuple((mac, mac, be_u16)))(input)?;
+    let (input, vlan) = match ether_type {
+        0x8100 => {
+            let (input, (tci, inner_type)) = context("vlan tag", tuple((be_u16, be_u16)))(input)?;
+            ether_type = inner_type;
+            (input, Some(tci & 0x0fff))
+        }
+        _ => (input, None),
+    };
+    let (input, payload) = rest(input)?;
+    Ok((
+        input,
+        Ethernet {
+            destination,
+            source,
+            vlan,
+            ether_type: ether_type.into(),
+            payload,
+        },
+    ))
+}
+
+/// Parses an IPv4 header. The payload is bounded by the total length, so
+/// any Ethernet padding is left in the remaining input.
+pub fn ipv4(input: &[u8]) -> Res<&[u8], Ipv4<'_>> {
+    let start = input;
+    let (input, (version_ihl, tos, total_length, identification, flags_fragment)) =
+        context("ipv4 header", tuple((u8, u8, be_u16, be_u16, be_u16)))(input)?;
+    let (input, (ttl, protocol, checksum, source, destination)) = context(
+        "ipv4 header",
+        tuple((u8, u8, be_u16, ipv4_address, ipv4_address)),
+    )(input)?;
+    if version_ihl >> 4 != 4 {
+        return Err(failure(start, "not an ipv4 packet"));
+    }
+    let header_length = usize::from(version_ihl & 0x0f) * 4;
+    if header_length < 20 || usize::from(total_length) < header_length {
+        return Err(failure(start, "invalid ipv4 header length"));
+    }
+    let (input, options) = context("ipv4 options", take(header_length - 20))(input)?;
+    let (input, payload) = context(
+        "ipv4 payload",
+        take(usize::from(total_length) - header_length),
+    )(input)?;
+    Ok((
+        input,
+        Ipv4 {
+            dscp: tos >> 2,
+            ecn: tos & 0x03,
+            total_length,
+            identification,
+            dont_fragment: flags_fragment & 0x4000 != 0,
+            more_fragments: flags_fragment & 0x2000 != 0,
+            fragment_offset: flags_fragment & 0x1fff,
+            ttl,
+            protocol,
+            checksum,
+            source,
+            destination,
+            options,
+            payload,
+        },
+    ))
+}
+
+/// Parses a TCP header. The payload is the rest of the input.
+pub fn tcp(input: &[u8]) -> Res<&[u8], Tcp<'_>> {
+    let start = input;
+    let (input, (source_port, destination_port, sequence, acknowledgment, offset_flags)) =
+        context(
+            "tcp header",
+            tuple((be_u16, be_u16, be_u32, be_u32, be_u16)),
+        )(input)?;
+    let (input, (window, checksum, urgent_pointer)) =
+        context("tcp header", tuple((be_u16, be_u16, be_u16)))(input)?;
+    let header_length = usize::from(offset_flags >> 12) * 4;
+    if header_length < 20 {
+        return Err(failure(start, "invalid tcp data offset"));
+    }
+    let (input, options) = context("tcp options", take(header_length - 20))(input)?;
+    let (input, payload) = rest(input)?;
+    let flag = |bit: u16| offset_flags & (1 << bit) != 0;
+    Ok((
+        input,
+        Tcp {
+            source_port,
+            destination_port,
+            sequence,
+            acknowledgment,
+            flags: TcpFlags {
+                ns: flag(8),
+                cwr: flag(7),
+                ece: flag(6),
+                urg: flag(5),
+                ack: flag(4),
+                psh: flag(3),
+                rst: flag(2),
+                syn: flag(1),
+                fin: flag(0),
+            },
+            window,
+            checksum,
+            urgent_pointer,
+            options,
+            payload,
+        },
+    ))
+}
+
+fn tcp_option(input: &[u8]) -> Res<&[u8], TcpOption<'_>> {
+    let start = input;
+    let (input, kind) = u8(input)?;
+    match kind {
+        0 => return Ok((input, TcpOption::EndOfList)),
+        1 => return Ok((input, TcpOption::Nop)),
+        _ => {}
+    }
+    let (next_input, length) = context("tcp option", u8)(input)?;
+    if length < 2 {
+        return Err(failure(start, "invalid tcp option length"));
+    }
+    let (next_input, data) = context("tcp option", take(length - 2))(next_input)?;
+    let option = match (kind, data.len()) {
+        (2, 2) => TcpOption::MaximumSegmentSize(u16::from_be_bytes([data[0], data[1]])),
+        (3, 1) => TcpOption::WindowScale(data[0]),
+        (4, 0) => TcpOption::SackPermitted,
+        (5, n) if n % 8 == 0 => TcpOption::Sack(data),
+        (8, 8) => TcpOption::Timestamps(
+            u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+        ),
+        (2..=5 | 8, _) => return Err(failure(start, "invalid tcp option length")),
+        _ => TcpOption::Unknown(kind, data),
+    };
+    Ok((next_input, option))
+}
+
+/// Decodes TCP options, stopping after an end-of-list option.
+pub fn tcp_options(mut input: &[u8]) -> Res<&[u8], Vec<TcpOption<'_>>> {
+    let mut options = vec![];
+    while !input.is_empty() {
+        let (next_input, option) = tcp_option(input)?;
+        input = next_input;
+        options.push(option);
+        if option == TcpOption::EndOfList {
+            break;
+        }
+    }
+    Ok((input, options))
+}
+
+/// Parses a UDP header. The payload is bounded by the length field.
+pub fn udp(input: &[u8]) -> Res<&[u8], Udp<'_>> {
+    let start = input;
+    let (input, (source_port, destination_port, length, checksum)) =
+        context("udp header", tuple((be_u16, be_u16, be_u16, be_u16)))(input)?;
+    if length < 8 {
+        return Err(failure(start, "invalid udp length"));
+    }
+    let (input, payload) = context("udp payload", take(usize::from(length) - 8))(input)?;
+    Ok((
+        input,
+        Udp {
+            source_port,
+            destination_port,
+            length,
+            checksum,
+            payload,
+        },
+    ))
+}
+
+/// Dissects an Ethernet frame, dispatching on the EtherType and the IP
+/// protocol number. Transport headers are only parsed in unfragmented
+/// packets or first fragments.
+pub fn packet(input: &[u8]) -> Res<&[u8], Packet<'_>> {
+    let (input, ethernet) = ethernet(input)?;
+    let (network, transport) = match ethernet.ether_type {
+        EtherType::Ipv4 => {
+            let (_, ip) = ipv4(ethernet.payload)?;
+            let transport = match (ip.protocol, ip.fragment_offset) {
+                (PROTOCOL_TCP, 0) => Transport::Tcp(tcp(ip.payload)?.1),
+                (PROTOCOL_UDP, 0) => Transport::Udp(udp(ip.payload)?.1),
+                (protocol, _) => Transport::Other(protocol, ip.payload),
+            };
+            (Network::Ipv4(ip), Some(transport))
+        }
+        other => (Network::Other(other, ethernet.payload), None),
+    };
+    Ok((
+        input,
+        Packet {
+            ethernet,
+            network,
+            transport,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ethernet_header(ether_type: [u8; 2]) -> Vec<u8> {
+        let mut frame = vec![0xff; 6];
+        frame.extend([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        frame.extend(ether_type);
+        frame
+    }
+
+    fn tcp_frame() -> Vec<u8> {
+        let mut frame = ethernet_header([0x08, 0x00]);
+        frame.extend([
+            0x46, 0x00, 0x00, 0x32, // version 4, ihl 6, total length 50
+            0x12, 0x34, 0x40, 0x00, // id, don't fragment
+            0x40, 0x06, 0xab, 0xcd, // ttl 64, tcp, checksum
+            10, 0, 0, 1, // source
+            10, 0, 0, 2, // destination
+            0x01, 0x01, 0x01, 0x00, // options: nop, nop, nop, end
+        ]);
+        frame.extend([
+            0x30, 0x39, 0x00, 0x50, // ports 12345 -> 80
+            0x00, 0x00, 0x00, 0x01, // sequence
+            0x00, 0x00, 0x00, 0x00, // acknowledgment
+            0x60, 0x02, 0xff, 0xff, // data offset 6, syn, window
+            0x00, 0x00, 0x00, 0x00, // checksum, urgent pointer
+            0x02, 0x04, 0x05, 0xb4, // mss 1460
+        ]);
+        frame.extend(b"hi");
+        // Ethernet padding after the IP packet.
+        frame.extend([0, 0]);
+        frame
+    }
+
+    #[test]
+    fn test_tcp_packet() {
+        let frame = tcp_frame();
+        let (rest, packet) = packet(&frame).unwrap();
+        assert_eq!(rest, b"");
+        assert_eq!(packet.ethernet.ether_type, EtherType::Ipv4);
+        let Network::Ipv4(ip) = packet.network else {
+            panic!("expected ipv4")
+        };
+        assert_eq!(ip.source, [10, 0, 0, 1]);
+        assert!(ip.dont_fragment);
+        assert_eq!(ip.options, &[1, 1, 1, 0]);
+        let Some(Transport::Tcp(tcp)) = packet.transport else {
+            panic!("expected tcp")
+        };
+        assert_eq!((tcp.source_port, tcp.destination_port), (12345, 80));
+        assert!(tcp.flags.syn && !tcp.flags.ack);
+        assert_eq!(tcp.payload, b"hi");
+        assert_eq!(
+            tcp_options(tcp.options),
+            Ok((&b""[..], vec![TcpOption::MaximumSegmentSize(1460)]))
+        );
+    }
+
+    #[test]
+    fn test_udp_and_vlan() {
+        let mut frame = ethernet_header([0x81, 0x00]);
+        frame.extend([0x20, 0x64, 0x08, 0x00]);
+        frame.extend([
+            0x45, 0x00, 0x00, 0x1e, 0x00, 0x00, 0x20, 0x00, 0x40, 0x11, 0x00, 0x00, 1, 1, 1, 1, 8,
+            8, 8, 8,
+        ]);
+        frame.extend([0x00, 0x35, 0x00, 0x35, 0x00, 0x0a, 0x00, 0x00, b'o', b'k']);
+        let (_, packet) = packet(&frame).unwrap();
+        assert_eq!(packet.ethernet.vlan, Some(100));
+        let Network::Ipv4(ip) = packet.network else {
+            panic!("expected ipv4")
+        };
+        assert!(ip.more_fragments);
+        assert_eq!(
+            packet.transport,
+            Some(Transport::Udp(Udp {
+                source_port: 53,
+                destination_port: 53,
+                length: 10,
+                checksum: 0,
+                payload: b"ok",
+            }))
+        );
+    }
+
+    #[test]
+    fn test_other_ether_type() {
+        let mut frame = ethernet_header([0x86, 0xdd]);
+        frame.extend([0x60, 0x00]);
+        let (_, packet) = packet(&frame).unwrap();
+        assert_eq!(
+            packet.network,
+            Network::Other(EtherType::Ipv6, &[0x60, 0x00])
+        );
+        assert_eq!(packet.transport, None);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut frame = tcp_frame();
+        frame[14] = 0x44;
+        assert!(packet(&frame).is_err());
+        let mut frame = tcp_frame();
+        frame[14] = 0x65;
+        assert!(packet(&frame).is_err());
+        assert!(packet(&tcp_frame()[..40]).is_err());
+        assert_eq!(
+            tcp_options(&[0x02, 0x03, 0x00]),
+            Err(failure(&[0x02, 0x03, 0x00], "invalid tcp option length"))
+        );
+        assert_eq!(
+            tcp_options(&[0x01, 0x00, 0xff]),
+            Ok((&[0xff][..], vec![TcpOption::Nop, TcpOption::EndOfList]))
+        );
+    }
+}
@@ -0,0 +1,468 @@
+use std::{cmp::Ordering, collections::HashMap, str::FromStr};
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till1, take_while, take_while1},
+    character::complete::{alphanumeric1, char, space0, space1},
+    combinator::{all_consuming, map, opt, recognize, value},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::{many0, separated_list0, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    Err as NomErr, IResult,
+};
+
+use crate::limits::nested;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Operator {
+    Compatible,
+    Equal,
+    NotEqual,
+    LessEqual,
+    GreaterEqual,
+    Less,
+    Greater,
+    ArbitraryEqual,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Specifier {
+    pub operator: Operator,
+    pub version: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum VersionOrUrl {
+    Specifiers(Vec<Specifier>),
+    Url(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MarkerValue {
+    /// An environment variable such as `python_version`.
+    Variable(String),
+    String(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MarkerOperator {
+    Version(Operator),
+    In,
+    NotIn,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Marker {
+    Expression {
+        left: MarkerValue,
+        operator: MarkerOperator,
+        right: MarkerValue,
+    },
+    And(Vec<Marker>),
+    Or(Vec<Marker>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Requirement {
+    pub name: String,
+    pub extras: Vec<String>,
+    pub version_or_url: Option<VersionOrUrl>,
+    pub marker: Option<Marker>,
+}
+
+/// The environment variables a marker may refer to.
+const VARIABLES: [&str; 13] = [
+    "python_version",
+    "python_full_version",
+    "os_name",
+    "sys_platform",
+    "platform_release",
+    "platform_system",
+    "platform_version",
+    "platform_machine",
+    "platform_python_implementation",
+    "implementation_name",
+    "implementation_version",
+    "extra",
+    "os.name",
+];
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+fn ws<'a, O>(
+    inner: impl FnMut(&'a str) -> Res<&'a str, O>,
+) -> impl FnMut(&'a str) -> Res<&'a str, O> {
+    delimited(space0, inner, space0)
+}
+
+/// Names and extras start and end with an ASCII letter or digit, with
+/// `.`, `_` and `-` allowed in between.
+fn identifier(input: &str) -> Res<&str, &str> {
+    context(
+        "identifier",
+        recognize(pair(
+            alphanumeric1,
+            many0(pair(take_while(|c: char| "._-".contains(c)), alphanumeric1)),
+        )),
+    )(input)
+}
+
+fn extras(input: &str) -> Res<&str, Vec<String>> {
+    context(
+        "extras",
+        delimited(
+            ws(char('[')),
+            separated_list0(ws(char(',')), map(identifier, str::to_owned)),
+            ws(char(']')),
+        ),
+    )(input)
+}
+
+fn operator(input: &str) -> Res<&str, Operator> {
+    alt((
+        value(Operator::ArbitraryEqual, tag("===")),
+        value(Operator::Compatible, tag("~=")),
+        value(Operator::Equal, tag("==")),
+        value(Operator::NotEqual, tag("!=")),
+        value(Operator::LessEqual, tag("<=")),
+        value(Operator::GreaterEqual, tag(">=")),
+        value(Operator::Less, tag("<")),
+        value(Operator::Greater, tag(">")),
+    ))(input)
+}
+
+fn specifier(input: &str) -> Res<&str, Specifier> {
+    context(
+        "specifier",
+        tuple((
+            ws(operator),
+            take_while1(|c: char| c.is_ascii_alphanumeric() || "._*+!-".contains(c)),
+        )),
+    )(input)
+    .map(|(next_input, (operator, version))| {
+        (
+            next_input,
+            Specifier {
+                operator,
+                version: version.to_owned(),
+            },
+        )
+    })
+}
+
+fn specifiers(input: &str) -> Res<&str, Vec<Specifier>> {
+    let list = |input| separated_list1(ws(char(',')), specifier)(input);
+    context(
+        "version specifiers",
+        alt((delimited(ws(char('(')), list, ws(char(')'))), list)),
+    )(input)
+}
+
+fn url(input: &str) -> Res<&str, String> {
+    context(
+        "url",
+        preceded(
+            ws(char('@')),
+            map(
+                take_till1(|c: char| c.is_whitespace() || c == ';'),
+                str::to_owned,
+            ),
+        ),
+    )(input)
+}
+
+fn quoted(input: &str) -> Res<&str, String> {
+    map(
+        alt((
+            delimited(char('"'), take_while(|c| c != '"'), char('"')),
+            delimited(char('\''), take_while(|c| c != '\''), char('\'')),
+        )),
+        str::to_owned,
+    )(input)
+}
+
+fn variable(input: &str) -> Res<&str, MarkerValue> {
+    let (next_input, name) =
+        take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.')(input)?;
+    if !VARIABLES.contains(&name) {
+        return Err(failure(input, "unknown marker variable"));
+    }
+    Ok((next_input, MarkerValue::Variable(name.to_owned())))
+}
+
+fn marker_value(input: &str) -> Res<&str, MarkerValue> {
+    ws(alt((map(quoted, MarkerValue::String), variable)))(input)
+}
+
+fn marker_operator(input: &str) -> Res<&str, MarkerOperator> {
+    ws(alt((
+        map(operator, MarkerOperator::Version),
+        value(
+            MarkerOperator::NotIn,
+            tuple((tag("not"), space1, tag("in"))),
+        ),
+        value(MarkerOperator::In, tag("in")),
+    )))(input)
+}
+
+fn marker_expression(input: &str) -> Res<&str, Marker> {
+    alt((
+        nested(
+            ws(char('(')),
+            delimited(ws(char('(')), marker, ws(char(')'))),
+        ),
+        map(
+            tuple((marker_value, marker_operator, marker_value)),
+            |(left, operator, right)| Marker::Expression {
+                left,
+                operator,
+                right,
+            },
+        ),
+    ))(input)
+}
+
+fn marker_and(input: &str) -> Res<&str, Marker> {
+    map(
+        separated_list1(ws(tag("and")), marker_expression),
+        |mut markers| match markers.len() {
+            1 => markers.remove(0),
+            _ => Marker::And(markers),
+        },
+    )(input)
+}
+
+/// Parses an environment marker expression such as
+/// `python_version < "3.9" and os_name == "posix"`.
+pub fn marker(input: &str) -> Res<&str, Marker> {
+    context(
+        "marker",
+        map(
+            separated_list1(ws(tag("or")), marker_and),
+            |mut markers| match markers.len() {
+                1 => markers.remove(0),
+                _ => Marker::Or(markers),
+            },
+        ),
+    )(input)
+}
+
+/// Parses a complete requirement line, such as
+/// `requests[security]>=2.8.1,==2.8.* ; python_version < "3.9"`.
+pub fn requirement(input: &str) -> Res<&str, Requirement> {
+    context(
+        "requirement",
+        all_consuming(tuple((
+            ws(identifier),
+            opt(extras),
+            opt(alt((
+                map(url, VersionOrUrl::Url),
+                map(specifiers, VersionOrUrl::Specifiers),
+            ))),
+            opt(preceded(ws(char(';')), marker)),
+            terminated(space0, opt(pair(char('#'), take_while(|_| true)))),
+        ))),
+    )(input)
+    .map(|(next_input, (name, extras, version_or_url, marker, _))| {
+        (
+            next_input,
+            Requirement {
+                name: name.to_owned(),
+                extras: extras.unwrap_or_default(),
+                version_or_url,
+                marker,
+            },
+        )
+    })
+}
+
+/// Compares dotted versions numerically, component by component, falling
+/// back to a string comparison when a component isn't a number.
+fn compare_versions(left: &str, right: &str) -> Ordering {
+    let mut left_parts = left.split('.');
+    let mut right_parts = right.split('.');
+    loop {
+        match (left_parts.next(), right_parts.next()) {
+            (None, None) => return Ordering::Equal,
+            (l, r) => {
+                let (l, r) = (l.unwrap_or("0"), r.unwrap_or("0"));
+                let ordering = match (l.parse::<u64>(), r.parse::<u64>()) {
+                    (Ok(l), Ok(r)) => l.cmp(&r),
+                    _ => l.cmp(r),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+fn version_matches(operator: Operator, left: &str, right: &str) -> bool {
+    if let Some(prefix) = right.strip_suffix(".*") {
+        let matches = left == prefix || left.starts_with(&format!("{}.", prefix));
+        return match operator {
+            Operator::Equal => matches,
+            Operator::NotEqual => !matches,
+            _ => false,
+        };
+    }
+    let ordering = compare_versions(left, right);
+    match operator {
+        Operator::Equal => ordering == Ordering::Equal,
+        Operator::NotEqual => ordering != Ordering::Equal,
+        Operator::LessEqual => ordering != Ordering::Greater,
+        Operator::GreaterEqual => ordering != Ordering::Less,
+        Operator::Less => ordering == Ordering::Less,
+        Operator::Greater => ordering == Ordering::Greater,
+        Operator::ArbitraryEqual => left == right,
+        Operator::Compatible => {
+            // `~= 3.8.1` means `>= 3.8.1, == 3.8.*`.
+            let prefix: Vec<_> = right.split('.').collect();
+            let prefix = prefix[..prefix.len().saturating_sub(1).max(1)].join(".");
+            ordering != Ordering::Less
+                && (left == prefix || left.starts_with(&format!("{}.", prefix)))
+        }
+    }
+}
+
+impl Marker {
+    /// Evaluates the marker against an environment mapping variable names
+    /// to values. Unknown variables evaluate as empty strings.
+    pub fn evaluate(&self, environment: &HashMap<&str, &str>) -> bool {
+        match self {
+            Marker::And(markers) => markers.iter().all(|m| m.evaluate(environment)),
+            Marker::Or(markers) => markers.iter().any(|m| m.evaluate(environment)),
+            Marker::Expression {
+                left,
+                operator,
+                right,
+            } => {
+                let resolve = |value: &MarkerValue| match value {
+                    MarkerValue::String(s) => s.clone(),
+                    MarkerValue::Variable(name) => environment
+                        .get(name.as_str())
+                        .copied()
+                        .unwrap_or("")
+                        .to_owned(),
+                };
+                let (l, r) = (resolve(left), resolve(right));
+                match operator {
+                    MarkerOperator::In => r.contains(&l),
+                    MarkerOperator::NotIn => !r.contains(&l),
+                    MarkerOperator::Version(op) => version_matches(*op, &l, &r),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Requirement {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::error::parse_all("pep508", s, requirement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(operator: Operator, version: &str) -> Specifier {
+        Specifier {
+            operator,
+            version: version.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_requirement() {
+        let (_, req) =
+            requirement(r#"requests[security, socks]>=2.8.1,==2.8.* ; python_version < "3.9""#)
+                .unwrap();
+        assert_eq!(req.name, "requests");
+        assert_eq!(req.extras, vec!["security".to_owned(), "socks".to_owned()]);
+        assert_eq!(
+            req.version_or_url,
+            Some(VersionOrUrl::Specifiers(vec![
+                spec(Operator::GreaterEqual, "2.8.1"),
+                spec(Operator::Equal, "2.8.*"),
+            ]))
+        );
+        assert_eq!(
+            req.marker,
+            Some(Marker::Expression {
+                left: MarkerValue::Variable("python_version".to_owned()),
+                operator: MarkerOperator::Version(Operator::Less),
+                right: MarkerValue::String("3.9".to_owned()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_requirement_forms() {
+        let (_, req) = requirement("name").unwrap();
+        assert_eq!(req.version_or_url, None);
+        let (_, req) = requirement("name (>=1.0, <2)").unwrap();
+        assert_eq!(
+            req.version_or_url,
+            Some(VersionOrUrl::Specifiers(vec![
+                spec(Operator::GreaterEqual, "1.0"),
+                spec(Operator::Less, "2"),
+            ]))
+        );
+        let (_, req) = requirement("pip @ https://github.com/pypa/pip/archive/1.3.1.zip").unwrap();
+        assert_eq!(
+            req.version_or_url,
+            Some(VersionOrUrl::Url(
+                "https://github.com/pypa/pip/archive/1.3.1.zip".to_owned()
+            ))
+        );
+        let (_, req) = requirement("zope.interface~=5.0 # pinned").unwrap();
+        assert_eq!(req.name, "zope.interface");
+        assert!(requirement("-bad").is_err());
+        assert!(requirement("name >=").is_err());
+    }
+
+    #[test]
+    fn test_marker_logic() {
+        let (_, m) =
+            marker(r#"os_name == "nt" or (python_version >= "3.8" and 'linux' in sys_platform)"#)
+                .unwrap();
+        let Marker::Or(ref alternatives) = m else {
+            panic!("expected or")
+        };
+        assert!(matches!(alternatives[1], Marker::And(ref a) if a.len() == 2));
+
+        let env = HashMap::from([
+            ("os_name", "posix"),
+            ("python_version", "3.10"),
+            ("sys_platform", "linux"),
+        ]);
+        assert!(m.evaluate(&env));
+        let env = HashMap::from([
+            ("os_name", "posix"),
+            ("python_version", "3.7"),
+            ("sys_platform", "linux"),
+        ]);
+        assert!(!m.evaluate(&env));
+        assert!(marker("unknown_var == '1'").is_err());
+    }
+
+    #[test]
+    fn test_version_matches() {
+        assert!(!version_matches(Operator::Less, "3.10", "3.9"));
+        assert!(version_matches(Operator::Equal, "2.8.5", "2.8.*"));
+        assert!(version_matches(Operator::NotEqual, "2.9", "2.8.*"));
+        assert!(version_matches(Operator::Compatible, "3.8.4", "3.8.1"));
+        assert!(!version_matches(Operator::Compatible, "3.9.0", "3.8.1"));
+        assert!(version_matches(Operator::Compatible, "3.9", "3.1"));
+    }
+}
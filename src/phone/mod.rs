@@ -0,0 +1,216 @@
+use std::str::FromStr;
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag_no_case,
+    character::complete::{char, digit1, one_of, space0},
+    combinator::{opt, recognize},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::many1,
+    sequence::{delimited, preceded, tuple},
+    Err as NomErr, IResult,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PhoneNumber {
+    pub country_code: u16,
+    pub region: &'static str,
+    pub national_number: String,
+    pub extension: Option<String>,
+}
+
+impl PhoneNumber {
+    /// The number in E.164 form, e.g. `+14155552671`. The extension is not
+    /// part of E.164 and is left out.
+    pub fn e164(&self) -> String {
+        format!("+{}{}", self.country_code, self.national_number)
+    }
+}
+
+/// Calling code, region, and the minimum and maximum length of the national
+/// significant number.
+type Region = (u16, &'static str, usize, usize);
+
+const REGIONS: [Region; 30] = [
+    (1, "NANP", 10, 10),
+    (7, "RU", 10, 10),
+    (20, "EG", 8, 10),
+    (27, "ZA", 9, 9),
+    (30, "GR", 10, 10),
+    (31, "NL", 9, 9),
+    (32, "BE", 8, 9),
+    (33, "FR", 9, 9),
+    (34, "ES", 9, 9),
+    (39, "IT", 6, 11),
+    (41, "CH", 9, 9),
+    (44, "GB", 9, 10),
+    (45, "DK", 8, 8),
+    (46, "SE", 7, 10),
+    (47, "NO", 8, 8),
+    (48, "PL", 9, 9),
+    (49, "DE", 6, 13),
+    (52, "MX", 10, 10),
+    (55, "BR", 10, 11),
+    (61, "AU", 9, 9),
+    (62, "ID", 8, 12),
+    (64, "NZ", 8, 10),
+    (65, "SG", 8, 8),
+    (81, "JP", 9, 10),
+    (82, "KR", 8, 10),
+    (86, "CN", 10, 11),
+    (91, "IN", 10, 10),
+    (234, "NG", 8, 10),
+    (254, "KE", 9, 9),
+    (353, "IE", 7, 9),
+];
+
+/// E.164 caps the full number, country code included, at 15 digits.
+const MAX_DIGITS: usize = 15;
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn separator(input: &str) -> Res<&str, char> {
+    one_of(" -.")(input)
+}
+
+fn digit_group(input: &str) -> Res<&str, &str> {
+    context(
+        "digit group",
+        preceded(
+            opt(separator),
+            alt((delimited(char('('), digit1, char(')')), digit1)),
+        ),
+    )(input)
+}
+
+fn extension(input: &str) -> Res<&str, &str> {
+    context(
+        "extension",
+        preceded(
+            tuple((
+                space0,
+                alt((
+                    tag_no_case("extension"),
+                    recognize(tuple((tag_no_case("ext"), opt(char('.'))))),
+                    tag_no_case("x"),
+                    tag_no_case("#"),
+                )),
+                space0,
+            )),
+            digit1,
+        ),
+    )(input)
+}
+
+fn region(digits: &str) -> Option<&'static Region> {
+    (1..=3).filter(|len| *len <= digits.len()).find_map(|len| {
+        let code: u16 = digits[..len].parse().ok()?;
+        REGIONS.iter().find(|region| region.0 == code)
+    })
+}
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+/// Parses an international number such as `+1 (415) 555-2671 ext. 42` and
+/// validates it against the region table.
+pub fn phone_number(input: &str) -> Res<&str, PhoneNumber> {
+    let (next_input, (groups, extension)) = context(
+        "phone number",
+        tuple((preceded(char('+'), many1(digit_group)), opt(extension))),
+    )(input)?;
+
+    let digits: String = groups.concat();
+    if digits.len() > MAX_DIGITS {
+        return Err(failure(input, "too many digits"));
+    }
+    let (country_code, region, min, max) =
+        *region(&digits).ok_or_else(|| failure(input, "unknown country code"))?;
+    let national_number = &digits[country_code.to_string().len()..];
+    if national_number.len() < min || national_number.len() > max {
+        return Err(failure(input, "invalid length for region"));
+    }
+
+    Ok((
+        next_input,
+        PhoneNumber {
+            country_code,
+            region,
+            national_number: national_number.to_owned(),
+            extension: extension.map(str::to_owned),
+        },
+    ))
+}
+
+impl FromStr for PhoneNumber {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::error::parse_all("phone number", s, phone_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension() {
+        assert_eq!(extension(" ext. 42"), Ok(("", "42")));
+        assert_eq!(extension(" EXT 42"), Ok(("", "42")));
+        assert_eq!(extension("x7"), Ok(("", "7")));
+        assert_eq!(extension(" extension 100"), Ok(("", "100")));
+    }
+
+    #[test]
+    fn test_formats() {
+        let expected = PhoneNumber {
+            country_code: 1,
+            region: "NANP",
+            national_number: "4155552671".to_owned(),
+            extension: None,
+        };
+        for input in [
+            "+14155552671",
+            "+1 415 555 2671",
+            "+1 (415) 555-2671",
+            "+1.415.555.2671",
+            "+1-415-555-2671",
+        ] {
+            assert_eq!(phone_number(input), Ok(("", expected.clone())));
+        }
+    }
+
+    #[test]
+    fn test_e164_and_extension() {
+        let (rest, number) = phone_number("+44 20 7946 0958 ext. 42").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(number.region, "GB");
+        assert_eq!(number.e164(), "+442079460958");
+        assert_eq!(number.extension, Some("42".to_owned()));
+
+        let (_, number) = phone_number("+353 1 234 5678").unwrap();
+        assert_eq!(number.country_code, 353);
+        assert_eq!(number.e164(), "+35312345678");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            phone_number("+1 415 555 267"),
+            Err(failure("+1 415 555 267", "invalid length for region"))
+        );
+        assert_eq!(
+            phone_number("+999 1234 5678"),
+            Err(failure("+999 1234 5678", "unknown country code"))
+        );
+        assert_eq!(
+            phone_number("+1234567890123456"),
+            Err(failure("+1234567890123456", "too many digits"))
+        );
+        assert!(phone_number("415 555 2671").is_err());
+    }
+}
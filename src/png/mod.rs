@@ -0,0 +1,318 @@
+use nom::{
+    bytes::complete::{tag, take, take_until},
+    combinator::{map, rest},
+    error::{context, VerboseError, VerboseErrorKind},
+    number::complete::{be_u32, u8},
+    sequence::{terminated, tuple},
+    Err as NomErr, IResult,
+};
+
+use crate::crc32::{crc32, update};
+
+pub const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Chunk<'a> {
+    pub chunk_type: [u8; 4],
+    pub data: &'a [u8],
+    pub crc: u32,
+}
+
+impl Chunk<'_> {
+    /// Critical chunks have an uppercase first letter in their type.
+    pub fn is_critical(&self) -> bool {
+        self.chunk_type[0].is_ascii_uppercase()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub interlaced: bool,
+}
+
+/// A `tEXt` chunk. Both parts are Latin-1 and are decoded to `String`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Text {
+    pub keyword: String,
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PhysUnit {
+    Unknown,
+    Meter,
+}
+
+/// A `pHYs` chunk: the intended pixel size or aspect ratio.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Phys {
+    pub pixels_per_unit_x: u32,
+    pub pixels_per_unit_y: u32,
+    pub unit: PhysUnit,
+}
+
+/// The metadata this module decodes from a PNG file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Metadata {
+    pub header: Ihdr,
+    pub texts: Vec<Text>,
+    pub phys: Option<Phys>,
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a [u8], reason: &'static str) -> NomErr<VerboseError<&'a [u8]>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| char::from(b)).collect()
+}
+
+/// Parses one chunk and verifies its CRC.
+pub fn chunk(input: &[u8]) -> Res<&[u8], Chunk<'_>> {
+    let (next_input, (length, chunk_type)) =
+        context("chunk", tuple((be_u32, take(4usize))))(input)?;
+    let (next_input, (data, crc)) =
+        context("chunk", tuple((take(length as usize), be_u32)))(next_input)?;
+    if !chunk_type.iter().all(u8::is_ascii_alphabetic) {
+        return Err(failure(input, "invalid chunk type"));
+    }
+    // The CRC covers the chunk type and data but not the length.
+    if update(crc32(chunk_type), data) != crc {
+        return Err(failure(input, "crc mismatch"));
+    }
+    Ok((
+        next_input,
+        Chunk {
+            chunk_type: [chunk_type[0], chunk_type[1], chunk_type[2], chunk_type[3]],
+            data,
+            crc,
+        },
+    ))
+}
+
+/// Parses a whole PNG file into its chunks, up to and including `IEND`.
+pub fn png(input: &[u8]) -> Res<&[u8], Vec<Chunk<'_>>> {
+    let (mut input, _) = context("signature", tag(&SIGNATURE[..]))(input)?;
+    let mut chunks: Vec<Chunk> = vec![];
+    loop {
+        let (next_input, chunk) = chunk(input)?;
+        if chunks.is_empty() && &chunk.chunk_type != b"IHDR" {
+            return Err(failure(input, "first chunk is not IHDR"));
+        }
+        chunks.push(chunk);
+        input = next_input;
+        if &chunk.chunk_type == b"IEND" {
+            return Ok((input, chunks));
+        }
+    }
+}
+
+/// Decodes the data of an `IHDR` chunk.
+pub fn ihdr(input: &[u8]) -> Res<&[u8], Ihdr> {
+    let (next_input, (width, height, bit_depth, color_type, compression, filter, interlace)) =
+        context("IHDR", tuple((be_u32, be_u32, u8, u8, u8, u8, u8)))(input)?;
+    let (color_type, depths): (_, &[u8]) = match color_type {
+        0 => (ColorType::Grayscale, &[1, 2, 4, 8, 16]),
+        2 => (ColorType::Rgb, &[8, 16]),
+        3 => (ColorType::Indexed, &[1, 2, 4, 8]),
+        4 => (ColorType::GrayscaleAlpha, &[8, 16]),
+        6 => (ColorType::Rgba, &[8, 16]),
+        _ => return Err(failure(input, "invalid color type")),
+    };
+    if !depths.contains(&bit_depth) {
+        return Err(failure(input, "invalid bit depth"));
+    }
+    if width == 0 || height == 0 {
+        return Err(failure(input, "empty image"));
+    }
+    if compression != 0 || filter != 0 || interlace > 1 {
+        return Err(failure(input, "unknown method"));
+    }
+    Ok((
+        next_input,
+        Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlaced: interlace == 1,
+        },
+    ))
+}
+
+/// Decodes the data of a `tEXt` chunk.
+pub fn text(input: &[u8]) -> Res<&[u8], Text> {
+    let (next_input, (keyword, text)) = context(
+        "tEXt",
+        tuple((terminated(take_until(&b"\0"[..]), tag(&b"\0"[..])), rest)),
+    )(input)?;
+    if keyword.is_empty() || keyword.len() > 79 {
+        return Err(failure(input, "invalid keyword"));
+    }
+    Ok((
+        next_input,
+        Text {
+            keyword: latin1(keyword),
+            text: latin1(text),
+        },
+    ))
+}
+
+/// Decodes the data of a `pHYs` chunk.
+pub fn phys(input: &[u8]) -> Res<&[u8], Phys> {
+    context(
+        "pHYs",
+        tuple((
+            be_u32,
+            be_u32,
+            map(u8, |unit| match unit {
+                1 => PhysUnit::Meter,
+                _ => PhysUnit::Unknown,
+            }),
+        )),
+    )(input)
+    .map(
+        |(next_input, (pixels_per_unit_x, pixels_per_unit_y, unit))| {
+            (
+                next_input,
+                Phys {
+                    pixels_per_unit_x,
+                    pixels_per_unit_y,
+                    unit,
+                },
+            )
+        },
+    )
+}
+
+/// Parses a PNG file and decodes its `IHDR`, `tEXt` and `pHYs` chunks.
+pub fn metadata(input: &[u8]) -> Res<&[u8], Metadata> {
+    let (next_input, chunks) = png(input)?;
+    let (_, header) = ihdr(chunks[0].data)?;
+    let mut metadata = Metadata {
+        header,
+        texts: vec![],
+        phys: None,
+    };
+    for chunk in &chunks[1..] {
+        match &chunk.chunk_type {
+            b"tEXt" => metadata.texts.push(text(chunk.data)?.1),
+            b"pHYs" => metadata.phys = Some(phys(chunk.data)?.1),
+            _ => {}
+        }
+    }
+    Ok((next_input, metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes = (data.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(chunk_type);
+        bytes.extend(data);
+        bytes.extend(update(crc32(chunk_type), data).to_be_bytes());
+        bytes
+    }
+
+    fn sample() -> Vec<u8> {
+        let mut bytes = SIGNATURE.to_vec();
+        bytes.extend(encode_chunk(
+            b"IHDR",
+            &[0, 0, 0, 16, 0, 0, 0, 8, 8, 6, 0, 0, 1],
+        ));
+        bytes.extend(encode_chunk(b"tEXt", b"Title\0Caf\xe9"));
+        bytes.extend(encode_chunk(
+            b"pHYs",
+            &[0, 0, 0x0b, 0x13, 0, 0, 0x0b, 0x13, 1],
+        ));
+        bytes.extend(encode_chunk(b"IDAT", &[0x78, 0x9c]));
+        bytes.extend(encode_chunk(b"IEND", &[]));
+        bytes
+    }
+
+    #[test]
+    fn test_png() {
+        let bytes = sample();
+        let (rest, chunks) = png(&bytes).unwrap();
+        assert!(rest.is_empty());
+        let types: Vec<_> = chunks.iter().map(|c| c.chunk_type).collect();
+        assert_eq!(
+            types,
+            vec![*b"IHDR", *b"tEXt", *b"pHYs", *b"IDAT", *b"IEND"]
+        );
+        assert!(chunks[0].is_critical());
+        assert!(!chunks[1].is_critical());
+        assert_eq!(chunks[4].crc, 0xae42_6082);
+    }
+
+    #[test]
+    fn test_metadata() {
+        let (_, metadata) = metadata(&sample()).unwrap();
+        assert_eq!(
+            metadata,
+            Metadata {
+                header: Ihdr {
+                    width: 16,
+                    height: 8,
+                    bit_depth: 8,
+                    color_type: ColorType::Rgba,
+                    interlaced: true,
+                },
+                texts: vec![Text {
+                    keyword: "Title".to_owned(),
+                    text: "Café".to_owned(),
+                }],
+                phys: Some(Phys {
+                    pixels_per_unit_x: 2835,
+                    pixels_per_unit_y: 2835,
+                    unit: PhysUnit::Meter,
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut bytes = sample();
+        bytes[0] = 0;
+        assert!(png(&bytes).is_err());
+
+        let mut bytes = sample();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert_eq!(
+            png(&bytes),
+            Err(failure(&bytes[bytes.len() - 12..], "crc mismatch"))
+        );
+
+        let mut bytes = SIGNATURE.to_vec();
+        bytes.extend(encode_chunk(b"IEND", &[]));
+        assert_eq!(
+            png(&bytes),
+            Err(failure(&bytes[8..], "first chunk is not IHDR"))
+        );
+
+        let data = [0, 0, 0, 1, 0, 0, 0, 1, 16, 3, 0, 0, 0];
+        assert_eq!(ihdr(&data), Err(failure(&data, "invalid bit depth")));
+        assert!(text(b"no separator").is_err());
+    }
+}
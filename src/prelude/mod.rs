@@ -0,0 +1,15 @@
+//! The types and entry points most callers need:
+//!
+//! ```
+//! use parsers::prelude::*;
+//!
+//! let uri: UriBuf = "https://example.org/a?b=c".parse()?;
+//! let value: JsonValue = r#"{"uri": true}"#.parse()?;
+//! # Ok::<(), Error>(())
+//! ```
+
+pub use crate::error::Error;
+pub use crate::http::{nested_query_params, uri, UriBuf, URI};
+pub use crate::json::{parse_json, JsonValue};
+pub use crate::span::{Span, Spanned};
+pub use crate::value::{FromValue, ToValue, Value};
@@ -0,0 +1,402 @@
+//! Human-readable output for parsed values, so every tree in the crate has
+//! the same way to be printed, indented to taste, into any `fmt::Write`.
+
+use std::fmt::{self, Write};
+
+use crate::http::URI;
+use crate::json::JsonValue;
+use crate::value::Value;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Indent {
+    /// Spaces per level, or `None` to print as compactly as the format
+    /// allows.
+    pub width: Option<usize>,
+    /// The level to start at, for printing a value nested in other output.
+    pub level: usize,
+}
+
+impl Indent {
+    pub fn compact() -> Self {
+        Indent {
+            width: None,
+            level: 0,
+        }
+    }
+
+    pub fn spaces(width: usize) -> Self {
+        Indent {
+            width: Some(width),
+            level: 0,
+        }
+    }
+
+    pub fn nested(self) -> Self {
+        Indent {
+            level: self.level + 1,
+            ..self
+        }
+    }
+
+    /// Starts a new line at this level. Does nothing when compact.
+    pub fn newline(self, out: &mut dyn Write) -> fmt::Result {
+        match self.width {
+            Some(width) => write!(out, "\n{:1$}", "", width * self.level),
+            None => Ok(()),
+        }
+    }
+
+    /// The indentation at the start of a line at this level.
+    fn margin(self, out: &mut dyn Write) -> fmt::Result {
+        write!(out, "{:1$}", "", self.width.unwrap_or(0) * self.level)
+    }
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Indent::spaces(2)
+    }
+}
+
+pub trait PrettyPrint {
+    fn pretty_print(&self, out: &mut dyn Write, indent: Indent) -> fmt::Result;
+
+    fn to_pretty_string(&self, indent: Indent) -> String {
+        let mut out = String::new();
+        self.pretty_print(&mut out, indent)
+            .expect("writing to a String can't fail");
+        out
+    }
+}
+
+/// A double-quoted string with JSON escapes, which TOML shares.
+fn write_string(out: &mut dyn Write, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+/// JSON, with one member or element per line unless compact. Numbers
+/// that JSON can't represent are written as `null`.
+impl PrettyPrint for JsonValue {
+    fn pretty_print(&self, out: &mut dyn Write, indent: Indent) -> fmt::Result {
+        let inner = indent.nested();
+        match self {
+            JsonValue::Null => out.write_str("null"),
+            JsonValue::Boolean(b) => write!(out, "{}", b),
+            JsonValue::Number(n) if n.is_finite() => write!(out, "{}", n),
+            JsonValue::Number(_) => out.write_str("null"),
+            JsonValue::Decimal(n) => write!(out, "{}", n),
+            JsonValue::String(s) => write_string(out, s),
+            JsonValue::Array(items) if items.is_empty() => out.write_str("[]"),
+            JsonValue::Array(items) => {
+                out.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.write_char(',')?;
+                    }
+                    inner.newline(out)?;
+                    item.pretty_print(out, inner)?;
+                }
+                indent.newline(out)?;
+                out.write_char(']')
+            }
+            JsonValue::Object(members) if members.is_empty() => out.write_str("{}"),
+            JsonValue::Object(members) => {
+                let separator = if indent.width.is_some() { ": " } else { ":" };
+                out.write_char('{')?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        out.write_char(',')?;
+                    }
+                    inner.newline(out)?;
+                    write_string(out, key)?;
+                    out.write_str(separator)?;
+                    value.pretty_print(out, inner)?;
+                }
+                indent.newline(out)?;
+                out.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_key(out: &mut dyn Write, key: &str) -> fmt::Result {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    match bare {
+        true => out.write_str(key),
+        false => write_string(out, key),
+    }
+}
+
+fn is_array_of_tables(value: &Value) -> bool {
+    matches!(value, Value::Array(items)
+        if !items.is_empty() && items.iter().all(|item| matches!(item, Value::Table(_))))
+}
+
+/// A value on one line, as it appears after `key = `.
+fn write_inline(out: &mut dyn Write, value: &Value) -> fmt::Result {
+    match value {
+        // Only reached for a null on its own; nulls inside are left out.
+        Value::Null => out.write_str("{}"),
+        Value::Boolean(b) => write!(out, "{}", b),
+        Value::Integer(n) => write!(out, "{}", n),
+        Value::Float(f) if f.is_nan() => out.write_str("nan"),
+        Value::Float(f) if f.is_infinite() => out.write_str(if *f > 0.0 { "inf" } else { "-inf" }),
+        // `Debug` always includes a `.0` or exponent, as TOML requires.
+        Value::Float(f) => write!(out, "{:?}", f),
+        Value::String(s) => write_string(out, s),
+        Value::Array(items) => {
+            out.write_char('[')?;
+            for (i, item) in items
+                .iter()
+                .filter(|item| **item != Value::Null)
+                .enumerate()
+            {
+                if i > 0 {
+                    out.write_str(", ")?;
+                }
+                write_inline(out, item)?;
+            }
+            out.write_char(']')
+        }
+        Value::Table(entries) => {
+            out.write_char('{')?;
+            for (i, (key, value)) in entries
+                .iter()
+                .filter(|(_, v)| *v != Value::Null)
+                .enumerate()
+            {
+                out.write_str(if i > 0 { ", " } else { " " })?;
+                write_key(out, key)?;
+                out.write_str(" = ")?;
+                write_inline(out, value)?;
+            }
+            out.write_str(if entries.is_empty() { "}" } else { " }" })
+        }
+    }
+}
+
+/// The entries of the table at `path`, followed by its subtables.
+fn write_table(
+    out: &mut dyn Write,
+    path: &mut Vec<String>,
+    entries: &[(String, Value)],
+    indent: Indent,
+) -> fmt::Result {
+    let section = |value: &Value| matches!(value, Value::Table(_)) || is_array_of_tables(value);
+    for (key, value) in entries {
+        if *value == Value::Null || section(value) {
+            continue;
+        }
+        indent.margin(out)?;
+        write_key(out, key)?;
+        out.write_str(" = ")?;
+        write_inline(out, value)?;
+        out.write_char('\n')?;
+    }
+    let header = |out: &mut dyn Write, path: &[String], brackets: &str| -> fmt::Result {
+        if indent.width.is_some() {
+            out.write_char('\n')?;
+        }
+        indent.margin(out)?;
+        out.write_str(brackets)?;
+        for (i, key) in path.iter().enumerate() {
+            if i > 0 {
+                out.write_char('.')?;
+            }
+            write_key(out, key)?;
+        }
+        out.write_str(&brackets.replace('[', "]"))?;
+        out.write_char('\n')
+    };
+    for (key, value) in entries.iter().filter(|(_, value)| section(value)) {
+        path.push(key.clone());
+        match value {
+            Value::Table(entries) => {
+                header(out, path, "[")?;
+                write_table(out, path, entries, indent.nested())?;
+            }
+            Value::Array(tables) => {
+                for table in tables {
+                    if let Value::Table(entries) = table {
+                        header(out, path, "[[")?;
+                        write_table(out, path, entries, indent.nested())?;
+                    }
+                }
+            }
+            _ => unreachable!("only tables and arrays of tables are sections"),
+        }
+        path.pop();
+    }
+    Ok(())
+}
+
+/// TOML. A table is written as a document, with `[section]` headers for
+/// its subtables, indented one level per section when `indent` has a
+/// width; other values are written inline. TOML has no null, so nulls are
+/// left out.
+impl PrettyPrint for Value {
+    fn pretty_print(&self, out: &mut dyn Write, indent: Indent) -> fmt::Result {
+        match self {
+            Value::Table(entries) => write_table(out, &mut vec![], entries, indent),
+            value => write_inline(out, value),
+        }
+    }
+}
+
+/// The URI's canonical text when compact, or otherwise an outline of its
+/// parts, one per line.
+impl PrettyPrint for URI<'_> {
+    fn pretty_print(&self, out: &mut dyn Write, indent: Indent) -> fmt::Result {
+        if indent.width.is_none() {
+            return write!(out, "{}", self);
+        }
+        // A path after an authority starts with `/`, an opaque one doesn't.
+        let root = if self.host.is_some() { "/" } else { "" };
+        let path = self
+            .path
+            .as_ref()
+            .map(|path| format!("{}{}", root, path));
+        let mut line = |label: &str, value: &dyn fmt::Display| -> fmt::Result {
+            indent.margin(out)?;
+            writeln!(out, "{}: {}", label, value)
+        };
+        line("scheme", &self.scheme.as_str())?;
+        if let Some(user) = self.user() {
+            line("user", &user)?;
+        }
+        if let Some(password) = self.password() {
+            line("password", &password)?;
+        }
+        if let Some(host) = &self.host {
+            line("host", host)?;
+        }
+        if let Some(port) = self.port {
+            line("port", &port)?;
+        }
+        if let Some(path) = &path {
+            line("path", path)?;
+        }
+        if let Some(fragment) = self.fragment {
+            line("fragment", &fragment)?;
+        }
+        if let Some(query) = &self.query {
+            indent.margin(out)?;
+            out.write_str("query:\n")?;
+            for (key, value) in query {
+                indent.nested().margin(out)?;
+                match value {
+                    Some(value) => writeln!(out, "{} = {}", key, value)?,
+                    None => writeln!(out, "{}", key)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let value = JsonValue::Object(vec![
+            (
+                "a".to_string(),
+                JsonValue::Array(vec![JsonValue::Number(1.0), JsonValue::Number(2.5)]),
+            ),
+            ("b".to_string(), JsonValue::Object(vec![])),
+            ("c".to_string(), JsonValue::String("x\ty".to_string())),
+        ]);
+        assert_eq!(
+            value.to_pretty_string(Indent::compact()),
+            r#"{"a":[1,2.5],"b":{},"c":"x\ty"}"#
+        );
+        assert_eq!(
+            value.to_pretty_string(Indent::default()),
+            "{\n  \"a\": [\n    1,\n    2.5\n  ],\n  \"b\": {},\n  \"c\": \"x\\ty\"\n}"
+        );
+    }
+
+    #[test]
+    fn test_value() {
+        let value = Value::Table(vec![
+            ("version".to_string(), Value::Integer(3)),
+            ("skipped".to_string(), Value::Null),
+            (
+                "package".to_string(),
+                Value::Array(vec![Value::Table(vec![
+                    ("name".to_string(), Value::String("nom".to_string())),
+                    ("weight".to_string(), Value::Float(1.0)),
+                    (
+                        "meta".to_string(),
+                        Value::Table(vec![("a b".to_string(), Value::Boolean(true))]),
+                    ),
+                ])]),
+            ),
+            (
+                "tags".to_string(),
+                Value::Array(vec![
+                    Value::String("x".to_string()),
+                    Value::Table(vec![("y".to_string(), Value::Integer(1))]),
+                ]),
+            ),
+        ]);
+        assert_eq!(
+            value.to_pretty_string(Indent::default()),
+            "version = 3\n\
+             tags = [\"x\", { y = 1 }]\n\
+             \n\
+             [[package]]\n  \
+             name = \"nom\"\n  \
+             weight = 1.0\n\
+             \n  \
+             [package.meta]\n    \
+             \"a b\" = true\n"
+        );
+        assert_eq!(
+            value.to_pretty_string(Indent::compact()),
+            "version = 3\ntags = [\"x\", { y = 1 }]\n[[package]]\nname = \"nom\"\n\
+             weight = 1.0\n[package.meta]\n\"a b\" = true\n"
+        );
+    }
+
+    #[test]
+    fn test_uri() {
+        let text = "https://ada:pw@example.com:8443/a/b?x=1&y=2#top";
+        let uri = crate::http::uri(text).unwrap().1;
+        assert_eq!(uri.to_pretty_string(Indent::compact()), text);
+        assert_eq!(
+            uri.to_pretty_string(Indent::spaces(4).nested()),
+            "    scheme: https\n    \
+             user: ada\n    \
+             password: pw\n    \
+             host: example.com\n    \
+             port: 8443\n    \
+             path: /a/b\n    \
+             fragment: top\n    \
+             query:\n        \
+             x = 1\n        \
+             y = 2\n"
+        );
+
+        let text = "mailto:ada@example.com?subject=hi";
+        let uri = crate::http::uri(text).unwrap().1;
+        assert_eq!(uri.to_pretty_string(Indent::compact()), text);
+    }
+}
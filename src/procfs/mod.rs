@@ -0,0 +1,356 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while1},
+    character::complete::{char, digit1, line_ending, not_line_ending, space0, space1},
+    combinator::{map, map_res, opt, value},
+    error::{context, VerboseError},
+    multi::{many0, separated_list0},
+    sequence::{pair, preceded, terminated, tuple},
+    IResult,
+};
+
+/// The contents of `/proc/meminfo`. Values with a `kB` unit are converted
+/// to bytes; unitless values such as `HugePages_Total` are kept as counts.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct MemInfo<'a> {
+    pub entries: Vec<(&'a str, u64)>,
+}
+
+impl<'a> MemInfo<'a> {
+    pub fn get(&self, key: &str) -> Option<u64> {
+        self.entries
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| *value)
+    }
+
+    pub fn mem_total(&self) -> Option<u64> {
+        self.get("MemTotal")
+    }
+
+    pub fn mem_free(&self) -> Option<u64> {
+        self.get("MemFree")
+    }
+
+    pub fn mem_available(&self) -> Option<u64> {
+        self.get("MemAvailable")
+    }
+
+    pub fn swap_total(&self) -> Option<u64> {
+        self.get("SwapTotal")
+    }
+
+    pub fn swap_free(&self) -> Option<u64> {
+        self.get("SwapFree")
+    }
+
+    /// Memory in use, counting reclaimable caches as free.
+    pub fn mem_used(&self) -> Option<u64> {
+        Some(self.mem_total()?.saturating_sub(self.mem_available()?))
+    }
+}
+
+/// Time spent by a CPU in each state, in `USER_HZ` ticks. Fields that an
+/// older kernel doesn't report are zero.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct CpuTimes {
+    pub user: u64,
+    pub nice: u64,
+    pub system: u64,
+    pub idle: u64,
+    pub iowait: u64,
+    pub irq: u64,
+    pub softirq: u64,
+    pub steal: u64,
+    pub guest: u64,
+    pub guest_nice: u64,
+}
+
+impl CpuTimes {
+    /// Total ticks. `guest` and `guest_nice` are already included in `user`
+    /// and `nice`, so they are not added again.
+    pub fn total(&self) -> u64 {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait
+            + self.irq
+            + self.softirq
+            + self.steal
+    }
+
+    pub fn idle_total(&self) -> u64 {
+        self.idle + self.iowait
+    }
+
+    /// The ticks elapsed between an `earlier` sample and this one.
+    pub fn delta(&self, earlier: &CpuTimes) -> CpuTimes {
+        CpuTimes {
+            user: self.user.saturating_sub(earlier.user),
+            nice: self.nice.saturating_sub(earlier.nice),
+            system: self.system.saturating_sub(earlier.system),
+            idle: self.idle.saturating_sub(earlier.idle),
+            iowait: self.iowait.saturating_sub(earlier.iowait),
+            irq: self.irq.saturating_sub(earlier.irq),
+            softirq: self.softirq.saturating_sub(earlier.softirq),
+            steal: self.steal.saturating_sub(earlier.steal),
+            guest: self.guest.saturating_sub(earlier.guest),
+            guest_nice: self.guest_nice.saturating_sub(earlier.guest_nice),
+        }
+    }
+
+    /// The fraction of time the CPU was busy since `earlier`, between 0
+    /// and 1. Returns 0 when no time has passed.
+    pub fn usage(&self, earlier: &CpuTimes) -> f64 {
+        let delta = self.delta(earlier);
+        match delta.total() {
+            0 => 0.0,
+            total => (total - delta.idle_total()) as f64 / total as f64,
+        }
+    }
+}
+
+/// The contents of `/proc/stat`.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Stat {
+    pub cpu: CpuTimes,
+    pub cpus: Vec<CpuTimes>,
+    /// The total interrupt count followed by the count per interrupt.
+    pub interrupts: Vec<u64>,
+    pub context_switches: u64,
+    pub boot_time: u64,
+    pub processes: u64,
+    pub procs_running: u64,
+    pub procs_blocked: u64,
+    /// The total softirq count followed by the count per softirq type.
+    pub softirqs: Vec<u64>,
+}
+
+impl Stat {
+    /// Counters elapsed between an `earlier` sample and this one. Gauges
+    /// such as `procs_running` and the boot time are taken from `self`.
+    pub fn delta(&self, earlier: &Stat) -> Stat {
+        let counters = |later: &[u64], earlier: &[u64]| {
+            later
+                .iter()
+                .zip(earlier.iter().chain(std::iter::repeat(&0)))
+                .map(|(later, earlier)| later.saturating_sub(*earlier))
+                .collect()
+        };
+        Stat {
+            cpu: self.cpu.delta(&earlier.cpu),
+            cpus: self
+                .cpus
+                .iter()
+                .zip(
+                    earlier
+                        .cpus
+                        .iter()
+                        .chain(std::iter::repeat(&CpuTimes::default())),
+                )
+                .map(|(later, earlier)| later.delta(earlier))
+                .collect(),
+            interrupts: counters(&self.interrupts, &earlier.interrupts),
+            context_switches: self
+                .context_switches
+                .saturating_sub(earlier.context_switches),
+            boot_time: self.boot_time,
+            processes: self.processes.saturating_sub(earlier.processes),
+            procs_running: self.procs_running,
+            procs_blocked: self.procs_blocked,
+            softirqs: counters(&self.softirqs, &earlier.softirqs),
+        }
+    }
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn number(input: &str) -> Res<&str, u64> {
+    map_res(digit1, str::parse)(input)
+}
+
+fn meminfo_line(input: &str) -> Res<&str, (&str, u64)> {
+    context(
+        "meminfo line",
+        tuple((
+            take_while1(|c: char| c != ':' && c != '\n'),
+            preceded(pair(char(':'), space0), number),
+            opt(preceded(space1, tag("kB"))),
+        )),
+    )(input)
+    .map(|(next_input, (key, value, unit))| {
+        let value = match unit {
+            Some(_) => value * 1024,
+            None => value,
+        };
+        (next_input, (key, value))
+    })
+}
+
+pub fn meminfo(input: &str) -> Res<&str, MemInfo<'_>> {
+    context(
+        "meminfo",
+        many0(terminated(meminfo_line, alt((line_ending, tag(""))))),
+    )(input)
+    .map(|(next_input, entries)| (next_input, MemInfo { entries }))
+}
+
+fn numbers(input: &str) -> Res<&str, Vec<u64>> {
+    preceded(space0, separated_list0(space1, number))(input)
+}
+
+fn cpu_times(input: &str) -> Res<&str, CpuTimes> {
+    map(numbers, |fields| {
+        let field = |i: usize| fields.get(i).copied().unwrap_or(0);
+        CpuTimes {
+            user: field(0),
+            nice: field(1),
+            system: field(2),
+            idle: field(3),
+            iowait: field(4),
+            irq: field(5),
+            softirq: field(6),
+            steal: field(7),
+            guest: field(8),
+            guest_nice: field(9),
+        }
+    })(input)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum StatLine {
+    Cpu(CpuTimes),
+    NumberedCpu(CpuTimes),
+    Interrupts(Vec<u64>),
+    SoftIrqs(Vec<u64>),
+    Counter(&'static str, u64),
+    Unknown,
+}
+
+fn stat_line(input: &str) -> Res<&str, StatLine> {
+    let counter = |name: &'static str| {
+        map(preceded(pair(tag(name), space1), number), move |n| {
+            StatLine::Counter(name, n)
+        })
+    };
+    context(
+        "stat line",
+        alt((
+            map(preceded(tag("cpu "), cpu_times), StatLine::Cpu),
+            map(
+                preceded(tuple((tag("cpu"), digit1)), cpu_times),
+                StatLine::NumberedCpu,
+            ),
+            map(preceded(tag("intr"), numbers), StatLine::Interrupts),
+            map(preceded(tag("softirq"), numbers), StatLine::SoftIrqs),
+            counter("ctxt"),
+            counter("btime"),
+            counter("processes"),
+            counter("procs_running"),
+            counter("procs_blocked"),
+            value(StatLine::Unknown, not_line_ending),
+        )),
+    )(input)
+}
+
+/// Parses `/proc/stat`. Lines this parser doesn't know about are skipped.
+pub fn stat(input: &str) -> Res<&str, Stat> {
+    let mut stat = Stat::default();
+    let mut input = input;
+    while !input.is_empty() {
+        let (next_input, line) = terminated(stat_line, alt((line_ending, tag(""))))(input)?;
+        match line {
+            StatLine::Cpu(times) => stat.cpu = times,
+            StatLine::NumberedCpu(times) => stat.cpus.push(times),
+            StatLine::Interrupts(counts) => stat.interrupts = counts,
+            StatLine::SoftIrqs(counts) => stat.softirqs = counts,
+            StatLine::Counter("ctxt", n) => stat.context_switches = n,
+            StatLine::Counter("btime", n) => stat.boot_time = n,
+            StatLine::Counter("processes", n) => stat.processes = n,
+            StatLine::Counter("procs_running", n) => stat.procs_running = n,
+            StatLine::Counter(_, n) => stat.procs_blocked = n,
+            StatLine::Unknown => {}
+        }
+        if next_input.len() == input.len() {
+            break;
+        }
+        input = next_input;
+    }
+    Ok((input, stat))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMINFO: &str = "MemTotal:       16318560 kB
+MemFree:         1234560 kB
+MemAvailable:    8159280 kB
+SwapTotal:             0 kB
+HugePages_Total:       4
+";
+
+    const STAT: &str = "cpu  100 10 50 800 20 5 5 0 0 0
+cpu0 50 5 25 400 10 3 2 0 0 0
+cpu1 50 5 25 400 10 2 3 0 0 0
+intr 1000 10 0 20
+ctxt 5000
+btime 1700000000
+processes 300
+procs_running 2
+procs_blocked 0
+softirq 400 1 100
+";
+
+    #[test]
+    fn test_meminfo() {
+        let (rest, info) = meminfo(MEMINFO).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(info.entries.len(), 5);
+        assert_eq!(info.mem_total(), Some(16318560 * 1024));
+        assert_eq!(info.mem_used(), Some((16318560 - 8159280) * 1024));
+        assert_eq!(info.swap_total(), Some(0));
+        assert_eq!(info.get("HugePages_Total"), Some(4));
+        assert_eq!(info.get("Nope"), None);
+    }
+
+    #[test]
+    fn test_stat() {
+        let (rest, stat) = stat(STAT).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(stat.cpu.user, 100);
+        assert_eq!(stat.cpu.total(), 990);
+        assert_eq!(stat.cpus.len(), 2);
+        assert_eq!(stat.cpus[1].softirq, 3);
+        assert_eq!(stat.interrupts, vec![1000, 10, 0, 20]);
+        assert_eq!(stat.softirqs, vec![400, 1, 100]);
+        assert_eq!(stat.context_switches, 5000);
+        assert_eq!(stat.boot_time, 1700000000);
+        assert_eq!(stat.processes, 300);
+        assert_eq!(stat.procs_running, 2);
+    }
+
+    #[test]
+    fn test_old_kernel_cpu_line() {
+        let (_, stat) = stat("cpu 1 2 3 4\nunknown line 1 2\n").unwrap();
+        assert_eq!(stat.cpu.idle, 4);
+        assert_eq!(stat.cpu.iowait, 0);
+    }
+
+    #[test]
+    fn test_delta() {
+        let (_, earlier) = stat(STAT).unwrap();
+        let later = STAT
+            .replace("cpu  100 10 50 800", "cpu  150 10 50 850")
+            .replace("ctxt 5000", "ctxt 5600");
+        let (_, later) = stat(&later).unwrap();
+        let delta = later.delta(&earlier);
+        assert_eq!(delta.cpu.user, 50);
+        assert_eq!(delta.cpu.total(), 100);
+        assert_eq!(delta.context_switches, 600);
+        assert_eq!(delta.interrupts, vec![0, 0, 0, 0]);
+        assert_eq!(later.cpu.usage(&earlier.cpu), 0.5);
+        assert_eq!(earlier.cpu.usage(&earlier.cpu), 0.0);
+    }
+}
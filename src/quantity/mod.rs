@@ -0,0 +1,273 @@
+use std::fmt;
+use std::str::FromStr;
+
+use nom::{
+    character::complete::space0,
+    error::{context, VerboseError, VerboseErrorKind},
+    number::complete::recognize_float,
+    Err as NomErr, IResult,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Dimension {
+    Length,
+    Mass,
+    Time,
+    Temperature,
+    DataSize,
+    /// A dimension for units added to a [`UnitTable`] by the caller.
+    Custom(&'static str),
+}
+
+/// A unit, defined by how to convert it to the base unit of its dimension:
+/// `base = value * factor + offset`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Unit {
+    /// The symbols the unit is written with. The first is used for display.
+    pub symbols: &'static [&'static str],
+    pub dimension: Dimension,
+    pub factor: f64,
+    pub offset: f64,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Quantity {
+    pub value: f64,
+    pub unit: Unit,
+}
+
+const fn unit(symbols: &'static [&'static str], dimension: Dimension, factor: f64) -> Unit {
+    Unit {
+        symbols,
+        dimension,
+        factor,
+        offset: 0.0,
+    }
+}
+
+/// The built-in units. Base units are metres, kilograms, seconds, kelvin
+/// and bytes.
+pub const UNITS: &[Unit] = &[
+    unit(&["m"], Dimension::Length, 1.0),
+    unit(&["km"], Dimension::Length, 1e3),
+    unit(&["cm"], Dimension::Length, 1e-2),
+    unit(&["mm"], Dimension::Length, 1e-3),
+    unit(&["µm", "um"], Dimension::Length, 1e-6),
+    unit(&["nm"], Dimension::Length, 1e-9),
+    unit(&["in"], Dimension::Length, 0.0254),
+    unit(&["ft"], Dimension::Length, 0.3048),
+    unit(&["yd"], Dimension::Length, 0.9144),
+    unit(&["mi"], Dimension::Length, 1609.344),
+    unit(&["kg"], Dimension::Mass, 1.0),
+    unit(&["g"], Dimension::Mass, 1e-3),
+    unit(&["mg"], Dimension::Mass, 1e-6),
+    unit(&["t"], Dimension::Mass, 1e3),
+    unit(&["lb"], Dimension::Mass, 0.453_592_37),
+    unit(&["oz"], Dimension::Mass, 0.028_349_523_125),
+    unit(&["s"], Dimension::Time, 1.0),
+    unit(&["ms"], Dimension::Time, 1e-3),
+    unit(&["µs", "us"], Dimension::Time, 1e-6),
+    unit(&["ns"], Dimension::Time, 1e-9),
+    unit(&["min"], Dimension::Time, 60.0),
+    unit(&["h"], Dimension::Time, 3600.0),
+    unit(&["d"], Dimension::Time, 86400.0),
+    unit(&["K"], Dimension::Temperature, 1.0),
+    Unit {
+        symbols: &["°C"],
+        dimension: Dimension::Temperature,
+        factor: 1.0,
+        offset: 273.15,
+    },
+    Unit {
+        symbols: &["°F"],
+        dimension: Dimension::Temperature,
+        factor: 5.0 / 9.0,
+        offset: 459.67 * 5.0 / 9.0,
+    },
+    unit(&["B"], Dimension::DataSize, 1.0),
+    unit(&["b", "bit"], Dimension::DataSize, 0.125),
+    unit(&["kB"], Dimension::DataSize, 1e3),
+    unit(&["MB"], Dimension::DataSize, 1e6),
+    unit(&["GB"], Dimension::DataSize, 1e9),
+    unit(&["KiB"], Dimension::DataSize, 1024.0),
+    unit(&["MiB"], Dimension::DataSize, 1_048_576.0),
+    unit(&["GiB"], Dimension::DataSize, 1_073_741_824.0),
+];
+
+/// The units a parser recognises. The default table holds [`UNITS`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct UnitTable {
+    pub units: Vec<Unit>,
+}
+
+impl Default for UnitTable {
+    fn default() -> Self {
+        UnitTable {
+            units: UNITS.to_vec(),
+        }
+    }
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+impl UnitTable {
+    pub fn add(&mut self, unit: Unit) {
+        self.units.push(unit);
+    }
+
+    pub fn unit(&self, symbol: &str) -> Option<Unit> {
+        self.units
+            .iter()
+            .find(|unit| unit.symbols.contains(&symbol))
+            .copied()
+    }
+
+    /// Matches the longest unit symbol at the start of `input` that isn't
+    /// immediately followed by more letters, so `5 miles` is not `5 mi`.
+    fn unit_symbol<'i>(&self, input: &'i str) -> Res<&'i str, Unit> {
+        self.units
+            .iter()
+            .flat_map(|unit| unit.symbols.iter().map(move |symbol| (unit, symbol)))
+            .filter(|(_, symbol)| {
+                input.starts_with(**symbol)
+                    && !input[symbol.len()..].starts_with(|c: char| c.is_alphanumeric())
+            })
+            .max_by_key(|(_, symbol)| symbol.len())
+            .map(|(unit, symbol)| (&input[symbol.len()..], *unit))
+            .ok_or_else(|| failure(input, "unknown unit"))
+    }
+
+    /// Parses a number followed by one of this table's units, such as
+    /// `3.2 km` or `250ms`.
+    pub fn parse<'i>(&self, input: &'i str) -> Res<&'i str, Quantity> {
+        let (next_input, number) = context("number", recognize_float)(input)?;
+        let value = number
+            .parse()
+            .map_err(|_| failure(input, "invalid number"))?;
+        let (next_input, _) = space0(next_input)?;
+        let (next_input, unit) = context("unit", |i| self.unit_symbol(i))(next_input)?;
+        Ok((next_input, Quantity { value, unit }))
+    }
+}
+
+/// Parses a quantity using the built-in units.
+pub fn quantity(input: &str) -> Res<&str, Quantity> {
+    UnitTable::default().parse(input)
+}
+
+impl Quantity {
+    /// The value expressed in the base unit of its dimension.
+    pub fn to_base(&self) -> f64 {
+        self.value * self.unit.factor + self.unit.offset
+    }
+
+    /// Converts to another unit of the same dimension.
+    pub fn convert(&self, unit: Unit) -> Option<Quantity> {
+        if unit.dimension != self.unit.dimension {
+            return None;
+        }
+        Some(Quantity {
+            value: (self.to_base() - unit.offset) / unit.factor,
+            unit,
+        })
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.value, self.unit.symbols[0])
+    }
+}
+
+impl FromStr for Quantity {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::error::parse_all("quantity", s, quantity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn test_quantity() {
+        let (rest, q) = quantity("3.2 km").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(q.value, 3.2);
+        assert_eq!(q.unit.symbols, &["km"]);
+        let (_, q) = quantity("250ms").unwrap();
+        assert_eq!((q.value, q.unit.dimension), (250.0, Dimension::Time));
+        let (_, q) = quantity("72°F").unwrap();
+        assert_eq!(q.unit.dimension, Dimension::Temperature);
+        let (rest, q) = quantity("15 kg, rest").unwrap();
+        assert_eq!((q.value, rest), (15.0, ", rest"));
+        let (_, q) = quantity("-1.5e3 um").unwrap();
+        assert_eq!(q.value, -1500.0);
+        assert_eq!(q.to_string(), "-1500 µm");
+    }
+
+    #[test]
+    fn test_quantity_errors() {
+        assert_eq!(
+            quantity("5 miles"),
+            Err(NomErr::Failure(VerboseError {
+                errors: vec![
+                    ("miles", VerboseErrorKind::Context("unknown unit")),
+                    ("miles", VerboseErrorKind::Context("unit")),
+                ]
+            }))
+        );
+        assert!(quantity("km").is_err());
+    }
+
+    #[test]
+    fn test_convert() {
+        let table = UnitTable::default();
+        let (_, q) = quantity("72°F").unwrap();
+        let celsius = q.convert(table.unit("°C").unwrap()).unwrap();
+        assert!(approx(celsius.value, 22.222_222_222));
+        let (_, q) = quantity("1 mi").unwrap();
+        assert!(approx(
+            q.convert(table.unit("km").unwrap()).unwrap().value,
+            1.609_344
+        ));
+        let (_, q) = quantity("2 GiB").unwrap();
+        assert!(approx(
+            q.convert(table.unit("MiB").unwrap()).unwrap().value,
+            2048.0
+        ));
+        assert_eq!(q.convert(table.unit("s").unwrap()), None);
+    }
+
+    #[test]
+    fn test_custom_unit() {
+        let mut table = UnitTable::default();
+        let pixel = Unit {
+            symbols: &["px"],
+            dimension: Dimension::Custom("screen"),
+            factor: 1.0,
+            offset: 0.0,
+        };
+        table.add(pixel);
+        table.add(Unit {
+            symbols: &["rem"],
+            factor: 16.0,
+            ..pixel
+        });
+        let (_, q) = table.parse("1.5rem").unwrap();
+        assert_eq!(q.convert(pixel).unwrap().value, 24.0);
+        assert!(quantity("1.5rem").is_err());
+    }
+}
@@ -0,0 +1,496 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while1, take_while_m_n},
+    character::complete::{char, digit1, none_of},
+    combinator::{all_consuming, map, map_opt, map_res, opt, value},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::{many0, separated_list1},
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+    Err as NomErr, IResult,
+};
+
+use crate::limits::nested;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Ast {
+    Empty,
+    Literal(char),
+    Dot,
+    Perl(PerlClass),
+    Class(Class),
+    Anchor(Anchor),
+    Group(Group),
+    Repetition(Repetition),
+    Concat(Vec<Ast>),
+    Alternation(Vec<Ast>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Anchor {
+    Start,
+    End,
+    WordBoundary,
+    NotWordBoundary,
+}
+
+/// The `\d`, `\w` and `\s` shorthand classes and their negations.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PerlClass {
+    Digit,
+    NotDigit,
+    Word,
+    NotWord,
+    Space,
+    NotSpace,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ClassItem {
+    Char(char),
+    Range(char, char),
+    Perl(PerlClass),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Class {
+    pub negated: bool,
+    pub items: Vec<ClassItem>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum GroupKind {
+    /// Capturing groups are numbered from 1 in order of their opening
+    /// parenthesis.
+    Capturing {
+        index: u32,
+        name: Option<String>,
+    },
+    NonCapturing,
+    LookAhead,
+    NegativeLookAhead,
+    LookBehind,
+    NegativeLookBehind,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Group {
+    pub kind: GroupKind,
+    pub ast: Box<Ast>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Repetition {
+    pub ast: Box<Ast>,
+    pub min: u32,
+    pub max: Option<u32>,
+    pub greedy: bool,
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+/// Characters that must be escaped to be matched literally.
+const META: &str = "|()[*+?.^$\\";
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+fn perl_class(input: &str) -> Res<&str, PerlClass> {
+    alt((
+        value(PerlClass::Digit, char('d')),
+        value(PerlClass::NotDigit, char('D')),
+        value(PerlClass::Word, char('w')),
+        value(PerlClass::NotWord, char('W')),
+        value(PerlClass::Space, char('s')),
+        value(PerlClass::NotSpace, char('S')),
+    ))(input)
+}
+
+fn hex_char<'a>(prefix: char, digits: usize) -> impl FnMut(&'a str) -> Res<&'a str, char> {
+    map_opt(
+        preceded(
+            char(prefix),
+            take_while_m_n(digits, digits, |c: char| c.is_ascii_hexdigit()),
+        ),
+        |hex| u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+    )
+}
+
+/// Escapes that stand for a single character, valid both inside and
+/// outside of classes.
+fn char_escape(input: &str) -> Res<&str, char> {
+    alt((
+        value('\n', char('n')),
+        value('\t', char('t')),
+        value('\r', char('r')),
+        value('\x0c', char('f')),
+        value('\x0b', char('v')),
+        value('\0', char('0')),
+        hex_char('x', 2),
+        hex_char('u', 4),
+        none_of("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789"),
+    ))(input)
+}
+
+fn escape(input: &str) -> Res<&str, Ast> {
+    context(
+        "escape",
+        preceded(
+            char('\\'),
+            alt((
+                map(perl_class, Ast::Perl),
+                value(Ast::Anchor(Anchor::WordBoundary), char('b')),
+                value(Ast::Anchor(Anchor::NotWordBoundary), char('B')),
+                map(char_escape, Ast::Literal),
+            )),
+        ),
+    )(input)
+}
+
+fn class_char(input: &str) -> Res<&str, char> {
+    alt((preceded(char('\\'), char_escape), none_of("\\]")))(input)
+}
+
+fn class_item(input: &str) -> Res<&str, ClassItem> {
+    let (next_input, item) = alt((
+        map(preceded(char('\\'), perl_class), ClassItem::Perl),
+        // A trailing `-` cannot form a range and is taken as a literal.
+        map(
+            separated_pair(class_char, char('-'), class_char),
+            |(start, end)| ClassItem::Range(start, end),
+        ),
+        map(class_char, ClassItem::Char),
+    ))(input)?;
+    match item {
+        ClassItem::Range(start, end) if start > end => Err(failure(input, "invalid class range")),
+        item => Ok((next_input, item)),
+    }
+}
+
+fn class(input: &str) -> Res<&str, Class> {
+    context(
+        "class",
+        delimited(
+            char('['),
+            tuple((
+                map(opt(char('^')), |negated| negated.is_some()),
+                // `]` is a literal when it is the first character.
+                opt(value(ClassItem::Char(']'), char(']'))),
+                many0(class_item),
+            )),
+            char(']'),
+        ),
+    )(input)
+    .map(|(next_input, (negated, first, rest))| {
+        let items = first.into_iter().chain(rest).collect();
+        (next_input, Class { negated, items })
+    })
+}
+
+fn group_kind(input: &str) -> Res<&str, GroupKind> {
+    let name = |input| {
+        map(
+            take_while1(|c: char| c.is_alphanumeric() || c == '_'),
+            |name: &str| GroupKind::Capturing {
+                index: 0,
+                name: Some(name.to_owned()),
+            },
+        )(input)
+    };
+    alt((
+        preceded(
+            char('?'),
+            alt((
+                value(GroupKind::NonCapturing, char(':')),
+                value(GroupKind::LookAhead, char('=')),
+                value(GroupKind::NegativeLookAhead, char('!')),
+                value(GroupKind::LookBehind, tag("<=")),
+                value(GroupKind::NegativeLookBehind, tag("<!")),
+                delimited(alt((tag("P<"), tag("<"))), name, char('>')),
+            )),
+        ),
+        value(
+            GroupKind::Capturing {
+                index: 0,
+                name: None,
+            },
+            tag(""),
+        ),
+    ))(input)
+}
+
+fn group(input: &str) -> Res<&str, Group> {
+    context(
+        "group",
+        delimited(char('('), pair(group_kind, alternation), char(')')),
+    )(input)
+    .map(|(next_input, (kind, ast))| {
+        (
+            next_input,
+            Group {
+                kind,
+                ast: Box::new(ast),
+            },
+        )
+    })
+}
+
+fn atom(input: &str) -> Res<&str, Ast> {
+    context(
+        "atom",
+        alt((
+            map(nested(char('('), group), Ast::Group),
+            map(class, Ast::Class),
+            escape,
+            value(Ast::Dot, char('.')),
+            value(Ast::Anchor(Anchor::Start), char('^')),
+            value(Ast::Anchor(Anchor::End), char('$')),
+            map(none_of(META), Ast::Literal),
+        )),
+    )(input)
+}
+
+fn number(input: &str) -> Res<&str, u32> {
+    map_res(digit1, str::parse)(input)
+}
+
+/// Parses a quantifier into its bounds. A `{` that does not start a valid
+/// bounded quantifier is left to be matched as a literal.
+fn quantifier(input: &str) -> Res<&str, (u32, Option<u32>, bool)> {
+    let (next_input, (min, max)) = context(
+        "quantifier",
+        alt((
+            value((0, None), char('*')),
+            value((1, None), char('+')),
+            value((0, Some(1)), char('?')),
+            delimited(
+                char('{'),
+                alt((
+                    map(separated_pair(number, char(','), number), |(min, max)| {
+                        (min, Some(max))
+                    }),
+                    map(terminated(number, char(',')), |min| (min, None)),
+                    map(number, |n| (n, Some(n))),
+                )),
+                char('}'),
+            ),
+        )),
+    )(input)?;
+    if matches!(max, Some(max) if max < min) {
+        return Err(failure(input, "invalid repetition range"));
+    }
+    let (next_input, lazy) = opt(char('?'))(next_input)?;
+    Ok((next_input, (min, max, lazy.is_none())))
+}
+
+fn repeat(input: &str) -> Res<&str, Ast> {
+    let (next_input, (ast, quantifier)) = pair(atom, opt(quantifier))(input)?;
+    if matches!(ast, Ast::Anchor(_)) && quantifier.is_some() {
+        return Err(failure(input, "nothing to repeat"));
+    }
+    let ast = match quantifier {
+        Some((min, max, greedy)) => Ast::Repetition(Repetition {
+            ast: Box::new(ast),
+            min,
+            max,
+            greedy,
+        }),
+        None => ast,
+    };
+    Ok((next_input, ast))
+}
+
+fn concat(input: &str) -> Res<&str, Ast> {
+    map(many0(repeat), |mut asts| match asts.len() {
+        0 => Ast::Empty,
+        1 => asts.remove(0),
+        _ => Ast::Concat(asts),
+    })(input)
+}
+
+fn alternation(input: &str) -> Res<&str, Ast> {
+    map(separated_list1(char('|'), concat), |mut asts| {
+        if asts.len() == 1 {
+            asts.remove(0)
+        } else {
+            Ast::Alternation(asts)
+        }
+    })(input)
+}
+
+fn number_groups(ast: &mut Ast, next: &mut u32) {
+    match ast {
+        Ast::Group(group) => {
+            if let GroupKind::Capturing { index, .. } = &mut group.kind {
+                *next += 1;
+                *index = *next;
+            }
+            number_groups(&mut group.ast, next);
+        }
+        Ast::Repetition(repetition) => number_groups(&mut repetition.ast, next),
+        Ast::Concat(asts) | Ast::Alternation(asts) => {
+            asts.iter_mut().for_each(|ast| number_groups(ast, next))
+        }
+        _ => {}
+    }
+}
+
+/// Parses a complete regular expression pattern into an [`Ast`].
+pub fn pattern(input: &str) -> Res<&str, Ast> {
+    let (next_input, mut ast) = context("pattern", all_consuming(alternation))(input)?;
+    number_groups(&mut ast, &mut 0);
+    Ok((next_input, ast))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(c: char) -> Ast {
+        Ast::Literal(c)
+    }
+
+    #[test]
+    fn test_concat_and_alternation() {
+        assert_eq!(
+            pattern("ab|c"),
+            Ok((
+                "",
+                Ast::Alternation(vec![Ast::Concat(vec![lit('a'), lit('b')]), lit('c')])
+            ))
+        );
+        assert_eq!(
+            pattern("a|"),
+            Ok(("", Ast::Alternation(vec![lit('a'), Ast::Empty])))
+        );
+    }
+
+    #[test]
+    fn test_quantifier() {
+        assert_eq!(quantifier("*"), Ok(("", (0, None, true))));
+        assert_eq!(quantifier("+?"), Ok(("", (1, None, false))));
+        assert_eq!(quantifier("{3}"), Ok(("", (3, Some(3), true))));
+        assert_eq!(quantifier("{2,}"), Ok(("", (2, None, true))));
+        assert_eq!(quantifier("{2,5}?"), Ok(("", (2, Some(5), false))));
+        assert!(quantifier("{5,2}").is_err());
+        assert_eq!(
+            pattern("a{x"),
+            Ok(("", Ast::Concat(vec![lit('a'), lit('{'), lit('x')])))
+        );
+    }
+
+    #[test]
+    fn test_class() {
+        assert_eq!(
+            class("[^a-z_\\d-]"),
+            Ok((
+                "",
+                Class {
+                    negated: true,
+                    items: vec![
+                        ClassItem::Range('a', 'z'),
+                        ClassItem::Char('_'),
+                        ClassItem::Perl(PerlClass::Digit),
+                        ClassItem::Char('-'),
+                    ]
+                }
+            ))
+        );
+        assert_eq!(
+            class("[]a]"),
+            Ok((
+                "",
+                Class {
+                    negated: false,
+                    items: vec![ClassItem::Char(']'), ClassItem::Char('a')]
+                }
+            ))
+        );
+        assert!(class("[z-a]").is_err());
+        assert!(class("[abc").is_err());
+    }
+
+    #[test]
+    fn test_groups() {
+        let (_, ast) = pattern("(a)(?:b)(?P<year>\\d{4})((c))").unwrap();
+        let Ast::Concat(asts) = ast else {
+            panic!("expected concat")
+        };
+        let kinds: Vec<_> = asts
+            .iter()
+            .map(|ast| match ast {
+                Ast::Group(group) => group.kind.clone(),
+                _ => panic!("expected group"),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                GroupKind::Capturing {
+                    index: 1,
+                    name: None
+                },
+                GroupKind::NonCapturing,
+                GroupKind::Capturing {
+                    index: 2,
+                    name: Some("year".to_owned())
+                },
+                GroupKind::Capturing {
+                    index: 3,
+                    name: None
+                },
+            ]
+        );
+        let Ast::Group(outer) = &asts[3] else {
+            unreachable!()
+        };
+        assert_eq!(
+            *outer.ast,
+            Ast::Group(Group {
+                kind: GroupKind::Capturing {
+                    index: 4,
+                    name: None
+                },
+                ast: Box::new(lit('c')),
+            })
+        );
+        assert!(matches!(
+            pattern("(?<!x)y"),
+            Ok((_, Ast::Concat(ref asts))) if matches!(
+                asts[0],
+                Ast::Group(Group { kind: GroupKind::NegativeLookBehind, .. })
+            )
+        ));
+    }
+
+    #[test]
+    fn test_anchors_and_escapes() {
+        assert_eq!(
+            pattern("^\\bx\\.\\x41\\n.$"),
+            Ok((
+                "",
+                Ast::Concat(vec![
+                    Ast::Anchor(Anchor::Start),
+                    Ast::Anchor(Anchor::WordBoundary),
+                    lit('x'),
+                    lit('.'),
+                    lit('A'),
+                    lit('\n'),
+                    Ast::Dot,
+                    Ast::Anchor(Anchor::End),
+                ])
+            ))
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(pattern("(ab").is_err());
+        assert!(pattern("ab)").is_err());
+        assert!(pattern("*a").is_err());
+        assert!(pattern("^*").is_err());
+        assert!(pattern("\\q").is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! Formats as trait objects, so code that works with "any format", like
+//! detection or the CLI, can be given formats defined outside this crate.
+//!
+//! ```
+//! use parsers::registry::{Format, Registry};
+//! use parsers::{Error, Value};
+//!
+//! struct Lines;
+//!
+//! impl Format for Lines {
+//!     fn name(&self) -> &str {
+//!         "lines"
+//!     }
+//!
+//!     fn sniff(&self, input: &[u8]) -> f32 {
+//!         if input.ends_with(b"\n") { 0.1 } else { 0.0 }
+//!     }
+//!
+//!     fn parse(&self, input: &[u8]) -> Result<Value, Error> {
+//!         let text = String::from_utf8_lossy(input);
+//!         Ok(Value::Array(text.lines().map(|l| Value::String(l.into())).collect()))
+//!     }
+//! }
+//!
+//! let mut registry = Registry::default();
+//! registry.register(Lines);
+//! let (format, _) = registry.parse_any(b"one\ntwo\n")?;
+//! assert_eq!(format.name(), "lines");
+//! # Ok::<(), Error>(())
+//! ```
+
+use crate::detect::{self, parse_as};
+use crate::value::{ToValue, Value};
+use crate::Error;
+
+pub trait Format: Send + Sync {
+    /// A short lowercase name, such as `"json"`. Registering a format with
+    /// the same name as another replaces it.
+    fn name(&self) -> &str;
+
+    /// How confident the format is that `input` is in it, from 0 (not at
+    /// all) to 1 (certain, as with magic bytes).
+    fn sniff(&self, input: &[u8]) -> f32;
+
+    fn parse(&self, input: &[u8]) -> Result<Value, Error>;
+}
+
+/// One of the formats from `detect`.
+struct Builtin(detect::Format);
+
+impl Format for Builtin {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn sniff(&self, input: &[u8]) -> f32 {
+        detect::detect(input)
+            .into_iter()
+            .find(|guess| guess.format == self.0)
+            .map_or(0.0, |guess| guess.confidence)
+    }
+
+    fn parse(&self, input: &[u8]) -> Result<Value, Error> {
+        parse_as(self.0, input).map(|document| document.to_value())
+    }
+}
+
+const BUILTINS: [detect::Format; 11] = [
+    detect::Format::Png,
+    detect::Format::Gzip,
+    detect::Format::Zip,
+    detect::Format::Json,
+    detect::Format::Gpx,
+    detect::Format::WebVtt,
+    detect::Format::Srt,
+    detect::Format::Bibtex,
+    detect::Format::CargoLock,
+    detect::Format::Pgn,
+    detect::Format::Obj,
+];
+
+pub struct Registry {
+    formats: Vec<Box<dyn Format>>,
+}
+
+impl Registry {
+    /// A registry with no formats. `Registry::default()` has the built-in
+    /// ones.
+    pub fn empty() -> Self {
+        Registry { formats: vec![] }
+    }
+
+    pub fn register(&mut self, format: impl Format + 'static) {
+        self.formats.retain(|f| f.name() != format.name());
+        self.formats.push(Box::new(format));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Format> {
+        self.formats()
+            .find(|format| format.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn formats(&self) -> impl Iterator<Item = &dyn Format> {
+        self.formats.iter().map(|format| format.as_ref())
+    }
+
+    /// The formats that think `input` might be theirs, most confident
+    /// first.
+    pub fn detect(&self, input: &[u8]) -> Vec<(&dyn Format, f32)> {
+        let mut guesses: Vec<_> = self
+            .formats()
+            .map(|format| (format, format.sniff(input)))
+            .filter(|(_, confidence)| *confidence > 0.0)
+            .collect();
+        guesses.sort_by(|a, b| b.1.total_cmp(&a.1));
+        guesses
+    }
+
+    pub fn parse(&self, name: &str, input: &[u8]) -> Result<Value, Error> {
+        match self.get(name) {
+            Some(format) => format.parse(input),
+            None => Err(Error {
+                format: "unknown",
+                span: 0..0,
+                line: 1,
+                column: 1,
+                expected: Some("a registered format".to_string()),
+                found: format!("`{}`", name),
+                context: vec![],
+            }),
+        }
+    }
+
+    /// Like `detect::parse_any`, over the registered formats.
+    pub fn parse_any(&self, input: &[u8]) -> Result<(&dyn Format, Value), Error> {
+        let mut first_error = None;
+        for (format, _) in self.detect(input) {
+            match format.parse(input) {
+                Ok(value) => return Ok((format, value)),
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        Err(first_error.unwrap_or_else(|| Error {
+            format: "unknown",
+            span: 0..input.len().min(1),
+            line: 1,
+            column: 1,
+            expected: Some("a known format".to_string()),
+            found: input.first().map_or("end of input".to_string(), |byte| {
+                format!("byte 0x{:02x}", byte)
+            }),
+            context: vec![],
+        }))
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry::empty();
+        for format in BUILTINS {
+            registry.register(Builtin(format));
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Csv;
+
+    impl Format for Csv {
+        fn name(&self) -> &str {
+            "csv"
+        }
+
+        fn sniff(&self, input: &[u8]) -> f32 {
+            match input.contains(&b',') {
+                true => 0.2,
+                false => 0.0,
+            }
+        }
+
+        fn parse(&self, input: &[u8]) -> Result<Value, Error> {
+            let text = std::str::from_utf8(input)
+                .map_err(|_| Error::expected("csv", input, input, "UTF-8 text"))?;
+            Ok(Value::Array(
+                text.lines()
+                    .map(|line| Value::Array(line.split(',').map(str::to_value).collect()))
+                    .collect(),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_builtins() {
+        let registry = Registry::default();
+        assert_eq!(registry.formats().count(), 11);
+        let (format, value) = registry.parse_any(br#"{"a": [1, 2.5]}"#).unwrap();
+        assert_eq!(format.name(), "json");
+        assert_eq!(
+            value,
+            Value::Table(vec![(
+                "a".to_string(),
+                Value::Array(vec![Value::Integer(1), Value::Float(2.5)])
+            )])
+        );
+        let error = registry.parse("toml", b"").unwrap_err();
+        assert_eq!(error.found, "`toml`");
+    }
+
+    #[test]
+    fn test_register() {
+        let mut registry = Registry::default();
+        registry.register(Csv);
+        assert_eq!(registry.get("CSV").map(|f| f.name()), Some("csv"));
+        let (format, value) = registry.parse_any(b"a,b\nc,d").unwrap();
+        assert_eq!(format.name(), "csv");
+        assert_eq!(
+            value,
+            Value::Array(vec![
+                vec!["a".to_string(), "b".to_string()].to_value(),
+                vec!["c".to_string(), "d".to_string()].to_value(),
+            ])
+        );
+        // JSON is still preferred where it applies.
+        let (format, _) = registry.parse_any(b"[1, 2]").unwrap();
+        assert_eq!(format.name(), "json");
+
+        registry.register(Csv);
+        assert_eq!(registry.formats().count(), 12);
+    }
+}
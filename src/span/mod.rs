@@ -0,0 +1,128 @@
+//! Source locations. Every parser here works on subslices of its input, so
+//! a location can be recovered from where a slice sits in the original
+//! string instead of threading a position through each parser.
+
+use std::ops::Range;
+
+use nom::{IResult, Offset, Parser};
+
+/// A slice of the source along with where it starts. Lines and columns are
+/// 1-based; columns count characters, not bytes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Span<'a> {
+    pub fragment: &'a str,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A parse result and the span of input it was parsed from.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Spanned<'a, T> {
+    pub value: T,
+    pub span: Span<'a>,
+}
+
+impl<'a> Span<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Span {
+            fragment: source,
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// Locates `fragment`, which must be a subslice of `source`.
+    ///
+    /// # Panics
+    ///
+    /// If `fragment` does not point into `source`.
+    pub fn locate(source: &'a str, fragment: &'a str) -> Self {
+        let start = source.as_ptr() as usize;
+        let position = fragment.as_ptr() as usize;
+        assert!(
+            position >= start && position + fragment.len() <= start + source.len(),
+            "fragment is not part of the source"
+        );
+        Span::at(source, source.offset(fragment), fragment.len())
+    }
+
+    /// The span of `len` bytes at `offset` in `source`, clamped to the end.
+    pub fn at(source: &'a str, offset: usize, len: usize) -> Self {
+        let offset = offset.min(source.len());
+        let end = (offset + len).min(source.len());
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Span {
+            fragment: &source[offset..end],
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.fragment.len()
+    }
+}
+
+/// Runs `parser` and records the span of `source` it consumed. The input
+/// given to the returned parser must be a suffix of `source`.
+pub fn spanned<'a, O, E, F>(
+    source: &'a str,
+    mut parser: F,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Spanned<'a, O>, E>
+where
+    F: Parser<&'a str, O, E>,
+{
+    move |input: &'a str| {
+        let (rest, value) = parser.parse(input)?;
+        let consumed = &input[..input.offset(rest)];
+        Ok((
+            rest,
+            Spanned {
+                value,
+                span: Span::locate(source, consumed),
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::{bytes::complete::tag, character::complete::multispace0, sequence::preceded};
+
+    use super::*;
+
+    #[test]
+    fn test_locate() {
+        let source = "ab\ncé\nd";
+        assert_eq!(Span::locate(source, &source[..0]).column, 1);
+        let span = Span::locate(source, &source[6..7]);
+        assert_eq!((span.offset, span.line, span.column), (6, 2, 3));
+        assert_eq!(span.fragment, "\n");
+        let span = Span::at(source, 7, 10);
+        assert_eq!((span.line, span.column, span.fragment), (3, 1, "d"));
+        assert_eq!(span.range(), 7..8);
+    }
+
+    #[test]
+    #[should_panic(expected = "fragment is not part of the source")]
+    fn test_locate_foreign() {
+        Span::locate("abc", "abc".to_string().leak());
+    }
+
+    #[test]
+    fn test_spanned() {
+        let source = "[\n  true ]";
+        let (_, value) = preceded(
+            tag("["),
+            preceded(multispace0, spanned(source, crate::json::parse_boolean)),
+        )(source)
+        .unwrap();
+        assert!(value.value);
+        assert_eq!(value.span.fragment, "true");
+        assert_eq!((value.span.line, value.span.column), (2, 3));
+    }
+}
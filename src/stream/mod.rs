@@ -0,0 +1,250 @@
+//! Driving parsers from a byte stream. A `StreamingParse` implementation
+//! looks at whatever has been buffered and says whether it holds a whole
+//! item; `Decoder` owns the buffer, so every protocol can share the same
+//! read loop.
+
+use nom::{error::VerboseError, Err as NomErr, IResult};
+
+use crate::error::line_column;
+use crate::frame::{self, Prefix};
+use crate::json::JsonValue;
+use crate::websocket;
+use crate::Error;
+
+/// What a parser made of the bytes buffered so far.
+#[derive(Debug, PartialEq)]
+pub enum Step<T> {
+    /// The buffer holds the start of an item, but not all of it.
+    NeedMore,
+    Item(T),
+    Error(Error),
+}
+
+pub trait StreamingParse {
+    type Item;
+
+    /// Parses one item from the start of `buffer`, returning it along with
+    /// the number of bytes it took up.
+    fn parse_next(&mut self, buffer: &[u8]) -> Step<(usize, Self::Item)>;
+}
+
+/// Runs a nom streaming parser, mapping `Incomplete` to `NeedMore`.
+fn nom_step<'a, O, T>(
+    format: &'static str,
+    buffer: &'a [u8],
+    result: IResult<&'a [u8], O, VerboseError<&'a [u8]>>,
+    item: impl FnOnce(O) -> T,
+) -> Step<(usize, T)> {
+    match result {
+        Ok((rest, value)) => Step::Item((buffer.len() - rest.len(), item(value))),
+        Err(NomErr::Incomplete(_)) => Step::NeedMore,
+        Err(error) => Step::Error(Error::from_verbose(format, buffer, error)),
+    }
+}
+
+/// Netstrings such as `5:hello,`, yielding their payloads.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Netstrings;
+
+impl StreamingParse for Netstrings {
+    type Item = Vec<u8>;
+
+    fn parse_next(&mut self, buffer: &[u8]) -> Step<(usize, Vec<u8>)> {
+        let result = frame::streaming::netstring(buffer);
+        nom_step("netstring", buffer, result, <[u8]>::to_vec)
+    }
+}
+
+/// Frames preceded by their length, yielding their payloads.
+#[derive(Debug, Clone, Copy)]
+pub struct LengthPrefixed(pub Prefix);
+
+impl StreamingParse for LengthPrefixed {
+    type Item = Vec<u8>;
+
+    fn parse_next(&mut self, buffer: &[u8]) -> Step<(usize, Vec<u8>)> {
+        let result = frame::streaming::length_prefixed(self.0)(buffer);
+        nom_step("frame", buffer, result, <[u8]>::to_vec)
+    }
+}
+
+/// WebSocket frames. Use `websocket::Decoder` to reassemble messages.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WebSocketFrames;
+
+impl StreamingParse for WebSocketFrames {
+    type Item = websocket::Frame;
+
+    fn parse_next(&mut self, buffer: &[u8]) -> Step<(usize, websocket::Frame)> {
+        let result = websocket::streaming::frame(buffer);
+        nom_step("websocket", buffer, result, |frame| frame)
+    }
+}
+
+/// Newline-delimited JSON: one value per line. Blank lines are skipped,
+/// and a line is only parsed once its newline has arrived.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Ndjson;
+
+impl StreamingParse for Ndjson {
+    type Item = JsonValue;
+
+    fn parse_next(&mut self, buffer: &[u8]) -> Step<(usize, JsonValue)> {
+        let mut start = 0;
+        while let Some(end) = buffer[start..].iter().position(|&b| b == b'\n') {
+            let line = &buffer[start..start + end];
+            let consumed = start + end + 1;
+            if line.iter().all(u8::is_ascii_whitespace) {
+                start = consumed;
+                continue;
+            }
+            let text = match std::str::from_utf8(line) {
+                Ok(text) => text,
+                Err(error) => {
+                    let at = &line[error.valid_up_to()..];
+                    let mut error = Error::expected("ndjson", line, at, "utf-8 text");
+                    error.relocate(start, line_column(&buffer[..start]).0, 1);
+                    return Step::Error(error);
+                }
+            };
+            return match text.parse() {
+                Ok(value) => Step::Item((consumed, value)),
+                Err(mut error) => {
+                    error.format = "ndjson";
+                    error.relocate(start, line_column(&buffer[..start]).0, 1);
+                    Step::Error(error)
+                }
+            };
+        }
+        Step::NeedMore
+    }
+}
+
+/// Buffers bytes from a connection and hands out items as they complete.
+/// Error spans are offsets into the whole stream. After an error the
+/// stream can't be resynchronised, and the same error is returned again.
+#[derive(Debug, Default)]
+pub struct Decoder<P> {
+    parser: P,
+    buffer: Vec<u8>,
+    /// Bytes consumed before the start of `buffer`.
+    offset: usize,
+    /// Lines, and characters on the last of them, consumed before the
+    /// start of `buffer`.
+    lines: usize,
+    columns: usize,
+}
+
+impl<P: StreamingParse> Decoder<P> {
+    pub fn new(parser: P) -> Self {
+        Decoder {
+            parser,
+            buffer: vec![],
+            offset: 0,
+            lines: 0,
+            columns: 0,
+        }
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Bytes received but not yet part of an item.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn next_item(&mut self) -> Step<P::Item> {
+        match self.parser.parse_next(&self.buffer) {
+            Step::Item((consumed, item)) => {
+                let (line, column) = line_column(&self.buffer[..consumed]);
+                if line > 1 {
+                    self.columns = 0;
+                }
+                self.lines += line - 1;
+                self.columns += column - 1;
+                self.buffer.drain(..consumed);
+                self.offset += consumed;
+                Step::Item(item)
+            }
+            Step::NeedMore => Step::NeedMore,
+            Step::Error(mut error) => {
+                error.relocate(self.offset, self.lines + 1, self.columns + 1);
+                Step::Error(error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items<P: StreamingParse>(decoder: &mut Decoder<P>) -> Vec<P::Item> {
+        let mut items = vec![];
+        while let Step::Item(item) = decoder.next_item() {
+            items.push(item);
+        }
+        items
+    }
+
+    #[test]
+    fn test_netstrings() {
+        let mut decoder = Decoder::new(Netstrings);
+        decoder.feed(b"5:hello,3:a");
+        assert_eq!(items(&mut decoder), vec![b"hello".to_vec()]);
+        assert_eq!(decoder.next_item(), Step::NeedMore);
+        decoder.feed(b"bc,0:,");
+        assert_eq!(items(&mut decoder), vec![b"abc".to_vec(), vec![]]);
+        assert!(decoder.buffered().is_empty());
+        decoder.feed(b"x");
+        let Step::Error(error) = decoder.next_item() else {
+            panic!("expected an error");
+        };
+        assert_eq!((error.span, error.column), (17..18, 18));
+    }
+
+    #[test]
+    fn test_length_prefixed() {
+        let mut decoder = Decoder::new(LengthPrefixed(Prefix::U16Be));
+        decoder.feed(&[0, 2, b'h']);
+        assert_eq!(decoder.next_item(), Step::NeedMore);
+        decoder.feed(&[b'i', 0, 0]);
+        assert_eq!(items(&mut decoder), vec![b"hi".to_vec(), vec![]]);
+    }
+
+    #[test]
+    fn test_websocket() {
+        let mut decoder = Decoder::new(WebSocketFrames);
+        decoder.feed(&[0x81, 0x02, b'h']);
+        assert_eq!(decoder.next_item(), Step::NeedMore);
+        decoder.feed(b"i");
+        let frames = items(&mut decoder);
+        assert_eq!(frames[0].payload, b"hi");
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let mut decoder = Decoder::new(Ndjson);
+        decoder.feed(b"[1]\n\n{\"a\": tr");
+        assert_eq!(
+            items(&mut decoder),
+            vec![JsonValue::Array(vec![JsonValue::Number(1.0)])]
+        );
+        decoder.feed(b"ue}\r\n[2");
+        assert_eq!(
+            items(&mut decoder),
+            vec![JsonValue::Object(vec![(
+                "a".to_string(),
+                JsonValue::Boolean(true)
+            )])]
+        );
+        decoder.feed(b"] x\n");
+        let Step::Error(error) = decoder.next_item() else {
+            panic!("expected an error");
+        };
+        assert_eq!((error.format, error.span), ("ndjson", 22..23));
+        assert_eq!((error.line, error.column), (4, 5));
+    }
+}
@@ -0,0 +1,532 @@
+use std::time::Duration;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till1, take_while1, take_while_m_n},
+    character::complete::{
+        char, digit1, line_ending, multispace0, not_line_ending, space0, space1,
+    },
+    combinator::{map, map_res, opt, recognize, verify},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::{many0, separated_list0, separated_list1},
+    sequence::{pair, preceded, separated_pair, terminated, tuple},
+    Err as NomErr, IResult,
+};
+
+use crate::limits::Depth;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Cue<'a> {
+    pub id: Option<&'a str>,
+    pub start: Duration,
+    pub end: Duration,
+    /// WebVTT cue settings such as `align:start`. Always empty for SRT.
+    pub settings: Vec<(&'a str, &'a str)>,
+    /// The raw cue text, which may span several lines and contain markup.
+    pub text: &'a str,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct WebVtt<'a> {
+    /// Text after `WEBVTT` on the first line, if any.
+    pub title: Option<&'a str>,
+    pub cues: Vec<Cue<'a>>,
+}
+
+/// Cue text markup, shared by SRT and WebVTT.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Span {
+    Text(String),
+    Italic(Vec<Span>),
+    Bold(Vec<Span>),
+    Underline(Vec<Span>),
+    Voice {
+        name: String,
+        children: Vec<Span>,
+    },
+    /// A `<c>` span or any other tag, with its annotation.
+    Other {
+        tag: String,
+        annotation: Option<String>,
+        children: Vec<Span>,
+    },
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+fn digits(n: usize) -> impl FnMut(&str) -> Res<&str, u64> {
+    move |input| {
+        map_res(
+            take_while_m_n(n, n, |c: char| c.is_ascii_digit()),
+            str::parse,
+        )(input)
+    }
+}
+
+fn to_duration(hours: u64, minutes: u64, seconds: u64, millis: u64) -> Duration {
+    Duration::from_millis(((hours * 60 + minutes) * 60 + seconds) * 1000 + millis)
+}
+
+/// `HH:MM:SS,mmm`, where the hours may have more than two digits.
+fn srt_timestamp(input: &str) -> Res<&str, Duration> {
+    context(
+        "timestamp",
+        tuple((
+            terminated(map_res(digit1, str::parse), char(':')),
+            terminated(digits(2), char(':')),
+            terminated(digits(2), alt((char(','), char('.')))),
+            digits(3),
+        )),
+    )(input)
+    .map(|(next_input, (h, m, s, ms))| (next_input, to_duration(h, m, s, ms)))
+}
+
+/// `[HH:]MM:SS.mmm`.
+fn vtt_timestamp(input: &str) -> Res<&str, Duration> {
+    let minutes_seconds = |input| {
+        tuple((
+            terminated(digits(2), char(':')),
+            terminated(digits(2), char('.')),
+            digits(3),
+        ))(input)
+    };
+    context(
+        "timestamp",
+        alt((
+            map(
+                pair(
+                    terminated(map_res(digit1, str::parse), char(':')),
+                    minutes_seconds,
+                ),
+                |(h, (m, s, ms))| (h, m, s, ms),
+            ),
+            map(minutes_seconds, |(m, s, ms)| (0, m, s, ms)),
+        )),
+    )(input)
+    .and_then(|(next_input, (h, m, s, ms))| match m > 59 || s > 59 {
+        true => Err(failure(input, "invalid timestamp")),
+        false => Ok((next_input, to_duration(h, m, s, ms))),
+    })
+}
+
+fn arrow(input: &str) -> Res<&str, &str> {
+    recognize(tuple((space1, tag("-->"), space1)))(input)
+}
+
+fn nonblank_line(input: &str) -> Res<&str, &str> {
+    verify(not_line_ending, |line: &str| !line.trim().is_empty())(input)
+}
+
+/// Lines up to the next blank line, as one slice.
+fn text_block(input: &str) -> Res<&str, &str> {
+    recognize(separated_list1(line_ending, nonblank_line))(input)
+}
+
+fn srt_cue(input: &str) -> Res<&str, Cue<'_>> {
+    let (next_input, (id, (start, end), _, _, text)) = context(
+        "cue",
+        tuple((
+            terminated(digit1, pair(space0, line_ending)),
+            separated_pair(srt_timestamp, arrow, srt_timestamp),
+            // SRT files sometimes carry display coordinates here.
+            not_line_ending,
+            opt(line_ending),
+            opt(text_block),
+        )),
+    )(input)?;
+    if end < start {
+        return Err(failure(input, "cue ends before it starts"));
+    }
+    Ok((
+        next_input,
+        Cue {
+            id: Some(id),
+            start,
+            end,
+            settings: vec![],
+            text: text.unwrap_or(""),
+        },
+    ))
+}
+
+fn blank_lines(input: &str) -> Res<&str, &str> {
+    multispace0(input)
+}
+
+/// Parses an SRT file.
+pub fn srt(input: &str) -> Res<&str, Vec<Cue<'_>>> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    context(
+        "srt",
+        preceded(blank_lines, many0(terminated(srt_cue, blank_lines))),
+    )(input)
+}
+
+fn setting(input: &str) -> Res<&str, (&str, &str)> {
+    separated_pair(
+        take_till1(|c: char| c == ':' || c.is_whitespace()),
+        char(':'),
+        take_till1(char::is_whitespace),
+    )(input)
+}
+
+fn vtt_cue(input: &str) -> Res<&str, Cue<'_>> {
+    let (next_input, (id, (start, end), settings, _, text)) = context(
+        "cue",
+        tuple((
+            opt(terminated(
+                verify(nonblank_line, |line: &str| !line.contains("-->")),
+                line_ending,
+            )),
+            separated_pair(vtt_timestamp, arrow, vtt_timestamp),
+            preceded(space0, separated_list0(space1, setting)),
+            pair(space0, opt(line_ending)),
+            opt(text_block),
+        )),
+    )(input)?;
+    if end < start {
+        return Err(failure(input, "cue ends before it starts"));
+    }
+    Ok((
+        next_input,
+        Cue {
+            id,
+            start,
+            end,
+            settings,
+            text: text.unwrap_or(""),
+        },
+    ))
+}
+
+/// `NOTE`, `STYLE` and `REGION` blocks, which carry no cues.
+fn vtt_other_block(input: &str) -> Res<&str, &str> {
+    recognize(pair(
+        alt((tag("NOTE"), tag("STYLE"), tag("REGION"))),
+        opt(text_block),
+    ))(input)
+}
+
+/// Parses a WebVTT file. Comments, styles and regions are skipped.
+pub fn webvtt(input: &str) -> Res<&str, WebVtt<'_>> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let (input, (_, title, _)) = context(
+        "webvtt header",
+        tuple((
+            tag("WEBVTT"),
+            opt(preceded(space1, nonblank_line)),
+            // Header metadata lines up to the first blank line.
+            opt(preceded(line_ending, text_block)),
+        )),
+    )(input)?;
+    let (input, blocks) = many0(preceded(
+        blank_lines,
+        alt((map(vtt_other_block, |_| None), map(vtt_cue, Some))),
+    ))(input)?;
+    let (input, _) = blank_lines(input)?;
+    Ok((
+        input,
+        WebVtt {
+            title,
+            cues: blocks.into_iter().flatten().collect(),
+        },
+    ))
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&lrm;", "\u{200e}")
+        .replace("&rlm;", "\u{200f}")
+        .replace("&amp;", "&")
+}
+
+/// A `<tag.class annotation>` start tag, as name and annotation.
+fn start_tag(input: &str) -> Res<&str, (&str, Option<&str>)> {
+    tuple((
+        preceded(char('<'), take_while1(|c: char| c.is_ascii_alphanumeric())),
+        terminated(
+            preceded(
+                opt(take_while1(|c: char| {
+                    c == '.' || c.is_alphanumeric() || c == '-' || c == '_'
+                })),
+                opt(preceded(space1, take_till1(|c| c == '>'))),
+            ),
+            char('>'),
+        ),
+    ))(input)
+}
+
+fn end_tag(input: &str) -> Res<&str, &str> {
+    preceded(
+        tag("</"),
+        terminated(take_while1(|c: char| c.is_ascii_alphanumeric()), char('>')),
+    )(input)
+}
+
+/// Parses spans until the end tag for `open`, or the end of the input.
+/// Unclosed tags are closed at the end, as browsers do.
+fn spans<'a>(mut input: &'a str, open: Option<&str>) -> Res<&'a str, Vec<Span>> {
+    let mut parsed = vec![];
+    while !input.is_empty() {
+        if let Ok((next_input, name)) = end_tag(input) {
+            input = next_input;
+            if Some(name) == open {
+                break;
+            }
+            // Stray end tags are dropped.
+            continue;
+        }
+        if let Ok((next_input, (name, annotation))) = start_tag(input) {
+            let Some(_depth) = Depth::enter() else {
+                return Err(failure(input, "nesting too deep"));
+            };
+            let (next_input, children) = spans(next_input, Some(name))?;
+            input = next_input;
+            parsed.push(match name {
+                "i" => Span::Italic(children),
+                "b" => Span::Bold(children),
+                "u" => Span::Underline(children),
+                "v" => Span::Voice {
+                    name: annotation.unwrap_or("").trim().to_owned(),
+                    children,
+                },
+                _ => Span::Other {
+                    tag: name.to_owned(),
+                    annotation: annotation.map(str::to_owned),
+                    children,
+                },
+            });
+            continue;
+        }
+        // Timestamp tags such as `<00:01.500>` are dropped.
+        if let Ok((next_input, _)) = tuple((char('<'), vtt_timestamp, char('>')))(input) {
+            input = next_input;
+            continue;
+        }
+        // The first character is text even if it's a `<` that starts no tag.
+        let first = input.chars().next().map_or(0, char::len_utf8);
+        let end = input[first..].find('<').map_or(input.len(), |i| i + first);
+        parsed.push(Span::Text(decode_entities(&input[..end])));
+        input = &input[end..];
+    }
+    Ok((input, parsed))
+}
+
+/// Parses cue text into spans of markup.
+pub fn cue_text(input: &str) -> Res<&str, Vec<Span>> {
+    context("cue text", |i| spans(i, None))(input)
+}
+
+fn format_timestamp(time: Duration, separator: char) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+fn srt_text(spans: &[Span], out: &mut String) {
+    for span in spans {
+        match span {
+            Span::Text(text) => out.push_str(text),
+            Span::Italic(children) | Span::Bold(children) | Span::Underline(children) => {
+                let tag = match span {
+                    Span::Italic(_) => "i",
+                    Span::Bold(_) => "b",
+                    _ => "u",
+                };
+                out.push_str(&format!("<{}>", tag));
+                srt_text(children, out);
+                out.push_str(&format!("</{}>", tag));
+            }
+            // SRT has no voices or classes, so only their text is kept.
+            Span::Voice { children, .. } | Span::Other { children, .. } => srt_text(children, out),
+        }
+    }
+}
+
+/// Writes cues as SRT, numbering them from 1. Markup SRT doesn't support
+/// is reduced to its text.
+pub fn to_srt(cues: &[Cue]) -> String {
+    let mut out = String::new();
+    for (i, cue) in cues.iter().enumerate() {
+        let mut text = String::new();
+        if let Ok((_, spans)) = cue_text(cue.text) {
+            srt_text(&spans, &mut text);
+        }
+        out.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            format_timestamp(cue.start, ','),
+            format_timestamp(cue.end, ','),
+            text
+        ));
+    }
+    out
+}
+
+/// Writes cues as WebVTT, keeping their ids, settings and markup.
+pub fn to_webvtt(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in cues {
+        if let Some(id) = cue.id {
+            out.push_str(id);
+            out.push('\n');
+        }
+        out.push_str(&format_timestamp(cue.start, '.'));
+        out.push_str(" --> ");
+        out.push_str(&format_timestamp(cue.end, '.'));
+        for (key, value) in &cue.settings {
+            out.push_str(&format!(" {}:{}", key, value));
+        }
+        out.push('\n');
+        out.push_str(cue.text);
+        out.push_str("\n\n");
+    }
+    out
+}
+
+/// Moves every cue by `offset_ms` milliseconds, which may be negative.
+/// Times that would fall before zero are clamped to zero.
+pub fn shift(cues: &mut [Cue], offset_ms: i64) {
+    let apply = |time: Duration| {
+        let millis = time.as_millis() as i64 + offset_ms;
+        Duration::from_millis(millis.max(0) as u64)
+    };
+    for cue in cues {
+        cue.start = apply(cue.start);
+        cue.end = apply(cue.end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRT: &str = "1\r\n00:00:01,000 --> 00:00:04,500\r\nHello <i>there</i>\r\nsecond line\r\n\r\n2\r\n01:02:03,004 --> 01:02:05,000\r\n&lt;3\r\n";
+
+    const VTT: &str = "WEBVTT - Example
+
+NOTE this is a comment
+over two lines
+
+intro
+00:01.000 --> 00:04.000 align:start line:0
+<v Roger Bingham>We are in <i>New York</i>
+
+00:01:05.000 --> 00:01:06.250
+<c.loud>Hey</c>
+";
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_srt() {
+        let (rest, cues) = srt(SRT).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].id, Some("1"));
+        assert_eq!((cues[0].start, cues[0].end), (ms(1000), ms(4500)));
+        assert_eq!(cues[0].text, "Hello <i>there</i>\r\nsecond line");
+        assert_eq!(cues[1].start, ms(3_723_004));
+        assert!(srt("1\n00:00:05,000 --> 00:00:01,000\nx\n").is_err());
+    }
+
+    #[test]
+    fn test_webvtt() {
+        let (rest, vtt) = webvtt(VTT).unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(vtt.title, Some("- Example"));
+        assert_eq!(vtt.cues.len(), 2);
+        let cue = &vtt.cues[0];
+        assert_eq!(cue.id, Some("intro"));
+        assert_eq!((cue.start, cue.end), (ms(1000), ms(4000)));
+        assert_eq!(cue.settings, vec![("align", "start"), ("line", "0")]);
+        assert_eq!(vtt.cues[1].id, None);
+        assert_eq!(vtt.cues[1].end, ms(66_250));
+        assert!(webvtt("WEBVTX\n").is_err());
+    }
+
+    #[test]
+    fn test_cue_text() {
+        assert_eq!(
+            cue_text("<v Roger Bingham>We are in <i>New York</i> &amp; <00:02.000>more"),
+            Ok((
+                "",
+                vec![Span::Voice {
+                    name: "Roger Bingham".to_owned(),
+                    children: vec![
+                        Span::Text("We are in ".to_owned()),
+                        Span::Italic(vec![Span::Text("New York".to_owned())]),
+                        Span::Text(" & ".to_owned()),
+                        Span::Text("more".to_owned()),
+                    ]
+                }]
+            ))
+        );
+        assert_eq!(
+            cue_text("<c.loud>Hey</b></c>"),
+            Ok((
+                "",
+                vec![Span::Other {
+                    tag: "c".to_owned(),
+                    annotation: None,
+                    children: vec![Span::Text("Hey".to_owned())]
+                }]
+            ))
+        );
+        assert_eq!(
+            cue_text("€x <b>été</b>"),
+            Ok((
+                "",
+                vec![
+                    Span::Text("€x ".to_owned()),
+                    Span::Bold(vec![Span::Text("été".to_owned())]),
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_conversion() {
+        let (_, vtt) = webvtt(VTT).unwrap();
+        assert_eq!(
+            to_srt(&vtt.cues),
+            "1\n00:00:01,000 --> 00:00:04,000\nWe are in <i>New York</i>\n\n\
+             2\n00:01:05,000 --> 00:01:06,250\nHey\n\n"
+        );
+        let (_, vtt) = webvtt("WEBVTT\n\n00:01.000 --> 00:02.000\nÜber <i>€5</i>\n").unwrap();
+        assert_eq!(
+            to_srt(&vtt.cues),
+            "1\n00:00:01,000 --> 00:00:02,000\nÜber <i>€5</i>\n\n"
+        );
+        let (_, cues) = srt(SRT).unwrap();
+        let converted = to_webvtt(&cues);
+        let (_, vtt) = webvtt(&converted).unwrap();
+        assert_eq!(vtt.cues.len(), 2);
+        assert_eq!(vtt.cues[1].start, ms(3_723_004));
+    }
+
+    #[test]
+    fn test_shift() {
+        let (_, mut cues) = srt(SRT).unwrap();
+        shift(&mut cues, -2000);
+        assert_eq!((cues[0].start, cues[0].end), (ms(0), ms(2500)));
+        shift(&mut cues, 500);
+        assert_eq!(cues[1].start, ms(3_721_504));
+    }
+}
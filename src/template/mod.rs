@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_until, take_while1},
+    character::complete::{char, multispace0},
+    combinator::{eof, map, rest, verify},
+    error::{context, VerboseError, VerboseErrorKind},
+    sequence::{delimited, preceded, terminated},
+    Err as NomErr, IResult,
+};
+
+use crate::json::JsonValue;
+use crate::limits::Depth;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Node<'a> {
+    Text(&'a str),
+    /// `{{name}}` is HTML-escaped, `{{{name}}}` and `{{&name}}` are not.
+    Variable {
+        name: &'a str,
+        escaped: bool,
+    },
+    Section {
+        name: &'a str,
+        inverted: bool,
+        children: Vec<Node<'a>>,
+    },
+    Partial(&'a str),
+    Comment(&'a str),
+}
+
+/// A tag as it appears in the source, before sections are matched up.
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Tag<'a> {
+    Variable(&'a str, bool),
+    Open(&'a str, bool),
+    Close(&'a str),
+    Partial(&'a str),
+    Comment(&'a str),
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a str, reason: &'static str) -> NomErr<VerboseError<&'a str>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+fn name(input: &str) -> Res<&str, &str> {
+    context(
+        "name",
+        delimited(
+            multispace0,
+            verify(
+                take_while1(|c: char| !c.is_whitespace() && c != '}'),
+                |name: &str| {
+                    name.chars()
+                        .all(|c| c.is_alphanumeric() || "._-".contains(c))
+                },
+            ),
+            multispace0,
+        ),
+    )(input)
+}
+
+fn tag_body(input: &str) -> Res<&str, Tag<'_>> {
+    alt((
+        map(preceded(char('#'), name), |name| Tag::Open(name, false)),
+        map(preceded(char('^'), name), |name| Tag::Open(name, true)),
+        map(preceded(char('/'), name), Tag::Close),
+        map(preceded(char('>'), name), Tag::Partial),
+        map(preceded(char('!'), rest), |comment: &str| {
+            Tag::Comment(comment.trim())
+        }),
+        map(preceded(char('&'), name), |name| Tag::Variable(name, false)),
+        map(name, |name| Tag::Variable(name, true)),
+    ))(input)
+}
+
+fn mustache_tag(input: &str) -> Res<&str, Tag<'_>> {
+    let triple = map(delimited(tag("{{{"), name, tag("}}}")), |name| {
+        Tag::Variable(name, false)
+    });
+    let double = |input| {
+        let (next_input, body) = delimited(tag("{{"), take_until("}}"), tag("}}"))(input)?;
+        let (_, tag) =
+            terminated(tag_body, eof)(body).map_err(|_| failure(input, "invalid tag"))?;
+        Ok((next_input, tag))
+    };
+    context("tag", alt((triple, double)))(input)
+}
+
+fn text(input: &str) -> Res<&str, &str> {
+    match input.find("{{") {
+        Some(0) => Err(NomErr::Error(VerboseError { errors: vec![] })),
+        Some(end) => Ok((&input[end..], &input[..end])),
+        None if input.is_empty() => Err(NomErr::Error(VerboseError { errors: vec![] })),
+        None => Ok(("", input)),
+    }
+}
+
+/// Parses nodes until the closing tag of `section`, or until the end of
+/// input at the top level.
+fn nodes<'a>(mut input: &'a str, section: Option<&'a str>) -> Res<&'a str, Vec<Node<'a>>> {
+    let mut nodes = vec![];
+    loop {
+        if input.is_empty() {
+            return match section {
+                Some(_) => Err(failure(input, "unclosed section")),
+                None => Ok((input, nodes)),
+            };
+        }
+        if let Ok((next_input, text)) = text(input) {
+            nodes.push(Node::Text(text));
+            input = next_input;
+            continue;
+        }
+        let (next_input, tag) = mustache_tag(input)?;
+        input = match tag {
+            Tag::Variable(name, escaped) => {
+                nodes.push(Node::Variable { name, escaped });
+                next_input
+            }
+            Tag::Partial(name) => {
+                nodes.push(Node::Partial(name));
+                next_input
+            }
+            Tag::Comment(comment) => {
+                nodes.push(Node::Comment(comment));
+                next_input
+            }
+            Tag::Open(name, inverted) => {
+                let Some(_depth) = Depth::enter() else {
+                    return Err(failure(input, "nesting too deep"));
+                };
+                let (next_input, children) = self::nodes(next_input, Some(name))?;
+                nodes.push(Node::Section {
+                    name,
+                    inverted,
+                    children,
+                });
+                next_input
+            }
+            Tag::Close(name) if Some(name) == section => return Ok((next_input, nodes)),
+            Tag::Close(_) => return Err(failure(input, "mismatched closing tag")),
+        };
+    }
+}
+
+/// Parses a whole template.
+pub fn template(input: &str) -> Res<&str, Vec<Node<'_>>> {
+    nodes(input, None)
+}
+
+fn lookup<'v>(stack: &[&'v JsonValue], name: &str) -> Option<&'v JsonValue> {
+    if name == "." {
+        return stack.last().copied();
+    }
+    let mut parts = name.split('.');
+    let first = parts.next()?;
+    // Only the first part of a dotted name is searched for up the stack.
+    let mut value = stack.iter().rev().find_map(|value| field(value, first))?;
+    for part in parts {
+        value = field(value, part)?;
+    }
+    Some(value)
+}
+
+fn field<'v>(value: &'v JsonValue, name: &str) -> Option<&'v JsonValue> {
+    match value {
+        JsonValue::Object(pairs) => pairs.iter().find(|(key, _)| key == name).map(|(_, v)| v),
+        _ => None,
+    }
+}
+
+fn is_truthy(value: Option<&JsonValue>) -> bool {
+    match value {
+        None | Some(JsonValue::Null) | Some(JsonValue::Boolean(false)) => false,
+        Some(JsonValue::Array(items)) => !items.is_empty(),
+        Some(_) => true,
+    }
+}
+
+fn to_text(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(s) => s.clone(),
+        JsonValue::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => (*n as i64).to_string(),
+        JsonValue::Number(n) => n.to_string(),
+        JsonValue::Decimal(n) => n.to_string(),
+        JsonValue::Boolean(b) => b.to_string(),
+        JsonValue::Null | JsonValue::Array(_) | JsonValue::Object(_) => String::new(),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    stack: &mut Vec<&JsonValue>,
+    partials: &HashMap<&str, Vec<Node>>,
+    out: &mut String,
+) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Comment(_) => {}
+            Node::Variable { name, escaped } => {
+                let text = lookup(stack, name).map(to_text).unwrap_or_default();
+                if *escaped {
+                    out.push_str(&escape_html(&text));
+                } else {
+                    out.push_str(&text);
+                }
+            }
+            Node::Partial(name) => {
+                if let Some(partial) = partials.get(name) {
+                    render_nodes(partial, stack, partials, out);
+                }
+            }
+            Node::Section {
+                name,
+                inverted,
+                children,
+            } => {
+                let value = lookup(stack, name);
+                if *inverted {
+                    if !is_truthy(value) {
+                        render_nodes(children, stack, partials, out);
+                    }
+                    continue;
+                }
+                match value {
+                    Some(value) if !is_truthy(Some(value)) => {}
+                    Some(JsonValue::Array(items)) => {
+                        for item in items {
+                            stack.push(item);
+                            render_nodes(children, stack, partials, out);
+                            stack.pop();
+                        }
+                    }
+                    Some(value) => {
+                        stack.push(value);
+                        render_nodes(children, stack, partials, out);
+                        stack.pop();
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+}
+
+/// Renders a parsed template against a JSON context. Missing names render
+/// as empty text, and partials are looked up by name in `partials`.
+pub fn render(nodes: &[Node], context: &JsonValue, partials: &HashMap<&str, Vec<Node>>) -> String {
+    let mut out = String::new();
+    render_nodes(nodes, &mut vec![context], partials, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::parse_json;
+
+    fn render_str(input: &str, context: &str) -> String {
+        let (_, nodes) = template(input).unwrap();
+        let (_, context) = parse_json(context).unwrap();
+        render(&nodes, &context, &HashMap::new())
+    }
+
+    #[test]
+    fn test_template() {
+        assert_eq!(
+            template(
+                "Hi {{ name }}!{{! note }}{{#items}}<{{{.}}}>{{/items}}{{^items}}none{{/items}}"
+            ),
+            Ok((
+                "",
+                vec![
+                    Node::Text("Hi "),
+                    Node::Variable {
+                        name: "name",
+                        escaped: true
+                    },
+                    Node::Text("!"),
+                    Node::Comment("note"),
+                    Node::Section {
+                        name: "items",
+                        inverted: false,
+                        children: vec![
+                            Node::Text("<"),
+                            Node::Variable {
+                                name: ".",
+                                escaped: false
+                            },
+                            Node::Text(">"),
+                        ]
+                    },
+                    Node::Section {
+                        name: "items",
+                        inverted: true,
+                        children: vec![Node::Text("none")]
+                    },
+                ]
+            ))
+        );
+        assert_eq!(
+            template("{{> header}}{{&raw}}"),
+            Ok((
+                "",
+                vec![
+                    Node::Partial("header"),
+                    Node::Variable {
+                        name: "raw",
+                        escaped: false
+                    }
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_template_errors() {
+        assert_eq!(template("{{#a}}x"), Err(failure("", "unclosed section")));
+        assert_eq!(
+            template("{{#a}}x{{/b}}"),
+            Err(failure("{{/b}}", "mismatched closing tag"))
+        );
+        assert_eq!(
+            template("{{a b}}"),
+            Err(NomErr::Failure(VerboseError {
+                errors: vec![
+                    ("{{a b}}", VerboseErrorKind::Context("invalid tag")),
+                    ("{{a b}}", VerboseErrorKind::Context("tag")),
+                ]
+            }))
+        );
+    }
+
+    #[test]
+    fn test_render_variables() {
+        assert_eq!(
+            render_str(
+                "{{greeting}}, {{user.name}}! {{count}} {{ratio}} {{missing}}{{{html}}} {{html}}",
+                r#"{"greeting": "Hello", "user": {"name": "Ada"}, "count": 3, "ratio": 0.5, "html": "<b>"}"#
+            ),
+            "Hello, Ada! 3 0.5 <b> &lt;b&gt;"
+        );
+    }
+
+    #[test]
+    fn test_render_sections() {
+        let context =
+            r#"{"items": [{"n": "a"}, {"n": "b"}], "empty": [], "flag": true, "title": "T"}"#;
+        assert_eq!(
+            render_str("{{#items}}[{{n}}{{title}}]{{/items}}", context),
+            "[aT][bT]"
+        );
+        assert_eq!(
+            render_str("{{#empty}}x{{/empty}}{{^empty}}none{{/empty}}", context),
+            "none"
+        );
+        assert_eq!(
+            render_str("{{#flag}}yes{{/flag}}{{^flag}}no{{/flag}}", context),
+            "yes"
+        );
+        assert_eq!(render_str("{{#nope}}x{{/nope}}", context), "");
+    }
+
+    #[test]
+    fn test_render_partials() {
+        let (_, nodes) = template("{{#people}}{{> person}}{{/people}}").unwrap();
+        let (_, person) = template("<{{name}}>").unwrap();
+        let partials = HashMap::from([("person", person)]);
+        let (_, context) = parse_json(r#"{"people": [{"name": "a"}, {"name": "b"}]}"#).unwrap();
+        assert_eq!(render(&nodes, &context, &partials), "<a><b>");
+    }
+}
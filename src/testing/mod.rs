@@ -0,0 +1,234 @@
+//! Property-testing support: `proptest` strategies that generate values the
+//! parsers accept, and a helper for round-trip properties. Enabled by the
+//! `testing` feature.
+//!
+//! ```
+//! use parsers::json::JsonValue;
+//! use proptest::prelude::*;
+//!
+//! proptest!(|(value: JsonValue)| {
+//!     // Every generated value is something the JSON parser can produce.
+//!     let _ = value;
+//! });
+//! ```
+#![cfg(feature = "testing")]
+
+use std::fmt::Debug;
+use std::str::FromStr;
+
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+
+use crate::http::UriBuf;
+use crate::json::JsonValue;
+use crate::value::Value;
+
+/// Strings without quotes or backslashes, so they can be printed without
+/// escapes.
+fn json_string() -> impl Strategy<Value = String> {
+    "[a-zA-Z0-9 ,.:{}\\[\\]_-]{0,12}"
+}
+
+/// JSON values up to `depth` levels of nesting. Numbers are exactly
+/// representable in decimal, so they print back unchanged.
+pub fn json_value(depth: u32) -> impl Strategy<Value = JsonValue> {
+    let leaf = prop_oneof![
+        Just(JsonValue::Null),
+        any::<bool>().prop_map(JsonValue::Boolean),
+        (-1_000_000i32..1_000_000).prop_map(|n| JsonValue::Number(n.into())),
+        (0u32..1_000).prop_map(|n| JsonValue::Number(f64::from(n) + 0.25)),
+        json_string().prop_map(JsonValue::String),
+    ];
+    leaf.prop_recursive(depth, 64, 4, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..4).prop_map(JsonValue::Array),
+            prop::collection::vec((json_string(), inner), 0..4).prop_map(JsonValue::Object),
+        ]
+    })
+}
+
+impl Arbitrary for JsonValue {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<JsonValue>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        json_value(3).boxed()
+    }
+}
+
+pub fn value(depth: u32) -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Boolean),
+        any::<i64>().prop_map(Value::Integer),
+        any::<f64>()
+            .prop_filter("finite", |f| f.is_finite())
+            .prop_map(Value::Float),
+        any::<String>().prop_map(Value::String),
+    ];
+    leaf.prop_recursive(depth, 64, 4, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..4).prop_map(Value::Array),
+            prop::collection::vec((any::<String>(), inner), 0..4).prop_map(Value::Table),
+        ]
+    })
+}
+
+impl Arbitrary for Value {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Value>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        value(3).boxed()
+    }
+}
+
+/// The text of an `http` or `https` URI, built from the parts the URI
+/// parser understands.
+pub fn uri_text() -> impl Strategy<Value = String> {
+    let label = "[a-z][a-z0-9-]{0,6}[a-z0-9]";
+    (
+        prop_oneof!["http", "https"],
+        proptest::option::of(("[a-z0-9]{1,8}", proptest::option::of("[a-z0-9]{1,8}"))),
+        prop::collection::vec(label, 1..4),
+        "[a-z]{2,6}",
+        proptest::option::of(any::<u16>()),
+        proptest::option::of(prop::collection::vec("[a-z0-9.-]{1,8}", 0..4)),
+        proptest::option::of(prop::collection::vec(
+            ("[a-z0-9]{1,6}", "[a-z0-9]{0,6}"),
+            1..4,
+        )),
+        proptest::option::of("[a-z0-9]{1,8}"),
+    )
+        .prop_map(|(scheme, user, labels, tld, port, path, query, fragment)| {
+            let mut uri = format!("{}://", scheme);
+            if let Some((name, password)) = user {
+                uri += &name;
+                if let Some(password) = password {
+                    uri += &format!(":{}", password);
+                }
+                uri += "@";
+            }
+            uri += &labels.join(".");
+            uri += &format!(".{}", tld);
+            if let Some(port) = port {
+                uri += &format!(":{}", port);
+            }
+            if let Some(path) = path {
+                uri += &format!("/{}", path.join("/"));
+            }
+            if let Some(query) = query {
+                let pairs: Vec<String> =
+                    query.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                uri += &format!("?{}", pairs.join("&"));
+            }
+            if let Some(fragment) = fragment {
+                uri += &format!("#{}", fragment);
+            }
+            uri
+        })
+}
+
+impl Arbitrary for UriBuf {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<UriBuf>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        uri_text()
+            .prop_map(|text| text.parse().expect("generated an invalid URI"))
+            .boxed()
+    }
+}
+
+/// Checks that `parse(print(value)) == value`, for use inside `proptest!`.
+pub fn round_trip<T, E>(
+    value: &T,
+    print: impl Fn(&T) -> String,
+    parse: impl Fn(&str) -> Result<T, E>,
+) -> Result<(), TestCaseError>
+where
+    T: PartialEq + Debug,
+    E: Debug,
+{
+    let text = print(value);
+    match parse(&text) {
+        Ok(parsed) => {
+            prop_assert_eq!(&parsed, value, "printed as {:?}", text);
+            Ok(())
+        }
+        Err(error) => Err(TestCaseError::fail(format!(
+            "{:?} didn't parse: {:?}",
+            text, error
+        ))),
+    }
+}
+
+/// `round_trip` for types whose `Display` output is meant to parse back
+/// with `FromStr`.
+pub fn display_round_trip<T>(value: &T) -> Result<(), TestCaseError>
+where
+    T: PartialEq + Debug + ToString + FromStr,
+    T::Err: Debug,
+{
+    round_trip(value, T::to_string, str::parse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::{FromValue, ToValue};
+
+    fn print(value: &JsonValue) -> String {
+        match value {
+            JsonValue::Null => "null".to_string(),
+            JsonValue::Boolean(b) => b.to_string(),
+            JsonValue::Number(n) => n.to_string(),
+            JsonValue::Decimal(n) => n.to_string(),
+            JsonValue::String(s) => format!("\"{}\"", s),
+            JsonValue::Array(items) => {
+                let items: Vec<String> = items.iter().map(print).collect();
+                format!("[{}]", items.join(", "))
+            }
+            JsonValue::Object(members) => {
+                let members: Vec<String> = members
+                    .iter()
+                    .map(|(key, value)| format!("\"{}\": {}", key, print(value)))
+                    .collect();
+                format!("{{{}}}", members.join(", "))
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_json_round_trip(value: JsonValue) {
+            round_trip(&value, print, str::parse)?;
+        }
+
+        #[test]
+        fn test_json_value_round_trip(value: JsonValue) {
+            prop_assert_eq!(JsonValue::from_value(value.to_value()), Ok(value));
+        }
+
+        #[test]
+        fn test_uri(uri: UriBuf) {
+            display_round_trip(&uri)?;
+            prop_assert!(crate::http::uri(uri.as_str()).is_ok());
+        }
+
+        #[test]
+        fn test_uri_display_round_trip(uri: UriBuf) {
+            let parsed = uri.uri();
+            let printed = parsed.to_string();
+            let reparsed = crate::error::parse_all("uri", &printed, crate::http::uri);
+            prop_assert!(reparsed.is_ok(), "{:?} didn't parse", printed);
+            let mut reparsed = reparsed.unwrap();
+            prop_assert_eq!(&reparsed.to_string(), &printed);
+            // Only a default port is lost in printing.
+            reparsed.port = reparsed.port_or_default();
+            let mut parsed = parsed;
+            parsed.port = parsed.port_or_default();
+            prop_assert_eq!(reparsed, parsed);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! A data model shared by the formats here, so a document parsed from one
+//! format can be converted into another through a single representation.
+
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Keys in document order. Duplicates are kept as they appeared.
+    Table(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The kind of value, for error messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Boolean(_) => "boolean",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Table(_) => "table",
+        }
+    }
+
+    /// Looks up `key` in a table. The last occurrence wins.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Table(entries) => entries
+                .iter()
+                .rev()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+/// A value that didn't have the shape a `FromValue` impl needed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ValueError {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+pub trait ToValue {
+    fn to_value(&self) -> Value;
+}
+
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self, ValueError>;
+}
+
+/// Converts between any two representations, e.g. a lockfile to JSON.
+pub fn convert<T: FromValue>(from: &impl ToValue) -> Result<T, ValueError> {
+    T::from_value(from.to_value())
+}
+
+fn mismatch<T>(expected: &'static str, value: &Value) -> Result<T, ValueError> {
+    Err(ValueError {
+        expected,
+        found: value.kind(),
+    })
+}
+
+impl ToValue for Value {
+    fn to_value(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        Ok(value)
+    }
+}
+
+impl ToValue for bool {
+    fn to_value(&self) -> Value {
+        Value::Boolean(*self)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Boolean(b) => Ok(b),
+            other => mismatch("boolean", &other),
+        }
+    }
+}
+
+impl ToValue for i64 {
+    fn to_value(&self) -> Value {
+        Value::Integer(*self)
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Integer(i) => Ok(i),
+            other => mismatch("integer", &other),
+        }
+    }
+}
+
+impl ToValue for f64 {
+    fn to_value(&self) -> Value {
+        Value::Float(*self)
+    }
+}
+
+/// Integers are accepted too, since most formats don't distinguish `1`
+/// from `1.0`.
+impl FromValue for f64 {
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Float(f) => Ok(f),
+            Value::Integer(i) => Ok(i as f64),
+            other => mismatch("float", &other),
+        }
+    }
+}
+
+impl ToValue for str {
+    fn to_value(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl ToValue for String {
+    fn to_value(&self) -> Value {
+        Value::String(self.clone())
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        match value {
+            Value::String(s) => Ok(s),
+            other => mismatch("string", &other),
+        }
+    }
+}
+
+impl<T: ToValue> ToValue for Option<T> {
+    fn to_value(&self) -> Value {
+        self.as_ref().map_or(Value::Null, T::to_value)
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Null => Ok(None),
+            other => T::from_value(other).map(Some),
+        }
+    }
+}
+
+impl<T: ToValue> ToValue for [T] {
+    fn to_value(&self) -> Value {
+        Value::Array(self.iter().map(T::to_value).collect())
+    }
+}
+
+impl<T: ToValue> ToValue for Vec<T> {
+    fn to_value(&self) -> Value {
+        self.as_slice().to_value()
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Array(items) => items.into_iter().map(T::from_value).collect(),
+            other => mismatch("array", &other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_primitives() {
+        assert_eq!(convert::<f64>(&3i64), Ok(3.0));
+        assert_eq!(
+            convert::<Vec<Option<String>>>(&vec![Some("a".to_string()), None]),
+            Ok(vec![Some("a".to_string()), None])
+        );
+        assert_eq!(
+            convert::<bool>(&"yes".to_string()),
+            Err(ValueError {
+                expected: "boolean",
+                found: "string"
+            })
+        );
+        let table = Value::Table(vec![
+            ("a".to_string(), Value::Integer(1)),
+            ("a".to_string(), Value::Integer(2)),
+        ]);
+        assert_eq!(table.get("a"), Some(&Value::Integer(2)));
+        assert_eq!(table.get("b"), None);
+    }
+}
@@ -0,0 +1,272 @@
+//! Walking parsed documents without matching on each format's tree. A pass
+//! such as redaction or collecting statistics is written once as a
+//! `Visitor` and runs over any tree that implements `Tree`.
+//!
+//! Implemented for `JsonValue` and for `Value`, which is also how TOML
+//! documents like `Cargo.lock` are walked (via `ToValue`).
+
+use crate::json::JsonValue;
+use crate::value::Value;
+
+/// One step from a node to its child.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// What the walk should do after a callback.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Control {
+    Continue,
+    /// Don't descend into this node's children. Only meaningful from
+    /// `enter`; `leave` treats it as `Continue`.
+    SkipChildren,
+    /// End the walk. No further callbacks are made, not even `leave` for
+    /// the nodes being walked.
+    Stop,
+}
+
+pub trait Tree {
+    fn children(&self) -> Vec<(Segment, &Self)>;
+    fn children_mut(&mut self) -> Vec<(Segment, &mut Self)>;
+}
+
+/// Callbacks for `walk`. `path` leads from the root to `node`, so it is
+/// empty for the root itself.
+pub trait Visitor<T: ?Sized> {
+    fn enter(&mut self, _path: &[Segment], _node: &T) -> Control {
+        Control::Continue
+    }
+
+    fn leave(&mut self, _path: &[Segment], _node: &T) -> Control {
+        Control::Continue
+    }
+}
+
+/// `Visitor` for `walk_mut`. Children are visited after `enter` returns,
+/// so a node replaced there has its new children walked.
+pub trait VisitorMut<T: ?Sized> {
+    fn enter(&mut self, _path: &[Segment], _node: &mut T) -> Control {
+        Control::Continue
+    }
+
+    fn leave(&mut self, _path: &[Segment], _node: &mut T) -> Control {
+        Control::Continue
+    }
+}
+
+/// Walks `tree` depth first, in document order. Returns `Control::Stop`
+/// if the visitor stopped the walk and `Control::Continue` otherwise.
+pub fn walk<T: Tree, V: Visitor<T>>(tree: &T, visitor: &mut V) -> Control {
+    walk_node(tree, &mut vec![], visitor)
+}
+
+fn walk_node<T: Tree, V: Visitor<T>>(
+    node: &T,
+    path: &mut Vec<Segment>,
+    visitor: &mut V,
+) -> Control {
+    match visitor.enter(path, node) {
+        Control::Stop => return Control::Stop,
+        Control::SkipChildren => {}
+        Control::Continue => {
+            for (segment, child) in node.children() {
+                path.push(segment);
+                let control = walk_node(child, path, visitor);
+                path.pop();
+                if control == Control::Stop {
+                    return Control::Stop;
+                }
+            }
+        }
+    }
+    match visitor.leave(path, node) {
+        Control::Stop => Control::Stop,
+        _ => Control::Continue,
+    }
+}
+
+pub fn walk_mut<T: Tree, V: VisitorMut<T>>(tree: &mut T, visitor: &mut V) -> Control {
+    walk_node_mut(tree, &mut vec![], visitor)
+}
+
+fn walk_node_mut<T: Tree, V: VisitorMut<T>>(
+    node: &mut T,
+    path: &mut Vec<Segment>,
+    visitor: &mut V,
+) -> Control {
+    match visitor.enter(path, node) {
+        Control::Stop => return Control::Stop,
+        Control::SkipChildren => {}
+        Control::Continue => {
+            for (segment, child) in node.children_mut() {
+                path.push(segment);
+                let control = walk_node_mut(child, path, visitor);
+                path.pop();
+                if control == Control::Stop {
+                    return Control::Stop;
+                }
+            }
+        }
+    }
+    match visitor.leave(path, node) {
+        Control::Stop => Control::Stop,
+        _ => Control::Continue,
+    }
+}
+
+impl Tree for JsonValue {
+    fn children(&self) -> Vec<(Segment, &Self)> {
+        match self {
+            JsonValue::Object(members) => members
+                .iter()
+                .map(|(key, value)| (Segment::Key(key.clone()), value))
+                .collect(),
+            JsonValue::Array(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| (Segment::Index(i), item))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn children_mut(&mut self) -> Vec<(Segment, &mut Self)> {
+        match self {
+            JsonValue::Object(members) => members
+                .iter_mut()
+                .map(|(key, value)| (Segment::Key(key.clone()), value))
+                .collect(),
+            JsonValue::Array(items) => items
+                .iter_mut()
+                .enumerate()
+                .map(|(i, item)| (Segment::Index(i), item))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+impl Tree for Value {
+    fn children(&self) -> Vec<(Segment, &Self)> {
+        match self {
+            Value::Table(entries) => entries
+                .iter()
+                .map(|(key, value)| (Segment::Key(key.clone()), value))
+                .collect(),
+            Value::Array(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| (Segment::Index(i), item))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn children_mut(&mut self) -> Vec<(Segment, &mut Self)> {
+        match self {
+            Value::Table(entries) => entries
+                .iter_mut()
+                .map(|(key, value)| (Segment::Key(key.clone()), value))
+                .collect(),
+            Value::Array(items) => items
+                .iter_mut()
+                .enumerate()
+                .map(|(i, item)| (Segment::Index(i), item))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::ToValue;
+
+    #[derive(Default)]
+    struct Depth {
+        nodes: usize,
+        deepest: usize,
+    }
+
+    impl<T> Visitor<T> for Depth {
+        fn enter(&mut self, path: &[Segment], _: &T) -> Control {
+            self.nodes += 1;
+            self.deepest = self.deepest.max(path.len());
+            Control::Continue
+        }
+    }
+
+    #[test]
+    fn test_walk() {
+        let json: JsonValue = r#"{"a": [1, {"b": null}], "c": true}"#.parse().unwrap();
+        let mut depth = Depth::default();
+        assert_eq!(walk(&json, &mut depth), Control::Continue);
+        assert_eq!((depth.nodes, depth.deepest), (6, 3));
+
+        let lockfile = crate::cargo_lock::lockfile(
+            "version = 3\n\n[[package]]\nname = \"nom\"\nversion = \"7.1.3\"\n",
+        )
+        .unwrap()
+        .1;
+        let mut depth = Depth::default();
+        walk(&lockfile.to_value(), &mut depth);
+        assert_eq!((depth.nodes, depth.deepest), (6, 3));
+    }
+
+    #[test]
+    fn test_stop() {
+        struct Find(Option<Vec<Segment>>);
+
+        impl Visitor<JsonValue> for Find {
+            fn enter(&mut self, path: &[Segment], node: &JsonValue) -> Control {
+                match node {
+                    JsonValue::Boolean(true) => {
+                        self.0 = Some(path.to_vec());
+                        Control::Stop
+                    }
+                    JsonValue::String(_) => Control::SkipChildren,
+                    _ => Control::Continue,
+                }
+            }
+        }
+
+        let json: JsonValue = r#"[false, {"x": true}, true]"#.parse().unwrap();
+        let mut find = Find(None);
+        assert_eq!(walk(&json, &mut find), Control::Stop);
+        assert_eq!(
+            find.0,
+            Some(vec![Segment::Index(1), Segment::Key("x".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_walk_mut() {
+        struct Redact;
+
+        impl VisitorMut<JsonValue> for Redact {
+            fn enter(&mut self, path: &[Segment], node: &mut JsonValue) -> Control {
+                match path.last() {
+                    Some(Segment::Key(key)) if key == "password" => {
+                        *node = JsonValue::String("***".to_string());
+                        Control::SkipChildren
+                    }
+                    _ => Control::Continue,
+                }
+            }
+        }
+
+        let mut json: JsonValue = r#"{"users": [{"name": "ada", "password": {"hash": "x"}}]}"#
+            .parse()
+            .unwrap();
+        walk_mut(&mut json, &mut Redact);
+        assert_eq!(
+            json,
+            r#"{"users": [{"name": "ada", "password": "***"}]}"#
+                .parse()
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! JavaScript bindings, enabled with the `wasm` feature. Results come back
+//! as plain JS objects and errors as `{ message, start, end, expected,
+//! found, context }` objects, so callers don't need generated classes.
+
+use js_sys::{Array, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::http::UriBuf;
+use crate::json::JsonValue;
+
+fn set(object: &Object, key: &str, value: JsValue) {
+    // Setting a property on a plain object can't fail.
+    Reflect::set(object, &JsValue::from_str(key), &value).unwrap();
+}
+
+fn to_js(value: &JsonValue) -> JsValue {
+    match value {
+        JsonValue::Object(fields) => {
+            let object = Object::new();
+            for (key, value) in fields {
+                set(&object, key, to_js(value));
+            }
+            object.into()
+        }
+        JsonValue::Array(items) => items.iter().map(to_js).collect::<Array>().into(),
+        JsonValue::String(string) => JsValue::from_str(string),
+        JsonValue::Number(number) => JsValue::from_f64(*number),
+        JsonValue::Decimal(number) => JsValue::from_f64(number.as_f64()),
+        JsonValue::Boolean(boolean) => JsValue::from_bool(*boolean),
+        JsonValue::Null => JsValue::NULL,
+    }
+}
+
+fn error_to_js(error: &Error, input: &str) -> JsValue {
+    let object = Object::new();
+    set(&object, "message", JsValue::from_str(&error.to_string()));
+    set(&object, "rendered", JsValue::from_str(&error.render(input)));
+    set(&object, "start", JsValue::from_f64(error.span.start as f64));
+    set(&object, "end", JsValue::from_f64(error.span.end as f64));
+    set(&object, "line", JsValue::from_f64(error.line as f64));
+    set(&object, "column", JsValue::from_f64(error.column as f64));
+    set(
+        &object,
+        "expected",
+        error
+            .expected
+            .as_deref()
+            .map_or(JsValue::NULL, JsValue::from_str),
+    );
+    set(&object, "found", JsValue::from_str(&error.found));
+    set(
+        &object,
+        "context",
+        error
+            .context
+            .iter()
+            .map(|context| JsValue::from_str(context))
+            .collect::<Array>()
+            .into(),
+    );
+    object.into()
+}
+
+/// Parses a complete URI into an object with `scheme`, `host`, `port`,
+/// `path`, `query` and `fragment` properties.
+#[wasm_bindgen(js_name = parseUri)]
+pub fn parse_uri(input: &str) -> Result<JsValue, JsValue> {
+    input
+        .parse::<UriBuf>()
+        .map(|uri| to_js(&uri.uri().to_json()))
+        .map_err(|error| error_to_js(&error, input))
+}
+
+/// Parses a complete JSON document.
+#[wasm_bindgen(js_name = parseJson)]
+pub fn parse_json(input: &str) -> Result<JsValue, JsValue> {
+    input
+        .parse::<JsonValue>()
+        .map(|value| to_js(&value))
+        .map_err(|error| error_to_js(&error, input))
+}
@@ -0,0 +1,366 @@
+use nom::{
+    bytes::complete::take,
+    combinator::map,
+    error::{context, VerboseError, VerboseErrorKind},
+    number::complete::{be_u16, be_u64, u8},
+    sequence::pair,
+    Err as NomErr, IResult,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    pub fn is_control(self) -> bool {
+        matches!(self, Opcode::Close | Opcode::Ping | Opcode::Pong)
+    }
+}
+
+/// A single frame. The payload has already been unmasked.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Frame {
+    pub fin: bool,
+    /// The three reserved bits, used by extensions such as compression.
+    pub rsv: u8,
+    pub opcode: Opcode,
+    pub mask: Option<[u8; 4]>,
+    pub payload: Vec<u8>,
+}
+
+/// A complete message, reassembled from its fragments.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    /// The status code and reason, if the close frame carried any.
+    Close(Option<(u16, String)>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DecodeError {
+    InvalidFrame,
+    /// A continuation frame arrived with no fragmented message in progress.
+    UnexpectedContinuation,
+    /// A new data frame arrived before the fragmented message was finished.
+    ExpectedContinuation,
+    InvalidUtf8,
+    InvalidClosePayload,
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+type Header = (bool, u8, Opcode, usize);
+
+fn failure<'a>(input: &'a [u8], reason: &'static str) -> NomErr<VerboseError<&'a [u8]>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+/// Checks the first header byte against the payload length, returning the
+/// FIN bit, reserved bits and opcode, and the length as a `usize`.
+fn validate(input: &[u8], first: u8, length: u64) -> Result<Header, NomErr<VerboseError<&[u8]>>> {
+    let fin = first & 0x80 != 0;
+    let opcode = match first & 0x0f {
+        0x0 => Opcode::Continuation,
+        0x1 => Opcode::Text,
+        0x2 => Opcode::Binary,
+        0x8 => Opcode::Close,
+        0x9 => Opcode::Ping,
+        0xa => Opcode::Pong,
+        _ => return Err(failure(input, "reserved opcode")),
+    };
+    if opcode.is_control() && !fin {
+        return Err(failure(input, "fragmented control frame"));
+    }
+    if opcode.is_control() && length > 125 {
+        return Err(failure(input, "control frame too long"));
+    }
+    // The most significant bit of a 64-bit length must be zero.
+    let length = match usize::try_from(length) {
+        Ok(length) if length as u64 >> 63 == 0 => length,
+        _ => return Err(failure(input, "invalid payload length")),
+    };
+    Ok((fin, (first >> 4) & 0x07, opcode, length))
+}
+
+fn unmask(payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+    match mask {
+        Some(key) => payload
+            .iter()
+            .zip(key.iter().cycle())
+            .map(|(byte, key)| byte ^ key)
+            .collect(),
+        None => payload.to_vec(),
+    }
+}
+
+fn mask_key(key: &[u8]) -> Option<[u8; 4]> {
+    Some([key[0], key[1], key[2], key[3]])
+}
+
+/// Parses one frame, unmasking its payload.
+pub fn frame(input: &[u8]) -> Res<&[u8], Frame> {
+    let (input, (first, second)) = context("frame header", pair(u8, u8))(input)?;
+    let (input, length) = match second & 0x7f {
+        126 => map(be_u16, u64::from)(input)?,
+        127 => be_u64(input)?,
+        n => (input, u64::from(n)),
+    };
+    let (input, mask) = match second & 0x80 {
+        0 => (input, None),
+        _ => map(take(4usize), mask_key)(input)?,
+    };
+    let (fin, rsv, opcode, length) = validate(input, first, length)?;
+    let (input, payload) = context("payload", take(length))(input)?;
+    Ok((
+        input,
+        Frame {
+            fin,
+            rsv,
+            opcode,
+            mask,
+            payload: unmask(payload, mask),
+        },
+    ))
+}
+
+/// A variant of the frame parser that returns `Err::Incomplete` until a
+/// whole frame has been buffered.
+pub mod streaming {
+    use nom::{
+        bytes::streaming::take,
+        combinator::map,
+        error::context,
+        number::streaming::{be_u16, be_u64, u8},
+        sequence::pair,
+    };
+
+    use super::{mask_key, unmask, validate, Frame, Res};
+
+    pub fn frame(input: &[u8]) -> Res<&[u8], Frame> {
+        let (input, (first, second)) = context("frame header", pair(u8, u8))(input)?;
+        let (input, length) = match second & 0x7f {
+            126 => map(be_u16, u64::from)(input)?,
+            127 => be_u64(input)?,
+            n => (input, u64::from(n)),
+        };
+        let (input, mask) = match second & 0x80 {
+            0 => (input, None),
+            _ => map(take(4usize), mask_key)(input)?,
+        };
+        let (fin, rsv, opcode, length) = validate(input, first, length)?;
+        let (input, payload) = context("payload", take(length))(input)?;
+        Ok((
+            input,
+            Frame {
+                fin,
+                rsv,
+                opcode,
+                mask,
+                payload: unmask(payload, mask),
+            },
+        ))
+    }
+}
+
+/// Buffers bytes from a connection and yields frames or reassembled
+/// messages as they become complete.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+    fragments: Option<(Opcode, Vec<u8>)>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Returns the next buffered frame, or `None` if more bytes are needed.
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, DecodeError> {
+        let (consumed, frame) = match streaming::frame(&self.buffer) {
+            Ok((rest, frame)) => (self.buffer.len() - rest.len(), frame),
+            Err(NomErr::Incomplete(_)) => return Ok(None),
+            Err(_) => return Err(DecodeError::InvalidFrame),
+        };
+        self.buffer.drain(..consumed);
+        Ok(Some(frame))
+    }
+
+    /// Returns the next complete message, or `None` if more bytes are
+    /// needed. Control messages may arrive between the fragments of a data
+    /// message and are returned as soon as they are read.
+    pub fn next_message(&mut self) -> Result<Option<Message>, DecodeError> {
+        while let Some(frame) = self.next_frame()? {
+            let (opcode, payload) = match (frame.opcode, self.fragments.take()) {
+                (opcode, fragments) if opcode.is_control() => {
+                    self.fragments = fragments;
+                    (opcode, frame.payload)
+                }
+                (Opcode::Continuation, None) => return Err(DecodeError::UnexpectedContinuation),
+                (Opcode::Continuation, Some((opcode, mut payload))) => {
+                    payload.extend(frame.payload);
+                    (opcode, payload)
+                }
+                (_, Some(_)) => return Err(DecodeError::ExpectedContinuation),
+                (opcode, None) => (opcode, frame.payload),
+            };
+            if !frame.fin {
+                self.fragments = Some((opcode, payload));
+                continue;
+            }
+            return to_message(opcode, payload).map(Some);
+        }
+        Ok(None)
+    }
+}
+
+fn to_message(opcode: Opcode, payload: Vec<u8>) -> Result<Message, DecodeError> {
+    let text = |bytes: Vec<u8>| String::from_utf8(bytes).map_err(|_| DecodeError::InvalidUtf8);
+    Ok(match opcode {
+        Opcode::Text => Message::Text(text(payload)?),
+        Opcode::Binary | Opcode::Continuation => Message::Binary(payload),
+        Opcode::Ping => Message::Ping(payload),
+        Opcode::Pong => Message::Pong(payload),
+        Opcode::Close => match payload.len() {
+            0 => Message::Close(None),
+            1 => return Err(DecodeError::InvalidClosePayload),
+            _ => {
+                let code = u16::from_be_bytes([payload[0], payload[1]]);
+                Message::Close(Some((code, text(payload[2..].to_vec())?)))
+            }
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::Needed;
+
+    use super::*;
+
+    /// The masked "Hello" frame from RFC 6455 section 5.7.
+    const MASKED_HELLO: [u8; 11] = [
+        0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+    ];
+
+    #[test]
+    fn test_frame() {
+        assert_eq!(
+            frame(&MASKED_HELLO),
+            Ok((
+                &b""[..],
+                Frame {
+                    fin: true,
+                    rsv: 0,
+                    opcode: Opcode::Text,
+                    mask: Some([0x37, 0xfa, 0x21, 0x3d]),
+                    payload: b"Hello".to_vec(),
+                }
+            ))
+        );
+        let (_, unmasked) = frame(&[0x01, 0x03, b'H', b'e', b'l']).unwrap();
+        assert_eq!((unmasked.fin, unmasked.payload), (false, b"Hel".to_vec()));
+    }
+
+    #[test]
+    fn test_extended_lengths() {
+        let mut input = vec![0x82, 126, 0x01, 0x00];
+        input.extend(vec![7; 256]);
+        let (_, f) = frame(&input).unwrap();
+        assert_eq!(f.payload.len(), 256);
+        let mut input = vec![0x82, 127, 0, 0, 0, 0, 0, 1, 0, 0];
+        input.extend(vec![7; 65536]);
+        let (_, f) = frame(&input).unwrap();
+        assert_eq!(f.payload.len(), 65536);
+        let input = [0x82, 127, 0x80, 0, 0, 0, 0, 0, 0, 1];
+        assert_eq!(frame(&input), Err(failure(&[], "invalid payload length")));
+    }
+
+    #[test]
+    fn test_frame_validation() {
+        assert_eq!(frame(&[0x83, 0x00]), Err(failure(&[], "reserved opcode")));
+        assert_eq!(
+            frame(&[0x09, 0x00]),
+            Err(failure(&[], "fragmented control frame"))
+        );
+        assert_eq!(
+            frame(&[0x89, 126, 0x00, 0x7e]),
+            Err(failure(&[], "control frame too long"))
+        );
+        assert!(frame(&[0x81, 0x05, b'a']).is_err());
+    }
+
+    #[test]
+    fn test_streaming_frame() {
+        assert_eq!(
+            streaming::frame(&MASKED_HELLO[..1]),
+            Err(NomErr::Incomplete(Needed::new(1)))
+        );
+        assert_eq!(
+            streaming::frame(&MASKED_HELLO[..8]),
+            Err(NomErr::Incomplete(Needed::new(3)))
+        );
+        assert!(streaming::frame(&MASKED_HELLO).is_ok());
+    }
+
+    #[test]
+    fn test_decoder_reassembly() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0x01, 0x03, b'H', b'e', b'l']);
+        assert_eq!(decoder.next_message(), Ok(None));
+        // A ping may arrive between fragments.
+        decoder.feed(&[0x89, 0x01, b'p', 0x80]);
+        assert_eq!(
+            decoder.next_message(),
+            Ok(Some(Message::Ping(b"p".to_vec())))
+        );
+        decoder.feed(&[0x02, b'l', b'o']);
+        assert_eq!(
+            decoder.next_message(),
+            Ok(Some(Message::Text("Hello".to_owned())))
+        );
+        decoder.feed(&[0x88, 0x04, 0x03, 0xe8, b'o', b'k']);
+        assert_eq!(
+            decoder.next_message(),
+            Ok(Some(Message::Close(Some((1000, "ok".to_owned())))))
+        );
+        assert_eq!(decoder.next_message(), Ok(None));
+    }
+
+    #[test]
+    fn test_decoder_errors() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0x80, 0x00]);
+        assert_eq!(
+            decoder.next_message(),
+            Err(DecodeError::UnexpectedContinuation)
+        );
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0x01, 0x00, 0x81, 0x00]);
+        assert_eq!(
+            decoder.next_message(),
+            Err(DecodeError::ExpectedContinuation)
+        );
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0x81, 0x01, 0xff]);
+        assert_eq!(decoder.next_message(), Err(DecodeError::InvalidUtf8));
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0x8f, 0x00]);
+        assert_eq!(decoder.next_frame(), Err(DecodeError::InvalidFrame));
+    }
+}
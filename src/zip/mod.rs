@@ -0,0 +1,438 @@
+use std::borrow::Cow;
+
+use nom::{
+    bytes::complete::{tag, take},
+    combinator::map,
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::count,
+    number::complete::{le_u16, le_u32, le_u64},
+    sequence::tuple,
+    Err as NomErr, IResult,
+};
+
+const END_OF_CENTRAL_DIRECTORY: [u8; 4] = [0x50, 0x4b, 0x05, 0x06];
+const ZIP64_END_OF_CENTRAL_DIRECTORY: [u8; 4] = [0x50, 0x4b, 0x06, 0x06];
+const ZIP64_LOCATOR: [u8; 4] = [0x50, 0x4b, 0x06, 0x07];
+const CENTRAL_DIRECTORY_ENTRY: [u8; 4] = [0x50, 0x4b, 0x01, 0x02];
+const ZIP64_EXTRA_FIELD: u16 = 0x0001;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CompressionMethod {
+    Stored,
+    Deflated,
+    Bzip2,
+    Lzma,
+    Zstd,
+    Other(u16),
+}
+
+impl From<u16> for CompressionMethod {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => CompressionMethod::Stored,
+            8 => CompressionMethod::Deflated,
+            12 => CompressionMethod::Bzip2,
+            14 => CompressionMethod::Lzma,
+            93 => CompressionMethod::Zstd,
+            other => CompressionMethod::Other(other),
+        }
+    }
+}
+
+/// An MS-DOS timestamp, as stored in ZIP headers. Seconds are even.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DosDateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DosDateTime {
+    fn new(date: u16, time: u16) -> Self {
+        DosDateTime {
+            year: 1980 + (date >> 9),
+            month: ((date >> 5) & 0x0f) as u8,
+            day: (date & 0x1f) as u8,
+            hour: (time >> 11) as u8,
+            minute: ((time >> 5) & 0x3f) as u8,
+            second: ((time & 0x1f) * 2) as u8,
+        }
+    }
+}
+
+/// A central directory entry. Sizes and offsets are already widened from
+/// the Zip64 extra field where needed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Entry<'a> {
+    pub name: &'a [u8],
+    pub flags: u16,
+    pub method: CompressionMethod,
+    pub modified: DosDateTime,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub local_header_offset: u64,
+    pub external_attributes: u32,
+    pub extra: &'a [u8],
+    pub comment: &'a [u8],
+}
+
+impl Entry<'_> {
+    /// The name, decoded as UTF-8. Names without the UTF-8 flag are
+    /// nominally code page 437 but are decoded the same way.
+    pub fn name(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.name)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with(b"/")
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & 0x0001 != 0
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Archive<'a> {
+    pub entries: Vec<Entry<'a>>,
+    pub comment: &'a [u8],
+    pub zip64: bool,
+}
+
+/// The fields of the end of central directory record this module uses.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct EndOfCentralDirectory<'a> {
+    pub entries: u64,
+    pub size: u64,
+    pub offset: u64,
+    pub comment: &'a [u8],
+}
+
+type Res<T, U> = IResult<T, U, VerboseError<T>>;
+
+fn failure<'a>(input: &'a [u8], reason: &'static str) -> NomErr<VerboseError<&'a [u8]>> {
+    NomErr::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(reason))],
+    })
+}
+
+/// Parses the classic end of central directory record.
+pub fn end_of_central_directory(input: &[u8]) -> Res<&[u8], EndOfCentralDirectory<'_>> {
+    let (input, (_, _disk, _cd_disk, _disk_entries, entries, size, offset, comment_length)) =
+        context(
+            "end of central directory",
+            tuple((
+                tag(&END_OF_CENTRAL_DIRECTORY[..]),
+                le_u16,
+                le_u16,
+                le_u16,
+                le_u16,
+                le_u32,
+                le_u32,
+                le_u16,
+            )),
+        )(input)?;
+    let (input, comment) = context("archive comment", take(comment_length))(input)?;
+    Ok((
+        input,
+        EndOfCentralDirectory {
+            entries: entries.into(),
+            size: size.into(),
+            offset: offset.into(),
+            comment,
+        },
+    ))
+}
+
+fn zip64_locator(input: &[u8]) -> Res<&[u8], u64> {
+    context(
+        "zip64 locator",
+        map(
+            tuple((tag(&ZIP64_LOCATOR[..]), le_u32, le_u64, le_u32)),
+            |(_, _, offset, _)| offset,
+        ),
+    )(input)
+}
+
+fn zip64_end_of_central_directory(input: &[u8]) -> Res<&[u8], (u64, u64, u64)> {
+    context(
+        "zip64 end of central directory",
+        map(
+            tuple((
+                tag(&ZIP64_END_OF_CENTRAL_DIRECTORY[..]),
+                le_u64,
+                le_u16,
+                le_u16,
+                le_u32,
+                le_u32,
+                le_u64,
+                le_u64,
+                le_u64,
+                le_u64,
+            )),
+            |(_, _, _, _, _, _, _, entries, size, offset)| (entries, size, offset),
+        ),
+    )(input)
+}
+
+/// Replaces 32-bit fields set to `0xFFFFFFFF` with the values from a Zip64
+/// extra field, which only holds the fields that overflowed, in order.
+fn apply_zip64_extra<'a>(
+    entry: &mut Entry<'a>,
+    input: &'a [u8],
+) -> Result<(), NomErr<VerboseError<&'a [u8]>>> {
+    let mut extra = entry.extra;
+    while extra.len() >= 4 {
+        let (rest, (id, size)) = tuple((le_u16, le_u16))(extra)?;
+        let (rest, mut data) = take(size)(rest)?;
+        extra = rest;
+        if id != ZIP64_EXTRA_FIELD {
+            continue;
+        }
+        for field in [
+            &mut entry.uncompressed_size,
+            &mut entry.compressed_size,
+            &mut entry.local_header_offset,
+        ] {
+            if *field == u64::from(u32::MAX) {
+                let (rest, value) = le_u64::<_, VerboseError<_>>(data)
+                    .map_err(|_| failure(input, "truncated zip64 extra field"))?;
+                *field = value;
+                data = rest;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses one central directory file header.
+pub fn central_directory_entry(input: &[u8]) -> Res<&[u8], Entry<'_>> {
+    let start = input;
+    let (input, (_, _made_by, _needed, flags, method, time, date, crc32)) = context(
+        "central directory entry",
+        tuple((
+            tag(&CENTRAL_DIRECTORY_ENTRY[..]),
+            le_u16,
+            le_u16,
+            le_u16,
+            le_u16,
+            le_u16,
+            le_u16,
+            le_u32,
+        )),
+    )(input)?;
+    let (input, (compressed, uncompressed, name_length, extra_length, comment_length)) =
+        context(
+            "central directory entry",
+            tuple((le_u32, le_u32, le_u16, le_u16, le_u16)),
+        )(input)?;
+    let (input, (_disk, _internal, external_attributes, offset)) = context(
+        "central directory entry",
+        tuple((le_u16, le_u16, le_u32, le_u32)),
+    )(input)?;
+    let (input, (name, extra, comment)) = context(
+        "central directory entry",
+        tuple((take(name_length), take(extra_length), take(comment_length))),
+    )(input)?;
+    let mut entry = Entry {
+        name,
+        flags,
+        method: method.into(),
+        modified: DosDateTime::new(date, time),
+        crc32,
+        compressed_size: compressed.into(),
+        uncompressed_size: uncompressed.into(),
+        local_header_offset: offset.into(),
+        external_attributes,
+        extra,
+        comment,
+    };
+    apply_zip64_extra(&mut entry, start)?;
+    Ok((input, entry))
+}
+
+/// Lists the entries of a whole ZIP file by reading its central directory.
+/// Nothing is decompressed.
+pub fn archive(input: &[u8]) -> Res<&[u8], Archive<'_>> {
+    // The record is at least 22 bytes and may be followed by a comment of
+    // up to 65535 bytes, so search backwards for a signature whose comment
+    // length reaches exactly to the end of the file.
+    let search_start = input.len().saturating_sub(22 + usize::from(u16::MAX));
+    let (eocd_position, eocd) = (search_start..input.len().saturating_sub(21))
+        .rev()
+        .find_map(
+            |position| match end_of_central_directory(&input[position..]) {
+                Ok((&[], eocd)) => Some((position, eocd)),
+                _ => None,
+            },
+        )
+        .ok_or_else(|| failure(input, "end of central directory not found"))?;
+
+    let mut zip64 = false;
+    let (mut entries, mut size, mut offset) = (eocd.entries, eocd.size, eocd.offset);
+    if let Some(locator) = eocd_position
+        .checked_sub(20)
+        .map(|p| &input[p..eocd_position])
+    {
+        if let Ok((_, record_offset)) = zip64_locator(locator) {
+            let record = usize::try_from(record_offset)
+                .ok()
+                .and_then(|offset| input.get(offset..))
+                .ok_or_else(|| failure(locator, "invalid zip64 locator"))?;
+            (_, (entries, size, offset)) = zip64_end_of_central_directory(record)?;
+            zip64 = true;
+        }
+    }
+
+    let directory = usize::try_from(offset)
+        .ok()
+        .zip(usize::try_from(size).ok())
+        .and_then(|(offset, size)| input.get(offset..offset.checked_add(size)?))
+        .ok_or_else(|| failure(&input[eocd_position..], "central directory out of bounds"))?;
+    let entries = usize::try_from(entries).map_err(|_| failure(directory, "too many entries"))?;
+    let (_, entries) = count(central_directory_entry, entries)(directory)?;
+    Ok((
+        &input[input.len()..],
+        Archive {
+            entries,
+            comment: eocd.comment,
+            zip64,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory_entry(
+        name: &[u8],
+        compressed: u32,
+        uncompressed: u32,
+        offset: u32,
+        extra: &[u8],
+    ) -> Vec<u8> {
+        let mut bytes = CENTRAL_DIRECTORY_ENTRY.to_vec();
+        bytes.extend(20u16.to_le_bytes()); // made by
+        bytes.extend(20u16.to_le_bytes()); // needed
+        bytes.extend(0x0800u16.to_le_bytes()); // utf-8 names
+        bytes.extend(8u16.to_le_bytes()); // deflated
+        bytes.extend(((13u16 << 11) | (30 << 5) | 5).to_le_bytes());
+        bytes.extend((((2024u16 - 1980) << 9) | (2 << 5) | 29).to_le_bytes());
+        bytes.extend(0xdead_beefu32.to_le_bytes());
+        bytes.extend(compressed.to_le_bytes());
+        bytes.extend(uncompressed.to_le_bytes());
+        bytes.extend((name.len() as u16).to_le_bytes());
+        bytes.extend((extra.len() as u16).to_le_bytes());
+        bytes.extend(0u16.to_le_bytes()); // comment
+        bytes.extend([0, 0, 0, 0]); // disk, internal attributes
+        bytes.extend(0o100644u32.wrapping_shl(16).to_le_bytes());
+        bytes.extend(offset.to_le_bytes());
+        bytes.extend(name);
+        bytes.extend(extra);
+        bytes
+    }
+
+    fn end_record(entries: u16, size: u32, offset: u32, comment: &[u8]) -> Vec<u8> {
+        let mut bytes = END_OF_CENTRAL_DIRECTORY.to_vec();
+        bytes.extend([0, 0, 0, 0]);
+        bytes.extend(entries.to_le_bytes());
+        bytes.extend(entries.to_le_bytes());
+        bytes.extend(size.to_le_bytes());
+        bytes.extend(offset.to_le_bytes());
+        bytes.extend((comment.len() as u16).to_le_bytes());
+        bytes.extend(comment);
+        bytes
+    }
+
+    #[test]
+    fn test_archive() {
+        // Stand-in for the local headers and file data.
+        let mut bytes = vec![0u8; 40];
+        let mut directory = directory_entry(b"docs/", 0, 0, 0, &[]);
+        directory.extend(directory_entry(b"docs/readme.txt", 12, 20, 20, &[]));
+        let directory_size = directory.len() as u32;
+        bytes.extend(directory);
+        // A comment that happens to contain the signature.
+        bytes.extend(end_record(2, directory_size, 40, b"PK\x05\x06!"));
+
+        let (rest, archive) = archive(&bytes).unwrap();
+        assert!(rest.is_empty());
+        assert!(!archive.zip64);
+        assert_eq!(archive.comment, b"PK\x05\x06!");
+        assert_eq!(archive.entries.len(), 2);
+        assert!(archive.entries[0].is_dir());
+        let entry = &archive.entries[1];
+        assert_eq!(entry.name(), "docs/readme.txt");
+        assert_eq!(entry.method, CompressionMethod::Deflated);
+        assert_eq!((entry.compressed_size, entry.uncompressed_size), (12, 20));
+        assert_eq!(entry.local_header_offset, 20);
+        assert_eq!(entry.crc32, 0xdead_beef);
+        assert!(!entry.is_encrypted());
+        assert_eq!(
+            entry.modified,
+            DosDateTime {
+                year: 2024,
+                month: 2,
+                day: 29,
+                hour: 13,
+                minute: 30,
+                second: 10
+            }
+        );
+    }
+
+    #[test]
+    fn test_zip64() {
+        let mut extra = ZIP64_EXTRA_FIELD.to_le_bytes().to_vec();
+        extra.extend(16u16.to_le_bytes());
+        extra.extend(5_000_000_000u64.to_le_bytes());
+        extra.extend(4_000_000_000u64.to_le_bytes());
+        let directory = directory_entry(b"big.bin", u32::MAX, u32::MAX, 0, &extra);
+
+        let mut bytes = directory.clone();
+        let record_offset = bytes.len() as u64;
+        bytes.extend(ZIP64_END_OF_CENTRAL_DIRECTORY);
+        bytes.extend(44u64.to_le_bytes());
+        bytes.extend([45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend(1u64.to_le_bytes());
+        bytes.extend(1u64.to_le_bytes());
+        bytes.extend((directory.len() as u64).to_le_bytes());
+        bytes.extend(0u64.to_le_bytes());
+        bytes.extend(ZIP64_LOCATOR);
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(record_offset.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(end_record(u16::MAX, u32::MAX, u32::MAX, b""));
+
+        let (_, archive) = archive(&bytes).unwrap();
+        assert!(archive.zip64);
+        let entry = &archive.entries[0];
+        assert_eq!(entry.uncompressed_size, 5_000_000_000);
+        assert_eq!(entry.compressed_size, 4_000_000_000);
+        assert_eq!(entry.local_header_offset, 0);
+    }
+
+    #[test]
+    fn test_errors() {
+        let bytes = vec![0u8; 64];
+        assert_eq!(
+            archive(&bytes),
+            Err(failure(&bytes, "end of central directory not found"))
+        );
+        let bytes = end_record(1, 46, 100, b"");
+        assert_eq!(
+            archive(&bytes),
+            Err(failure(&bytes, "central directory out of bounds"))
+        );
+        let entry = directory_entry(b"x", u32::MAX, 1, 0, &[1, 0, 4, 0, 1, 2, 3, 4]);
+        assert_eq!(
+            central_directory_entry(&entry),
+            Err(failure(&entry, "truncated zip64 extra field"))
+        );
+    }
+}